@@ -0,0 +1,228 @@
+use ggez::graphics::{self, DrawMode, DrawParam, Mesh};
+use ggez::{Context, GameResult};
+
+use crate::eval::Weights;
+use crate::game_state::GameState;
+use crate::input::Action;
+use crate::jobs::{JobId, JobQueue};
+use crate::monitor::Monitor;
+use crate::notation;
+use crate::replay::{self, Ghost, Replay};
+use crate::scene::{MoveNumber, ReplayViewer, Scene, Transition};
+use crate::solver::{self, Solution};
+use crate::variation::{Eval, NodeId, VariationTree};
+
+// 終わったゲームを 1 手ずつたどり、別の手を試してソルバーの読みと比べる場面
+#[derive(Debug)]
+pub struct Analysis {
+    tree: VariationTree,
+    current: NodeId,
+    jobs: JobQueue<(NodeId, Solution)>,
+    // 読んでいる局面の仕事
+    job: Option<(NodeId, JobId)>,
+    cursor: Option<(usize, usize)>,
+    jump: MoveNumber,
+}
+
+impl Analysis {
+    const BEAM_WIDTH: usize = 64;
+    // 手順の表示に出す手の数
+    const LINE_MOVES: usize = 6;
+
+    pub fn new(replay: &Replay) -> Self {
+        Self {
+            tree: VariationTree::new(replay),
+            current: VariationTree::ROOT,
+            jobs: JobQueue::new(1),
+            job: None,
+            cursor: None,
+            jump: MoveNumber::default(),
+        }
+    }
+
+    // 今の局面を読んでいなければ読ませる。前の局面の読みは取り消す。
+    fn request_eval(&mut self) {
+        let node = self.tree.get(self.current);
+        if node.eval.is_some() || self.job.map(|(id, _)| id) == Some(self.current) {
+            return;
+        }
+        if node.finished {
+            let eval = Eval {
+                score: node.score,
+                line: Solution::default(),
+            };
+            self.tree.set_eval(self.current, eval);
+            return;
+        }
+
+        if let Some((_, job)) = self.job.take() {
+            self.jobs.cancel(job);
+        }
+        let id = self.current;
+        let board = node.board.clone();
+        let job = self.jobs.submit(move |monitor: &mut Monitor| {
+            let solution =
+                solver::beam_search_with(&board, Self::BEAM_WIDTH, Weights::default(), monitor);
+            (id, solution)
+        });
+        self.job = Some((id, job));
+    }
+
+    fn go(&mut self, id: NodeId) {
+        self.current = id;
+        self.request_eval();
+    }
+
+    fn play(&mut self, x: usize, y: usize) {
+        if let Some(id) = self.tree.play(self.current, replay::Action::Click(x, y)) {
+            self.go(id);
+        }
+    }
+
+    // 根から今の局面までの手の表記。長ければ最後のほうだけ。
+    fn line(&self) -> String {
+        let path = self.tree.path(self.current);
+        let moves: Vec<_> = path
+            .windows(2)
+            .map(|pair| {
+                let (prev, node) = (self.tree.get(pair[0]), self.tree.get(pair[1]));
+                match node.action {
+                    Some(replay::Action::Click(x, y)) => notation::format_move(&prev.board, x, y),
+                    _ => "?".to_owned(),
+                }
+            })
+            .collect();
+        let skip = moves.len().saturating_sub(Self::LINE_MOVES);
+        let mut line = moves[skip..].join(" ");
+        if skip > 0 {
+            line.insert_str(0, "... ");
+        }
+        line
+    }
+}
+
+impl Scene for Analysis {
+    fn update(&mut self, _state: &mut GameState) -> Transition {
+        for (job, (id, solution)) in self.jobs.poll(1) {
+            if self.job.map(|(_, j)| j) == Some(job) {
+                self.job = None;
+            }
+            let eval = Eval {
+                score: self.tree.get(id).score + solution.score,
+                line: solution,
+            };
+            self.tree.set_eval(id, eval);
+        }
+        self.request_eval();
+        Transition::None
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let area = state.board_area();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), area, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let node = self.tree.get(self.current);
+        state.draw_board(ctx, &node.board)?;
+
+        if let Some(eval) = &node.eval {
+            let plan = eval.line.plan(&node.board, GameState::PLAN_MOVES);
+            state.draw_plan(ctx, &plan)?;
+        }
+        if let Some((x, y)) = self.cursor {
+            let rect = state.cell_rect(x, y);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), rect, graphics::WHITE)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        let depth = self.tree.path(self.current).len() - 1;
+        let length = depth + self.tree.main_line(self.current).len() - 1;
+        let (i, n) = self.tree.sibling_index(self.current);
+        let eval = match &node.eval {
+            Some(eval) => match eval.best() {
+                Some((x, y)) => format!(
+                    "Best: {} ({})",
+                    eval.score,
+                    notation::format_move(&node.board, x, y)
+                ),
+                None => format!("Final: {}", eval.score),
+            },
+            None => "Best: thinking...".to_owned(),
+        };
+        let font = state.font();
+        let y = area.y + area.h + 10.0;
+        font.draw_str(
+            ctx,
+            10.0,
+            y,
+            format!(
+                "Analysis  Move {}/{}  Score: {}  {}  Variation {}/{}",
+                depth,
+                length,
+                node.score,
+                eval,
+                i + 1,
+                n
+            ),
+        )?;
+        font.draw_str(ctx, 10.0, y + 17.0, self.line())?;
+        let help = self.jump.prompt().unwrap_or_else(|| {
+            "Left/Right:Step Up/Down:Variation 0-9:Go to Click:Try Enter:Best V:Replay Esc:Back"
+                .to_owned()
+        });
+        font.draw_str(ctx, 10.0, y + 34.0, help)?;
+        if let Some(comment) = &node.comment {
+            let width = graphics::screen_coordinates(ctx).w - 20.0;
+            font.draw_wrapped(ctx, 10.0, y + 51.0, comment, width, graphics::WHITE)?;
+        }
+        Ok(())
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, _repeat: bool) -> Transition {
+        match action {
+            Action::Cancel if !self.jump.is_empty() => {
+                self.jump.take();
+            }
+            Action::Cancel | Action::Analyze => return Transition::Pop,
+            Action::Pick(digit) => self.jump.push(digit),
+            Action::DeleteChar => self.jump.pop(),
+            Action::Confirm if !self.jump.is_empty() => {
+                if let Some(n) = self.jump.take() {
+                    let id = self.tree.at_depth(self.current, n);
+                    self.go(id);
+                }
+            }
+            Action::ViewReplay => {
+                let replay = self.tree.replay_to(self.current);
+                return Transition::Push(Box::new(ReplayViewer::new(Ghost::new(replay))));
+            }
+            Action::MoveCursor(-1, 0) => {
+                if let Some(parent) = self.tree.parent(self.current) {
+                    self.go(parent);
+                }
+            }
+            Action::MoveCursor(1, 0) => {
+                if let Some(child) = self.tree.main_child(self.current) {
+                    self.go(child);
+                }
+            }
+            Action::MoveCursor(0, dy) => {
+                let id = self.tree.sibling(self.current, dy);
+                self.go(id);
+            }
+            Action::Confirm => {
+                let best = self.tree.get(self.current).eval.as_ref();
+                if let Some((x, y)) = best.and_then(Eval::best) {
+                    self.play(x, y);
+                }
+            }
+            Action::Click(x, y) => {
+                if let Some((x, y)) = state.cell_at(x, y) {
+                    self.play(x, y);
+                }
+            }
+            Action::Point(x, y) => self.cursor = state.cell_at(x, y),
+            _ => {}
+        }
+        Transition::None
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+// 進み具合(0 から 1)の変化の付け方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ease {
+    Linear,
+    // 終わりに向けて遅くする
+    OutCubic,
+}
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::OutCubic => 1.0 - (1.0 - t).powi(3),
+        }
+    }
+}
+
+// 演出用の時計。更新ごとに経過時間だけ進めるので、描画の頻度によらず同じ速さで動く。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnimClock {
+    now: Duration,
+}
+
+impl AnimClock {
+    pub fn advance(&mut self, dt: Duration) {
+        self.now += dt;
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    // 今から seconds 秒かけて進む変化
+    pub fn tween(&self, seconds: f32, ease: Ease) -> Tween {
+        Tween::new(self.now, seconds, ease)
+    }
+}
+
+// 時刻 start から seconds 秒かけて 0 から 1 まで進む変化
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween {
+    start: Duration,
+    seconds: f32,
+    ease: Ease,
+}
+
+impl Tween {
+    pub fn new(start: Duration, seconds: f32, ease: Ease) -> Self {
+        Self {
+            start,
+            seconds,
+            ease,
+        }
+    }
+
+    // 時刻 now での進み具合。始まる前は 0。終わっていれば None。
+    pub fn progress(&self, now: Duration) -> Option<f32> {
+        let elapsed = now.checked_sub(self.start).unwrap_or_default();
+        let t = elapsed.as_secs_f32() / self.seconds;
+        if t >= 1.0 {
+            None
+        } else {
+            Some(self.ease.apply(t))
+        }
+    }
+
+    pub fn is_running(&self, now: Duration) -> bool {
+        self.progress(now).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ease() {
+        assert_eq!(Ease::Linear.apply(0.25), 0.25);
+        assert_eq!(Ease::Linear.apply(2.0), 1.0);
+        assert_eq!(Ease::OutCubic.apply(0.0), 0.0);
+        assert_eq!(Ease::OutCubic.apply(0.5), 0.875);
+        assert_eq!(Ease::OutCubic.apply(1.0), 1.0);
+    }
+
+    #[test]
+    fn tween() {
+        let mut clock = AnimClock::default();
+        clock.advance(Duration::from_secs(1));
+        let tween = clock.tween(0.25, Ease::OutCubic);
+        assert_eq!(tween.progress(Duration::from_millis(500)), Some(0.0));
+        assert_eq!(tween.progress(clock.now()), Some(0.0));
+
+        // 前半で大きく進む
+        clock.advance(Duration::from_millis(125));
+        assert_eq!(tween.progress(clock.now()), Some(0.875));
+        assert!(tween.is_running(clock.now()));
+
+        clock.advance(Duration::from_millis(125));
+        assert_eq!(tween.progress(clock.now()), None);
+        assert!(!tween.is_running(clock.now()));
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::mem;
+use std::ops::{Index, IndexMut, Range};
+
+// 探索木のノードなどを 1 つの Vec に積んでいく。個別には解放せず、探索を終えたらまとめて捨てる。
+// ノードごとに Vec を持つと確保と解放が探索の時間の多くを占めるので、ノードからは添字で指す。
+#[derive(Debug)]
+pub struct Arena<T> {
+    items: Vec<T>,
+    // items を確保し直した回数
+    grows: usize,
+}
+
+// Arena に続けて積んだ要素の範囲
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    start: u32,
+    len: u32,
+}
+
+impl Span {
+    pub fn len(self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+
+    fn range(self) -> Range<usize> {
+        let start = self.start as usize;
+        start..start + self.len as usize
+    }
+}
+
+// Arena の使われ方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    pub items: usize,
+    pub bytes: usize,
+    pub grows: usize,
+}
+
+impl ArenaStats {
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            items: self.items + other.items,
+            bytes: self.bytes + other.bytes,
+            grows: self.grows + other.grows,
+        }
+    }
+}
+
+impl fmt::Display for ArenaStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "arena {} KiB in {} items ({} grows)",
+            self.bytes >> 10,
+            self.items,
+            self.grows
+        )
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+            grows: 0,
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        if self.items.len() + additional > self.items.capacity() {
+            self.grows += 1;
+            self.items.reserve(additional);
+        }
+    }
+
+    // 1 つ積んで添字を返す
+    pub fn alloc(&mut self, value: T) -> usize {
+        self.reserve(1);
+        self.items.push(value);
+        self.items.len() - 1
+    }
+
+    // values を続けて積む
+    pub fn alloc_all<I: ExactSizeIterator<Item = T>>(&mut self, values: I) -> Span {
+        let start = self.items.len();
+        self.reserve(values.len());
+        self.items.extend(values);
+        Span {
+            start: start as u32,
+            len: (self.items.len() - start) as u32,
+        }
+    }
+
+    pub fn slice(&self, span: Span) -> &[T] {
+        &self.items[span.range()]
+    }
+
+    pub fn slice_mut(&mut self, span: Span) -> &mut [T] {
+        &mut self.items[span.range()]
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            items: self.items.len(),
+            bytes: self.items.capacity() * mem::size_of::<T>(),
+            grows: self.grows,
+        }
+    }
+}
+
+impl<T> Index<usize> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, i: usize) -> &T {
+        &self.items[i]
+    }
+}
+
+impl<T> IndexMut<usize> for Arena<T> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        &mut self.items[i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena() {
+        let mut arena = Arena::with_capacity(2);
+        assert_eq!(arena.alloc(1), 0);
+        let span = arena.alloc_all(vec![2, 3, 4].into_iter());
+        assert_eq!(span.len(), 3);
+        assert_eq!(arena.slice(span), &[2, 3, 4]);
+        arena.slice_mut(span).swap(0, 2);
+        arena[0] = 5;
+        assert_eq!(arena.slice(span), &[4, 3, 2]);
+        assert_eq!(arena[0], 5);
+        assert!(arena.alloc_all(vec![].into_iter()).is_empty());
+
+        let stats = arena.stats();
+        assert_eq!(stats.items, 4);
+        assert_eq!(stats.grows, 1);
+        assert!(stats.bytes >= 4 * mem::size_of::<i32>());
+    }
+}
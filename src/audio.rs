@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use ggez::audio::{SoundSource, Source};
+use ggez::Context;
+
+// 効果音の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Sound {
+    // グループを消したとき
+    Pop,
+    // 選択中のグループの大きさを知らせる
+    Tone,
+}
+
+impl Sound {
+    const ALL: &'static [Sound] = &[Sound::Pop, Sound::Tone];
+
+    fn path(self) -> &'static str {
+        match self {
+            Self::Pop => "/pop.wav",
+            Self::Tone => "/tone.wav",
+        }
+    }
+}
+
+// 1 回の再生のしかた
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Playback {
+    // 1.0 で元の高さ
+    pub pitch: f32,
+    pub volume: f32,
+}
+
+impl Playback {
+    // この個数で音の高さが MAX_PITCH に届く
+    const FULL_GROUP: usize = 40;
+    const MAX_PITCH: f32 = 2.0;
+
+    // n 個のグループを指したときの音。2-4, 5-9, 10 以上で高さを変える。消せなければ None。
+    pub fn for_hover(n: usize) -> Option<Self> {
+        let pitch = match n {
+            0..=1 => return None,
+            2..=4 => 1.0,
+            5..=9 => 1.5,
+            _ => 2.0,
+        };
+        Some(Self { pitch, volume: 0.6 })
+    }
+
+    // n 個のグループを消したときの音。大きいほど高く、少し大きく鳴らす。
+    pub fn for_group(n: usize) -> Self {
+        let t = (n.saturating_sub(2) as f32 / (Self::FULL_GROUP - 2) as f32).min(1.0);
+        // 小さいグループの差が聞き分けられるよう、最初に大きく上げる
+        let t = t.sqrt();
+        Self {
+            pitch: 1.0 + (Self::MAX_PITCH - 1.0) * t,
+            volume: 0.7 + 0.3 * t,
+        }
+    }
+}
+
+// 効果音を読み込んでおき、鳴らす。読めなかった音は鳴らさない。
+#[derive(Debug)]
+pub struct Audio {
+    sources: BTreeMap<Sound, Source>,
+}
+
+impl Audio {
+    pub fn new(ctx: &mut Context) -> Self {
+        let mut sources = BTreeMap::new();
+        for &sound in Sound::ALL {
+            match Source::new(ctx, sound.path()) {
+                Ok(source) => {
+                    sources.insert(sound, source);
+                }
+                Err(e) => eprintln!("cannot load sound {}: {}", sound.path(), e),
+            }
+        }
+        Self { sources }
+    }
+
+    // 鳴っている音は止めずに重ねて鳴らす
+    pub fn play(&mut self, sound: Sound, playback: Playback) {
+        let source = match self.sources.get_mut(&sound) {
+            Some(source) => source,
+            None => return,
+        };
+        source.set_pitch(playback.pitch);
+        source.set_volume(playback.volume);
+        if let Err(e) = source.play_detached() {
+            eprintln!("cannot play sound: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_group() {
+        assert_eq!(
+            Playback::for_group(2),
+            Playback {
+                pitch: 1.0,
+                volume: 0.7
+            }
+        );
+        assert_eq!(
+            Playback::for_group(Playback::FULL_GROUP).pitch,
+            Playback::MAX_PITCH
+        );
+        assert_eq!(Playback::for_group(1000).pitch, Playback::MAX_PITCH);
+        assert!(Playback::for_group(5).pitch < Playback::for_group(6).pitch);
+    }
+
+    #[test]
+    fn for_hover() {
+        assert_eq!(Playback::for_hover(1), None);
+        let pitch = |n| Playback::for_hover(n).unwrap().pitch;
+        assert_eq!(pitch(2), pitch(4));
+        assert_eq!(pitch(5), pitch(9));
+        assert_eq!(pitch(10), pitch(200));
+        assert!(pitch(4) < pitch(5));
+        assert!(pitch(9) < pitch(10));
+    }
+}
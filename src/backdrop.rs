@@ -0,0 +1,70 @@
+use ggez::graphics::{Color, Rect};
+
+// 盤面の後ろに描く背景
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backdrop {
+    // 背景色で塗るだけ
+    Flat,
+    // 背景色から bottom へ縦に変わるグラデーション。ゆっくり上下に流れる。
+    Gradient { bottom: (u8, u8, u8) },
+    // 画面を覆うように引き伸ばす画像のリソースのパス
+    Image(&'static str),
+}
+
+// グラデーションを分ける帯の数
+const BANDS: usize = 32;
+// グラデーションが上下に 1 往復する秒数と、ずれる幅(画面の高さに対する割合)
+const DRIFT_SECONDS: f32 = 30.0;
+const DRIFT: f32 = 0.15;
+
+// 時刻 t (秒) でのグラデーションのずれ
+pub fn drift(t: f32) -> f32 {
+    DRIFT * (t * std::f32::consts::TAU / DRIFT_SECONDS).sin()
+}
+
+// screen を横長の帯に分け、top から bottom へ変わる色を塗ったもの。
+// shift だけ色の変わり目を下にずらす。
+pub fn gradient_bands(screen: Rect, top: Color, bottom: Color, shift: f32) -> Vec<(Rect, Color)> {
+    let h = screen.h / BANDS as f32;
+    (0..BANDS)
+        .map(|i| {
+            let t = ((i as f32 + 0.5) / BANDS as f32 - shift).clamp(0.0, 1.0);
+            let color = Color::new(
+                top.r + (bottom.r - top.r) * t,
+                top.g + (bottom.g - top.g) * t,
+                top.b + (bottom.b - top.b) * t,
+                1.0,
+            );
+            // 継ぎ目が見えないよう少し重ねる
+            let rect = Rect::new(screen.x, screen.y + h * i as f32, screen.w, h + 1.0);
+            (rect, color)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient() {
+        let screen = Rect::new(0.0, 0.0, 100.0, 320.0);
+        let black = Color::new(0.0, 0.0, 0.0, 1.0);
+        let white = Color::new(1.0, 1.0, 1.0, 1.0);
+        let bands = gradient_bands(screen, black, white, 0.0);
+        assert_eq!(bands.len(), BANDS);
+        assert_eq!(bands[0].0.y, 0.0);
+        assert_eq!(bands[BANDS - 1].0.y, 310.0);
+        assert!(bands[0].1.r < 0.05);
+        assert!(bands[BANDS - 1].1.r > 0.95);
+        assert!(bands.windows(2).all(|w| w[0].1.r < w[1].1.r));
+
+        // 下にずらすと上のほうは top のまま
+        let bands = gradient_bands(screen, black, white, 0.5);
+        assert_eq!(bands[0].1.r, 0.0);
+        assert_eq!(bands[BANDS / 2 - 1].1.r, 0.0);
+
+        assert_eq!(drift(0.0), 0.0);
+        assert!(drift(DRIFT_SECONDS / 4.0) <= DRIFT);
+    }
+}
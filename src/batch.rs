@@ -0,0 +1,249 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::board;
+use crate::game::{Game, GameConfig};
+use crate::policy::{Policy, PolicyKind};
+use crate::sim::{Clock, ManualClock};
+
+// 連番のシードで作る盤面の集合
+#[derive(Debug, StructOpt)]
+pub struct BoardSetOpt {
+    /// Number of boards to play
+    #[structopt(short = "n", long, default_value = "100")]
+    count: u64,
+
+    /// Seed of the first board; board i uses seed + i
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    #[structopt(long, default_value = "20")]
+    width: usize,
+
+    #[structopt(long, default_value = "10")]
+    height: usize,
+
+    #[structopt(long, default_value = "5")]
+    colors: u8,
+}
+
+impl BoardSetOpt {
+    pub fn config(&self) -> Result<GameConfig> {
+        anyhow::ensure!(self.count > 0, "count must be positive");
+        anyhow::ensure!(self.width > 0 && self.height > 0, "board must not be empty");
+        anyhow::ensure!(
+            (1..=board::MAX_COLORS).contains(&self.colors),
+            "colors must be 1-{}",
+            board::MAX_COLORS
+        );
+
+        Ok(GameConfig {
+            width: self.width,
+            height: self.height,
+            colors: self.colors,
+            ..GameConfig::default()
+        })
+    }
+
+    pub fn seeds(&self) -> impl Iterator<Item = u64> {
+        let first = self.seed;
+        (0..self.count).map(move |i| first.wrapping_add(i))
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SimOpt {
+    #[structopt(flatten)]
+    boards: BoardSetOpt,
+
+    /// random, greedy, beam or beam:<width>
+    #[structopt(long, default_value = "greedy")]
+    policy: PolicyKind,
+
+    /// Output format: csv or json
+    #[structopt(long, default_value = "csv")]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("unknown format: {}", s),
+        }
+    }
+}
+
+// 1 盤面を遊んだ結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    pub score: i32,
+    pub cleared: bool,
+}
+
+// 描画なしで policy に最後まで遊ばせる。1 手ごとに時計を 1 秒進める。
+pub fn play(seed: u64, config: GameConfig, policy: &mut dyn Policy) -> Outcome {
+    let mut game = Game::new(seed, config);
+    let mut clock = ManualClock::default();
+    while !game.is_finished() {
+        let (x, y) = match policy.next_move(game.board()) {
+            Some(cell) => cell,
+            None => break,
+        };
+        clock.advance(Duration::from_secs(1));
+        if !game.click(x, y, clock.now()) {
+            break;
+        }
+    }
+
+    Outcome {
+        score: game.score(),
+        cleared: game.board().tile_count() == 0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Percentile {
+    pub p: u32,
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub policy: String,
+    pub boards: usize,
+    pub mean_score: f64,
+    pub clear_rate: f64,
+    pub percentiles: Vec<Percentile>,
+}
+
+impl Stats {
+    pub const PERCENTILES: [u32; 7] = [0, 10, 25, 50, 75, 90, 100];
+
+    pub fn new(policy: &dyn fmt::Display, outcomes: &[Outcome]) -> Self {
+        assert!(!outcomes.is_empty());
+        let n = outcomes.len();
+
+        let mut scores: Vec<_> = outcomes.iter().map(|o| o.score).collect();
+        scores.sort();
+        let sum: i64 = scores.iter().map(|&s| i64::from(s)).sum();
+        let cleared = outcomes.iter().filter(|o| o.cleared).count();
+
+        // nearest-rank
+        let percentiles = Self::PERCENTILES
+            .iter()
+            .map(|&p| {
+                let rank = (p as usize * n + 99) / 100;
+                Percentile {
+                    p,
+                    score: scores[rank.max(1) - 1],
+                }
+            })
+            .collect();
+
+        Self {
+            policy: policy.to_string(),
+            boards: n,
+            mean_score: sum as f64 / n as f64,
+            clear_rate: cleared as f64 / n as f64,
+            percentiles,
+        }
+    }
+
+    pub fn write_csv<W: Write>(&self, mut wtr: W) -> Result<()> {
+        write!(wtr, "policy,boards,mean_score,clear_rate")?;
+        for pc in &self.percentiles {
+            write!(wtr, ",p{}", pc.p)?;
+        }
+        writeln!(wtr)?;
+
+        write!(
+            wtr,
+            "{},{},{:.2},{:.4}",
+            self.policy, self.boards, self.mean_score, self.clear_rate
+        )?;
+        for pc in &self.percentiles {
+            write!(wtr, ",{}", pc.score)?;
+        }
+        writeln!(wtr)?;
+
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, mut wtr: W) -> Result<()> {
+        serde_json::to_writer_pretty(&mut wtr, self)?;
+        writeln!(wtr)?;
+        Ok(())
+    }
+}
+
+pub fn run(opt: &SimOpt) -> Result<()> {
+    let config = opt.boards.config()?;
+    let outcomes: Vec<_> = opt
+        .boards
+        .seeds()
+        .map(|seed| play(seed, config, &mut *opt.policy.build(seed)))
+        .collect();
+
+    let stats = Stats::new(&opt.policy, &outcomes);
+    let stdout = io::stdout();
+    match opt.format {
+        Format::Csv => stats.write_csv(stdout.lock()),
+        Format::Json => stats.write_json(stdout.lock()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let outcomes: Vec<_> = (1..=10)
+            .map(|i| Outcome {
+                score: i * 10,
+                cleared: i > 8,
+            })
+            .collect();
+        let stats = Stats::new(&"greedy", &outcomes);
+        assert_eq!(stats.boards, 10);
+        assert!((stats.mean_score - 55.0).abs() < 1e-9);
+        assert!((stats.clear_rate - 0.2).abs() < 1e-9);
+
+        let mut buf = vec![];
+        stats.write_csv(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "policy,boards,mean_score,clear_rate,p0,p10,p25,p50,p75,p90,p100\n\
+             greedy,10,55.00,0.2000,10,10,30,50,80,90,100\n"
+        );
+    }
+
+    #[test]
+    fn play() {
+        let config = GameConfig {
+            width: 8,
+            height: 6,
+            ..GameConfig::default()
+        };
+        let greedy = super::play(1, config, &mut *PolicyKind::Greedy.build(1));
+        let beam = super::play(1, config, &mut *PolicyKind::Beam { width: 20 }.build(1));
+        assert!(greedy.score > 0);
+        assert!(beam.score >= greedy.score);
+    }
+}
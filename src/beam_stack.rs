@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use crate::board::Board;
+use crate::eval::{Evaluator, Weights};
+use crate::monitor::Monitor;
+use crate::rules;
+use crate::solver::{self, Solution};
+
+#[derive(Debug, Clone)]
+struct Node {
+    board: Board,
+    solution: Solution,
+    // 上界から見積もった最終得点の上限
+    bound: i32,
+    // 層の中で並べるための評価
+    value: f64,
+}
+
+// ビームスタックサーチ (Zhou & Hansen)。
+// 各層で評価(既定では得点)の高い順に width 個ずつ展開し、下の層を調べ終えたら次の width 個に移る。
+// 最初の一巡は普通のビームサーチと同じなので途中で打ち切っても良い手順が得られ、
+// 最後まで調べれば最適解であることが保証される。
+#[derive(Debug)]
+pub struct BeamStack<'a> {
+    board: &'a Board,
+    width: usize,
+    weights: Weights,
+    best: Solution,
+    timeout: bool,
+}
+
+impl<'a> BeamStack<'a> {
+    pub fn new(board: &'a Board, width: usize) -> Self {
+        assert!(width > 0);
+        Self {
+            board,
+            width,
+            weights: Weights::default(),
+            best: Solution::default(),
+            timeout: false,
+        }
+    }
+
+    // 層の中の並べ方を得点から weights による評価に変える。
+    // 刈り込みは得点の上界で行うので、最後まで調べれば最適解なのは変わらない。
+    pub fn weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    // 最良の手順と、それが最適だと証明できたかどうか。monitor の期限で打ち切る。
+    pub fn search(mut self, monitor: &mut Monitor) -> (Solution, bool) {
+        let root = Node {
+            board: self.board.clone(),
+            solution: Solution::default(),
+            bound: solver::upper_bound(self.board),
+            value: 0.0,
+        };
+        let eval = Evaluator::new(self.weights, self.board);
+        self.layer(monitor, &eval, &[root]);
+        (self.best, !self.timeout)
+    }
+
+    fn is_timeout(&mut self, monitor: &Monitor) -> bool {
+        self.timeout |= monitor.is_over();
+        self.timeout
+    }
+
+    // nodes の子を幅ごとに区切って順に調べる
+    fn layer(&mut self, monitor: &mut Monitor, eval: &Evaluator, nodes: &[Node]) {
+        // 同じ盤面 (左右逆も同じとみなす) は得点の高い方だけ残す
+        let mut children: HashMap<Board, (Board, Solution)> = HashMap::new();
+        for node in nodes {
+            for ((x, y), n) in node.board.groups() {
+                let mut board = node.board.clone();
+                board.erase_component(x, y);
+
+                let mut solution = node.solution.clone();
+                solution.moves.push((x, y));
+                solution.score += rules::group_score(n);
+                let key = board.canonical();
+                if let Some((_, other)) = children.get(&key) {
+                    if other.score >= solution.score {
+                        continue;
+                    }
+                }
+                children.insert(key, (board, solution));
+            }
+        }
+
+        let mut children: Vec<_> = children
+            .into_iter()
+            .map(|(_, (board, solution))| {
+                let bound = solution.score + solver::upper_bound(&board);
+                let value = eval.evaluate(&board, solution.score);
+                let fingerprint = board.fingerprint();
+                let node = Node {
+                    board,
+                    solution,
+                    bound,
+                    value,
+                };
+                (fingerprint, node)
+            })
+            .collect();
+        // 評価の高い順、同点なら盤面の識別子順
+        children.sort_by(|(fa, a), (fb, b)| {
+            b.value
+                .partial_cmp(&a.value)
+                .expect("NaN in evaluation")
+                .then(fa.cmp(fb))
+        });
+        let children: Vec<_> = children.into_iter().map(|(_, node)| node).collect();
+        for node in &children {
+            if node.solution.score > self.best.score {
+                self.best = node.solution.clone();
+            }
+        }
+        monitor.visit(children.len() as u64, &self.best);
+
+        for batch in children.chunks(self.width) {
+            if self.is_timeout(monitor) {
+                return;
+            }
+            // 今の最良を超えられない子は調べない
+            let batch: Vec<_> = batch
+                .iter()
+                .filter(|node| node.bound > self.best.score)
+                .cloned()
+                .collect();
+            if !batch.is_empty() {
+                self.layer(monitor, eval, &batch);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    use super::*;
+
+    // 全探索した最高得点
+    fn optimum(board: &Board, memo: &mut HashMap<Board, i32>) -> i32 {
+        if let Some(&score) = memo.get(board) {
+            return score;
+        }
+        let score = board
+            .groups()
+            .into_iter()
+            .map(|((x, y), n)| {
+                let mut board = board.clone();
+                board.erase_component(x, y);
+                rules::group_score(n) + optimum(&board, memo)
+            })
+            .max()
+            .unwrap_or(0);
+        memo.insert(board.clone(), score);
+        score
+    }
+
+    #[test]
+    fn beam_stack() {
+        for seed in 0..10 {
+            let mut rng = Pcg32::seed_from_u64(seed);
+            let board = Board::random_with(5, 4, 3, &mut rng);
+            let (solution, proven) = BeamStack::new(&board, 2).search(&mut Monitor::new(None));
+            assert!(proven);
+            assert_eq!(solution.score, optimum(&board, &mut HashMap::new()));
+
+            let trace = solution.trace(&board);
+            assert_eq!(trace.iter().map(|e| e.score).sum::<i32>(), solution.score);
+        }
+
+        // 時間切れでも最初の一巡ぶんの手順は返す
+        let mut rng = Pcg32::seed_from_u64(1);
+        let board = Board::random_with(15, 10, 4, &mut rng);
+        let mut monitor = Monitor::new(Some(Instant::now()));
+        let (solution, proven) = BeamStack::new(&board, 4).search(&mut monitor);
+        assert!(!proven);
+        assert!(solution.score > 0);
+    }
+}
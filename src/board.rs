@@ -1,12 +1,70 @@
+use std::convert::TryFrom;
 use std::io::{self, BufRead};
 use std::iter;
 use std::mem;
 
 use anyhow::{Context, Result};
 use itertools::iproduct;
-use rand::prelude::*;
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::hash;
+use crate::profiling;
+use crate::sim::GameRng;
+
+const CELL_NB: u8 = 9;
+
+// 色数の上限
+pub const MAX_COLORS: u8 = CELL_NB - 1;
+// 特に指定がないときの色数
+pub const DEFAULT_COLORS: u8 = 5;
+// 幅と高さの上限。共有コードやリンクには 1 バイトで書く。
+pub const MAX_SIZE: usize = u8::MAX as usize;
+
+// 8 セルを 1 語にまとめて調べる。足りない分は pad で埋める。
+fn load_word(cells: &[u8], pad: u8) -> u64 {
+    let mut bytes = [pad; 8];
+    bytes[..cells.len()].copy_from_slice(cells);
+    u64::from_le_bytes(bytes)
+}
+
+const LOW7: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+
+// 各バイトについて、0 なら 0x80、そうでなければ 0
+fn zero_bytes(v: u64) -> u64 {
+    !(((v & LOW7).wrapping_add(LOW7)) | v | LOW7)
+}
+
+// 各バイトについて、0 でなければ 0x80、0 なら 0
+fn nonzero_bytes(v: u64) -> u64 {
+    (((v & LOW7).wrapping_add(LOW7)) | v) & !LOW7
+}
+
+// 最初の空きセル
+fn first_empty(cells: &[u8]) -> Option<usize> {
+    cells.chunks(8).enumerate().find_map(|(i, chunk)| {
+        let zeros = zero_bytes(load_word(chunk, 1));
+        if zeros == 0 {
+            None
+        } else {
+            Some(8 * i + zeros.trailing_zeros() as usize / 8)
+        }
+    })
+}
 
-const CELL_NB: u8 = 6;
+fn has_tile(cells: &[u8]) -> bool {
+    cells.chunks(8).any(|chunk| load_word(chunk, 0) != 0)
+}
+
+// 同じ位置に同じ色のタイルがあるか
+fn has_same_tile(a: &[u8], b: &[u8]) -> bool {
+    a.chunks(8).zip(b.chunks(8)).any(|(a, b)| {
+        let a = load_word(a, 0);
+        let b = load_word(b, 0);
+        zero_bytes(a ^ b) & nonzero_bytes(a) != 0
+    })
+}
 
 // ```
 // ^^^^^^E
@@ -16,21 +74,78 @@ const CELL_NB: u8 = 6;
 //
 // S:Start, E:End
 // ```
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawBoard")]
 pub struct Board {
     w: usize,
     h: usize,
     cells: Vec<u8>,
 }
 
+// 読み込んだままの Board。大きさとセルが食い違っていれば Board にしない。
+#[derive(Deserialize)]
+struct RawBoard {
+    w: usize,
+    h: usize,
+    cells: Vec<u8>,
+}
+
+impl TryFrom<RawBoard> for Board {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawBoard) -> Result<Self> {
+        let RawBoard { w, h, cells } = raw;
+        anyhow::ensure!(w > 0 && h > 0, "invalid board size");
+        anyhow::ensure!(
+            w <= MAX_SIZE && h <= MAX_SIZE,
+            "board is too large: {}x{} (at most {}x{})",
+            w,
+            h,
+            MAX_SIZE,
+            MAX_SIZE
+        );
+        anyhow::ensure!(
+            cells.len() == w * h,
+            "board has {} cells for {}x{}",
+            cells.len(),
+            w,
+            h
+        );
+        if let Some(&color) = cells.iter().find(|&&c| c > MAX_COLORS) {
+            anyhow::bail!("invalid color: {}", color);
+        }
+        Ok(Self { w, h, cells })
+    }
+}
+
+// clone_from はセルの Vec を使い回すので、探索で盤面を写し直すたびに確保しなくてよい
+impl Clone for Board {
+    fn clone(&self) -> Self {
+        Self {
+            w: self.w,
+            h: self.h,
+            cells: self.cells.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.w = source.w;
+        self.h = source.h;
+        self.cells.clone_from(&source.cells);
+    }
+}
+
 impl Board {
     pub fn random(w: usize, h: usize) -> Self {
+        Self::random_with(w, h, DEFAULT_COLORS, &mut Pcg32::from_entropy())
+    }
+
+    pub fn random_with(w: usize, h: usize, colors: u8, rng: &mut dyn GameRng) -> Self {
         assert!(w > 0);
         assert!(h > 0);
+        assert!((1..=MAX_COLORS).contains(&colors));
 
-        let mut rng = rand::thread_rng();
-        let dist = rand::distributions::Uniform::new(1, CELL_NB);
-        let cells: Vec<_> = iter::repeat_with(|| dist.sample(&mut rng))
+        let cells: Vec<_> = iter::repeat_with(|| 1 + rng.below(u32::from(colors)) as u8)
             .take(w * h)
             .collect();
 
@@ -61,21 +176,80 @@ impl Board {
         };
         anyhow::ensure!(w > 0, "w must be positive");
         anyhow::ensure!(h > 0, "h must be positive");
+        // 確保する前に弾く
+        anyhow::ensure!(
+            w <= MAX_SIZE && h <= MAX_SIZE,
+            "board is too large: {}x{} (at most {}x{})",
+            w,
+            h,
+            MAX_SIZE,
+            MAX_SIZE
+        );
 
         let mut cells = vec![0_u8; w * h];
         let mut lines = rdr.lines();
         for y in 0..h {
             let line = lines.next().context("incomplete input")??;
-            for (x, c) in line.chars().enumerate() {
-                anyhow::ensure!(('0'..='5').contains(&c), "invalid char");
+            // 手で貼り付けた文字列も読めるよう、行末の空白は無視する
+            for (x, c) in line.trim_end().chars().enumerate() {
+                anyhow::ensure!(x < w, "line {} is longer than {}", y + 1, w);
+                let color = c
+                    .to_digit(10)
+                    .filter(|&d| d <= u32::from(MAX_COLORS))
+                    .context("invalid char")?;
                 let i = Self::xy2idx_h(h, x, y);
-                cells[i] = c.to_digit(10).expect("internal error") as u8;
+                cells[i] = color as u8;
             }
         }
 
         Ok(Self { w, h, cells })
     }
 
+    // parse で読める形式に書き出す
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", self.w, self.h);
+        for y in 0..self.h {
+            text.extend((0..self.w).map(|x| char::from(b'0' + self.at(x, y))));
+            text.push('\n');
+        }
+        text
+    }
+
+    // 共有用に詰めたバイト列。幅と高さ (u8) のあとに、parse と同じ順で 1 セル 4 ビットずつ並べる。
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![
+            u8::try_from(self.w).expect("width too large"),
+            u8::try_from(self.h).expect("height too large"),
+        ];
+        let cells: Vec<_> = iproduct!(0..self.h, 0..self.w)
+            .map(|(y, x)| self.at(x, y))
+            .collect();
+        buf.extend(
+            cells
+                .chunks(2)
+                .map(|c| c[0] << 4 | c.get(1).copied().unwrap_or(0)),
+        );
+        buf
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self> {
+        anyhow::ensure!(buf.len() >= 2, "packed board too short");
+        let (w, h) = (usize::from(buf[0]), usize::from(buf[1]));
+        anyhow::ensure!(w > 0 && h > 0, "invalid board size");
+        anyhow::ensure!(
+            buf.len() == 2 + (w * h + 1) / 2,
+            "packed board length does not match its size"
+        );
+
+        let mut cells = vec![0_u8; w * h];
+        let colors = buf[2..].iter().flat_map(|&b| vec![b >> 4, b & 0xf]);
+        for ((y, x), color) in iproduct!(0..h, 0..w).zip(colors) {
+            anyhow::ensure!(color <= MAX_COLORS, "invalid color: {}", color);
+            cells[Self::xy2idx_h(h, x, y)] = color;
+        }
+        Ok(Self { w, h, cells })
+    }
+
     pub fn width(&self) -> usize {
         self.w
     }
@@ -89,6 +263,144 @@ impl Board {
         self.cells[i]
     }
 
+    // 空でないセルの数
+    pub fn tile_count(&self) -> usize {
+        self.cells.iter().filter(|&&color| color != 0).count()
+    }
+
+    // 盤面の大きさと内容から決まる識別子 (FNV-1a)。既知の盤面を探すのに使う。
+    pub fn fingerprint(&self) -> u64 {
+        let dims = [self.w as u32, self.h as u32];
+        let bytes = dims.iter().flat_map(|d| d.to_le_bytes().to_vec());
+        hash::fnv1a(bytes.chain(self.cells.iter().copied()))
+    }
+
+    // 使っている色の数 (いちばん大きい色番号)。空の盤面なら 0。
+    pub fn colors(&self) -> u8 {
+        self.cells.iter().copied().max().unwrap_or(0)
+    }
+
+    // 色 color のセルの数
+    pub fn color_count(&self, color: u8) -> usize {
+        self.cells.iter().filter(|&&c| c == color).count()
+    }
+
+    // 1 セルの色を変える。落下や列の詰め直しはしない。
+    pub fn set(&mut self, x: usize, y: usize, color: u8) {
+        assert!(color <= MAX_COLORS);
+        self.replace(x, y, color);
+    }
+
+    // 宙に浮いたタイルを落とし、空になった列を詰める
+    pub fn settle(&mut self) {
+        self.pack();
+    }
+
+    // 時計回りに 90 度回し、落下と列の詰め直しをやり直す。幅と高さが入れ替わる。
+    pub fn rotate(&mut self) {
+        let (w, h) = (self.h, self.w);
+        let mut cells = vec![0; w * h];
+        for (x, y) in iproduct!(0..self.w, 0..self.h) {
+            // 左上が右上に来る
+            cells[Self::xy2idx_h(h, self.h - 1 - y, x)] = self.at(x, y);
+        }
+        self.w = w;
+        self.h = h;
+        self.cells = cells;
+        self.pack();
+    }
+
+    // 空でない列の並びを左右逆にした盤面。空の列は右に詰めたまま。
+    // 落下と列の詰め直しは左右どちらにも同じように働くので、元の盤面と同じ得点が取れる。
+    pub fn mirror_h(&self) -> Self {
+        let cols: Vec<_> = self
+            .cells
+            .chunks(self.h)
+            .filter(|col| col.iter().any(|&color| color != 0))
+            .collect();
+        let mut cells: Vec<_> = cols.iter().rev().flat_map(|col| col.to_vec()).collect();
+        cells.resize(self.w * self.h, 0);
+        Self {
+            w: self.w,
+            h: self.h,
+            cells,
+        }
+    }
+
+    // 左右逆にした盤面と比べて小さいほう。探索で左右対称な局面を 1 つにまとめるのに使う。
+    pub fn canonical(&self) -> Self {
+        let mirror = self.mirror_h();
+        if mirror.cells < self.cells {
+            mirror
+        } else {
+            self.clone()
+        }
+    }
+
+    // 色をセルの並び (列ごとに下から) で初めて出てくる順に 1, 2, ... と付け直した盤面。
+    // 得点はグループの大きさだけで決まるので、色の名前を入れ替えても取れる得点は変わらない。
+    pub fn relabel_colors(&self) -> Self {
+        let mut map = [0_u8; MAX_COLORS as usize + 1];
+        let mut next = 0;
+        let cells = self
+            .cells
+            .iter()
+            .map(|&color| {
+                let i = usize::from(color);
+                if color != 0 && map[i] == 0 {
+                    next += 1;
+                    map[i] = next;
+                }
+                map[i]
+            })
+            .collect();
+        Self {
+            w: self.w,
+            h: self.h,
+            cells,
+        }
+    }
+
+    // canonical と同じく左右逆を同じとみなし、さらに色の付け方の違いも同じとみなした盤面
+    pub fn canonical_colors(&self) -> Self {
+        let plain = self.relabel_colors();
+        let mirror = self.mirror_h().relabel_colors();
+        if mirror.cells < plain.cells {
+            mirror
+        } else {
+            plain
+        }
+    }
+
+    // 左右逆にしても同じ盤面か。そういう盤面では左右対称な手の片方だけ調べればよい。
+    pub fn is_symmetric_h(&self) -> bool {
+        self.mirror_h() == *self
+    }
+
+    // groups から、左右対称な盤面で左右逆の手と同じになるものを片方だけ残したもの。
+    // ソルバーは根の手をこれで作る。
+    pub fn distinct_groups(&self) -> Vec<((usize, usize), usize)> {
+        let groups = self.groups();
+        if !self.is_symmetric_h() {
+            return groups;
+        }
+
+        // 対称なら空でない列は左に詰まっている
+        let cols = self
+            .cells
+            .chunks(self.h)
+            .take_while(|col| col.iter().any(|&color| color != 0))
+            .count();
+        groups
+            .into_iter()
+            .filter(|&((x, y), _)| {
+                // 左右逆のグループを groups が先に見つけていれば、そちらを残す
+                let mirror = self.calc_component(cols - 1 - x, y);
+                mirror.into_iter().min().expect("internal error") >= (x, y)
+            })
+            .collect()
+    }
+
     fn replace(&mut self, x: usize, y: usize, color: u8) -> u8 {
         let i = self.xy2idx(x, y);
         mem::replace(&mut self.cells[i], color)
@@ -124,22 +436,58 @@ impl Board {
         res
     }
 
-    pub fn is_finished(&self) -> bool {
+    // 消せるグループを全て列挙する。各グループの代表セルと大きさを返す。
+    pub fn groups(&self) -> Vec<((usize, usize), usize)> {
+        let _scope = profiling::scope("board::groups");
+        let mut res = vec![];
+        let mut done = vec![false; self.w * self.h];
         for (x, y) in iproduct!(0..self.w, 0..self.h) {
-            if self.at(x, y) == 0 {
+            let color = self.at(x, y);
+            if color == 0 || done[self.xy2idx(x, y)] {
                 continue;
             }
-            if x > 0 && self.at(x, y) == self.at(x - 1, y) {
+
+            let mut n = 0;
+            let mut stack = vec![(x, y)];
+            done[self.xy2idx(x, y)] = true;
+            while let Some((cx, cy)) = stack.pop() {
+                n += 1;
+                for (xx, yy) in self.neighbor(cx, cy) {
+                    let i = self.xy2idx(xx, yy);
+                    if done[i] || self.at(xx, yy) != color {
+                        continue;
+                    }
+                    done[i] = true;
+                    stack.push((xx, yy));
+                }
+            }
+
+            if n >= 2 {
+                res.push(((x, y), n));
+            }
+        }
+        res
+    }
+
+    // 列ごとに、縦に隣り合うセルと左の列のセルを 8 セルずつまとめて比べる
+    pub fn is_finished(&self) -> bool {
+        let _scope = profiling::scope("board::is_finished");
+        let h = self.h;
+        let mut prev: Option<&[u8]> = None;
+        for col in self.cells.chunks_exact(h) {
+            if has_same_tile(&col[1..], &col[..h - 1]) {
                 return false;
             }
-            if y > 0 && self.at(x, y) == self.at(x, y - 1) {
+            if matches!(prev, Some(left) if has_same_tile(col, left)) {
                 return false;
             }
+            prev = Some(col);
         }
         true
     }
 
     pub fn erase_component(&mut self, x: usize, y: usize) -> usize {
+        let _scope = profiling::scope("board::erase_component");
         let color = self.at(x, y);
         if color == 0 {
             return 0;
@@ -164,49 +512,125 @@ impl Board {
             return 0;
         }
 
-        self.pack_cellwise();
-        self.pack_colwise();
+        self.pack();
+
+        res
+    }
+
+    // 指定色のタイルを全て消し、消した数を返す
+    pub fn erase_color(&mut self, color: u8) -> usize {
+        if color == 0 {
+            return 0;
+        }
+
+        let mut res = 0;
+        for cell in self.cells.iter_mut().filter(|cell| **cell == color) {
+            *cell = 0;
+            res += 1;
+        }
+
+        if res > 0 {
+            self.pack();
+        }
 
         res
     }
 
-    // セル単位での詰め直し(各列について落下処理)
+    // 隣接する 2 つのタイルの色を入れ替える。入れ替えられなければ false。
+    pub fn swap(&mut self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> bool {
+        if !self.neighbor(x1, y1).contains(&(x2, y2)) {
+            return false;
+        }
+        let (c1, c2) = (self.at(x1, y1), self.at(x2, y2));
+        if c1 == 0 || c2 == 0 || c1 == c2 {
+            return false;
+        }
+
+        self.replace(x1, y1, c2);
+        self.replace(x2, y2, c1);
+        true
+    }
+
+    // 空でないセルの色をシャッフルする(空セルの位置は変わらない)
+    pub fn shuffle(&mut self, rng: &mut dyn GameRng) {
+        let idxs: Vec<_> = (0..self.cells.len())
+            .filter(|&i| self.cells[i] != 0)
+            .collect();
+        self.shuffle_cells(&idxs, rng);
+    }
+
+    // (x, y) を左上とする w x h の範囲だけシャッフルする。盤面からはみ出す分は無視する。
+    pub fn shuffle_region(
+        &mut self,
+        (x, y): (usize, usize),
+        (w, h): (usize, usize),
+        rng: &mut dyn GameRng,
+    ) {
+        let xs = x.min(self.w)..(x + w).min(self.w);
+        let ys = y.min(self.h)..(y + h).min(self.h);
+        let idxs: Vec<_> = iproduct!(xs, ys)
+            .map(|(x, y)| self.xy2idx(x, y))
+            .filter(|&i| self.cells[i] != 0)
+            .collect();
+        self.shuffle_cells(&idxs, rng);
+    }
+
+    fn shuffle_cells(&mut self, idxs: &[usize], rng: &mut dyn GameRng) {
+        let mut colors: Vec<_> = idxs.iter().map(|&i| self.cells[i]).collect();
+        // Fisher-Yates
+        for i in (1..colors.len()).rev() {
+            let j = rng.below(i as u32 + 1) as usize;
+            colors.swap(i, j);
+        }
+        for (&i, color) in idxs.iter().zip(colors) {
+            self.cells[i] = color;
+        }
+    }
+
+    fn pack(&mut self) {
+        let _scope = profiling::scope("board::pack");
+        self.pack_cellwise();
+        self.pack_colwise();
+    }
+
+    // セル単位での詰め直し(各列について落下処理)。
+    // 下から続くタイルの上に空きがなければ、その列はそのままでよい。
     fn pack_cellwise(&mut self) {
         for col in self.cells.chunks_exact_mut(self.h) {
+            let hole = match first_empty(col) {
+                Some(hole) => hole,
+                None => continue,
+            };
+            if !has_tile(&col[hole..]) {
+                continue;
+            }
             // stable_partition
-            let mut i = 0;
-            for j in 0..self.h {
+            let mut i = hole;
+            for j in hole + 1..col.len() {
                 if col[j] != 0 {
-                    col.swap(i, j);
+                    col[i] = col[j];
                     i += 1;
                 }
             }
+            col[i..].fill(0);
         }
     }
 
-    // 列単位での詰め直し(空になった列を詰める)
+    // 列単位での詰め直し(空になった列を詰める)。
+    // 落下処理の後なので、一番下が空の列は空。
     fn pack_colwise(&mut self) {
+        let h = self.h;
         let mut x_target = 0;
         for x in 0..self.w {
-            let i_target = self.xy2idx(x_target, self.h - 1);
-            let (vl, vr) = {
-                let i = self.xy2idx(x, self.h - 1);
-                self.cells.split_at_mut(i)
-            };
-
-            let col = &mut vr[..self.h];
-            let empty = col.iter().all(|&color| color == 0);
-            if !empty {
-                if x_target != x {
-                    let col_target = &mut vl[i_target..i_target + self.h];
-                    col_target.copy_from_slice(col);
-                    for i in 0..self.h {
-                        col[i] = 0;
-                    }
-                }
-                x_target += 1;
+            if self.cells[h * x] == 0 {
+                continue;
+            }
+            if x_target != x {
+                self.cells.copy_within(h * x..h * (x + 1), h * x_target);
             }
+            x_target += 1;
         }
+        self.cells[h * x_target..].fill(0);
     }
 
     fn neighbor(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
@@ -239,12 +663,65 @@ impl Board {
 mod tests {
     use super::*;
 
+    #[test]
+    fn word_ops() {
+        assert_eq!(first_empty(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 0]), Some(9));
+        assert_eq!(first_empty(&[1, 2, 3]), None);
+        assert!(!has_tile(&[0; 11]));
+        assert!(has_tile(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]));
+        assert!(!has_same_tile(
+            &[0, 1, 2, 0, 0, 0, 0, 0, 0, 4],
+            &[0, 2, 1, 0, 0, 0, 0, 0, 0, 3]
+        ));
+        assert!(has_same_tile(
+            &[0, 1, 2, 0, 0, 0, 0, 0, 0, 4],
+            &[0, 2, 1, 0, 0, 0, 0, 0, 0, 4]
+        ));
+
+        // 1 セルずつ調べたときと同じ結果になる
+        let mut rng = Pcg32::seed_from_u64(1);
+        for _ in 0..100 {
+            let mut board = Board::random_with(13, 11, 3, &mut rng);
+            while let Some(&((x, y), _)) = board.groups().first() {
+                let cells = board.calc_component(x, y);
+                let mut expected = board.clone();
+                for &(x, y) in &cells {
+                    expected.replace(x, y, 0);
+                }
+                let mut cols: Vec<Vec<u8>> = expected
+                    .cells
+                    .chunks(expected.h)
+                    .map(|col| col.iter().copied().filter(|&c| c != 0).collect())
+                    .filter(|col: &Vec<u8>| !col.is_empty())
+                    .collect();
+                cols.resize(expected.w, vec![]);
+                for col in &mut cols {
+                    col.resize(expected.h, 0);
+                }
+                expected.cells = cols.concat();
+
+                board.erase_component(x, y);
+                assert_eq!(board, expected);
+                let finished = iproduct!(0..board.w, 0..board.h).all(|(x, y)| {
+                    let c = board.at(x, y);
+                    c == 0
+                        || (x == 0 || board.at(x - 1, y) != c)
+                            && (y == 0 || board.at(x, y - 1) != c)
+                });
+                assert_eq!(board.is_finished(), finished);
+            }
+        }
+    }
+
     #[test]
     fn random() {
         let board = Board::random(3, 14);
         assert_eq!(board.width(), 3);
         assert_eq!(board.height(), 14);
-        assert!(board.cells.iter().all(|color| (1..=5).contains(color)));
+        assert!(board
+            .cells
+            .iter()
+            .all(|color| (1..=DEFAULT_COLORS).contains(color)));
     }
 
     #[test]
@@ -262,6 +739,104 @@ mod tests {
         assert_eq!(board.width(), 4);
         assert_eq!(board.height(), 3);
         assert_eq!(board.cells, [2, 1, 0, 3, 2, 1, 4, 3, 2, 5, 4, 3]);
+
+        // 2 色だけの盤面も読める
+        let board = Board::parse("3 2\n121\n212\n".as_bytes()).unwrap();
+        assert_eq!(board.color_count(1), 3);
+        assert_eq!(board.color_count(2), 3);
+        assert!(Board::parse("2 1\n19\n".as_bytes()).is_err());
+        assert!(Board::parse("2 1\n1a\n".as_bytes()).is_err());
+        assert!(Board::parse("99999 99999\n".as_bytes()).is_err());
+        assert!(Board::parse("256 1\n1\n".as_bytes()).is_err());
+        assert!(Board::parse("2 1\n123\n".as_bytes()).is_err());
+        assert!(Board::parse("2 1\r\n12\r\n".as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn bytes() {
+        let board = Board::parse("3 3\n012\n345\n678\n".as_bytes()).unwrap();
+        let buf = board.to_bytes();
+        assert_eq!(buf, [3, 3, 0x01, 0x23, 0x45, 0x67, 0x80]);
+        assert_eq!(Board::from_bytes(&buf).unwrap(), board);
+
+        assert!(Board::from_bytes(&buf[..6]).is_err());
+        assert!(Board::from_bytes(&[0, 3]).is_err());
+        assert!(Board::from_bytes(&[1, 1, 0x90]).is_err());
+    }
+
+    #[test]
+    fn to_text() {
+        let text = "4 3\n0123\n1234\n2345\n";
+        let board = Board::parse(text.as_bytes()).unwrap();
+        assert_eq!(board.to_text(), text);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut board = Board::parse("3 2\n123\n450\n".as_bytes()).unwrap();
+        board.rotate();
+        assert_eq!(board, Board::parse("2 3\n01\n42\n53\n".as_bytes()).unwrap());
+
+        // 空いた列は詰める
+        let mut board = Board::parse("2 2\n10\n20\n".as_bytes()).unwrap();
+        board.rotate();
+        assert_eq!(board, Board::parse("2 2\n00\n21\n".as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn mirror() {
+        let board = Board::parse("4 2\n1200\n3340\n".as_bytes()).unwrap();
+        let mirror = board.mirror_h();
+        assert_eq!(
+            mirror,
+            Board::parse("4 2\n0210\n4330\n".as_bytes()).unwrap()
+        );
+        assert_eq!(mirror.mirror_h(), board);
+        assert_eq!(board.canonical(), mirror.canonical());
+        assert!(!board.is_symmetric_h());
+        assert!(Board::parse("3 2\n010\n121\n".as_bytes())
+            .unwrap()
+            .is_symmetric_h());
+
+        // 左右逆の盤面で左右逆の手を指せば、結果も左右逆になる
+        let board = Board::parse("4 3\n1211\n2231\n1321\n".as_bytes()).unwrap();
+        let mut erased = board.clone();
+        let n = erased.erase_component(3, 0);
+        let mut mirror = board.mirror_h();
+        assert_eq!(mirror.erase_component(0, 0), n);
+        assert_eq!(mirror, erased.mirror_h());
+        assert_eq!(board.distinct_groups(), board.groups());
+
+        // 対称な盤面では左右逆の手の片方だけを残す
+        let board = Board::parse("5 2\n12210\n13310\n".as_bytes()).unwrap();
+        assert!(board.is_symmetric_h());
+        assert_eq!(board.groups().len(), 4);
+        let distinct = board.distinct_groups();
+        assert_eq!(distinct.len(), 3);
+        assert!(distinct.iter().any(|&((x, _), _)| x == 0));
+        assert!(distinct.iter().all(|&((x, _), _)| x != 3));
+    }
+
+    #[test]
+    fn relabel() {
+        let board = Board::parse("3 2\n310\n332\n".as_bytes()).unwrap();
+        let other = Board::parse("3 2\n120\n113\n".as_bytes()).unwrap();
+        assert_ne!(board.canonical(), other.canonical());
+        assert_eq!(board.canonical_colors(), other.canonical_colors());
+        assert_eq!(
+            board.relabel_colors().relabel_colors(),
+            board.relabel_colors()
+        );
+
+        // 左右逆で色も違う盤面
+        let mirrored = Board::parse("3 2\n023\n133\n".as_bytes()).unwrap();
+        assert_eq!(board.canonical_colors(), mirrored.canonical_colors());
+        assert_ne!(
+            board.canonical_colors(),
+            Board::parse("3 2\n110\n332\n".as_bytes())
+                .unwrap()
+                .canonical_colors()
+        );
     }
 
     #[test]
@@ -283,6 +858,7 @@ mod tests {
             [(0, 1), (1, 0), (1, 1), (1, 2)]
         );
         assert!(!board.is_finished());
+        assert_eq!(board.groups(), [((0, 1), 4)]);
 
         assert_eq!(board.erase_component(0, 0), 0);
         assert_eq!(board.erase_component(3, 0), 0);
@@ -290,4 +866,112 @@ mod tests {
         assert_eq!(board.cells, [5, 2, 0, 3, 5, 0, 5, 4, 2, 0, 0, 0]);
         assert!(board.is_finished());
     }
+
+    #[test]
+    fn erase_color() {
+        let mut board = Board::parse(
+            b"\
+3 3
+121
+213
+121
+"
+            .as_ref(),
+        )
+        .unwrap();
+
+        assert_eq!(board.erase_color(4), 0);
+        assert_eq!(board.erase_color(1), 5);
+        assert_eq!(board.cells, [2, 0, 0, 2, 2, 0, 3, 0, 0]);
+    }
+
+    #[test]
+    fn swap() {
+        let mut board = Board::parse(
+            b"\
+3 2
+120
+312
+"
+            .as_ref(),
+        )
+        .unwrap();
+        assert!(board.is_finished());
+
+        assert!(!board.swap((0, 0), (1, 1)));
+        assert!(!board.swap((0, 0), (2, 0)));
+        assert!(!board.swap((1, 0), (2, 0)));
+        assert!(board.swap((0, 0), (0, 1)));
+        assert_eq!(board.cells, [1, 3, 1, 2, 2, 0]);
+        assert!(!board.is_finished());
+    }
+
+    #[test]
+    fn set_and_settle() {
+        let mut board = Board::parse(
+            b"\
+3 2
+000
+000
+"
+            .as_ref(),
+        )
+        .unwrap();
+        board.set(1, 0, 2);
+        board.set(2, 1, 3);
+        assert_eq!(board.at(1, 0), 2);
+        assert_eq!(board.at(1, 1), 0);
+
+        board.settle();
+        assert_eq!(board.at(0, 1), 2);
+        assert_eq!(board.at(1, 1), 3);
+        assert_eq!(board.at(0, 0), 0);
+        assert_eq!(board.tile_count(), 2);
+    }
+
+    #[test]
+    fn shuffle_region() {
+        let mut board = Board::parse(
+            b"\
+4 3
+1000
+2340
+5123
+"
+            .as_ref(),
+        )
+        .unwrap();
+        let before = board.clone();
+        board.shuffle_region((1, 1), (5, 5), &mut Pcg32::seed_from_u64(0));
+
+        // 範囲の外と空セルはそのまま
+        for (x, y) in iproduct!(0..4, 0..3) {
+            if x == 0 || y == 0 || before.at(x, y) == 0 {
+                assert_eq!(board.at(x, y), before.at(x, y));
+            }
+        }
+        let mut colors: Vec<_> = iproduct!(1..4, 1..3).map(|(x, y)| board.at(x, y)).collect();
+        colors.sort();
+        assert_eq!(colors, [0, 1, 2, 3, 3, 4]);
+    }
+
+    #[test]
+    fn shuffle() {
+        let mut board = Board::parse(
+            b"\
+4 2
+1200
+3345
+"
+            .as_ref(),
+        )
+        .unwrap();
+        board.shuffle(&mut Pcg32::seed_from_u64(0));
+
+        assert_eq!(board.at(2, 0), 0);
+        assert_eq!(board.at(3, 0), 0);
+        let mut colors = board.cells.clone();
+        colors.sort();
+        assert_eq!(colors, [0, 0, 1, 2, 3, 3, 4, 5]);
+    }
 }
@@ -0,0 +1,138 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use crate::board;
+use crate::game::GameConfig;
+use crate::rules::Rules;
+
+// 盤面サイズ・色数・ルール・シードを詰めた共有用コード。
+//
+// ```
+// version:u8 width:u8 height:u8 colors:u8 rules:u8 seed:u64(LE)
+// ```
+//
+// を URL-safe base64 (パディングなし) で表す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Challenge {
+    pub config: GameConfig,
+    pub seed: u64,
+}
+
+impl Challenge {
+    const VERSION: u8 = 1;
+    const LEN: usize = 13;
+
+    // 幅か高さが 255 を超える盤面はコードにできない
+    pub fn encode(&self) -> Option<String> {
+        let mut buf = Vec::with_capacity(Self::LEN);
+        buf.push(Self::VERSION);
+        buf.push(u8::try_from(self.config.width).ok()?);
+        buf.push(u8::try_from(self.config.height).ok()?);
+        buf.push(self.config.colors);
+        buf.push(self.config.rules.to_bits());
+        buf.extend_from_slice(&self.seed.to_le_bytes());
+
+        Some(base64::encode_config(&buf, base64::URL_SAFE_NO_PAD))
+    }
+
+    pub fn decode(s: &str) -> Result<Self> {
+        let buf = base64::decode_config(s.trim(), base64::URL_SAFE_NO_PAD)
+            .context("invalid challenge code")?;
+        anyhow::ensure!(buf.len() == Self::LEN, "invalid challenge code length");
+        anyhow::ensure!(
+            buf[0] == Self::VERSION,
+            "unsupported challenge code version: {}",
+            buf[0]
+        );
+
+        let width = usize::from(buf[1]);
+        let height = usize::from(buf[2]);
+        let colors = buf[3];
+        anyhow::ensure!(width > 0 && height > 0, "invalid board size");
+        anyhow::ensure!(
+            (1..=board::MAX_COLORS).contains(&colors),
+            "invalid color count"
+        );
+        let rules = Rules::from_bits(buf[4]).context("unknown rules")?;
+
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&buf[5..]);
+        let seed = u64::from_le_bytes(seed);
+
+        Ok(Self {
+            config: GameConfig {
+                width,
+                height,
+                colors,
+                rules,
+            },
+            seed,
+        })
+    }
+}
+
+impl FromStr for Challenge {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::decode(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::board::Board;
+
+    #[test]
+    fn roundtrip() {
+        let challenge = Challenge {
+            config: GameConfig {
+                width: 15,
+                height: 15,
+                colors: 4,
+                rules: Rules {
+                    streak: true,
+                    time_pressure: false,
+                    rotate: false,
+                    earthquake: false,
+                },
+            },
+            seed: 0x0123_4567_89ab_cdef,
+        };
+        let code = challenge.encode().unwrap();
+        assert_eq!(code.len(), 18);
+        assert_eq!(code.parse::<Challenge>().unwrap(), challenge);
+
+        assert!(Challenge::decode("").is_err());
+        assert!(Challenge::decode("!!!!").is_err());
+        assert!(Challenge::decode(&code[..16]).is_err());
+
+        let wide = Challenge {
+            config: GameConfig {
+                width: 256,
+                ..challenge.config
+            },
+            ..challenge
+        };
+        assert_eq!(wide.encode(), None);
+
+        // 開いたり貼り付けたりできる一番大きな盤面もコードにできる
+        let text = format!("{} 1\n{}\n", board::MAX_SIZE, "1".repeat(board::MAX_SIZE));
+        let largest = Board::parse(text.as_bytes()).unwrap();
+        let largest = Challenge {
+            config: GameConfig {
+                width: largest.width(),
+                height: largest.height(),
+                ..challenge.config
+            },
+            ..challenge
+        };
+        assert!(largest.encode().is_some());
+        let text = format!("{} 1\n1\n", board::MAX_SIZE + 1);
+        assert!(Board::parse(text.as_bytes()).is_err());
+    }
+}
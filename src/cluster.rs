@@ -0,0 +1,515 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::board::Board;
+use crate::eval::Weights;
+use crate::game::{Game, GameConfig};
+use crate::monitor::Monitor;
+use crate::notation;
+use crate::rules;
+use crate::solver::{Solution, SolverKind};
+
+// 親 (coordinator) が子 (worker) に解かせる 1 つの盤面
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Task {
+    pub id: usize,
+    pub board: Board,
+    pub solver: String,
+    pub seed: u64,
+    pub budget_ms: u64,
+}
+
+// 親から子への知らせ。子からの答えとともに 1 行に 1 つの JSON で送る。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Request {
+    Task(Task),
+    // もう解かせる盤面がない
+    Done,
+}
+
+// 子が解いた手順。得点は親が打ち直して数える。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Answer {
+    pub id: usize,
+    pub moves: Vec<(usize, usize)>,
+}
+
+// 配る盤面と、その盤面に着くまでに親が指した手
+#[derive(Debug, Clone)]
+pub struct Shard {
+    pub prefix: Vec<(usize, usize)>,
+    pub board: Board,
+    pub seed: u64,
+}
+
+// board を最初の手ごとに分ける
+pub fn root_shards(board: &Board) -> Vec<Shard> {
+    board
+        .groups()
+        .into_iter()
+        .map(|((x, y), _)| {
+            let mut after = board.clone();
+            after.erase_component(x, y);
+            Shard {
+                prefix: vec![(x, y)],
+                board: after,
+                seed: 0,
+            }
+        })
+        .collect()
+}
+
+// board 全体を seed を変えて n 回解かせる (NRPA などのやり直し)
+pub fn restart_shards(board: &Board, n: u64) -> Vec<Shard> {
+    (0..n)
+        .map(|seed| Shard {
+            prefix: vec![],
+            board: board.clone(),
+            seed,
+        })
+        .collect()
+}
+
+// 手順を打ち直した得点。消せない手があれば None。
+pub fn score_line(board: &Board, moves: &[(usize, usize)]) -> Option<i32> {
+    let mut board = board.clone();
+    let mut score = 0;
+    for &(x, y) in moves {
+        if x >= board.width() || y >= board.height() || board.calc_component(x, y).len() < 2 {
+            return None;
+        }
+        score += rules::group_score(board.erase_component(x, y));
+    }
+    Some(score)
+}
+
+// 配っている盤面。子が途中で切れたら解かせていた盤面を戻す。
+#[derive(Debug, Default)]
+struct Queue {
+    tasks: VecDeque<Task>,
+    running: usize,
+    // つながっている子の数と、それが 0 になった時刻
+    workers: usize,
+    orphaned: Option<Instant>,
+}
+
+impl Queue {
+    // 次に解かせる盤面。今はないがほかの子の分が戻るかもしれなければ Ok(None)、もう何もなければ Err(())。
+    fn take(&mut self) -> std::result::Result<Option<Task>, ()> {
+        match self.tasks.pop_front() {
+            Some(task) => {
+                self.running += 1;
+                Ok(Some(task))
+            }
+            None if self.running > 0 => Ok(None),
+            None => Err(()),
+        }
+    }
+}
+
+// 1 つの子に盤面を渡し続ける。持ち時間に grace を足しても答えない子は切って、盤面を戻す。
+fn serve(
+    stream: TcpStream,
+    queue: &Mutex<Queue>,
+    answers: &Sender<Answer>,
+    grace: Duration,
+) -> Result<()> {
+    const WAIT: Duration = Duration::from_millis(100);
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let task = match queue.lock().expect("internal error").take() {
+            Ok(Some(task)) => task,
+            Ok(None) => {
+                thread::sleep(WAIT);
+                continue;
+            }
+            Err(()) => {
+                send(&mut writer, &Request::Done)?;
+                return Ok(());
+            }
+        };
+
+        let timeout = Duration::from_millis(task.budget_ms) + grace;
+        let result = send(&mut writer, &Request::Task(task.clone())).and_then(|_| {
+            writer.set_read_timeout(Some(timeout))?;
+            let answer: Answer = receive(&mut reader)?;
+            anyhow::ensure!(answer.id == task.id, "worker answered another task");
+            Ok(answer)
+        });
+        let mut queue = queue.lock().expect("internal error");
+        queue.running -= 1;
+        match result {
+            Ok(answer) => {
+                // 親が集め終えていれば送れなくてよい
+                let _ = answers.send(answer);
+            }
+            Err(e) => {
+                queue.tasks.push_back(task);
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn send<W: Write, T: Serialize>(w: &mut W, value: &T) -> Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    w.write_all(&line)?;
+    w.flush()?;
+    Ok(())
+}
+
+fn receive<R: BufRead, T: for<'de> Deserialize<'de>>(r: &mut R) -> Result<T> {
+    let mut line = String::new();
+    anyhow::ensure!(r.read_line(&mut line)? > 0, "connection closed");
+    Ok(serde_json::from_str(&line)?)
+}
+
+// listener に来た子に tasks を配り、答えを届いた順に on_answer に渡す。全部の答えが揃ったら返る。
+// 最初の子が来るまではいつまでも待つが、来たあとで子が 1 つもいない時間が idle 続いたら諦める。
+pub fn coordinate<F: FnMut(Answer)>(
+    listener: TcpListener,
+    tasks: Vec<Task>,
+    idle: Duration,
+    grace: Duration,
+    mut on_answer: F,
+) -> Result<()> {
+    const WAIT: Duration = Duration::from_millis(100);
+    let total = tasks.len();
+    let queue = Arc::new(Mutex::new(Queue {
+        tasks: tasks.into(),
+        ..Queue::default()
+    }));
+    let (tx, rx) = mpsc::channel();
+    {
+        let queue = Arc::clone(&queue);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        eprintln!("cannot accept a worker: {}", e);
+                        continue;
+                    }
+                };
+                let queue = Arc::clone(&queue);
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    {
+                        let mut queue = queue.lock().expect("internal error");
+                        queue.workers += 1;
+                        queue.orphaned = None;
+                    }
+                    let peer = stream
+                        .peer_addr()
+                        .map_or_else(|_| "?".to_owned(), |addr| addr.to_string());
+                    if let Err(e) = serve(stream, &queue, &tx, grace) {
+                        eprintln!("worker {} dropped: {:#}", peer, e);
+                    }
+                    let mut queue = queue.lock().expect("internal error");
+                    queue.workers -= 1;
+                    if queue.workers == 0 {
+                        queue.orphaned = Some(Instant::now());
+                    }
+                });
+            }
+        });
+    }
+
+    let mut received = 0;
+    while received < total {
+        match rx.recv_timeout(WAIT) {
+            Ok(answer) => {
+                on_answer(answer);
+                received += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let orphaned = queue.lock().expect("internal error").orphaned;
+                if matches!(orphaned, Some(t) if t.elapsed() >= idle) {
+                    anyhow::bail!(
+                        "no workers left ({} of {} tasks unsolved)",
+                        total - received,
+                        total
+                    );
+                }
+            }
+            // 子を受け付けるスレッドが送り手を持ち続けるので来ない
+            Err(RecvTimeoutError::Disconnected) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+// 親から盤面をもらって解き、親がもう何もないと言うまで続ける。解いた数を返す。
+pub fn work(addr: &str) -> Result<usize> {
+    let stream = TcpStream::connect(addr).with_context(|| format!("cannot connect to {}", addr))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut solved = 0;
+    loop {
+        let task = match receive(&mut reader)? {
+            Request::Task(task) => task,
+            Request::Done => return Ok(solved),
+        };
+        let solver: SolverKind = task.solver.parse()?;
+        // 持ち時間が大きすぎれば締め切りなしで解く
+        let deadline = Instant::now().checked_add(Duration::from_millis(task.budget_ms));
+        let mut monitor = Monitor::new(deadline);
+        let solution = solver.solve(&task.board, Weights::default(), task.seed, &mut monitor);
+        send(
+            &mut writer,
+            &Answer {
+                id: task.id,
+                moves: solution.moves,
+            },
+        )?;
+        solved += 1;
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CoordinatorOpt {
+    /// Address to accept workers on (only this machine by default;
+    /// pass e.g. 0.0.0.0:7878 to let other machines join)
+    #[structopt(long, default_value = "127.0.0.1:7878")]
+    listen: String,
+
+    /// Solve the default-sized board with this seed
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Solver the workers run (see compare --solver)
+    #[structopt(long, default_value = "nrpa")]
+    solver: SolverKind,
+
+    /// Time budget of each task, in milliseconds
+    #[structopt(long, default_value = "60000")]
+    budget_ms: u64,
+
+    /// Hand out this many whole-board restarts with different seeds
+    /// instead of one task per first move
+    #[structopt(long)]
+    restarts: Option<u64>,
+
+    /// Give up when no worker has been connected for this many seconds
+    /// (the first worker is waited for indefinitely)
+    #[structopt(long, default_value = "60")]
+    idle_secs: u64,
+
+    /// Drop a worker and hand its task to another one when it has not
+    /// answered this many seconds after the task's time budget
+    #[structopt(long, default_value = "30")]
+    grace_secs: u64,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct WorkerOpt {
+    /// Coordinator to take tasks from (host:port)
+    connect: String,
+}
+
+pub fn run_coordinator(opt: &CoordinatorOpt) -> Result<()> {
+    let board = Game::new(opt.seed, GameConfig::default()).board().clone();
+    let shards = match opt.restarts {
+        Some(n) => restart_shards(&board, n),
+        None => root_shards(&board),
+    };
+    anyhow::ensure!(!shards.is_empty(), "nothing to solve");
+    let tasks = shards
+        .iter()
+        .enumerate()
+        .map(|(id, shard)| Task {
+            id,
+            board: shard.board.clone(),
+            solver: opt.solver.to_string(),
+            seed: shard.seed,
+            budget_ms: opt.budget_ms,
+        })
+        .collect();
+
+    let listener = TcpListener::bind(&opt.listen)
+        .with_context(|| format!("cannot listen on {}", opt.listen))?;
+    eprintln!(
+        "waiting for workers on {} ({} tasks)",
+        listener.local_addr()?,
+        shards.len()
+    );
+    let mut best = Solution::default();
+    let mut done = 0;
+    let idle = Duration::from_secs(opt.idle_secs);
+    let grace = Duration::from_secs(opt.grace_secs);
+    coordinate(listener, tasks, idle, grace, |answer| {
+        done += 1;
+        let shard = &shards[answer.id];
+        let line: Vec<_> = shard.prefix.iter().chain(&answer.moves).copied().collect();
+        match score_line(&board, &line) {
+            Some(score) => {
+                if score > best.score {
+                    best = Solution { moves: line, score };
+                }
+                eprintln!(
+                    "task {}/{}: {} (best {})",
+                    done,
+                    shards.len(),
+                    score,
+                    best.score
+                );
+            }
+            None => eprintln!("task {}/{}: invalid line", done, shards.len()),
+        }
+    })?;
+
+    let h = board.height();
+    let line: Vec<_> = best
+        .moves
+        .iter()
+        .map(|&(x, y)| notation::format_square(x, y, h))
+        .collect();
+    println!("best: {}\nline: {}", best.score, line.join(" "));
+    Ok(())
+}
+
+pub fn run_worker(opt: &WorkerOpt) -> Result<()> {
+    let solved = work(&opt.connect)?;
+    eprintln!("solved {} tasks", solved);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    #[test]
+    fn coordinate() {
+        let mut rng = Pcg32::seed_from_u64(1);
+        let board = Board::random_with(6, 5, 3, &mut rng);
+        let shards = root_shards(&board);
+        let tasks: Vec<_> = shards
+            .iter()
+            .enumerate()
+            .map(|(id, shard)| Task {
+                id,
+                board: shard.board.clone(),
+                solver: "greedy".to_owned(),
+                seed: shard.seed,
+                budget_ms: 1000,
+            })
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let workers: Vec<_> = (0..2)
+            .map(|_| {
+                let addr = addr.clone();
+                thread::spawn(move || work(&addr).unwrap())
+            })
+            .collect();
+
+        let mut answers = vec![];
+        let idle = Duration::from_secs(60);
+        let grace = Duration::from_secs(30);
+        super::coordinate(listener, tasks.clone(), idle, grace, |answer| {
+            answers.push(answer)
+        })
+        .unwrap();
+        let solved: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+        assert_eq!(solved, shards.len());
+        answers.sort_by_key(|a| a.id);
+        assert!(answers.iter().enumerate().all(|(i, a)| a.id == i));
+
+        // 最初の手ごとの答えのうち最良は、盤面全体を貪欲に解いたもの以上
+        let best = answers
+            .iter()
+            .map(|a| {
+                let line: Vec<_> = shards[a.id]
+                    .prefix
+                    .iter()
+                    .chain(&a.moves)
+                    .copied()
+                    .collect();
+                score_line(&board, &line).unwrap()
+            })
+            .max()
+            .unwrap();
+        let greedy =
+            SolverKind::Greedy.solve(&board, Weights::default(), 0, &mut Monitor::new(None));
+        assert!(best >= greedy.score);
+
+        assert_eq!(score_line(&board, &[(99, 0)]), None);
+        assert_eq!(restart_shards(&board, 3).len(), 3);
+
+        // 子が全部切れたら待ち続けずに諦める
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let quitter = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(stream);
+            let _: Request = receive(&mut reader).unwrap();
+        });
+        let idle = Duration::from_millis(200);
+        let res = super::coordinate(listener, tasks.clone(), idle, grace, |_| {});
+        quitter.join().unwrap();
+        assert!(res.unwrap_err().to_string().contains("no workers left"));
+
+        // 答えない子は持ち時間が過ぎたら切られ、その盤面はほかの子に回る
+        let tasks: Vec<_> = tasks
+            .into_iter()
+            .map(|task| Task {
+                budget_ms: 10,
+                ..task
+            })
+            .collect();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let staller = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(stream);
+            let _: Request = receive(&mut reader).unwrap();
+            tx.send(()).unwrap();
+            receive::<_, Request>(&mut reader).unwrap_err()
+        });
+        let worker = thread::spawn(move || {
+            rx.recv().unwrap();
+            work(&addr.to_string()).unwrap()
+        });
+        let mut received = 0;
+        let idle = Duration::from_secs(60);
+        let grace = Duration::from_millis(200);
+        super::coordinate(listener, tasks.clone(), idle, grace, |_| received += 1).unwrap();
+        assert_eq!(received, tasks.len());
+        assert_eq!(worker.join().unwrap(), tasks.len());
+        assert!(staller
+            .join()
+            .unwrap()
+            .to_string()
+            .contains("connection closed"));
+
+        // 大きさとセルの数が合わない盤面は読み込めない
+        let mut json = serde_json::to_value(&tasks[0]).unwrap();
+        json["board"]["w"] = 99.into();
+        assert!(serde_json::from_value::<Task>(json).is_err());
+
+        // 子は壊れた盤面を解こうとせずにエラーで返る
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let worker = thread::spawn(move || work(&addr));
+        let (mut stream, _) = listener.accept().unwrap();
+        let line = r#"{"Task":{"id":0,"board":{"w":3,"h":3,"cells":[1]},"solver":"greedy","seed":0,"budget_ms":10}}"#;
+        writeln!(stream, "{}", line).unwrap();
+        assert!(worker.join().unwrap().is_err());
+    }
+}
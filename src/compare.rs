@@ -0,0 +1,285 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result};
+use structopt::StructOpt;
+
+use crate::batch::BoardSetOpt;
+use crate::eval::Weights;
+use crate::game::Game;
+use crate::monitor::{CancelToken, Monitor, Progress};
+use crate::notation;
+use crate::solver::{SearchCache, Solution, SolverKind};
+
+#[derive(Debug, StructOpt)]
+pub struct CompareOpt {
+    #[structopt(flatten)]
+    boards: BoardSetOpt,
+
+    /// Solvers to compare: random, greedy, beam[:<width>], beamstack[:<width>], dfbnb[:relabel][:<MiB>],
+    /// nrpa[:<level>[:<iterations>]], mcts[:uct[:<c>]] or mcts[:sp[:<c>:<d>]]
+    #[structopt(long = "solver", required = true)]
+    solvers: Vec<SolverKind>,
+
+    /// Time budget per board for each solver, in milliseconds
+    #[structopt(long, default_value = "1000")]
+    budget_ms: u64,
+
+    /// JSON file of evaluation weights for the beam solvers
+    /// (group_size, color_count, tabu_color; omitted ones are 0)
+    #[structopt(long, parse(from_os_str))]
+    weights: Option<PathBuf>,
+
+    /// Show a live status line on stderr while the solvers run
+    #[structopt(long)]
+    progress: bool,
+
+    /// Directory to keep dfbnb search caches in (one file per board),
+    /// so later runs on the same boards resume where this one stopped
+    #[structopt(long, parse(from_os_str))]
+    cache: Option<PathBuf>,
+
+    /// Also save each dfbnb search to --cache every this many seconds while it runs
+    #[structopt(long, requires = "cache")]
+    checkpoint_secs: Option<u64>,
+
+    /// Continue each dfbnb search from where the last run on the board stopped,
+    /// instead of starting over with only its table and best line
+    #[structopt(long, requires = "cache")]
+    resume: bool,
+}
+
+// 1 ソルバー分の集計
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub solver: SolverKind,
+    pub mean_score: f64,
+    // 最高得点(同点を含む)を出した盤面の数
+    pub wins: usize,
+    pub clear_rate: f64,
+    pub mean_time: Duration,
+}
+
+// scores[i][j]: i 番目のソルバーが j 番目の盤面で出した得点と全消ししたかどうか
+pub fn summarize(
+    solvers: &[SolverKind],
+    results: &[Vec<(Solution, bool)>],
+    times: &[Duration],
+) -> Vec<Row> {
+    let boards = results.first().map_or(0, Vec::len);
+    assert!(boards > 0);
+
+    let best: Vec<_> = (0..boards)
+        .map(|j| results.iter().map(|r| r[j].0.score).max().unwrap_or(0))
+        .collect();
+
+    solvers
+        .iter()
+        .zip(results)
+        .zip(times)
+        .map(|((&solver, r), &time)| {
+            let sum: i64 = r.iter().map(|(s, _)| i64::from(s.score)).sum();
+            Row {
+                solver,
+                mean_score: sum as f64 / boards as f64,
+                wins: (0..boards).filter(|&j| r[j].0.score == best[j]).count(),
+                clear_rate: r.iter().filter(|(_, cleared)| *cleared).count() as f64 / boards as f64,
+                mean_time: time / boards as u32,
+            }
+        })
+        .collect()
+}
+
+pub fn write_table<W: Write>(mut wtr: W, rows: &[Row]) -> Result<()> {
+    writeln!(
+        wtr,
+        "{:<12} {:>10} {:>6} {:>7} {:>9}",
+        "solver", "mean", "wins", "clear", "time(ms)"
+    )?;
+    for row in rows {
+        writeln!(
+            wtr,
+            "{:<12} {:>10.2} {:>6} {:>6.1}% {:>9}",
+            row.solver.to_string(),
+            row.mean_score,
+            row.wins,
+            100.0 * row.clear_rate,
+            row.mean_time.as_millis()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn run(opt: &CompareOpt) -> Result<()> {
+    let budget = Duration::from_millis(opt.budget_ms);
+    let weights = match &opt.weights {
+        Some(path) => Weights::load(path)?,
+        None => Weights::default(),
+    };
+
+    // 盤面を何度も作り直すので作り方を持っておく
+    let config = opt.boards.config()?;
+    let games: Vec<Box<dyn Fn() -> Game>> = opt
+        .boards
+        .seeds()
+        .map(|seed| -> Box<dyn Fn() -> Game> { Box::new(move || Game::new(seed, config)) })
+        .collect();
+
+    let cache = match &opt.cache {
+        Some(dir) => {
+            fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+            Some(SearchCache {
+                dir: dir.clone(),
+                checkpoint: opt.checkpoint_secs.map(Duration::from_secs),
+                resume: opt.resume,
+            })
+        }
+        None => None,
+    };
+
+    // Ctrl+C で探索を止め、そこまでの結果を出す。2 回目はすぐ終了する。
+    let cancel = CancelToken::new();
+    {
+        let cancel = cancel.clone();
+        ctrlc::set_handler(move || {
+            if cancel.is_cancelled() {
+                process::exit(130);
+            }
+            cancel.cancel();
+        })
+        .context("cannot set the Ctrl+C handler")?;
+    }
+
+    let stdout = io::stdout();
+    let mut results = vec![vec![]; opt.solvers.len()];
+    let mut times = vec![Duration::default(); opt.solvers.len()];
+    'boards: for (j, new_game) in games.iter().enumerate() {
+        let game = new_game();
+        let seed = game.seed();
+        for (i, solver) in opt.solvers.iter().enumerate() {
+            let start = Instant::now();
+            let mut monitor = Monitor::new(Some(start + budget)).cancel_on(cancel.clone());
+            if opt.progress {
+                let board = j + 1;
+                let boards = games.len();
+                monitor = monitor.on_progress(move |p: &Progress| {
+                    let table = p.table.map_or_else(String::new, |t| format!(", {}", t));
+                    let arena = p.arena.map_or_else(String::new, |a| format!(", {}", a));
+                    eprint!(
+                        "\r{:<12} board {}/{}: {:>10} nodes, best {:>6} ({:>3.0}%){}{}",
+                        solver.to_string(),
+                        board,
+                        boards,
+                        p.nodes,
+                        p.best.score,
+                        100.0 * p.fraction.unwrap_or(0.0),
+                        table,
+                        arena
+                    );
+                });
+            }
+            let solution = match &cache {
+                Some(cache) => {
+                    solver.solve_cached(game.board(), weights, seed, &mut monitor, cache)?
+                }
+                None => solver.solve(game.board(), weights, seed, &mut monitor),
+            };
+            times[i] += start.elapsed();
+
+            // ゲーム本体のルールで打ち直して得点を確かめる
+            let mut check = new_game();
+            for (t, &(x, y)) in solution.moves.iter().enumerate() {
+                anyhow::ensure!(
+                    check.click(x, y, Duration::from_secs(t as u64)),
+                    "{} produced an invalid move",
+                    solver
+                );
+            }
+            anyhow::ensure!(
+                check.score() == solution.score,
+                "{} miscounted its score",
+                solver
+            );
+
+            if cancel.is_cancelled() {
+                if opt.progress {
+                    eprintln!();
+                }
+                writeln!(
+                    stdout.lock(),
+                    "interrupted: {} on board {} (seed {}): best {}\nline: {}",
+                    solver,
+                    j + 1,
+                    seed,
+                    solution.score,
+                    format_line(&solution, game.board().height())
+                )?;
+                // 全ソルバーが解き終えた盤面だけを集計する
+                for r in &mut results {
+                    r.truncate(j);
+                }
+                break 'boards;
+            }
+
+            let cleared = check.board().tile_count() == 0;
+            results[i].push((solution, cleared));
+        }
+    }
+
+    if opt.progress {
+        eprintln!();
+    }
+
+    if results[0].is_empty() {
+        return Ok(());
+    }
+    let rows = summarize(&opt.solvers, &results, &times);
+    write_table(stdout.lock(), &rows)
+}
+
+// "a1 c4 ..." (notation の表記)
+fn format_line(solution: &Solution, h: usize) -> String {
+    solution
+        .moves
+        .iter()
+        .map(|&(x, y)| notation::format_square(x, y, h))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize() {
+        let sol = |score| Solution {
+            moves: vec![],
+            score,
+        };
+        let solvers = [SolverKind::Greedy, SolverKind::Beam { width: Some(10) }];
+        let results = vec![
+            vec![(sol(10), false), (sol(30), true)],
+            vec![(sol(20), true), (sol(30), true)],
+        ];
+        let times = [Duration::from_millis(2), Duration::from_millis(100)];
+        let rows = super::summarize(&solvers, &results, &times);
+
+        assert_eq!(rows[0].wins, 1);
+        assert_eq!(rows[1].wins, 2);
+        assert!((rows[0].mean_score - 20.0).abs() < 1e-9);
+        assert!((rows[0].clear_rate - 0.5).abs() < 1e-9);
+        assert_eq!(rows[1].mean_time, Duration::from_millis(50));
+
+        let mut buf = vec![];
+        write_table(&mut buf, &rows).unwrap();
+        let table = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            table.lines().nth(2).unwrap(),
+            "beam:10           25.00      2  100.0%        50"
+        );
+    }
+}
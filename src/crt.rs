@@ -0,0 +1,50 @@
+use ggez::graphics::{self, Canvas, DrawParam, EmptyConst, Rect, Shader};
+use ggez::{Context, GameResult};
+
+// 描き終えた画面にブラウン管風の後処理をかける。
+// 1 フレームをキャンバスに描き、それをシェーダを通して画面に描く。
+#[derive(Debug)]
+pub struct Crt {
+    shader: Shader<EmptyConst>,
+    canvas: Canvas,
+}
+
+impl Crt {
+    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+        let shader = Shader::new(
+            ctx,
+            "/shader/basic.glslv",
+            "/shader/crt.glslf",
+            EmptyConst,
+            "Crt",
+            None,
+        )?;
+        let canvas = Canvas::with_window_size(ctx)?;
+        Ok(Self { shader, canvas })
+    }
+
+    // ウィンドウの大きさが変わったらキャンバスを作り直す
+    pub fn resize(&mut self, ctx: &mut Context) -> GameResult {
+        self.canvas = Canvas::with_window_size(ctx)?;
+        Ok(())
+    }
+
+    // これ以降の描画をキャンバスに向ける
+    pub fn begin(&self, ctx: &mut Context) {
+        graphics::set_canvas(ctx, Some(&self.canvas));
+    }
+
+    // キャンバスの中身をシェーダを通して画面に描く
+    pub fn finish(&self, ctx: &mut Context) -> GameResult {
+        graphics::set_canvas(ctx, None);
+        // キャンバスはウィンドウと同じ大きさなので、画面の座標もそれに合わせて描く
+        let screen = graphics::screen_coordinates(ctx);
+        let (w, h) = graphics::drawable_size(ctx);
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, w, h))?;
+        {
+            let _lock = graphics::use_shader(ctx, &self.shader);
+            graphics::draw(ctx, &self.canvas, DrawParam::default())?;
+        }
+        graphics::set_screen_coordinates(ctx, screen)
+    }
+}
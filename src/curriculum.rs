@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::challenge::Challenge;
+use crate::difficulty::Difficulty;
+use crate::game::{Game, GameConfig};
+use crate::generator;
+use crate::hash;
+use crate::pack::Pack;
+use crate::solver;
+use crate::storage::{self, SaveBackend};
+
+// 自動生成されるレベル。番号から決まるので全員が同じ盤面を遊ぶ。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    // 1 から
+    pub number: u32,
+    pub challenge: Challenge,
+    pub target: Difficulty,
+    // 星 1, 2, 3 個に必要な得点。星 1 個でクリア。
+    pub thresholds: [i32; 3],
+}
+
+impl Level {
+    // 10 レベルごとに色が増え、その中で盤面が大きく難しくなっていく
+    const TIER: u32 = 10;
+    const GOAL_BEAM_WIDTH: usize = 8;
+    const STAR_RATIOS: [f64; 3] = [0.6, 0.8, 1.0];
+
+    pub fn new(number: u32) -> Self {
+        assert!(number > 0);
+        let tier = (number - 1) / Self::TIER;
+        let i = (number - 1) % Self::TIER;
+
+        // 5 色になってからは周回ごとに盤面を広げる
+        let lap = tier.saturating_sub(3);
+        let config = GameConfig {
+            width: (6 + i + 2 * lap).min(25) as usize,
+            height: (4 + i / 2 + lap).min(12) as usize,
+            colors: (2 + tier).min(5) as u8,
+            ..GameConfig::default()
+        };
+        let target = match i {
+            0..=3 => Difficulty::Easy,
+            4..=6 => Difficulty::Medium,
+            _ => Difficulty::Hard,
+        };
+
+        let seed = generator::find_seed(config, Self::base_seed(number), target);
+        let challenge = Challenge { config, seed };
+
+        Self {
+            number,
+            challenge,
+            target,
+            thresholds: Self::auto_thresholds(challenge),
+        }
+    }
+
+    // ソルバーの得点を基準にした星の閾値
+    pub fn auto_thresholds(challenge: Challenge) -> [i32; 3] {
+        let game = Game::new(challenge.seed, challenge.config);
+        let best = f64::from(solver::beam_search(game.board(), Self::GOAL_BEAM_WIDTH, None).score);
+        let mut thresholds = [0; 3];
+        for (t, ratio) in thresholds.iter_mut().zip(&Self::STAR_RATIOS) {
+            *t = ((best * ratio).round() as i32).max(1);
+        }
+        thresholds
+    }
+
+    fn base_seed(number: u32) -> u64 {
+        hash::fnv1a(format!("Level:{}", number).bytes())
+    }
+
+    pub fn goal(&self) -> i32 {
+        self.thresholds[0]
+    }
+
+    pub fn stars(&self, score: i32) -> u8 {
+        self.thresholds.iter().filter(|&&t| score >= t).count() as u8
+    }
+
+    pub fn is_cleared(&self, score: i32) -> bool {
+        self.stars(score) > 0
+    }
+}
+
+// レベルの並び。自動生成の無限に続くものか、パックに書かれたもの。
+#[derive(Debug, Clone)]
+pub enum Campaign {
+    Generated,
+    Pack(Pack),
+}
+
+impl Campaign {
+    pub fn level(&self, number: u32) -> Option<Level> {
+        match self {
+            Self::Generated => Some(Level::new(number)),
+            Self::Pack(pack) => pack.level(number).cloned(),
+        }
+    }
+
+    // 最後のレベル。無限に続くなら None。
+    pub fn last(&self) -> Option<u32> {
+        match self {
+            Self::Generated => None,
+            Self::Pack(pack) => Some(pack.len()),
+        }
+    }
+
+    // 進行状況の保存先 (プロファイル内の相対キー)
+    pub fn progress_key(&self) -> String {
+        match self {
+            Self::Generated => "curriculum.json".to_owned(),
+            Self::Pack(pack) => format!("packs/{}.json", pack.name),
+        }
+    }
+}
+
+// クリアしたレベルの最高記録
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelRecord {
+    pub score: i32,
+    pub stars: u8,
+}
+
+// 到達した最も先のレベルと、クリアしたレベルごとの記録
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Progress {
+    pub furthest: u32,
+    #[serde(default)]
+    pub levels: BTreeMap<u32, LevelRecord>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            furthest: 1,
+            levels: BTreeMap::new(),
+        }
+    }
+}
+
+impl Progress {
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    // level を score で終えた。記録が変わったら true。
+    pub fn record(&mut self, level: &Level, score: i32) -> bool {
+        let stars = level.stars(score);
+        if stars == 0 {
+            return false;
+        }
+
+        let mut changed = false;
+        if level.number >= self.furthest {
+            self.furthest = level.number + 1;
+            changed = true;
+        }
+
+        let new = match self.get(level.number) {
+            Some(old) if old.score >= score && old.stars >= stars => return changed,
+            Some(old) => LevelRecord {
+                score: old.score.max(score),
+                stars: old.stars.max(stars),
+            },
+            None => LevelRecord { score, stars },
+        };
+        self.levels.insert(level.number, new);
+        true
+    }
+
+    pub fn get(&self, level: u32) -> Option<LevelRecord> {
+        self.levels.get(&level).copied()
+    }
+
+    pub fn total_stars(&self) -> u32 {
+        self.levels.values().map(|r| u32::from(r.stars)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let first = Level::new(1);
+        assert_eq!(first, Level::new(1));
+        assert_eq!(first.challenge.config.colors, 2);
+        assert!(first.goal() > 0);
+        assert_eq!(first.stars(first.thresholds[2]), 3);
+        assert_eq!(first.stars(first.goal() - 1), 0);
+
+        let later = Level::new(25);
+        assert_eq!(later.challenge.config.colors, 4);
+        assert!(later.challenge.config.width > first.challenge.config.width);
+
+        let far = Level::new(95);
+        assert_eq!(far.challenge.config.colors, 5);
+        assert!(far.challenge.config.width <= 25);
+        assert!(far.challenge.config.height <= 12);
+    }
+
+    #[test]
+    fn progress() {
+        let level = |number| Level {
+            number,
+            challenge: Challenge {
+                config: GameConfig::default(),
+                seed: 0,
+            },
+            target: Difficulty::Easy,
+            thresholds: [10, 20, 30],
+        };
+
+        let mut progress = Progress::default();
+        assert!(!progress.record(&level(1), 9));
+        assert_eq!(progress.furthest, 1);
+        assert!(progress.record(&level(1), 15));
+        assert_eq!(progress.furthest, 2);
+        assert_eq!(
+            progress.get(1),
+            Some(LevelRecord {
+                score: 15,
+                stars: 1
+            })
+        );
+
+        assert!(!progress.record(&level(1), 12));
+        assert!(progress.record(&level(1), 31));
+        assert_eq!(progress.get(1).unwrap().stars, 3);
+        assert_eq!(progress.furthest, 2);
+        assert_eq!(progress.get(2), None);
+        assert_eq!(progress.total_stars(), 3);
+    }
+}
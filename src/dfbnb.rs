@@ -0,0 +1,399 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::board::Board;
+use crate::monitor::Monitor;
+use crate::profiling;
+use crate::rules;
+use crate::solver::{self, Solution};
+use crate::tt::{self, TranspositionTable};
+
+// 深さ優先の分枝限定法。
+// 上界で今の最良を超えられない枝を刈り、同じ盤面 (左右逆も同じとみなす) に同じ得点以下で来た枝も刈る。
+// 時間内に調べきれば最適解。
+#[derive(Debug)]
+pub struct Dfbnb<'a> {
+    board: &'a Board,
+    // 色の付け方だけが違う盤面も同じ盤面とみなすか
+    relabel: bool,
+    // 盤面 (Board::canonical か、relabel なら Board::canonical_colors) ごとの、そこに来たときの最高得点。
+    // 決まった大きさに収め、あふれたら残りのタイルが少ない盤面から忘れる。
+    seen: TranspositionTable,
+    line: Solution,
+    best: Solution,
+    timeout: bool,
+    // line の各手が、その局面の手 (moves の順) の何番目か。打ち切ったらそこで止まる。
+    path: Vec<usize>,
+    // 前に打ち切った path を逆順に積んだもの。続きから調べる間だけ使う。
+    frontier: Vec<usize>,
+    // 書き残した path から続けるか
+    resume: bool,
+    // search_cached で途中経過を書き残す間隔と場所、最後に書いた時刻
+    checkpoint: Option<Duration>,
+    cache: Option<PathBuf>,
+    checkpointed: Instant,
+    // 途中経過を書き残せなかったら探索をやめて返す
+    failure: Option<anyhow::Error>,
+}
+
+impl<'a> Dfbnb<'a> {
+    // 置換表に使うメモリの既定の大きさ
+    pub const DEFAULT_MEMORY: usize = 64 << 20;
+    const CACHE_MAGIC: &'static [u8; 8] = b"SGDFBNB3";
+
+    pub fn new(board: &'a Board) -> Self {
+        Self {
+            board,
+            relabel: false,
+            seen: TranspositionTable::new(Self::DEFAULT_MEMORY),
+            line: Solution::default(),
+            best: Solution::default(),
+            timeout: false,
+            path: vec![],
+            frontier: vec![],
+            resume: false,
+            checkpoint: None,
+            cache: None,
+            checkpointed: Instant::now(),
+            failure: None,
+        }
+    }
+
+    // 色の名前を入れ替えただけの盤面を同じ盤面として刈る。
+    // 盤面を覚えるたびに色を付け直す手間はかかるが、調べる局面は減る。
+    pub fn relabel(mut self, relabel: bool) -> Self {
+        self.relabel = relabel;
+        self
+    }
+
+    // 置換表を memory バイトに収める
+    pub fn memory(mut self, memory: usize) -> Self {
+        self.seen = TranspositionTable::new(memory);
+        self
+    }
+
+    // 最良の手順と、それが最適だと証明できたかどうか。monitor の期限で打ち切る。
+    pub fn search(mut self, monitor: &mut Monitor) -> (Solution, bool) {
+        self.run(monitor);
+        (self.best, !self.timeout)
+    }
+
+    // search_cached で、前に打ち切ったところから続きを調べる。
+    // しなければ根から調べ直す (置換表と最良の手順はどちらでも使う)。
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    // search_cached で、探索の途中でも interval ごとに途中経過を書き残す
+    pub fn checkpoint(mut self, interval: Option<Duration>) -> Self {
+        self.checkpoint = interval;
+        self
+    }
+
+    // search と同じだが、path に前の探索の置換表と最良の手順があればその続きから調べ、
+    // 終わったら (打ち切っても) 書き戻す。同じ盤面を何度も長く調べるときに使う。
+    pub fn search_cached(mut self, monitor: &mut Monitor, path: &Path) -> Result<(Solution, bool)> {
+        match File::open(path) {
+            Ok(file) => self
+                .load_cache(&mut BufReader::new(file))
+                .with_context(|| format!("cannot read {}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("cannot open {}", path.display()));
+            }
+        }
+        self.cache = Some(path.to_owned());
+        self.checkpointed = Instant::now();
+        self.run(monitor);
+        if let Some(e) = self.failure.take() {
+            return Err(e);
+        }
+        self.write_cache(path)?;
+        Ok((self.best, !self.timeout))
+    }
+
+    // 書いている途中で止まっても前の中身が残るよう、別のファイルに書いてから置き換える
+    fn write_cache(&self, path: &Path) -> Result<()> {
+        let _scope = profiling::scope("dfbnb::write_cache");
+        let tmp = path.with_extension("tmp");
+        let mut w = BufWriter::new(
+            File::create(&tmp).with_context(|| format!("cannot create {}", tmp.display()))?,
+        );
+        self.save_cache(&mut w)
+            .and_then(|_| w.flush())
+            .with_context(|| format!("cannot write {}", tmp.display()))?;
+        drop(w);
+        fs::rename(&tmp, path).with_context(|| format!("cannot write {}", path.display()))
+    }
+
+    fn write_checkpoint(&mut self) {
+        let (interval, path) = match (self.checkpoint, &self.cache) {
+            (Some(interval), Some(path)) => (interval, path),
+            _ => return,
+        };
+        if self.checkpointed.elapsed() < interval {
+            return;
+        }
+        if let Err(e) = self.write_cache(path) {
+            self.failure = Some(e);
+        }
+        self.checkpointed = Instant::now();
+    }
+
+    fn run(&mut self, monitor: &mut Monitor) {
+        let _scope = profiling::scope("dfbnb::search");
+        self.seen.next_generation();
+        self.dfs(monitor, self.board.clone());
+    }
+
+    // 盤面と relabel の見出しのあとに最良の手順、打ち切った path、置換表を並べる
+    fn save_cache<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(Self::CACHE_MAGIC)?;
+        w.write_all(&self.board.fingerprint().to_le_bytes())?;
+        w.write_all(&[u8::from(self.relabel)])?;
+        w.write_all(&self.best.score.to_le_bytes())?;
+        w.write_all(&(self.best.moves.len() as u32).to_le_bytes())?;
+        for &(x, y) in &self.best.moves {
+            w.write_all(&(x as u32).to_le_bytes())?;
+            w.write_all(&(y as u32).to_le_bytes())?;
+        }
+        w.write_all(&(self.path.len() as u32).to_le_bytes())?;
+        for &i in &self.path {
+            w.write_all(&(i as u32).to_le_bytes())?;
+        }
+        self.seen.save(w)
+    }
+
+    fn load_cache<R: Read>(&mut self, r: &mut R) -> Result<()> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        anyhow::ensure!(&magic == Self::CACHE_MAGIC, "not a search cache");
+        let board = tt::read_u64(r)?;
+        let mut relabel = [0; 1];
+        r.read_exact(&mut relabel)?;
+        anyhow::ensure!(
+            board == self.board.fingerprint() && (relabel[0] != 0) == self.relabel,
+            "search cache is for another board or solver setting"
+        );
+        let score = tt::read_u32(r)? as i32;
+        let mut moves = vec![];
+        for _ in 0..tt::read_u32(r)? {
+            moves.push((tt::read_u32(r)? as usize, tt::read_u32(r)? as usize));
+        }
+        self.best = Solution { moves, score };
+        let mut path = vec![];
+        for _ in 0..tt::read_u32(r)? {
+            path.push(tt::read_u32(r)? as usize);
+        }
+        if self.resume {
+            self.frontier = path.into_iter().rev().collect();
+        }
+        self.seen.load(r)?;
+        Ok(())
+    }
+
+    fn is_timeout(&mut self, monitor: &Monitor) -> bool {
+        self.timeout |= monitor.is_over() || self.failure.is_some();
+        self.timeout
+    }
+
+    fn dfs(&mut self, monitor: &mut Monitor, board: Board) {
+        if self.line.score > self.best.score {
+            self.best = self.line.clone();
+        }
+        monitor.set_table(self.seen.stats());
+        monitor.visit(1, &self.best);
+        self.write_checkpoint();
+        // 続きから調べるなら、前に打ち切った手の手前までは調べ終えている
+        let skip = self.frontier.pop().unwrap_or(0);
+        if self.is_timeout(monitor)
+            || self.line.score + solver::upper_bound(&board) <= self.best.score
+        {
+            self.frontier.clear();
+            return;
+        }
+
+        let key = if self.relabel {
+            board.canonical_colors()
+        } else {
+            board.canonical()
+        }
+        .fingerprint();
+        match self.seen.get(key) {
+            Some(score) if score >= self.line.score => {
+                self.frontier.clear();
+                return;
+            }
+            _ => {}
+        }
+
+        let root = self.line.moves.is_empty();
+        for (i, (x, y)) in moves(&board, root).into_iter().enumerate().skip(skip) {
+            let mut next = board.clone();
+            let n = next.erase_component(x, y);
+            self.line.moves.push((x, y));
+            self.line.score += rules::group_score(n);
+            self.path.push(i);
+
+            self.dfs(monitor, next);
+            if self.timeout {
+                return;
+            }
+
+            self.path.pop();
+            self.line.moves.pop();
+            self.line.score -= rules::group_score(n);
+        }
+
+        // 調べきった盤面だけを覚える。途中で打ち切った盤面を覚えると、
+        // 置換表を書き残して続きから調べるときに、調べていない枝を刈ってしまう。
+        // 残りのタイルが多い盤面ほど先の探索を多く省ける。
+        let depth = board.tile_count().min(usize::from(u16::MAX)) as u16;
+        self.seen.insert(key, self.line.score, depth);
+    }
+}
+
+// 調べる順に並べた手 (手の並べ替え)。
+// それ以上大きくならないグループ(その色が盤面に他に残っていない)を先に消し、
+// あとは大きいグループから消す。根では左右対称な手の片方を除く。
+// 並べ替えるだけで枝は刈らない。大きくならないグループでも、先に消すのが最善とは限らないため。
+fn moves(board: &Board, root: bool) -> Vec<(usize, usize)> {
+    let groups = if root {
+        board.distinct_groups()
+    } else {
+        board.groups()
+    };
+    let mut groups: Vec<_> = groups
+        .into_iter()
+        .map(|((x, y), n)| {
+            let complete = board.color_count(board.at(x, y)) == n;
+            (!complete, std::cmp::Reverse(n), (x, y))
+        })
+        .collect();
+    groups.sort();
+    groups.into_iter().map(|(_, _, cell)| cell).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    use super::*;
+    use crate::beam_stack::BeamStack;
+    use crate::monitor::{CancelToken, Progress};
+
+    #[test]
+    fn dfbnb() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let (solution, proven) = Dfbnb::new(&board).search(&mut Monitor::new(None));
+        assert!(proven);
+        assert_eq!(solution.score, 9 + 1);
+
+        for seed in 0..10 {
+            let mut rng = Pcg32::seed_from_u64(seed);
+            let board = Board::random_with(6, 5, 3, &mut rng);
+            let (solution, proven) = Dfbnb::new(&board).search(&mut Monitor::new(None));
+            assert!(proven);
+            let (optimum, _) = BeamStack::new(&board, 4).search(&mut Monitor::new(None));
+            assert_eq!(solution.score, optimum.score);
+
+            let trace = solution.trace(&board);
+            assert_eq!(trace.iter().map(|e| e.score).sum::<i32>(), solution.score);
+        }
+
+        // 色を付け直しても同じ最適解で、覚える盤面は増えない
+        for seed in 0..5 {
+            let mut rng = Pcg32::seed_from_u64(seed);
+            let board = Board::random_with(6, 5, 3, &mut rng);
+            let mut plain = Dfbnb::new(&board);
+            plain.dfs(&mut Monitor::new(None), board.clone());
+            let mut relabeled = Dfbnb::new(&board).relabel(true);
+            relabeled.dfs(&mut Monitor::new(None), board.clone());
+            assert_eq!(relabeled.best.score, plain.best.score);
+            assert!(relabeled.seen.stats().filled <= plain.seen.stats().filled);
+        }
+
+        // 小さな置換表でも最適解は変わらない
+        let mut rng = Pcg32::seed_from_u64(4);
+        let board = Board::random_with(6, 5, 3, &mut rng);
+        let (expected, _) = Dfbnb::new(&board).search(&mut Monitor::new(None));
+        let mut small = Dfbnb::new(&board).memory(1 << 10);
+        small.dfs(&mut Monitor::new(None), board.clone());
+        assert_eq!(small.best.score, expected.score);
+        let stats = small.seen.stats();
+        assert!(stats.filled <= stats.capacity);
+        assert!(stats.replaced > 0);
+
+        // 打ち切った探索の続きから調べても最適解になり、調べきったものを読めばすぐに返る
+        let path = std::env::temp_dir().join(format!("samegame-dfbnb-{}", std::process::id()));
+        let past = Some(Instant::now());
+        let (_, proven) = Dfbnb::new(&board)
+            .search_cached(&mut Monitor::new(past), &path)
+            .unwrap();
+        assert!(!proven);
+        let (solution, proven) = Dfbnb::new(&board)
+            .search_cached(&mut Monitor::new(None), &path)
+            .unwrap();
+        assert!(proven);
+        assert_eq!(solution.score, expected.score);
+        let (cached, _) = Dfbnb::new(&board)
+            .search_cached(&mut Monitor::new(past), &path)
+            .unwrap();
+        assert_eq!(cached, solution);
+        assert!(Dfbnb::new(&board)
+            .relabel(true)
+            .search_cached(&mut Monitor::new(None), &path)
+            .is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        // 途中で止めて、止めたところから続けても最適解になる
+        for seed in 0..5 {
+            let mut rng = Pcg32::seed_from_u64(seed);
+            let board = Board::random_with(6, 5, 3, &mut rng);
+            let (expected, _) = Dfbnb::new(&board).search(&mut Monitor::new(None));
+            let cancel = CancelToken::new();
+            let half = expected.score / 2;
+            let mut monitor = {
+                let cancel = cancel.clone();
+                Monitor::new(None)
+                    .cancel_on(cancel.clone())
+                    .on_progress(move |p: &Progress| {
+                        if p.best.score >= half {
+                            cancel.cancel();
+                        }
+                    })
+            };
+            let (_, proven) = Dfbnb::new(&board)
+                .memory(1 << 12)
+                .checkpoint(Some(Duration::default()))
+                .search_cached(&mut monitor, &path)
+                .unwrap();
+            assert!(!proven);
+            let (solution, proven) = Dfbnb::new(&board)
+                .memory(1 << 12)
+                .resume(true)
+                .search_cached(&mut Monitor::new(None), &path)
+                .unwrap();
+            assert!(proven);
+            assert_eq!(solution.score, expected.score);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // 時間切れでもそれまでの最良を返す
+        let mut rng = Pcg32::seed_from_u64(1);
+        let board = Board::random_with(15, 10, 4, &mut rng);
+        let dfbnb = Dfbnb::new(&board);
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let (solution, proven) = dfbnb.search(&mut Monitor::new(Some(deadline)));
+        assert!(!proven);
+        assert!(solution.score > 0);
+    }
+}
@@ -0,0 +1,272 @@
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh, Rect};
+use ggez::{Context, GameResult};
+
+use crate::font::{Align, Font};
+use crate::input::Action;
+use crate::layout::{Anchor, Layout};
+use crate::widget::{Form, Widget};
+
+// ダイアログを閉じたときの答え
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Ok,
+    Yes,
+    No,
+    Text(String),
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Message,
+    Confirm,
+    Input { text: String, max_len: usize },
+}
+
+impl Kind {
+    // ボタンの並びと、それぞれを押したときの答え
+    fn buttons(&self) -> &'static [&'static str] {
+        match self {
+            Self::Message => &["OK"],
+            Self::Confirm => &["Yes", "No"],
+            Self::Input { .. } => &["OK", "Cancel"],
+        }
+    }
+
+    fn answer(&self, button: usize) -> Answer {
+        match (self, button) {
+            (Self::Message, _) => Answer::Ok,
+            (Self::Confirm, 0) => Answer::Yes,
+            (Self::Confirm, _) => Answer::No,
+            (Self::Input { text, .. }, 0) => Answer::Text(text.clone()),
+            (Self::Input { .. }, _) => Answer::Cancel,
+        }
+    }
+
+    // Esc で閉じたときの答え
+    fn dismiss(&self) -> Answer {
+        match self {
+            Self::Message => Answer::Ok,
+            Self::Confirm => Answer::No,
+            Self::Input { .. } => Answer::Cancel,
+        }
+    }
+}
+
+// tag は呼び出し側がダイアログの用途を見分けるためのもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialog<T> {
+    tag: T,
+    title: String,
+    message: String,
+    kind: Kind,
+    form: Form,
+}
+
+impl<T> Dialog<T> {
+    const WIDTH: f32 = 360.0;
+    const PADDING: f32 = 10.0;
+
+    fn new(tag: T, title: String, message: String, kind: Kind) -> Self {
+        let buttons = kind.buttons().iter().map(|&label| Widget::button(label));
+        let form = Form::new(
+            buttons.collect(),
+            0.0,
+            0.0,
+            Self::WIDTH - 2.0 * Self::PADDING,
+        );
+        Self {
+            tag,
+            title,
+            message,
+            kind,
+            form,
+        }
+    }
+
+    pub fn message<S: Into<String>, M: Into<String>>(tag: T, title: S, message: M) -> Self {
+        Self::new(tag, title.into(), message.into(), Kind::Message)
+    }
+
+    pub fn confirm<S: Into<String>, M: Into<String>>(tag: T, title: S, message: M) -> Self {
+        Self::new(tag, title.into(), message.into(), Kind::Confirm)
+    }
+
+    // 1 行の文字列を入力させる。initial を入力済みの状態で開く。
+    pub fn input<S: Into<String>, M: Into<String>>(
+        tag: T,
+        title: S,
+        message: M,
+        initial: &str,
+        max_len: usize,
+    ) -> Self {
+        let text = initial.chars().take(max_len).collect();
+        let kind = Kind::Input { text, max_len };
+        Self::new(tag, title.into(), message.into(), kind)
+    }
+}
+
+// 開いているダイアログ。一番上のものだけが入力を受け取る。
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogStack<T> {
+    dialogs: Vec<Dialog<T>>,
+}
+
+impl<T> Default for DialogStack<T> {
+    fn default() -> Self {
+        Self { dialogs: vec![] }
+    }
+}
+
+impl<T> DialogStack<T> {
+    pub fn push(&mut self, dialog: Dialog<T>) {
+        self.dialogs.push(dialog);
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.dialogs.is_empty()
+    }
+
+    pub fn top(&self) -> Option<&T> {
+        self.dialogs.last().map(|d| &d.tag)
+    }
+
+    fn close(&mut self, answer: Answer) -> Option<(T, Answer)> {
+        let dialog = self.dialogs.pop()?;
+        Some((dialog.tag, answer))
+    }
+
+    // 答えが決まれば一番上のダイアログを閉じてその用途と答えを返す
+    pub fn on_action(&mut self, action: Action) -> Option<(T, Answer)> {
+        let dialog = self.dialogs.last_mut()?;
+        match (action, &mut dialog.kind) {
+            (Action::Cancel, kind) => {
+                let answer = kind.dismiss();
+                self.close(answer)
+            }
+            (Action::Yes, Kind::Confirm) => self.close(Answer::Yes),
+            (Action::No, Kind::Confirm) => self.close(Answer::No),
+            (Action::DeleteChar, Kind::Input { text, .. }) => {
+                text.pop();
+                None
+            }
+            // 入力中の決定はどのボタンを選んでいても確定にする
+            (Action::Confirm, kind @ Kind::Input { .. }) => {
+                let answer = kind.answer(0);
+                self.close(answer)
+            }
+            _ => {
+                let (button, _) = dialog.form.on_action(action)?;
+                let answer = dialog.kind.answer(button);
+                self.close(answer)
+            }
+        }
+    }
+
+    pub fn on_text(&mut self, ch: char) {
+        if let Some(Dialog {
+            kind: Kind::Input { text, max_len },
+            ..
+        }) = self.dialogs.last_mut()
+        {
+            // フォントにある文字だけ受け付ける
+            if ('\x20'..='\x7E').contains(&ch) && text.chars().count() < *max_len {
+                text.push(ch);
+            }
+        }
+    }
+
+    // 画面全体を暗くして、一番上のダイアログを中央に描く
+    pub fn draw(&mut self, ctx: &mut Context, font: &Font, screen: Rect) -> GameResult {
+        let dialog = match self.dialogs.last_mut() {
+            Some(dialog) => dialog,
+            None => return Ok(()),
+        };
+
+        let dim = Color::from_rgba(0x00, 0x00, 0x00, 0xa0);
+        let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), screen, dim)?;
+        graphics::draw(ctx, &mesh, DrawParam::default())?;
+
+        let pad = Dialog::<T>::PADDING;
+        let line_height = font.glyph_height() as f32;
+        let inner_w = Dialog::<T>::WIDTH - 2.0 * pad;
+        let lines = font.wrap(ctx, &dialog.message, inner_w);
+        let input_h = match dialog.kind {
+            Kind::Input { .. } => line_height + 6.0,
+            _ => 0.0,
+        };
+        let body_h = line_height * (lines.len() + 1) as f32 + 6.0 + input_h;
+        let h = body_h + 6.0 + dialog.form.height() + 2.0 * pad;
+        let rect = Layout::new(screen).place(Anchor::Center, Dialog::<T>::WIDTH, h);
+
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let frame = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), rect, graphics::WHITE)?;
+        graphics::draw(ctx, &frame, DrawParam::default())?;
+
+        let (x, mut y) = (rect.x + pad, rect.y + pad);
+        let yellow = Color::from_rgb(0xff, 0xff, 0x00);
+        font.draw_str_with(ctx, x, y, &dialog.title, yellow, Align::Left)?;
+        y += line_height + 6.0;
+        font.draw_str(ctx, x, y, lines.join("\n"))?;
+        y += line_height * lines.len() as f32;
+
+        if let Kind::Input { text, .. } = &dialog.kind {
+            let field = Rect::new(x, y + 2.0, inner_w, line_height + 2.0);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), field, graphics::WHITE)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+            font.draw_str(ctx, x + 4.0, y + 3.0, format!("{}_", text))?;
+            y += input_h;
+        }
+
+        dialog.form.move_to(x, y + 6.0);
+        dialog.form.draw(ctx, font)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        let mut dialogs = DialogStack::default();
+        assert!(!dialogs.is_open());
+        assert_eq!(dialogs.on_action(Action::Confirm), None);
+
+        dialogs.push(Dialog::message(1, "Error", "cannot save"));
+        dialogs.push(Dialog::confirm(2, "Quit", "Quit the game?"));
+        assert_eq!(dialogs.top(), Some(&2));
+        dialogs.on_action(Action::MoveCursor(0, 1));
+        assert_eq!(dialogs.on_action(Action::Confirm), Some((2, Answer::No)));
+        assert_eq!(dialogs.on_action(Action::Cancel), Some((1, Answer::Ok)));
+        assert!(!dialogs.is_open());
+
+        dialogs.push(Dialog::confirm(3, "Quit", "Quit the game?"));
+        assert_eq!(dialogs.on_action(Action::Confirm), Some((3, Answer::Yes)));
+        dialogs.push(Dialog::confirm(4, "Quit", "Quit the game?"));
+        assert_eq!(dialogs.on_action(Action::No), Some((4, Answer::No)));
+    }
+
+    #[test]
+    fn input() {
+        let mut dialogs = DialogStack::default();
+        dialogs.push(Dialog::input((), "Name", "Enter your name", "default", 4));
+        dialogs.on_action(Action::DeleteChar);
+        dialogs.on_text('x');
+        dialogs.on_text('y');
+        dialogs.on_text('\u{3042}');
+        // ボタンを選んでいても Enter は確定
+        dialogs.on_action(Action::MoveCursor(0, 1));
+        assert_eq!(
+            dialogs.on_action(Action::Confirm),
+            Some(((), Answer::Text("defx".to_owned())))
+        );
+
+        dialogs.push(Dialog::input((), "Name", "Enter your name", "", 4));
+        assert_eq!(
+            dialogs.on_action(Action::Cancel),
+            Some(((), Answer::Cancel))
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::board::{self, Board};
+use crate::policy::GreedyPolicy;
+use crate::solver::{self, Solution};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    // この難しさになる見積もり値の範囲
+    pub fn range(self) -> Range<f64> {
+        match self {
+            Self::Easy => 0.0..DifficultyReport::EASY_BELOW,
+            Self::Medium => DifficultyReport::EASY_BELOW..DifficultyReport::HARD_FROM,
+            Self::Hard => DifficultyReport::HARD_FROM..f64::INFINITY,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Easy => "Easy",
+            Self::Medium => "Medium",
+            Self::Hard => "Hard",
+        };
+        f.write_str(s)
+    }
+}
+
+// 盤面の難しさの見積もり。各指標は 0.0 (易) から 1.0 (難)。
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyReport {
+    // 色の偏りのなさ(正規化エントロピー)。偏っているほど大きな塊ができやすい。
+    pub color_balance: f64,
+    // どのグループにも属さないタイルの割合
+    pub fragmentation: f64,
+    // 貪欲法で残ったタイルの割合
+    pub greedy_remaining: f64,
+    // 小さなビームサーチで残ったタイルの割合
+    pub beam_remaining: f64,
+    // 上の指標の加重平均
+    pub score: f64,
+    pub difficulty: Difficulty,
+}
+
+impl DifficultyReport {
+    const BEAM_WIDTH: usize = 8;
+
+    // 既定の 20x10 5 色でおおよそ上位 3 割が Hard になる
+    const EASY_BELOW: f64 = 0.2;
+    const HARD_FROM: f64 = 0.33;
+}
+
+fn remaining(board: &Board, solution: &Solution) -> usize {
+    let mut board = board.clone();
+    for &(x, y) in &solution.moves {
+        board.erase_component(x, y);
+    }
+    board.tile_count()
+}
+
+pub fn estimate_difficulty(board: &Board) -> DifficultyReport {
+    let total = board.tile_count();
+    if total == 0 {
+        return DifficultyReport {
+            color_balance: 0.0,
+            fragmentation: 0.0,
+            greedy_remaining: 0.0,
+            beam_remaining: 0.0,
+            score: 0.0,
+            difficulty: Difficulty::Easy,
+        };
+    }
+    let ratio = |n: usize| n as f64 / total as f64;
+
+    let mut counts = [0_usize; board::MAX_COLORS as usize + 1];
+    for x in 0..board.width() {
+        for y in 0..board.height() {
+            counts[board.at(x, y) as usize] += 1;
+        }
+    }
+    let present = counts[1..].iter().filter(|&&c| c > 0).count();
+    let color_balance = if present < 2 {
+        0.0
+    } else {
+        let entropy: f64 = counts[1..]
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = ratio(c);
+                -p * p.ln()
+            })
+            .sum();
+        // 既定の色数までは同じ尺度で測る
+        let colors = present.max(usize::from(board::DEFAULT_COLORS));
+        entropy / (colors as f64).ln()
+    };
+
+    let grouped: usize = board.groups().iter().map(|&(_, n)| n).sum();
+    let fragmentation = 1.0 - ratio(grouped);
+
+    let greedy = solver::rollout(board, &mut GreedyPolicy);
+    let greedy_remaining = ratio(remaining(board, &greedy));
+    let beam = solver::beam_search(board, DifficultyReport::BEAM_WIDTH, None);
+    let beam_remaining = ratio(remaining(board, &beam));
+
+    let score =
+        0.15 * color_balance + 0.15 * fragmentation + 0.2 * greedy_remaining + 0.5 * beam_remaining;
+    let difficulty = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
+        .iter()
+        .copied()
+        .find(|d| d.range().contains(&score))
+        .unwrap_or(Difficulty::Hard);
+
+    DifficultyReport {
+        color_balance,
+        fragmentation,
+        greedy_remaining,
+        beam_remaining,
+        score,
+        difficulty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    use super::*;
+
+    #[test]
+    fn estimate() {
+        // 単色ならすぐ全消しできる
+        let easy = Board::parse(
+            b"\
+4 2
+1111
+1111
+"
+            .as_ref(),
+        )
+        .unwrap();
+        let report = estimate_difficulty(&easy);
+        assert_eq!(report.difficulty, Difficulty::Easy);
+        assert_eq!(report.beam_remaining, 0.0);
+
+        // 市松模様は 1 手も打てない
+        let hard = Board::parse(
+            b"\
+4 2
+1212
+2121
+"
+            .as_ref(),
+        )
+        .unwrap();
+        let report = estimate_difficulty(&hard);
+        assert_eq!(report.difficulty, Difficulty::Hard);
+        assert_eq!(report.fragmentation, 1.0);
+
+        // 色数が多いほど難しい
+        let mut rng = Pcg32::seed_from_u64(0);
+        let mean = |colors, rng: &mut Pcg32| {
+            (0..5)
+                .map(|_| estimate_difficulty(&Board::random_with(10, 6, colors, rng)).score)
+                .sum::<f64>()
+        };
+        assert!(mean(2, &mut rng) < mean(5, &mut rng));
+    }
+}
@@ -0,0 +1,130 @@
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh};
+use ggez::{Context, GameResult};
+
+use crate::board::{self, Board};
+use crate::game_state::GameState;
+use crate::input::Action;
+use crate::scene::{Scene, Transition};
+
+// 盤面を 1 セルずつ塗って作る場面。Enter で落下を適用して遊ぶ。
+#[derive(Debug)]
+pub struct Editor {
+    board: Board,
+    // 塗る色。0 なら消す。
+    color: u8,
+    cursor: Option<(usize, usize)>,
+}
+
+impl Editor {
+    pub fn new(board: &Board) -> Self {
+        Self {
+            board: board.clone(),
+            color: 1,
+            cursor: None,
+        }
+    }
+
+    fn paint(&mut self, x: usize, y: usize) {
+        self.board.set(x, y, self.color);
+    }
+
+    // 落下を適用した盤面。タイルがなければ None。
+    fn finish(&self) -> Option<Board> {
+        let mut board = self.board.clone();
+        board.settle();
+        Some(board).filter(|board| board.tile_count() > 0)
+    }
+}
+
+impl Scene for Editor {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let area = state.board_area();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), area, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        state.draw_board(ctx, &self.board)?;
+
+        // 空のセルも分かるように枠を描く
+        let grid = Color::from_rgb(0x40, 0x40, 0x40);
+        for x in 0..self.board.width() {
+            for y in 0..self.board.height() {
+                let rect = state.cell_rect(x, y);
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), rect, grid)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+        }
+        if let Some((x, y)) = self.cursor {
+            let rect = state.cell_rect(x, y);
+            let (_, outline) = state.highlight_colors();
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), rect, outline)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        let font = state.font();
+        let y = area.y + area.h + 10.0;
+        let color = match self.color {
+            0 => "erase".to_owned(),
+            c => c.to_string(),
+        };
+        font.draw_str(ctx, 10.0, y, format!("Editor  Color: {}", color))?;
+        font.draw_str(
+            ctx,
+            10.0,
+            y + 17.0,
+            format!(
+                "Click:Paint 0-{}:Color Enter:Play Esc:Cancel",
+                board::MAX_COLORS
+            ),
+        )
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, _repeat: bool) -> Transition {
+        match action {
+            Action::Pick(color) if color <= board::MAX_COLORS => {
+                self.color = color;
+                Transition::None
+            }
+            Action::Cancel => Transition::Pop,
+            Action::Confirm => match self.finish() {
+                Some(board) => {
+                    state.play_board(board);
+                    Transition::Pop
+                }
+                None => Transition::None,
+            },
+            Action::Click(x, y) => {
+                if let Some((x, y)) = state.cell_at(x, y) {
+                    self.paint(x, y);
+                }
+                Transition::None
+            }
+            Action::Point(x, y) => {
+                self.cursor = state.cell_at(x, y);
+                Transition::None
+            }
+            _ => Transition::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finish() {
+        let board = Board::parse("3 2\n000\n000\n".as_bytes()).unwrap();
+        let mut editor = Editor::new(&board);
+        assert_eq!(editor.finish(), None);
+
+        editor.color = 2;
+        editor.paint(2, 0);
+        editor.color = 3;
+        editor.paint(2, 1);
+        editor.paint(1, 1);
+        editor.color = 0;
+        editor.paint(1, 1);
+
+        let board = editor.finish().unwrap();
+        assert_eq!(board, Board::parse("3 2\n200\n300\n".as_bytes()).unwrap());
+    }
+}
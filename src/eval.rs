@@ -0,0 +1,128 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::board::{self, Board};
+use crate::rules;
+
+// 探索で盤面を並べるときの評価の重み。全て 0 なら得点だけで比べる。
+// JSON で書いて読み込める。書かなかった項目は 0。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    // 残っているグループの (n-1)^2 の和に掛ける
+    pub group_size: f64,
+    // 残っている色の数に掛けて引く
+    pub color_count: f64,
+    // 残っているタブー色(最初の盤面で一番多い色)のタイルの数に掛ける。
+    // 正ならタブー色を最後にまとめて消す手順を好む。
+    pub tabu_color: f64,
+}
+
+impl Weights {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            fs::File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+        Self::parse(io::BufReader::new(file)).with_context(|| format!("in {}", path.display()))
+    }
+
+    pub fn parse<R: Read>(rdr: R) -> Result<Self> {
+        let weights: Self = serde_json::from_reader(rdr).context("invalid weights")?;
+        anyhow::ensure!(
+            [weights.group_size, weights.color_count, weights.tabu_color]
+                .iter()
+                .all(|w| w.is_finite()),
+            "weights must be finite"
+        );
+        Ok(weights)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// 1 回の探索の間使う評価関数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluator {
+    weights: Weights,
+    tabu: u8,
+}
+
+impl Evaluator {
+    // root は探索を始める盤面。タブー色をここから決める。
+    pub fn new(weights: Weights, root: &Board) -> Self {
+        let tabu = (1..=board::MAX_COLORS)
+            .max_by_key(|&color| (root.color_count(color), std::cmp::Reverse(color)))
+            .expect("internal error");
+        Self { weights, tabu }
+    }
+
+    // score はそこまでに稼いだ得点。大きいほど良い。
+    pub fn evaluate(&self, board: &Board, score: i32) -> f64 {
+        let mut value = f64::from(score);
+        if self.weights.is_zero() {
+            return value;
+        }
+
+        if self.weights.group_size != 0.0 {
+            let potential: i32 = board
+                .groups()
+                .into_iter()
+                .map(|(_, n)| rules::group_score(n))
+                .sum();
+            value += self.weights.group_size * f64::from(potential);
+        }
+        if self.weights.color_count != 0.0 {
+            let colors = (1..=board::MAX_COLORS)
+                .filter(|&color| board.color_count(color) > 0)
+                .count();
+            value -= self.weights.color_count * colors as f64;
+        }
+        value += self.weights.tabu_color * board.color_count(self.tabu) as f64;
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let weights =
+            Weights::parse(r#"{ "group_size": 0.5, "tabu_color": 2 }"#.as_bytes()).unwrap();
+        assert_eq!(
+            weights,
+            Weights {
+                group_size: 0.5,
+                color_count: 0.0,
+                tabu_color: 2.0,
+            }
+        );
+        assert!(Weights::parse("{}".as_bytes()).unwrap().is_zero());
+        assert!(Weights::parse(r#"{ "group": 1 }"#.as_bytes()).is_err());
+        assert!(Weights::parse(r#"{ "group_size": "big" }"#.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn evaluate() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+
+        let eval = Evaluator::new(Weights::default(), &board);
+        assert_eq!(eval.evaluate(&board, 7), 7.0);
+
+        let weights = Weights {
+            group_size: 1.0,
+            color_count: 10.0,
+            tabu_color: 0.5,
+        };
+        let eval = Evaluator::new(weights, &board);
+        // グループは 1 が 4 個の 1 つだけ、色は 2 つ、タブー色の 1 は 4 個
+        assert_eq!(eval.evaluate(&board, 0), 9.0 - 20.0 + 2.0);
+    }
+}
@@ -1,20 +1,118 @@
+use std::borrow::Cow;
+use std::io::Read;
 use std::path::Path;
 
+use anyhow::Context as _;
+
 use ggez::error::GameError::FontError;
-use ggez::graphics::{self, DrawParam, Image, Rect};
+use ggez::graphics::{self, Color, DrawParam, Image, Rect, Scale, Text, TextFragment};
 use ggez::mint;
-use ggez::{Context, GameResult};
+use ggez::{filesystem, Context, GameResult};
+
+// 文字列を置く位置。Center と Right は x から始まる幅の中で揃える。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center(f32),
+    Right(f32),
+}
+
+impl Align {
+    // 幅 text_width の文字列を描き始める x 座標
+    fn start(self, x: f32, text_width: f32) -> f32 {
+        match self {
+            Self::Left => x,
+            Self::Center(width) => x + (width - text_width) / 2.0,
+            Self::Right(width) => x + width - text_width,
+        }
+    }
+}
+
+// s を幅 max_width に収まるように単語の切れ目で折り返す。改行でも行を分ける。
+// 1 単語で収まらなければ文字の切れ目で分ける。
+fn wrap_lines<F: Fn(&str) -> f32>(s: &str, max_width: f32, measure: F) -> Vec<String> {
+    let mut res = vec![];
+    for paragraph in s.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ').filter(|w| !w.is_empty()) {
+            let joined = if line.is_empty() {
+                word.to_owned()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if measure(&joined) <= max_width {
+                line = joined;
+                continue;
+            }
+
+            if !line.is_empty() {
+                res.push(line);
+            }
+            line = String::new();
+            for ch in word.chars() {
+                line.push(ch);
+                if measure(&line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    res.push(line);
+                    line = ch.to_string();
+                }
+            }
+        }
+        res.push(line);
+    }
+    res
+}
+
+// 字形のない文字 (ASCII の表示文字以外) は '?' で描く
+fn printable(ch: char) -> char {
+    if ('\x20'..='\x7E').contains(&ch) {
+        ch
+    } else {
+        '?'
+    }
+}
+
+fn sanitize(s: &str) -> Cow<'_, str> {
+    if s.chars().all(|ch| printable(ch) == ch) {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(s.chars().map(printable).collect())
+    }
+}
+
+#[derive(Debug)]
+enum Glyphs {
+    Bitmap {
+        img: Image,
+        // 文字ごとの送り幅。None なら全て glyph_width。
+        widths: Option<Vec<u8>>,
+    },
+    // ggez 内蔵の TrueType フォント
+    Ttf,
+}
 
 #[derive(Debug)]
 pub struct Font {
-    img: Image,
+    glyphs: Glyphs,
 }
 
 impl Font {
     const NCOL: usize = 16;
     const NROW: usize = 6;
+    // TrueType フォントで描くときの 1 文字の大きさの目安
+    const TTF_GLYPH: (usize, usize) = (9, 17);
 
+    // 16x6 に文字を並べた画像を読む。
+    // 画像と同じ名前で拡張子が .widths のファイルがあれば、文字ごとの幅をそこから読む。
+    // 画像がなければ ggez 内蔵の TrueType フォントで描く。
     pub fn new<P: AsRef<Path>>(ctx: &mut Context, path: P) -> GameResult<Self> {
+        let path = path.as_ref();
+        if !filesystem::exists(ctx, path) {
+            eprintln!("{} not found; using the built-in font", path.display());
+            return Ok(Self {
+                glyphs: Glyphs::Ttf,
+            });
+        }
         let img = Image::new(ctx, path)?;
 
         if img.width() as usize % Self::NCOL != 0 {
@@ -24,19 +122,86 @@ impl Font {
             return Err(FontError("height is not divisible".to_owned()));
         }
 
-        Ok(Self { img })
+        let sidecar = path.with_extension("widths");
+        let widths = if filesystem::exists(ctx, &sidecar) {
+            let mut s = String::new();
+            filesystem::open(ctx, &sidecar)?.read_to_string(&mut s)?;
+            let cell = img.width() as usize / Self::NCOL;
+            let widths = Self::parse_widths(&s, cell)
+                .map_err(|e| FontError(format!("{}: {:#}", sidecar.display(), e)))?;
+            Some(widths)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            glyphs: Glyphs::Bitmap { img, widths },
+        })
+    }
+
+    // 0x20 から 0x7E までの文字の幅(ピクセル)を空白区切りで順に並べたもの。
+    // '#' 以降は無視する。
+    fn parse_widths(s: &str, max: usize) -> anyhow::Result<Vec<u8>> {
+        let widths = s
+            .lines()
+            .map(|line| line.split('#').next().expect("internal error"))
+            .flat_map(str::split_whitespace)
+            .map(|w| -> anyhow::Result<u8> {
+                let w: u8 = w.parse().with_context(|| format!("invalid width: {}", w))?;
+                anyhow::ensure!(usize::from(w) <= max, "width {} exceeds the cell", w);
+                Ok(w)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let n = Self::NCOL * Self::NROW - 1;
+        anyhow::ensure!(
+            widths.len() == n,
+            "expected {} widths, found {}",
+            n,
+            widths.len()
+        );
+        Ok(widths)
     }
 
     pub fn glyph_width(&self) -> usize {
-        self.img.width() as usize / Self::NCOL
+        match &self.glyphs {
+            Glyphs::Bitmap { img, .. } => img.width() as usize / Self::NCOL,
+            Glyphs::Ttf => Self::TTF_GLYPH.0,
+        }
     }
 
     pub fn glyph_height(&self) -> usize {
-        self.img.height() as usize / Self::NROW
+        match &self.glyphs {
+            Glyphs::Bitmap { img, .. } => img.height() as usize / Self::NROW,
+            Glyphs::Ttf => Self::TTF_GLYPH.1,
+        }
+    }
+
+    fn ttf_text(&self, s: &str, color: Color) -> Text {
+        let scale = Scale::uniform(Self::TTF_GLYPH.1 as f32);
+        Text::new(TextFragment::new(s).color(color).scale(scale))
     }
 
-    pub fn draw_char(&self, ctx: &mut Context, x: f32, y: f32, ch: char) -> GameResult {
-        assert!(('\x20'..='\x7E').contains(&ch));
+    // 白い字に color を掛けた色で描く。字形のない文字は '?' にする。
+    pub fn draw_char(
+        &self,
+        ctx: &mut Context,
+        x: f32,
+        y: f32,
+        ch: char,
+        color: Color,
+    ) -> GameResult {
+        let ch = printable(ch);
+        let img = match &self.glyphs {
+            Glyphs::Bitmap { img, .. } => img,
+            Glyphs::Ttf => {
+                let text = self.ttf_text(&ch.to_string(), color);
+                return graphics::draw(
+                    ctx,
+                    &text,
+                    DrawParam::default().dest(mint::Point2 { x, y }),
+                );
+            }
+        };
 
         let (ch_c, ch_r) = {
             let idx = ch as usize - 0x20;
@@ -45,8 +210,8 @@ impl Font {
 
         let src_coord = |x: usize, y: usize| -> (f32, f32) {
             (
-                x as f32 / self.img.width() as f32,
-                y as f32 / self.img.height() as f32,
+                x as f32 / img.width() as f32,
+                y as f32 / img.height() as f32,
             )
         };
         let (sx, sy) = src_coord(ch_c * self.glyph_width(), ch_r * self.glyph_height());
@@ -54,7 +219,7 @@ impl Font {
 
         graphics::draw(
             ctx,
-            &self.img,
+            img,
             DrawParam::default()
                 .src(Rect {
                     x: sx,
@@ -62,18 +227,169 @@ impl Font {
                     w: sw,
                     h: sh,
                 })
-                .dest(mint::Point2 { x, y }),
+                .dest(mint::Point2 { x, y })
+                .color(color),
         )
     }
 
     pub fn draw_str<S: AsRef<str>>(&self, ctx: &mut Context, x: f32, y: f32, s: S) -> GameResult {
-        let s = s.as_ref();
+        self.draw_str_with(ctx, x, y, s, graphics::WHITE, Align::Left)
+    }
 
-        for (i, ch) in s.chars().enumerate() {
-            let dx = (i * self.glyph_width()) as f32;
-            self.draw_char(ctx, x + dx, y, ch)?;
+    // color を掛けた色で、align に従って置いて描く。改行があれば次の行に描く。
+    pub fn draw_str_with<S: AsRef<str>>(
+        &self,
+        ctx: &mut Context,
+        x: f32,
+        y: f32,
+        s: S,
+        color: Color,
+        align: Align,
+    ) -> GameResult {
+        let line_height = self.glyph_height() as f32;
+        for (i, line) in s.as_ref().split('\n').enumerate() {
+            self.draw_line(ctx, x, y + line_height * i as f32, line, color, align)?;
+        }
+        Ok(())
+    }
+
+    // 幅 max_width で折り返して描き、描いた高さを返す
+    pub fn draw_wrapped<S: AsRef<str>>(
+        &self,
+        ctx: &mut Context,
+        x: f32,
+        y: f32,
+        s: S,
+        max_width: f32,
+        color: Color,
+    ) -> GameResult<f32> {
+        let lines = self.wrap(ctx, s, max_width);
+        self.draw_str_with(ctx, x, y, lines.join("\n"), color, Align::Left)?;
+        Ok(self.glyph_height() as f32 * lines.len() as f32)
+    }
+
+    // 幅 max_width で折り返した行
+    pub fn wrap<S: AsRef<str>>(&self, ctx: &Context, s: S, max_width: f32) -> Vec<String> {
+        wrap_lines(s.as_ref(), max_width, |line| self.measure_str(ctx, line))
+    }
+
+    fn draw_line(
+        &self,
+        ctx: &mut Context,
+        x: f32,
+        y: f32,
+        s: &str,
+        color: Color,
+        align: Align,
+    ) -> GameResult {
+        let s = sanitize(s);
+        let mut x = align.start(x, self.measure_str(ctx, &s));
+
+        if let Glyphs::Ttf = self.glyphs {
+            let text = self.ttf_text(&s, color);
+            return graphics::draw(ctx, &text, DrawParam::default().dest(mint::Point2 { x, y }));
+        }
+
+        for ch in s.chars() {
+            self.draw_char(ctx, x, y, ch, color)?;
+            x += self.advance(ch);
         }
 
         Ok(())
     }
+
+    // 次の文字までの幅
+    fn advance(&self, ch: char) -> f32 {
+        match &self.glyphs {
+            Glyphs::Bitmap {
+                widths: Some(widths),
+                ..
+            } => Self::proportional_advance(widths, ch),
+            _ => self.glyph_width() as f32,
+        }
+    }
+
+    fn proportional_advance(widths: &[u8], ch: char) -> f32 {
+        f32::from(widths[printable(ch) as usize - 0x20])
+    }
+
+    // 描いたときの幅
+    pub fn measure_str<S: AsRef<str>>(&self, ctx: &Context, s: S) -> f32 {
+        let s = sanitize(s.as_ref());
+        match self.glyphs {
+            Glyphs::Bitmap { .. } => s.chars().map(|ch| self.advance(ch)).sum(),
+            Glyphs::Ttf => self.ttf_text(&s, graphics::WHITE).width(ctx) as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_widths() {
+        let mut s = "# proportional\n3 2 5\n".to_owned();
+        s.push_str(&"6 ".repeat(92));
+        let widths = Font::parse_widths(&s, 8).unwrap();
+        assert_eq!(widths.len(), 95);
+        assert_eq!(&widths[..4], [3, 2, 5, 6]);
+
+        assert!(Font::parse_widths(&s, 5).is_err());
+        assert!(Font::parse_widths("3 2 5", 8).is_err());
+        assert!(Font::parse_widths(&s.replace("3 2", "3 x"), 8).is_err());
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(sanitize("plain"), Cow::Borrowed("plain"));
+        assert_eq!(sanitize("好手 ok\u{7f}"), "?? ok?");
+        assert_eq!(printable('é'), '?');
+
+        // 字形のない文字も '?' の幅で測る
+        let mut widths = vec![6; 95];
+        widths['?' as usize - 0x20] = 4;
+        assert_eq!(Font::proportional_advance(&widths, '日'), 4.0);
+        assert_eq!(Font::proportional_advance(&widths, 'a'), 6.0);
+
+        let measure = |s: &str| {
+            sanitize(s)
+                .chars()
+                .map(|ch| Font::proportional_advance(&widths, ch))
+                .sum::<f32>()
+        };
+        assert_eq!(measure("日本 a"), 4.0 + 4.0 + 6.0 + 6.0);
+        assert_eq!(
+            super::wrap_lines("初手は 左下", 10.0, measure),
+            ["初手", "は", "左下"]
+        );
+    }
+
+    #[test]
+    fn wrap_lines() {
+        let measure = |s: &str| 10.0 * s.chars().count() as f32;
+        assert_eq!(
+            super::wrap_lines("the quick brown fox", 100.0, measure),
+            ["the quick", "brown fox"]
+        );
+        assert_eq!(
+            super::wrap_lines("one\n\ntwo  three", 100.0, measure),
+            ["one", "", "two three"]
+        );
+        // 長すぎる単語は文字で分ける
+        assert_eq!(
+            super::wrap_lines("a abcdefghijkl b", 50.0, measure),
+            ["a", "abcde", "fghij", "kl b"]
+        );
+        assert_eq!(super::wrap_lines("", 50.0, measure), [""]);
+    }
+
+    #[test]
+    fn align() {
+        assert_eq!(Align::Left.start(10.0, 40.0), 10.0);
+        assert_eq!(Align::Center(100.0).start(10.0, 40.0), 40.0);
+        assert_eq!(Align::Right(100.0).start(10.0, 40.0), 70.0);
+        // 幅からはみ出すときは両側(右揃えなら左)にはみ出す
+        assert_eq!(Align::Center(20.0).start(10.0, 40.0), 0.0);
+    }
 }
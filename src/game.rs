@@ -0,0 +1,423 @@
+use std::time::Duration;
+
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::board::{self, Board};
+use crate::powerup::{self, Inventory, Outcome};
+use crate::replay::Action;
+use crate::rules::{self, Earthquake, Rules, Streak, TimePressure};
+use crate::sim::GameRng;
+use crate::snapshot::GameSnapshot;
+use crate::speedrun::Splits;
+
+// 盤面の生成条件とルール
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GameConfig {
+    pub width: usize,
+    pub height: usize,
+    pub colors: u8,
+    pub rules: Rules,
+}
+
+impl GameConfig {
+    // 2 色だけの小さめの盤面。大きなグループがつながって一気に消える。
+    pub fn two_color() -> Self {
+        Self {
+            width: 12,
+            height: 8,
+            colors: 2,
+            ..Self::default()
+        }
+    }
+
+    // ファイル名などに使う設定ごとの識別子
+    pub fn key(&self) -> String {
+        format!(
+            "{}x{}c{}r{}",
+            self.width,
+            self.height,
+            self.colors,
+            self.rules.to_bits()
+        )
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            width: 20,
+            height: 10,
+            colors: board::DEFAULT_COLORS,
+            rules: Rules::default(),
+        }
+    }
+}
+
+// 盤面を変化させた入力と、それで得た得点
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub action: Action,
+    pub score: i32,
+}
+
+// 描画から切り離したゲーム本体。
+// 乱数は全てシードから生成するので、同じ入力列を与えれば同じ結果になる。
+// 時刻はゲーム開始からの経過時間で与える。
+#[derive(Debug, Clone)]
+pub struct Game {
+    seed: u64,
+    config: GameConfig,
+    rng: Box<dyn GameRng>,
+    // 乱数で作らずに与えられた初期盤面
+    given_board: Option<Board>,
+
+    board: Board,
+    score: i32,
+    finished: bool,
+    streak: Streak,
+    earthquake: Earthquake,
+    move_started: Duration,
+    total_tiles: usize,
+    splits: Splits,
+    powerups: Inventory,
+    history: Vec<Move>,
+}
+
+impl Game {
+    pub fn new(seed: u64, config: GameConfig) -> Self {
+        Self::with_rng(seed, config, Box::new(Pcg32::seed_from_u64(seed)))
+    }
+
+    // 乱数源を差し替えて作る。seed はリプレイなどに記録されるだけ。
+    pub fn with_rng(seed: u64, config: GameConfig, mut rng: Box<dyn GameRng>) -> Self {
+        let board = Board::random_with(config.width, config.height, config.colors, &mut *rng);
+        Self::from_parts(seed, config, rng, board)
+    }
+
+    // 決まった盤面から始める。乱数はパワーアップなどに使う。
+    pub fn with_board(seed: u64, config: GameConfig, board: Board) -> Self {
+        assert_eq!(
+            (board.width(), board.height()),
+            (config.width, config.height)
+        );
+        let rng = Box::new(Pcg32::seed_from_u64(seed));
+        let mut game = Self::from_parts(seed, config, rng, board.clone());
+        game.given_board = Some(board);
+        game
+    }
+
+    fn from_parts(seed: u64, config: GameConfig, rng: Box<dyn GameRng>, board: Board) -> Self {
+        let finished = board.is_finished();
+        let total_tiles = board.tile_count();
+
+        Self {
+            seed,
+            config,
+            rng,
+            given_board: None,
+            board,
+            score: 0,
+            finished,
+            streak: Streak::new(),
+            earthquake: Earthquake::new(),
+            move_started: Duration::default(),
+            total_tiles,
+            splits: Splits::new(),
+            powerups: Self::initial_powerups(),
+            history: vec![],
+        }
+    }
+
+    fn initial_powerups() -> Inventory {
+        let mut inv = Inventory::new();
+        inv.add(Box::new(powerup::Shuffle), 1);
+        inv.add(Box::new(powerup::Swap), 2);
+        inv.add(Box::new(powerup::ColorBomb), 1);
+        inv
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn config(&self) -> GameConfig {
+        self.config
+    }
+
+    pub fn given_board(&self) -> Option<&Board> {
+        self.given_board.as_ref()
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.config.rules
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn streak(&self) -> &Streak {
+        &self.streak
+    }
+
+    pub fn earthquake(&self) -> &Earthquake {
+        &self.earthquake
+    }
+
+    pub fn splits(&self) -> &Splits {
+        &self.splits
+    }
+
+    pub fn powerups(&self) -> &Inventory {
+        &self.powerups
+    }
+
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            board: self.board.clone(),
+            score: self.score,
+            history: self.history.clone(),
+        }
+    }
+
+    // 現在の手の得点に掛かる時間減衰
+    pub fn time_factor(&self, now: Duration) -> f32 {
+        TimePressure::factor(now - self.move_started.min(now))
+    }
+
+    // セルをクリックする。パワーアップが発動待ちならそのターゲットになる。
+    // 盤面が変化したら true。
+    pub fn click(&mut self, x: usize, y: usize, now: Duration) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        if self.powerups.armed().is_some() {
+            let outcome = self.powerups.pick(&mut self.board, x, y, &mut *self.rng);
+            return self.on_powerup(Action::Click(x, y), outcome, now);
+        }
+
+        let n = self.board.erase_component(x, y);
+        if n < 2 {
+            return false;
+        }
+
+        let mul = if self.config.rules.streak {
+            self.streak.record(n)
+        } else {
+            1
+        };
+        let mut score = mul * rules::group_score(n);
+        if self.config.rules.time_pressure {
+            score = TimePressure::apply(score, now - self.move_started.min(now));
+        }
+        self.powerups.tick();
+
+        self.after_move(Action::Click(x, y), score, now);
+        true
+    }
+
+    pub fn select_powerup(&mut self, i: usize, now: Duration) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        let outcome = self.powerups.select(i, &mut self.board, &mut *self.rng);
+        self.on_powerup(Action::PowerUp(i), outcome, now)
+    }
+
+    // 回転ルールのときだけ盤面を回す。盤面が変化したら true。
+    pub fn rotate(&mut self, now: Duration) -> bool {
+        if self.finished || !self.config.rules.rotate {
+            return false;
+        }
+        self.earthquake.calm();
+        self.rotate_board(now);
+        true
+    }
+
+    // 回しても手の時間は数え直さない
+    fn rotate_board(&mut self, now: Duration) {
+        self.board.rotate();
+        self.history.push(Move {
+            action: Action::Rotate,
+            score: 0,
+        });
+        self.check_finished(now);
+    }
+
+    fn on_powerup(&mut self, action: Action, outcome: Outcome, now: Duration) -> bool {
+        match outcome {
+            Outcome::Applied(score) => {
+                self.after_move(action, score, now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn after_move(&mut self, action: Action, score: i32, now: Duration) {
+        self.score += score;
+        self.history.push(Move { action, score });
+        self.move_started = now;
+
+        let erased = self.total_tiles - self.board.tile_count();
+        self.splits.record(erased, self.total_tiles, now);
+
+        if self.config.rules.earthquake && self.earthquake.tick() {
+            self.quake();
+        }
+
+        // 回転ルールでは決まった手数ごとに勝手に回る。詰んだ盤面も回ると動けることがある。
+        if self.config.rules.rotate {
+            let moves = self
+                .history
+                .iter()
+                .filter(|m| m.action != Action::Rotate)
+                .count();
+            if moves % rules::ROTATE_EVERY == 0 {
+                self.rotate_board(now);
+                return;
+            }
+        }
+        self.check_finished(now);
+    }
+
+    // 盤面のどこかを Earthquake::SIZE 四方だけかき混ぜる
+    fn quake(&mut self) {
+        let w = Earthquake::SIZE.min(self.board.width());
+        let h = Earthquake::SIZE.min(self.board.height());
+        let x = self.rng.below((self.board.width() - w + 1) as u32) as usize;
+        let y = self.rng.below((self.board.height() - h + 1) as u32) as usize;
+        self.board.shuffle_region((x, y), (w, h), &mut *self.rng);
+        self.earthquake.strike((x, y, w, h));
+    }
+
+    fn check_finished(&mut self, now: Duration) {
+        self.finished = self.board.is_finished();
+        if self.finished {
+            self.splits.finish(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let config = GameConfig {
+            width: 8,
+            height: 6,
+            ..GameConfig::default()
+        };
+        let mut a = Game::new(42, config);
+        let mut b = Game::new(42, config);
+        assert_eq!(a.board(), b.board());
+
+        // シャッフルも同じ結果になる
+        assert!(a.select_powerup(0, Duration::from_secs(1)));
+        assert!(b.select_powerup(0, Duration::from_secs(1)));
+        assert_eq!(a.board(), b.board());
+    }
+
+    #[test]
+    fn two_color() {
+        let config = GameConfig::two_color();
+        let game = Game::new(7, config);
+        let board = game.board();
+        assert_eq!(
+            board.color_count(1) + board.color_count(2),
+            config.width * config.height
+        );
+        assert!(!game.is_finished());
+    }
+
+    #[test]
+    fn rotate() {
+        let mut config = GameConfig {
+            width: 6,
+            height: 4,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new(1, config);
+        assert!(!game.rotate(Duration::default()));
+
+        config.rules.rotate = true;
+        let mut game = Game::new(1, config);
+        assert!(game.rotate(Duration::default()));
+        assert_eq!((game.board().width(), game.board().height()), (4, 6));
+        assert_eq!(game.score(), 0);
+
+        // ROTATE_EVERY 手ごとに勝手に回る
+        let mut moves = 0;
+        while moves < rules::ROTATE_EVERY && !game.is_finished() {
+            let ((x, y), _) = game.board().groups()[0];
+            assert!(game.click(x, y, Duration::default()));
+            moves += 1;
+        }
+        if moves == rules::ROTATE_EVERY {
+            assert_eq!(
+                game.snapshot().history.last().unwrap().action,
+                Action::Rotate
+            );
+            assert_eq!((game.board().width(), game.board().height()), (6, 4));
+        }
+    }
+
+    #[test]
+    fn earthquake() {
+        let mut config = GameConfig {
+            width: 8,
+            height: 6,
+            ..GameConfig::default()
+        };
+        config.rules.earthquake = true;
+        let mut a = Game::new(3, config);
+        let mut b = Game::new(3, config);
+
+        // 同じ手を打てば同じように揺れる
+        for i in 1..=Earthquake::EVERY {
+            if a.is_finished() {
+                return;
+            }
+            let ((x, y), _) = a.board().groups()[0];
+            assert!(a.click(x, y, Duration::default()));
+            assert!(b.click(x, y, Duration::default()));
+            assert_eq!(a.board(), b.board());
+            assert_eq!(a.earthquake().region().is_some(), i == Earthquake::EVERY);
+        }
+        let (_, _, w, h) = a.earthquake().region().unwrap();
+        assert_eq!((w, h), (Earthquake::SIZE, Earthquake::SIZE));
+
+        // 揺れたあとに Z で回しても、もう一度は揺れない
+        config.rules.rotate = true;
+        let mut c = Game::new(3, config);
+        for _ in 0..Earthquake::EVERY {
+            let ((x, y), _) = c.board().groups()[0];
+            assert!(c.click(x, y, Duration::default()));
+        }
+        assert!(c.earthquake().region().is_some());
+        assert!(c.rotate(Duration::default()));
+        assert_eq!(c.earthquake().region(), None);
+    }
+
+    #[test]
+    fn config_key() {
+        let mut config = GameConfig::default();
+        assert_eq!(config.key(), "20x10c5r0");
+        config.rules.time_pressure = true;
+        assert_eq!(config.key(), "20x10c5r2");
+    }
+}
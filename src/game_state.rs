@@ -1,176 +1,2851 @@
-use ggez::event::{self, KeyCode, KeyMods, MouseButton};
-use ggez::graphics::{self, Color, DrawMode, Image, Mesh, Rect};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clipboard::{ClipboardContext, ClipboardProvider};
+use ggez::event;
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Image, Mesh, Rect};
 use ggez::mint;
 use ggez::{Context, GameResult};
 use itertools::iproduct;
 
-use crate::board::Board;
-use crate::font::Font;
+use crate::anim::{AnimClock, Ease, Tween};
+use crate::audio::{Audio, Playback, Sound};
+use crate::backdrop::{self, Backdrop};
+use crate::board::{self, Board};
+use crate::challenge::Challenge;
+use crate::curriculum::{Campaign, Level, Progress};
+use crate::dialog::{Answer, Dialog, DialogStack};
+use crate::difficulty::{self, Difficulty};
+use crate::eval::Weights;
+use crate::font::{Align, Font};
+use crate::game::{Game, GameConfig};
+use crate::highlight::{self, GroupGlow, HighlightCache};
+use crate::highscore::{self, HighScores};
+use crate::hint::{Hint, HintLevel};
+use crate::input;
+use crate::jobs::{JobId, JobQueue};
+use crate::layout::{Anchor, Layout, Stack};
+use crate::monitor::Monitor;
+use crate::movelog;
+use crate::narrator::Narrator;
+use crate::notation;
+use crate::opponent::Opponent;
+use crate::personal_best::PersonalBests;
+use crate::profile::Profile;
+use crate::rating::{self, Rating, Ratings};
+use crate::recent::RecentBoards;
+use crate::reference;
+use crate::replay::{Action, Ghost, Replay};
+use crate::rotation::{self, Period, Rotation};
+use crate::rumble::Rumble;
+use crate::settings::Settings;
+use crate::sim::Clock;
+use crate::snapshot::{self, Diff, GameSnapshot};
+use crate::solver;
+use crate::speedrun::{self, Splits};
+use crate::storage::SaveBackend;
+use crate::suspend::{Choice, Suspended};
+use crate::telemetry::{self, Telemetry};
+use crate::tiles::TileSet;
+use crate::tournament::{RoundResult, Tournament};
+use crate::tutorial::Tutorial;
+use crate::unlock::{self, Reward, RewardKind, Unlocks};
+use crate::widget::{Form, Response, Widget};
+
+// 描画スレッドの外で計算した結果
+#[derive(Debug)]
+enum JobOutput {
+    Hint(Option<Hint>),
+    Difficulty(Difficulty),
+    // コンピュータの相手が指した記録
+    Opponent(Replay),
+    // 今の局面から最後まで指したときに取れそうな得点の合計
+    Estimate(i32),
+    // 終わったゲームの、ソルバーの得点と比べた評価
+    Rating(Rating),
+    // 用意したレベル。パックを最後まで遊んでいれば None。
+    Level(Option<Level>),
+}
+
+// 開いているダイアログの用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DialogTag {
+    Quit,
+    // ハイスコアの順位
+    Name(usize),
+    Error,
+    // 閉じるだけのお知らせ
+    Notice,
+}
 
 const CURSOR_INVALID: (usize, usize) = (usize::max_value(), usize::max_value());
 
+// 盤面が回って幅と高さが入れ替わったときなどに、外に出たカーソルを消す
+fn cursor_within(cursor: (usize, usize), width: usize, height: usize) -> (usize, usize) {
+    if cursor.0 < width && cursor.1 < height {
+        cursor
+    } else {
+        CURSOR_INVALID
+    }
+}
+
+// 与えられた盤面を遊ぶときの設定。大きさと色数は盤面に合わせ、ルールはそのまま。
+fn config_for(board: &Board, config: GameConfig) -> GameConfig {
+    GameConfig {
+        width: board.width(),
+        height: board.height(),
+        colors: board.colors().max(1),
+        ..config
+    }
+}
+
 #[derive(Debug)]
 enum Command {
-    Nop,
     Erase(usize, usize),
+    PowerUp(usize),
+    ToggleStreak,
+    ToggleTimePressure,
+    ToggleRotate,
+    ToggleEarthquake,
+    TwoColor,
+    RotateBoard,
+    ToggleGhost,
+    CycleOpponent,
+    CopyBoard,
+    PasteBoard,
+    OpenBoard,
+    ExportMoves,
+    Challenge(Challenge),
+    Rotation(Rotation),
+    NextRound,
+    PlayLevel(u32, Progress),
+    ApplyWindowMode,
+    SelectReward(usize),
+    Hint,
+    HintLevel,
+    NextLevel,
+    AnswerResume(Choice, Suspended),
     Reset,
+    Retry,
     Quit,
 }
 
 #[derive(Debug)]
 pub struct GameState {
-    imgs_tile: Vec<Image>,
+    tiles: TileSet,
+    audio: Audio,
+    // ゲームパッドを開けなければ None
+    rumble: Option<Rumble>,
+    // 最後に音で知らせたグループ(左上のセル)
+    hover_cue: Option<(usize, usize)>,
+    // 読み上げなければ None
+    narrator: Option<Box<dyn Narrator>>,
+    // 別の画像を使うタイルの見た目ごとの画像。読めなかったものは既定の画像で描く。
+    themed_tiles: BTreeMap<&'static str, TileSet>,
+    // 背景の画像。読めなかったものは背景色だけで描く。
+    backdrops: BTreeMap<&'static str, Image>,
     font: Font,
 
-    board: Board,
+    backend: Box<dyn SaveBackend>,
+    profile: Profile,
+
+    config: GameConfig,
+    game: Game,
+    clock: Box<dyn Clock>,
     cursor: (usize, usize),
-    cmd: Command,
-    score: i32,
+    // 次の update で順に実行する操作。同じフレームの入力を取りこぼさないよう溜めておく。
+    cmds: VecDeque<Command>,
+    // カーソル下のグループの強調表示
+    hover: HighlightCache,
+    // 選択中のグループを明るくするシェーダ。使えなければ hover のメッシュで描く。
+    glow: Option<GroupGlow>,
+
+    // 開始時の盤面の難しさ(計算中なら None)と、表に載っている盤面ならこのクレートのソルバーの得点
+    difficulty: Option<Difficulty>,
+    difficulty_job: Option<JobId>,
+    reference_score: Option<i32>,
+    // 評価の棒と数字に出す、今の局面から取れそうな得点の合計と、それを計算中の仕事
+    estimate: Option<i32>,
+    estimate_job: Option<JobId>,
+    // 続けて指している間は計算しないよう、この時刻まで待ってから計算を始める
+    estimate_due: Option<Duration>,
+
+    // 演出用の時計
+    anim: AnimClock,
+    // 直前の手による変化と、それを見せる演出
+    last_move: Option<(Tween, Diff)>,
+    // 盤面が回ったときの演出
+    rotating: Option<Tween>,
+    // 地震で盤面を揺らす演出
+    shaking: Option<Tween>,
+
+    // 表示中のヒント。手を打ったら消える。
+    hint: Option<Hint>,
+    // 計算中のヒント。手を打ったら取り消す。
+    hint_job: Option<JobId>,
+    // ヒントでどこまで見せるか。Shift+I で切り替える。
+    hint_level: HintLevel,
+
+    // 描画スレッドの外で計算する仕事
+    jobs: JobQueue<JobOutput>,
+
+    replay: Replay,
+    ghost_enabled: bool,
+    ghost: Option<Ghost>,
+    // コンピュータの相手と競っていればその強さ。相手の手は計算してゴーストとして出す。
+    opponent: Option<Opponent>,
+    opponent_job: Option<JobId>,
+
+    // 日替わり・週替わりの盤面を遊んでいればその期間と専用のハイスコア
+    rotation: Option<(Period, HighScores)>,
+
+    // レベルを順に遊んでいれば現在のレベルと到達状況
+    campaign: Campaign,
+    curriculum: Option<(Level, Progress)>,
+    // 用意しているレベルの仕事と、その番号と到達状況
+    level_job: Option<(JobId, u32, Progress)>,
+
+    // トーナメント中なら進行状況と結果の書き出し先
+    tournament: Option<(Tournament, PathBuf)>,
+
+    // エディタで作った盤面を遊んでいればその盤面
+    edited: Option<Board>,
+
+    // 現在の設定のハイスコアと、直前のゲームの順位
+    highscores: HighScores,
+    rank: Option<usize>,
+
+    personal_bests: PersonalBests,
+    // パズルラッシュの連続正解の表
+    rush_scores: HighScores,
+    // これまでのゲームの評価
+    ratings: Ratings,
+    // 設定で有効にしたときだけ残す遊び方の記録
+    telemetry: Telemetry,
+
+    // 解放済みの見た目と、直前に解放されたものの通知
+    unlocks: Unlocks,
+    // 最近開いた盤面のファイル
+    recent: RecentBoards,
+    unlock_notice: Option<(Tween, &'static Reward)>,
+    // 操作を止めずにフッターにしばらく出す知らせ
+    status: Option<(Tween, String)>,
+    prefs: Settings,
+    // 設定画面に出す値が変わった回数。設定画面はこれが変わったら項目を作り直す。
+    settings_revision: u32,
+
+    // 画面の大きさ。ウィンドウの大きさが変わると更新する。
+    screen: Rect,
+
+    dialogs: DialogStack<DialogTag>,
 }
 
 impl GameState {
-    const BOARD_W: usize = 20;
-    const BOARD_H: usize = 10;
+    const CELL_SIZE: f32 = 32.0;
+    // 盤面の下の情報表示の高さと余白
+    const HUD_HEIGHT: f32 = 160.0;
+    const HUD_PADDING: f32 = 10.0;
+    // 右側の得点表示の幅
+    const HUD_WIDTH: f32 = 110.0;
+    pub const LINE_HEIGHT: f32 = 17.0;
+    // 読み筋を盤面に描く手数
+    pub const PLAN_MOVES: usize = 5;
+    // 手を指してから見積もりを計算し始めるまでの時間と、計算に使う時間
+    const ESTIMATE_DELAY: Duration = Duration::from_millis(500);
+    const ESTIMATE_BUDGET: Duration = Duration::from_secs(2);
+    const JOB_THREADS: usize = 2;
+    const JOBS_PER_FRAME: usize = 4;
 
-    pub fn new(ctx: &mut Context) -> GameResult<Self> {
-        let imgs_tile = (1..=5)
-            .map(|i| Image::new(ctx, format!("/tile-{}.png", i)))
-            .collect::<GameResult<Vec<_>>>()?;
+    pub fn new(
+        ctx: &mut Context,
+        clock: Box<dyn Clock>,
+        backend: Box<dyn SaveBackend>,
+        profile: Profile,
+        challenge: Option<Challenge>,
+        tournament: Option<(Tournament, PathBuf)>,
+        campaign: Campaign,
+    ) -> GameResult<Self> {
         let font = Font::new(ctx, "/font.png")?;
+        let audio = Audio::new(ctx);
+        let rumble = Rumble::new();
+        let hover_cue = None;
+        let narrator = None;
 
-        let board = Board::random(Self::BOARD_W, Self::BOARD_H);
+        let challenge = match &tournament {
+            Some((t, _)) => t.current(),
+            None => challenge,
+        };
+        let (config, seed) = match challenge {
+            Some(challenge) => (challenge.config, challenge.seed),
+            None => (GameConfig::default(), rand::random()),
+        };
+        let game = Game::new(seed, config);
+
+        // 読み込めなかったもの。dialogs ができてからまとめて知らせる。
+        let mut failures = vec![];
 
         let cursor = CURSOR_INVALID;
-        let cmd = Command::Nop;
+        let hover = HighlightCache::default();
+        let glow = match GroupGlow::new(ctx) {
+            Ok(glow) => Some(glow),
+            Err(e) => {
+                let e = anyhow::anyhow!("{}", e);
+                failures.push(("cannot load highlight shader".to_owned(), e));
+                None
+            }
+        };
+        let cmds = VecDeque::new();
+        let mut jobs = JobQueue::new(Self::JOB_THREADS);
+        let difficulty = None;
+        let difficulty_job = Some(Self::submit_difficulty(&mut jobs, &game));
+        let reference_score = reference::score(game.board());
+        let anim = AnimClock::default();
+        let last_move = None;
+        let rotating = None;
+        let shaking = None;
+        let hint = None;
+        let hint_job = None;
+
+        let replay = Replay::new(&game);
+        let ghost_enabled = true;
+        let ghost = None;
+
+        let rotation = None;
+        let curriculum = None;
 
-        let score = 0;
+        let highscores = Self::load_highscores(&*backend, &profile, &config);
+        let rank = None;
 
-        Ok(Self {
-            imgs_tile,
+        let personal_bests = PersonalBests::load(&*backend, &Self::personal_bests_key(&profile))
+            .unwrap_or_else(|e| {
+                failures.push(("cannot load personal bests".to_owned(), e));
+                PersonalBests::default()
+            });
+
+        let rush_scores = HighScores::load(&*backend, &Self::rush_scores_key(&profile))
+            .unwrap_or_else(|e| {
+                failures.push(("cannot load puzzle rush scores".to_owned(), e));
+                HighScores::default()
+            });
+        let ratings = Ratings::load(&*backend, &Self::ratings_key(&profile)).unwrap_or_else(|e| {
+            failures.push(("cannot load ratings".to_owned(), e));
+            Ratings::default()
+        });
+
+        let unlocks = Unlocks::load(&*backend, &Self::unlocks_key(&profile)).unwrap_or_else(|e| {
+            failures.push(("cannot load unlocks".to_owned(), e));
+            Unlocks::default()
+        });
+        let unlock_notice = None;
+        let recent =
+            RecentBoards::load(&*backend, &Self::recent_key(&profile)).unwrap_or_else(|e| {
+                failures.push(("cannot load recent boards".to_owned(), e));
+                RecentBoards::default()
+            });
+        let prefs = Settings::load(&*backend, &Settings::key(&profile)).unwrap_or_else(|e| {
+            failures.push(("cannot load settings".to_owned(), e));
+            Settings::default()
+        });
+        let tiles = TileSet::load(ctx, "", &prefs.tile_skins)?;
+        let mut themed_tiles = BTreeMap::new();
+        let mut backdrops = BTreeMap::new();
+        for reward in unlock::REWARDS {
+            match reward.kind {
+                RewardKind::TileTheme {
+                    sheet: Some(dir), ..
+                } => match TileSet::load(ctx, dir, &prefs.tile_skins) {
+                    Ok(set) => {
+                        themed_tiles.insert(dir, set);
+                    }
+                    Err(e) => failures.push((
+                        format!("cannot load tiles for {}", reward.id),
+                        anyhow::anyhow!("{}", e),
+                    )),
+                },
+                RewardKind::Background {
+                    backdrop: Backdrop::Image(path),
+                    ..
+                } => match Image::new(ctx, path) {
+                    Ok(img) => {
+                        backdrops.insert(path, img);
+                    }
+                    Err(e) => failures.push((
+                        format!("cannot load background for {}", reward.id),
+                        anyhow::anyhow!("{}", e),
+                    )),
+                },
+                _ => {}
+            }
+        }
+
+        let mut this = Self {
+            tiles,
+            audio,
+            rumble,
+            hover_cue,
+            narrator,
+            themed_tiles,
+            backdrops,
             font,
-            board,
+            backend,
+            profile,
+            config,
+            game,
+            clock,
             cursor,
-            cmd,
-            score,
+            cmds,
+            hover,
+            glow,
+            difficulty,
+            difficulty_job,
+            reference_score,
+            estimate: None,
+            estimate_job: None,
+            estimate_due: None,
+            anim,
+            last_move,
+            rotating,
+            shaking,
+            hint,
+            hint_job,
+            hint_level: HintLevel::default(),
+            jobs,
+            replay,
+            ghost_enabled,
+            ghost,
+            opponent: None,
+            opponent_job: None,
+            rotation,
+            campaign,
+            curriculum,
+            level_job: None,
+            tournament,
+            edited: None,
+            highscores,
+            rank,
+            personal_bests,
+            rush_scores,
+            ratings,
+            telemetry: Telemetry::default(),
+            unlocks,
+            recent,
+            unlock_notice,
+            status: None,
+            prefs,
+            settings_revision: 0,
+            screen: graphics::screen_coordinates(ctx),
+            dialogs: DialogStack::default(),
+        };
+        this.ghost = this.load_ghost();
+        this.refresh_estimate();
+        for (what, e) in failures {
+            this.report(&what, e);
+        }
+
+        Ok(this)
+    }
+
+    fn start(&mut self, seed: u64) {
+        self.abandon();
+
+        if self.game.config() != self.config {
+            self.highscores = Self::load_highscores(&*self.backend, &self.profile, &self.config);
+        }
+        self.rank = None;
+        self.last_move = None;
+        self.rotating = None;
+        self.shaking = None;
+        self.hint = None;
+        self.cancel_hint();
+
+        self.game = Self::new_game(seed, self.config, &self.edited);
+        self.fit_cursor();
+        if let Some(id) = self.difficulty_job.take() {
+            self.jobs.cancel(id);
+        }
+        self.difficulty = None;
+        self.difficulty_job = Some(Self::submit_difficulty(&mut self.jobs, &self.game));
+        self.reference_score = reference::score(self.game.board());
+        self.clock.reset(Duration::default());
+        self.replay = Replay::new(&self.game);
+        self.ghost = if self.ghost_enabled {
+            self.load_ghost()
+        } else {
+            None
+        };
+        self.race_opponent();
+        self.estimate = None;
+        self.refresh_estimate();
+        self.telemetry.restart();
+        let config = self.game.config();
+        self.log(telemetry::Event::Start {
+            width: config.width,
+            height: config.height,
+            colors: config.colors,
+        });
+    }
+
+    fn log(&mut self, event: telemetry::Event) {
+        if self.prefs.telemetry {
+            self.telemetry.log(self.game.seed(), event);
+        }
+    }
+
+    fn flush_telemetry(&mut self) {
+        let key = Telemetry::key(&self.profile);
+        if let Err(e) = self.telemetry.flush(&*self.backend, &key) {
+            self.report("cannot save telemetry", e);
+        }
+    }
+
+    fn submit_difficulty(jobs: &mut JobQueue<JobOutput>, game: &Game) -> JobId {
+        let board = game.board().clone();
+        jobs.submit(move |_: &mut Monitor| {
+            JobOutput::Difficulty(difficulty::estimate_difficulty(&board).difficulty)
         })
     }
 
-    fn calc_cursor(&self, x: f32, y: f32) -> (usize, usize) {
-        if x < 0.0 || y < 0.0 {
-            return CURSOR_INVALID;
+    // コンピュータの相手を決め直し、今のゲームで競う
+    pub fn set_opponent(&mut self, opponent: Option<Opponent>) {
+        self.opponent = opponent;
+        self.race_opponent();
+    }
+
+    // 相手がいれば最高記録のゴーストの代わりに相手の手を計算して出す
+    fn race_opponent(&mut self) {
+        if let Some(id) = self.opponent_job.take() {
+            self.jobs.cancel(id);
         }
+        let level = match self.opponent {
+            Some(level) => level,
+            None => return,
+        };
+        self.ghost = None;
+        // Game はスレッドをまたげないので、手のない記録から作り直す
+        let base = Replay::new(&self.replay.new_game());
+        self.opponent_job = Some(
+            self.jobs
+                .submit(move |_: &mut Monitor| JobOutput::Opponent(level.play(&base.new_game()))),
+        );
+    }
 
-        let cx = x as usize / 32;
-        let cy = y as usize / 32;
-        if cx >= self.board.width() || cy >= self.board.height() {
-            return CURSOR_INVALID;
+    fn wants_estimate(&self) -> bool {
+        self.prefs.eval_bar || self.prefs.eval_readout
+    }
+
+    // 見積もりを出していれば、少し待ってから今の局面で計算し直す。
+    // 新しい見積もりが出るまでは前の見積もりを出しておく。
+    fn refresh_estimate(&mut self) {
+        if let Some(id) = self.estimate_job.take() {
+            self.jobs.cancel(id);
+        }
+        self.estimate_due = None;
+        if !self.wants_estimate() {
+            self.estimate = None;
+            return;
+        }
+        if self.game.is_finished() {
+            self.estimate = Some(self.game.score());
+            return;
         }
+        self.estimate_due = Some(self.anim.now() + Self::ESTIMATE_DELAY);
+    }
 
-        (cx, cy)
+    fn submit_estimate(&mut self) {
+        let score = self.game.score();
+        let board = self.game.board().clone();
+        self.estimate_job = Some(self.jobs.submit(move |monitor: &mut Monitor| {
+            monitor.set_deadline(Some(Instant::now() + Self::ESTIMATE_BUDGET));
+            let line = solver::iterative_beam_search(&board, Weights::default(), monitor);
+            JobOutput::Estimate(score + line.score)
+        }));
     }
-}
 
-impl event::EventHandler for GameState {
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        match self.cmd {
-            Command::Erase(x, y) => {
-                let n = self.board.erase_component(x, y);
-                if n >= 2 {
-                    self.score += (n - 1).pow(2) as i32;
-                }
-            }
-            Command::Reset => {
-                self.board = Board::random(Self::BOARD_W, Self::BOARD_H);
-                self.score = 0;
+    pub fn set_narrator(&mut self, narrator: Box<dyn Narrator>) {
+        self.narrator = Some(narrator);
+    }
+
+    fn announce(&mut self, text: &str) {
+        if let Some(narrator) = &mut self.narrator {
+            narrator.announce(text);
+        }
+    }
+
+    fn cancel_hint(&mut self) {
+        if let Some(id) = self.hint_job.take() {
+            self.jobs.cancel(id);
+        }
+    }
+
+    // 自作の盤面のやり直しならその盤面から始める
+    fn new_game(seed: u64, config: GameConfig, edited: &Option<Board>) -> Game {
+        match edited {
+            Some(board) if board.fingerprint() == seed => {
+                Game::with_board(seed, config, board.clone())
             }
-            Command::Quit => {
-                event::quit(ctx);
+            _ => Game::new(seed, config),
+        }
+    }
+
+    fn resume(&mut self, suspended: Suspended) {
+        self.config = suspended.replay.config;
+        self.leave_modes();
+        self.edited = suspended.replay.board.clone();
+        self.start(suspended.replay.seed);
+
+        self.game = suspended.game();
+        self.fit_cursor();
+        self.replay = suspended.replay;
+        self.clock.reset(suspended.elapsed);
+        self.estimate = None;
+        self.refresh_estimate();
+    }
+
+    fn suspended_key(profile: &Profile) -> String {
+        profile.key("suspended.json")
+    }
+
+    // 前回途中で終了したゲームがあれば読む
+    pub fn load_suspended(&self) -> Option<Suspended> {
+        Suspended::load(&*self.backend, &Self::suspended_key(&self.profile)).unwrap_or_else(|e| {
+            eprintln!("cannot load suspended game: {:#}", e);
+            None
+        })
+    }
+
+    fn settle_suspended(&mut self, choice: Choice) {
+        let res = Suspended::settle(&*self.backend, &Self::suspended_key(&self.profile), choice);
+        if let Err(e) = res {
+            eprintln!("cannot remove suspended game: {:#}", e);
+        }
+    }
+
+    // 終了時に遊びかけのゲームとウィンドウの位置を保存する
+    fn on_quit(&mut self, ctx: &Context) {
+        // 計算中の仕事はもう要らない
+        self.jobs.cancel_all();
+        self.abandon();
+        if !self.prefs.fullscreen {
+            if let Some(position) = graphics::window(ctx).get_position() {
+                self.prefs.window_position = Some((position.x, position.y));
             }
-            _ => {}
         }
-        self.cmd = Command::Nop;
+        self.save_prefs();
 
-        Ok(())
+        let suspended = if self.game.is_finished() {
+            None
+        } else {
+            Suspended::new(&self.replay, self.now())
+        };
+        let suspended = match suspended {
+            Some(suspended) => suspended,
+            None => return,
+        };
+
+        let res = suspended.save(&*self.backend, &Self::suspended_key(&self.profile));
+        if let Err(e) = res {
+            eprintln!("cannot save suspended game: {:#}", e);
+        }
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        graphics::clear(ctx, graphics::BLACK);
+    // 保存できなかったことなどをダイアログで知らせる。パスなどの ASCII 以外の文字は Font が '?' にする。
+    fn report(&mut self, what: &str, e: anyhow::Error) {
+        let message = format!("{}: {:#}", what, e);
+        self.dialogs
+            .push(Dialog::message(DialogTag::Error, "Error", message));
+    }
 
-        for (x, y) in iproduct!(0..self.board.width(), 0..self.board.height()) {
-            let color = self.board.at(x, y);
-            if color == 0 {
-                continue;
+    // 今の盤面を Board::parse の形式でクリップボードに写す
+    fn copy_board(&mut self) {
+        let text = self.game.board().to_text();
+        let res = ClipboardContext::new().and_then(|mut clipboard| clipboard.set_contents(text));
+        match res {
+            Ok(()) => self.announce("Board copied."),
+            Err(e) => self.report("cannot copy the board", anyhow::anyhow!("{}", e)),
+        }
+    }
+
+    // クリップボードの盤面をすぐに遊ぶ。読めないか大きすぎればダイアログで知らせる。
+    fn paste_board(&mut self) {
+        let res = ClipboardContext::new()
+            .and_then(|mut clipboard| clipboard.get_contents())
+            .map_err(|e| anyhow::anyhow!("{}", e))
+            .and_then(|text| Board::parse(text.as_bytes()));
+        match res {
+            Ok(board) => self.play_board(board),
+            Err(e) => self.report("cannot paste the board", e),
+        }
+    }
+
+    fn recent_key(profile: &Profile) -> String {
+        profile.key("recent.json")
+    }
+
+    pub fn recent_boards(&self) -> &[PathBuf] {
+        self.recent.paths()
+    }
+
+    // ファイルを選ぶダイアログを開き、選んだ盤面を遊ぶ。遊び始めたら true。
+    pub fn open_board_dialog(&mut self) -> bool {
+        let filter: &[&str] = &["*.txt"];
+        match tinyfiledialogs::open_file_dialog("Open board", "", Some((filter, "Board files"))) {
+            Some(path) => self.open_board(Path::new(&path)),
+            None => false,
+        }
+    }
+
+    // Board::parse の形式のファイルを遊ぶ。読めないか大きすぎればダイアログで知らせて false。
+    pub fn open_board(&mut self, path: &Path) -> bool {
+        let res = std::fs::File::open(path)
+            .map_err(anyhow::Error::from)
+            .and_then(Board::parse);
+        let board = match res {
+            Ok(board) => board,
+            Err(e) => {
+                self.report(&format!("cannot open {}", path.display()), e);
+                return false;
             }
+        };
+        self.play_board(board);
+        self.recent.add(path);
+        let key = Self::recent_key(&self.profile);
+        if let Err(e) = self.recent.save(&*self.backend, &key) {
+            self.report("cannot save recent boards", e);
+        }
+        true
+    }
 
-            let img = &self.imgs_tile[(color - 1) as usize];
-            graphics::draw(
-                ctx,
-                img,
-                (mint::Point2 {
-                    x: 32.0 * x as f32,
-                    y: 32.0 * y as f32,
-                },),
-            )?;
+    pub fn confirm_quit(&mut self) {
+        if self.dialogs.top() != Some(&DialogTag::Quit) {
+            let dialog = Dialog::confirm(DialogTag::Quit, "Quit", "Quit the game?");
+            self.dialogs.push(dialog);
         }
+    }
 
-        if self.cursor != CURSOR_INVALID {
-            let ps = self.board.calc_component(self.cursor.0, self.cursor.1);
-            for (x, y) in ps {
-                let mesh = Mesh::new_rectangle(
-                    ctx,
-                    DrawMode::fill(),
-                    Rect::new(32.0 * x as f32, 32.0 * y as f32, 32.0, 32.0),
-                    Color::from_rgba(0xc0, 0xc0, 0xc0, 0x80),
-                )?;
-                graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+    fn on_dialog(&mut self, tag: DialogTag, answer: Answer) {
+        match (tag, answer) {
+            (DialogTag::Quit, Answer::Yes) => {
+                self.push(Command::Quit);
+            }
+            (DialogTag::Name(rank), Answer::Text(name)) => {
+                self.highscores.rename(rank, name);
+                self.save_highscores();
             }
+            _ => {}
         }
+    }
 
-        self.font
-            .draw_str(ctx, 520.0, 360.0, format!("Score: {}", self.score))?;
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
 
-        self.font
-            .draw_str(ctx, 10.0, 450.0, "R:Reset, Q/Esc:Quit")?;
+    fn highscores_key(profile: &Profile, config: &GameConfig) -> String {
+        profile.key(&format!("highscores/{}.json", config.key()))
+    }
 
-        graphics::present(ctx)?;
+    fn rush_scores_key(profile: &Profile) -> String {
+        profile.key("rush.json")
+    }
 
-        Ok(())
+    // パズルラッシュの連続正解を表に入れる。表の中の順位を返す。
+    pub fn record_rush(&mut self, streak: i32, time: Duration) -> Option<usize> {
+        let entry = highscore::Entry {
+            score: streak,
+            splits: Splits {
+                finish: Some(time),
+                ..Splits::default()
+            },
+            name: self.profile.to_string(),
+            assist: None,
+        };
+        let rank = self.rush_scores.insert(entry)?;
+        let key = Self::rush_scores_key(&self.profile);
+        if let Err(e) = self.rush_scores.save(&*self.backend, &key) {
+            self.report("cannot save puzzle rush scores", e);
+        }
+        Some(rank)
+    }
+
+    pub fn rush_scores(&self) -> &HighScores {
+        &self.rush_scores
+    }
+
+    fn ratings_key(profile: &Profile) -> String {
+        profile.key("ratings.json")
+    }
+
+    // 終わったゲームの盤面をソルバーにも最初から指させ、得点を比べる
+    fn submit_rating(&mut self) {
+        let seed = self.game.seed();
+        let score = self.game.score();
+        let board = self.replay.new_game().board().clone();
+        self.jobs.submit(move |_: &mut Monitor| {
+            JobOutput::Rating(Rating {
+                seed,
+                score,
+                baseline: rating::baseline(&board),
+            })
+        });
+    }
+
+    fn personal_bests_key(profile: &Profile) -> String {
+        profile.key("personal_bests.json")
     }
 
-    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
-        if button != MouseButton::Left {
+    fn record_personal_best(&mut self) {
+        let (seed, config) = (self.game.seed(), self.game.config());
+        if !self.personal_bests.update(seed, &config, self.game.score()) {
             return;
         }
 
-        let cursor = self.calc_cursor(x, y);
-        if cursor != CURSOR_INVALID {
-            self.cmd = Command::Erase(cursor.0, cursor.1);
+        let key = Self::personal_bests_key(&self.profile);
+        let res = self.personal_bests.save(&*self.backend, &key);
+        if let Err(e) = res {
+            self.report("cannot save personal bests", e);
         }
     }
 
-    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
-        self.cursor = self.calc_cursor(x, y);
+    // 途中でやめたゲームも自己ベストには数える
+    fn abandon(&mut self) {
+        if !self.game.is_finished() && self.game.score() > 0 {
+            self.record_personal_best();
+        }
+        self.flush_telemetry();
     }
 
-    fn key_down_event(
-        &mut self,
-        _ctx: &mut Context,
-        keycode: KeyCode,
-        _keymods: KeyMods,
-        repeat: bool,
-    ) {
-        if repeat {
-            return;
+    fn load_highscores(
+        backend: &dyn SaveBackend,
+        profile: &Profile,
+        config: &GameConfig,
+    ) -> HighScores {
+        HighScores::load(backend, &Self::highscores_key(profile, config)).unwrap_or_else(|e| {
+            eprintln!("cannot load high scores: {:#}", e);
+            HighScores::default()
+        })
+    }
+
+    fn rotation_scores_key(profile: &Profile, period: &Period) -> String {
+        let name = format!("{}-{}.json", period.rotation, period.id).to_lowercase();
+        profile.key(&format!("rotation/{}", name))
+    }
+
+    // 日替わりやレベルなどの特別な遊び方をやめる
+    fn leave_modes(&mut self) {
+        self.edited = None;
+        self.rotation = None;
+        self.curriculum = None;
+        self.cancel_level();
+    }
+
+    fn tutorial_key(profile: &Profile) -> String {
+        profile.key("tutorial.json")
+    }
+
+    // 遊び方の説明を一度見終えたか
+    pub fn tutorial_done(&self) -> bool {
+        Tutorial::load_done(&*self.backend, &Self::tutorial_key(&self.profile)).unwrap_or_else(
+            |e| {
+                eprintln!("cannot load tutorial state: {:#}", e);
+                true
+            },
+        )
+    }
+
+    // 説明を最後まで見るか飛ばしたら、次回から出さない
+    pub fn finish_tutorial(&mut self) {
+        let key = Self::tutorial_key(&self.profile);
+        if let Err(e) = Tutorial::save_done(&*self.backend, &key) {
+            self.report("cannot save tutorial state", e);
+        }
+    }
+
+    fn unlocks_key(profile: &Profile) -> String {
+        profile.key("unlocks.json")
+    }
+
+    // 解放した見た目を知らせる時間
+    const UNLOCK_NOTICE_SECONDS: f32 = 3.0;
+
+    // 記録に応じて見た目を解放する
+    fn check_unlocks(&mut self, stars: u32) {
+        let stats = unlock::Stats {
+            stars,
+            score: self.game.score(),
+        };
+        let new = self.unlocks.check(&stats);
+        if let Some(&reward) = new.last() {
+            let tween = self.anim.tween(Self::UNLOCK_NOTICE_SECONDS, Ease::Linear);
+            self.unlock_notice = Some((tween, reward));
+            self.save_unlocks();
+        }
+    }
+
+    fn save_unlocks(&mut self) {
+        let key = Self::unlocks_key(&self.profile);
+        if let Err(e) = self.unlocks.save(&*self.backend, &key) {
+            self.report("cannot save unlocks", e);
+        }
+    }
+
+    fn save_prefs(&mut self) {
+        let key = Settings::key(&self.profile);
+        if let Err(e) = self.prefs.save(&*self.backend, &key) {
+            self.report("cannot save settings", e);
+        }
+    }
+
+    fn curriculum_key(&self) -> String {
+        self.profile.key(&self.campaign.progress_key())
+    }
+
+    // 盤面探しと星の閾値を決めるソルバーは重いので、描画スレッドの外で用意する
+    fn start_level(&mut self, number: u32, progress: Progress) {
+        self.cancel_level();
+        let campaign = self.campaign.clone();
+        let id = self
+            .jobs
+            .submit(move |_: &mut Monitor| JobOutput::Level(campaign.level(number)));
+        self.level_job = Some((id, number, progress));
+    }
+
+    fn cancel_level(&mut self) {
+        if let Some((id, _, _)) = self.level_job.take() {
+            self.jobs.cancel(id);
+        }
+    }
+
+    fn begin_level(&mut self, level: Level, progress: Progress) {
+        self.leave_modes();
+        self.config = level.challenge.config;
+        self.start(level.challenge.seed);
+        self.curriculum = Some((level, progress));
+    }
+
+    fn start_rotation(&mut self, rotation: Rotation) {
+        let period = rotation.current();
+        let key = Self::rotation_scores_key(&self.profile, &period);
+        let scores = HighScores::load(&*self.backend, &key).unwrap_or_else(|e| {
+            eprintln!("cannot load {} scores: {:#}", rotation, e);
+            HighScores::default()
+        });
+
+        let challenge = period.challenge();
+        self.leave_modes();
+        self.config = challenge.config;
+        self.start(challenge.seed);
+        self.rotation = Some((period, scores));
+    }
+
+    fn ghost_key(profile: &Profile, seed: u64) -> String {
+        profile.key(&format!("ghosts/{:016x}.json", seed))
+    }
+
+    // 同じシード・同じ設定の過去のベストプレイ
+    pub fn load_best_replay(&self) -> Option<Replay> {
+        let key = Self::ghost_key(&self.profile, self.game.seed());
+        let res = Replay::load(&*self.backend, &key);
+        match res {
+            Ok(replay) => replay.filter(|replay| replay.config == self.replay.config),
+            Err(e) => {
+                eprintln!("cannot load ghost: {:#}", e);
+                None
+            }
         }
+    }
+
+    fn load_ghost(&self) -> Option<Ghost> {
+        self.load_best_replay().map(Ghost::new)
+    }
+
+    fn record(&mut self, action: Action) {
+        let now = self.now();
+        self.replay.push(now, action);
+    }
+
+    fn erase(&mut self, x: usize, y: usize) {
+        self.record(Action::Click(x, y));
+        let now = self.now();
+        let before = self.game.snapshot();
+        // パワーアップの対象に選んだときは消さない
+        let n = if self.game.powerups().armed().is_none() {
+            self.game.board().calc_component(x, y).len()
+        } else {
+            0
+        };
+        let groups = if self.prefs.telemetry && n >= 2 {
+            self.game.board().groups()
+        } else {
+            vec![]
+        };
+        if self.game.click(x, y, now) {
+            if n >= 2 {
+                self.audio.play(Sound::Pop, Playback::for_group(n));
+                let think = self.telemetry.think(now);
+                self.log(telemetry::Event::Move {
+                    x,
+                    y,
+                    group: n,
+                    largest: groups.iter().map(|&(_, n)| n).max().unwrap_or(0),
+                    groups: groups.len(),
+                    think_ms: think.as_millis() as u64,
+                });
+            }
+            self.on_moved(&before);
+        }
+    }
+
+    fn select_powerup(&mut self, i: usize) {
+        self.record(Action::PowerUp(i));
+        let now = self.now();
+        let before = self.game.snapshot();
+        if self.game.select_powerup(i, now) {
+            self.on_moved(&before);
+        }
+    }
+
+    fn rotate_board(&mut self) {
+        self.record(Action::Rotate);
+        let now = self.now();
+        let before = self.game.snapshot();
+        if self.game.rotate(now) {
+            self.on_moved(&before);
+        }
+    }
+
+    // 手を打ったあとに得点の増分を出す時間
+    const SCORE_POPUP_SECONDS: f32 = 1.0;
+    // 盤面が回る演出の時間
+    const ROTATE_SECONDS: f32 = 0.3;
+    // 選択中のグループが明滅する周期
+    const PULSE_SECONDS: f32 = 1.2;
+    // 地震で揺れる時間と最大の振れ幅
+    const SHAKE_SECONDS: f32 = 0.5;
+    const SHAKE_AMPLITUDE: f32 = 8.0;
 
-        match keycode {
-            KeyCode::Escape | KeyCode::Q => {
-                self.cmd = Command::Quit;
+    fn fit_cursor(&mut self) {
+        let board = self.game.board();
+        self.cursor = cursor_within(self.cursor, board.width(), board.height());
+    }
+
+    fn on_moved(&mut self, before: &GameSnapshot) {
+        // ROTATE_EVERY 手ごとや Z で盤面が回ると大きさが変わる
+        self.fit_cursor();
+        let tween = self.anim.tween(Self::SCORE_POPUP_SECONDS, Ease::Linear);
+        let diff = snapshot::diff(before, &self.game.snapshot());
+        if !self.prefs.reduce_motion && diff.moves.iter().any(|m| m.action == Action::Rotate) {
+            self.rotating = Some(self.anim.tween(Self::ROTATE_SECONDS, Ease::OutCubic));
+        }
+        if !self.prefs.reduce_motion && self.game.earthquake().region().is_some() {
+            self.shaking = Some(self.anim.tween(Self::SHAKE_SECONDS, Ease::Linear));
+        }
+        if let Some(rumble) = &mut self.rumble {
+            rumble.on_score(diff.score);
+        }
+        if diff.score > 0 {
+            let text = format!("{} points. Total {}.", diff.score, self.game.score());
+            self.announce(&text);
+        }
+        self.last_move = Some((tween, diff));
+        self.hint = None;
+        self.cancel_hint();
+        self.refresh_estimate();
+        if self.game.is_finished() {
+            self.on_finished();
+        }
+    }
+
+    fn on_finished(&mut self) {
+        self.record_personal_best();
+        self.replay.score = self.game.score();
+        self.save_ghost();
+        self.submit_rating();
+        self.log(telemetry::Event::Finish {
+            score: self.game.score(),
+            cleared: self.game.board().tile_count() == 0,
+            time_ms: self.elapsed().as_millis() as u64,
+        });
+        self.flush_telemetry();
+        if self.prefs.export_moves {
+            self.save_move_log();
+        }
+
+        let entry = highscore::Entry {
+            score: self.game.score(),
+            splits: self.game.splits().clone(),
+            name: self.profile.to_string(),
+            assist: self.replay.assist,
+        };
+
+        let result = RoundResult {
+            score: self.game.score(),
+            time: self.elapsed(),
+            cleared: self.game.board().tile_count() == 0,
+        };
+        let text = format!(
+            "Game over. {}Final score {}.",
+            if result.cleared {
+                "Board cleared. "
+            } else {
+                ""
+            },
+            result.score
+        );
+        self.announce(&text);
+        if let Some((t, path)) = &mut self.tournament {
+            t.record(result);
+            if t.is_over() {
+                if let Err(e) = t.save_summary(path) {
+                    self.report("cannot save tournament summary", e);
+                }
             }
-            KeyCode::R => {
-                self.cmd = Command::Reset;
+        }
+
+        let key = self.curriculum_key();
+        let mut stars = 0;
+        if let Some((level, progress)) = &mut self.curriculum {
+            let saved = if progress.record(level, self.game.score()) {
+                progress.save(&*self.backend, &key)
+            } else {
+                Ok(())
+            };
+            stars = progress.total_stars();
+            if let Err(e) = saved {
+                self.report("cannot save curriculum progress", e);
             }
-            _ => {}
         }
+        self.check_unlocks(stars);
+
+        if let Some((period, scores)) = &mut self.rotation {
+            if scores.insert(entry.clone()).is_some() {
+                let key = Self::rotation_scores_key(&self.profile, period);
+                if let Err(e) = scores.save(&*self.backend, &key) {
+                    let what = format!("cannot save {} scores", period.rotation);
+                    self.report(&what, e);
+                }
+            }
+        }
+
+        self.rank = self.highscores.insert(entry);
+        let rank = match self.rank {
+            Some(rank) => rank,
+            None => return,
+        };
+        self.save_highscores();
+
+        let dialog = Dialog::input(
+            DialogTag::Name(rank),
+            "New high score!",
+            format!("Rank {}. Enter your name:", rank + 1),
+            &self.profile.to_string(),
+            Self::NAME_LEN,
+        );
+        self.dialogs.push(dialog);
+    }
+
+    const NAME_LEN: usize = 8;
+
+    fn save_highscores(&mut self) {
+        let config = self.game.config();
+        let key = Self::highscores_key(&self.profile, &config);
+        if let Err(e) = self.highscores.save(&*self.backend, &key) {
+            self.report("cannot save high scores", e);
+        }
+    }
+
+    fn save_ghost(&mut self) {
+        if let Some(best) = self.load_best_replay() {
+            if best.score >= self.replay.score {
+                return;
+            }
+        }
+
+        let key = Self::ghost_key(&self.profile, self.game.seed());
+        let res = self.replay.save(&*self.backend, &key);
+        if let Err(e) = res {
+            self.report("cannot save ghost", e);
+        }
+    }
+
+    fn move_log_name(&self) -> String {
+        format!(
+            "moves-{:016x}-{}.csv",
+            self.game.seed(),
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )
+    }
+
+    // 今のゲームの手の記録を、保存先を尋ねて書き出す
+    fn export_moves(&mut self) {
+        let name = self.move_log_name();
+        let path = match tinyfiledialogs::save_file_dialog_with_filter(
+            "Save move log",
+            &name,
+            &["*.csv"],
+            "CSV files",
+        ) {
+            Some(path) => PathBuf::from(path),
+            None => return,
+        };
+        match movelog::save_csv(&self.replay, &path) {
+            Ok(()) => {
+                let message = format!("Saved to {}", path.display());
+                self.dialogs
+                    .push(Dialog::message(DialogTag::Notice, "Move log", message));
+            }
+            Err(e) => self.report("cannot save move log", e),
+        }
+    }
+
+    // ゲームが終わるたびの書き出し。毎回尋ねはせず、プロファイルの moves/ に置く。
+    fn save_move_log(&mut self) {
+        let key = self.profile.key(&format!("moves/{}", self.move_log_name()));
+        let mut csv = vec![];
+        let res = movelog::write_csv(&self.replay, &mut csv)
+            .and_then(|()| self.backend.store(&key, &csv));
+        // 頼まれて書き出したのではないので、ダイアログで手を止めない
+        let text = match res {
+            Ok(()) => format!("Move log saved to {} in the data folder", key),
+            Err(e) => {
+                eprintln!("cannot save move log: {:#}", e);
+                format!("Cannot save move log: {:#}", e)
+            }
+        };
+        self.set_status(text);
+    }
+
+    const STATUS_SECONDS: f32 = 3.0;
+
+    fn set_status(&mut self, text: String) {
+        self.announce(&text);
+        let tween = self.anim.tween(Self::STATUS_SECONDS, Ease::Linear);
+        self.status = Some((tween, text));
+    }
+
+    fn challenge(&self) -> Challenge {
+        Challenge {
+            config: self.game.config(),
+            seed: self.game.seed(),
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.game.splits().finish.unwrap_or_else(|| self.now())
+    }
+
+    fn calc_cursor(&self, x: f32, y: f32) -> (usize, usize) {
+        if x < 0.0 || y < 0.0 {
+            return CURSOR_INVALID;
+        }
+
+        let cs = self.cell_size();
+        let cx = (x / cs) as usize;
+        let cy = (y / cs) as usize;
+        if cx >= self.game.board().width() || cy >= self.game.board().height() {
+            return CURSOR_INVALID;
+        }
+
+        (cx, cy)
+    }
+
+    // 盤面を描く領域。画面から下の情報表示の分を除いたもの。
+    pub fn board_area(&self) -> Rect {
+        let h = (self.screen.h - Self::HUD_HEIGHT).max(0.0);
+        Rect::new(0.0, 0.0, self.screen.w, h)
+    }
+
+    // 盤面の下の情報表示の領域
+    fn hud(&self) -> Layout {
+        let area = Rect::new(0.0, self.board_area().h, self.screen.w, Self::HUD_HEIGHT);
+        Layout::new(area).padding(Self::HUD_PADDING)
+    }
+
+    // 右端の列。得点や遊び方ごとの情報を決まった行に出す。
+    fn hud_column(&self) -> Stack {
+        self.hud()
+            .stack(Anchor::TopRight, Self::HUD_WIDTH, Self::LINE_HEIGHT, 8)
+    }
+
+    // 左下の行。ヒント、通知、操作説明を出す。
+    fn hud_footer(&self) -> Stack {
+        let w = self.screen.w - 2.0 * Self::HUD_PADDING;
+        self.hud()
+            .stack(Anchor::BottomLeft, w, Self::LINE_HEIGHT, 4)
+    }
+
+    // 右下のチャレンジコードの行
+    pub fn code_rect(&self) -> Rect {
+        self.hud()
+            .place(Anchor::BottomRight, 290.0, Self::LINE_HEIGHT)
+    }
+
+    // 盤面の領域の右下に積んだ i 行目。参考の得点や難しさを出す。
+    fn board_corner(&self, i: usize) -> (f32, f32) {
+        let stack =
+            Layout::new(self.board_area()).stack(Anchor::BottomRight, 300.0, Self::LINE_HEIGHT, 2);
+        (stack.x(), stack.row(i))
+    }
+
+    // 盤面が描画領域に収まるようにセルの大きさを決める
+    pub fn cell_size(&self) -> f32 {
+        let board = self.game.board();
+        let area = self.board_area();
+        let w = area.w / board.width() as f32;
+        let h = area.h / board.height() as f32;
+        w.min(h).min(Self::CELL_SIZE).floor()
+    }
+
+    pub fn cell_rect(&self, x: usize, y: usize) -> Rect {
+        let cs = self.cell_size();
+        Rect::new(cs * x as f32, cs * y as f32, cs, cs)
+    }
+
+    fn draw_decay_bar(&self, ctx: &mut Context) -> GameResult {
+        const W: f32 = 100.0;
+        const H: f32 = 8.0;
+        let column = self.hud_column();
+        let (x, y) = (column.x(), column.row(3) + 4.0);
+
+        let factor = self.game.time_factor(self.now());
+        let color = Color::new(1.0 - factor, factor, 0.0, 1.0);
+
+        let fill =
+            Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(x, y, W * factor, H), color)?;
+        graphics::draw(ctx, &fill, DrawParam::default())?;
+        let frame = Mesh::new_rectangle(
+            ctx,
+            DrawMode::stroke(1.0),
+            Rect::new(x, y, W, H),
+            graphics::WHITE,
+        )?;
+        graphics::draw(ctx, &frame, DrawParam::default())?;
+
+        Ok(())
+    }
+
+    fn draw_timer(&self, ctx: &mut Context) -> GameResult {
+        // 得点の列の左に並べる
+        let rows = speedrun::SPLIT_PERCENTS.len() + 2;
+        let stack = self.hud().stack(
+            Anchor::TopRight,
+            Self::HUD_WIDTH + 230.0,
+            Self::LINE_HEIGHT,
+            rows,
+        );
+        let x = stack.x();
+
+        self.font.draw_str(
+            ctx,
+            x,
+            stack.row(0),
+            format!("Time: {}", speedrun::format_duration(self.elapsed())),
+        )?;
+
+        let labels = speedrun::SPLIT_PERCENTS
+            .iter()
+            .map(|percent| format!("{}%", percent))
+            .chain(std::iter::once("End".to_owned()));
+        let splits = self.game.splits();
+        let times = splits.cleared.iter().chain(std::iter::once(&splits.finish));
+        for (i, (label, time)) in labels.zip(times).enumerate() {
+            let time = time.map_or_else(|| "--:--.-".to_owned(), speedrun::format_duration);
+            self.font
+                .draw_str(ctx, x, stack.row(i + 1), format!("{:>4} {}", label, time))?;
+        }
+
+        Ok(())
+    }
+
+    // ゲーム終了時に現在の設定のハイスコア表を盤面に重ねて表示する
+    pub fn draw_highscores(&self, ctx: &mut Context) -> GameResult {
+        const W: f32 = 280.0;
+        const LINE: f32 = GameState::LINE_HEIGHT;
+
+        let h = LINE * (HighScores::CAPACITY + 3) as f32 + 8.0;
+        let Rect { x, y, .. } = Layout::new(self.board_area()).place(Anchor::Center, W, h);
+        let bg = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(x, y, W, h),
+            Color::from_rgba(0, 0, 0, 0xd0),
+        )?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let frame = Mesh::new_rectangle(
+            ctx,
+            DrawMode::stroke(1.0),
+            Rect::new(x, y, W, h),
+            graphics::WHITE,
+        )?;
+        graphics::draw(ctx, &frame, DrawParam::default())?;
+
+        let config = self.game.config();
+        let mut title = format!(
+            "{}x{} {} colors",
+            config.width, config.height, config.colors
+        );
+        if config.rules.streak {
+            title.push_str(" +M");
+        }
+        if config.rules.time_pressure {
+            title.push_str(" +T");
+        }
+        if config.rules.rotate {
+            title.push_str(" +O");
+        }
+        if config.rules.earthquake {
+            title.push_str(" +K");
+        }
+        self.font.draw_str(ctx, x + 8.0, y + 4.0, title)?;
+
+        for i in 0..HighScores::CAPACITY {
+            let yy = y + 4.0 + LINE * (i + 2) as f32;
+            if self.rank == Some(i) {
+                let mesh = Mesh::new_rectangle(
+                    ctx,
+                    DrawMode::fill(),
+                    Rect::new(x + 4.0, yy, W - 8.0, LINE),
+                    Color::from_rgba(0xff, 0xff, 0x00, 0x60),
+                )?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+
+            let line = match self.highscores.entries().get(i) {
+                Some(entry) => {
+                    let time = entry
+                        .splits
+                        .finish
+                        .map_or_else(|| "--:--.-".to_owned(), speedrun::format_duration);
+                    let name: String = entry.name.chars().take(Self::NAME_LEN).collect();
+                    // ヒントを使った記録には強さを添える
+                    let assist = entry
+                        .assist
+                        .map_or_else(|| "  ".to_owned(), |l| format!("H{}", l.number()));
+                    format!(
+                        "{:>2}. {:>6} {} {} {}",
+                        i + 1,
+                        entry.score,
+                        assist,
+                        time,
+                        name
+                    )
+                }
+                None => format!("{:>2}.      -", i + 1),
+            };
+            self.font.draw_str(ctx, x + 8.0, yy, line)?;
+        }
+
+        let yy = y + 4.0 + LINE * (HighScores::CAPACITY + 2) as f32;
+        self.font
+            .draw_str(ctx, x + 8.0, yy, "A:Analyze Shift+S:Stats Esc:Close")
+    }
+
+    // 直前の手で変化したセルを一瞬光らせ、得点の増分を出す
+    fn draw_last_move(&self, ctx: &mut Context) -> GameResult {
+        // 得点を出している間の最初の FLASH の割合だけ光らせる
+        const FLASH: f32 = 0.3;
+
+        let (tween, diff) = match &self.last_move {
+            Some(last_move) => last_move,
+            None => return Ok(()),
+        };
+        let t = match tween.progress(self.anim.now()) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        if t < FLASH {
+            let alpha = 0.5 * (1.0 - t / FLASH);
+            let color = Color::new(1.0, 1.0, 1.0, alpha);
+            for c in diff.cells.iter().filter(|c| c.to != 0) {
+                let mesh =
+                    Mesh::new_rectangle(ctx, DrawMode::fill(), self.cell_rect(c.x, c.y), color)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+        }
+
+        if diff.score != 0 {
+            // 得点の左に出す
+            let column = self.hud_column();
+            self.font.draw_str(
+                ctx,
+                column.x() - 60.0,
+                column.row(2),
+                format!("{:+}", diff.score),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_difficulty(&self, ctx: &mut Context) -> GameResult {
+        let difficulty = match self.difficulty {
+            Some(difficulty) => difficulty,
+            None => return Ok(()),
+        };
+
+        let (x, y) = self.board_corner(1);
+        let color = match difficulty {
+            Difficulty::Easy => Color::from_rgb(0x20, 0x80, 0x20),
+            Difficulty::Medium => Color::from_rgb(0x90, 0x70, 0x00),
+            Difficulty::Hard => Color::from_rgb(0xa0, 0x20, 0x20),
+        };
+
+        let label = difficulty.to_string();
+        let w = self.font.measure_str(ctx, &label);
+        let h = self.font.glyph_height() as f32;
+        let mesh = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(x - 2.0, y, w + 4.0, h),
+            color,
+        )?;
+        graphics::draw(ctx, &mesh, DrawParam::default())?;
+        self.font.draw_str(ctx, x, y, label)
+    }
+
+    fn draw_ghost(&self, ctx: &mut Context) -> GameResult {
+        let ghost = match &self.ghost {
+            Some(ghost) => ghost,
+            None => return Ok(()),
+        };
+        let color = Color::from_rgba(0x40, 0xe0, 0xff, 0x60);
+
+        for &(x, y) in ghost.flash(self.now()) {
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), self.cell_rect(x, y), color)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        if let Some((x, y)) = ghost.cursor() {
+            let mesh =
+                Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), self.cell_rect(x, y), color)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        let label = match self.opponent {
+            Some(level) => format!("CPU ({}): {}", level, ghost.game().score()),
+            None => format!("Ghost: {}", ghost.game().score()),
+        };
+        let column = self.hud_column();
+        self.font.draw_str(ctx, column.x(), column.row(4), label)?;
+
+        Ok(())
+    }
+
+    fn draw_rotation(&self, ctx: &mut Context) -> GameResult {
+        let (period, scores) = match &self.rotation {
+            Some(rotation) => rotation,
+            None => return Ok(()),
+        };
+        let column = self.hud_column();
+        let (x, y) = (column.x(), column.row(5));
+
+        self.font.draw_str(ctx, x, y, &period.id)?;
+
+        let next = if period.is_over() {
+            "New board!".to_owned()
+        } else {
+            rotation::format_countdown(period.remaining())
+        };
+        self.font.draw_str(ctx, x, y + Self::LINE_HEIGHT, next)?;
+
+        if let Some(top) = scores.entries().first() {
+            self.font.draw_str(
+                ctx,
+                x,
+                y + 2.0 * Self::LINE_HEIGHT,
+                format!("Top: {}", top.score),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_tournament(&self, ctx: &mut Context) -> GameResult {
+        let (t, path) = match &self.tournament {
+            Some(tournament) => tournament,
+            None => return Ok(()),
+        };
+        let column = self.hud_column();
+        let (x, y) = (column.x(), column.row(5));
+
+        let round = (t.round() + 1).min(t.round_count());
+        self.font
+            .draw_str(ctx, x, y, format!("Round {}/{}", round, t.round_count()))?;
+        self.font.draw_str(
+            ctx,
+            x,
+            y + Self::LINE_HEIGHT,
+            format!("Total: {}", t.total()),
+        )?;
+
+        if t.is_over() {
+            self.font
+                .draw_str(ctx, x, y + 2.0 * Self::LINE_HEIGHT, "Done!")?;
+            let rect = self.code_rect();
+            self.font.draw_str(
+                ctx,
+                rect.x,
+                rect.y,
+                format!(
+                    "Saved: {}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ),
+            )?;
+        } else if self.game.is_finished() {
+            self.font
+                .draw_str(ctx, x, y + 2.0 * Self::LINE_HEIGHT, "Enter:Next")?;
+        }
+
+        Ok(())
+    }
+
+    // 設定画面の項目。見た目の報酬、次のゲームの色の数、動きを減らすか、全画面、枠なしの全画面、
+    // 閉じるボタンの順に並べる。on_settings はこの並びで項目を見分ける。
+    pub fn settings_form(&self, focus: usize) -> Form {
+        let mut widgets: Vec<_> = unlock::REWARDS
+            .iter()
+            .map(|reward| {
+                let mut label = reward.name.to_owned();
+                let unlocked = self.unlocks.is_unlocked(reward);
+                if !unlocked {
+                    label += &format!("  (locked: {})", reward.requirement.describe());
+                }
+                Widget::toggle(label, self.unlocks.is_selected(reward)).enabled(unlocked)
+            })
+            .collect();
+        let colors = i32::from(self.config.colors);
+        let max = i32::from(board::MAX_COLORS);
+        widgets.push(Widget::slider("Colors (next game)", colors, 2..=max));
+        widgets.push(Widget::toggle("Reduce motion", self.prefs.reduce_motion));
+        widgets.push(Widget::toggle("Fullscreen (F11)", self.prefs.fullscreen));
+        widgets.push(Widget::toggle(
+            "Borderless fullscreen (desktop size)",
+            self.prefs.borderless,
+        ));
+        widgets.push(Widget::toggle("Retro CRT filter", self.prefs.crt));
+        let highlight = self.prefs.highlight;
+        widgets.push(Widget::button(format!("Highlight: {}", highlight.name())));
+        widgets.push(Widget::slider(
+            "Highlight opacity",
+            i32::from(highlight.alpha) / Self::OPACITY_STEP,
+            1..=255 / Self::OPACITY_STEP,
+        ));
+        widgets.push(Widget::toggle("Group size tones", self.prefs.hover_tones));
+        widgets.push(Widget::toggle(
+            "Save move log at game over",
+            self.prefs.export_moves,
+        ));
+        widgets.push(Widget::toggle("Coordinate labels", self.prefs.coordinates));
+        widgets.push(Widget::toggle("Evaluation bar", self.prefs.eval_bar));
+        widgets.push(Widget::toggle(
+            "Live evaluation readout",
+            self.prefs.eval_readout,
+        ));
+        widgets.push(Widget::toggle(
+            "Record play telemetry (local file only)",
+            self.prefs.telemetry,
+        ));
+        widgets.push(Widget::button("Close"));
+
+        let mut form = Form::new(widgets, 20.0, 28.0, self.screen.w - 40.0);
+        form.set_focus(focus);
+        form
+    }
+
+    // 設定画面で選ぶハイライトの濃さの刻み
+    const OPACITY_STEP: i32 = 32;
+
+    pub fn settings_revision(&self) -> u32 {
+        self.settings_revision
+    }
+
+    // 設定画面の項目を操作した。閉じるボタンを押したら true。
+    pub fn on_settings(&mut self, (i, res): (usize, Response)) -> bool {
+        let n = unlock::REWARDS.len();
+        if i < n {
+            self.push(Command::SelectReward(i));
+            return false;
+        }
+        match (i - n, res) {
+            (0, Response::Changed(colors)) => {
+                self.config.colors = colors as u8;
+            }
+            (1, Response::Toggled(on)) => {
+                self.prefs.reduce_motion = on;
+                self.save_prefs();
+            }
+            (2, Response::Toggled(on)) => {
+                self.prefs.fullscreen = on;
+                self.push(Command::ApplyWindowMode);
+            }
+            (3, Response::Toggled(on)) => {
+                self.prefs.borderless = on;
+                self.push(Command::ApplyWindowMode);
+            }
+            (4, Response::Toggled(on)) => {
+                self.prefs.crt = on;
+                self.save_prefs();
+            }
+            (5, Response::Clicked) => {
+                self.prefs.highlight = self.prefs.highlight.next_preset();
+                self.save_prefs();
+                self.settings_revision += 1;
+            }
+            (6, Response::Changed(n)) => {
+                self.prefs.highlight.alpha = (n * Self::OPACITY_STEP).min(255) as u8;
+                self.save_prefs();
+            }
+            (7, Response::Toggled(on)) => {
+                self.prefs.hover_tones = on;
+                self.save_prefs();
+            }
+            (8, Response::Toggled(on)) => {
+                self.prefs.export_moves = on;
+                self.save_prefs();
+            }
+            (9, Response::Toggled(on)) => {
+                self.prefs.coordinates = on;
+                self.save_prefs();
+            }
+            (10, Response::Toggled(on)) => {
+                self.prefs.eval_bar = on;
+                self.save_prefs();
+                self.refresh_estimate();
+            }
+            (11, Response::Toggled(on)) => {
+                self.prefs.eval_readout = on;
+                self.save_prefs();
+                self.refresh_estimate();
+            }
+            (12, Response::Toggled(on)) => {
+                self.prefs.telemetry = on;
+                self.save_prefs();
+                if !on {
+                    self.telemetry = Telemetry::default();
+                }
+            }
+            (_, Response::Clicked) => return true,
+            _ => {}
+        }
+        false
+    }
+
+    fn draw_curriculum(&self, ctx: &mut Context) -> GameResult {
+        let level = match &self.curriculum {
+            Some((level, _)) => level,
+            None => return Ok(()),
+        };
+        let column = self.hud_column();
+        let (x, y) = (column.x(), column.row(5));
+
+        self.font
+            .draw_str(ctx, x, y, format!("Level {}", level.number))?;
+        self.font.draw_str(
+            ctx,
+            x,
+            y + Self::LINE_HEIGHT,
+            format!("Goal: {}", level.goal()),
+        )?;
+
+        if self.game.is_finished() {
+            let stars = level.stars(self.game.score());
+            let line = if stars > 0 {
+                let stars: String = (0..3).map(|i| if i < stars { '*' } else { '.' }).collect();
+                format!("{} Enter:Next", stars)
+            } else {
+                "Enter:Retry".to_owned()
+            };
+            self.font
+                .draw_str(ctx, x, y + 2.0 * Self::LINE_HEIGHT, line)?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_hint(&self, ctx: &mut Context) -> GameResult {
+        if let Some((_, number, _)) = &self.level_job {
+            let text = format!("Level {}: preparing...", number);
+            let footer = self.hud_footer();
+            return self.font.draw_str(ctx, footer.x(), footer.row(0), text);
+        }
+        if let Some(id) = self.hint_job {
+            let text = match self.jobs.progress(id) {
+                Some((nodes, _)) => format!("Hint: thinking... ({} positions)", nodes),
+                None => "Hint: thinking...".to_owned(),
+            };
+            let footer = self.hud_footer();
+            return self.font.draw_str(ctx, footer.x(), footer.row(0), text);
+        }
+
+        let hint = match &self.hint {
+            Some(hint) => hint,
+            None => return Ok(()),
+        };
+
+        let board = self.game.board();
+        match self.hint_level {
+            HintLevel::Region => {
+                let ((x0, y0), (x1, y1)) = hint.region(board);
+                let (a, b) = (self.cell_rect(x0, y0), self.cell_rect(x1, y1));
+                let rect = Rect::new(a.x, a.y, b.x + b.w - a.x, b.y + b.h - a.y);
+                let fill = Color::from_rgba(0x00, 0xff, 0x80, 0x30);
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, fill)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+                let stroke = Color::from_rgb(0x00, 0xff, 0x80);
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), rect, stroke)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+            HintLevel::Group => {
+                let plan = hint.line.plan(board, 1);
+                self.draw_plan(ctx, &plan)?;
+            }
+            HintLevel::Line => {
+                let plan = hint.line.plan(board, Self::PLAN_MOVES);
+                self.draw_plan(ctx, &plan)?;
+            }
+        }
+
+        let footer = self.hud_footer();
+        self.font
+            .draw_str(ctx, footer.x(), footer.row(0), self.hint_text(hint))
+    }
+
+    // ヒントの強さに合わせた説明。範囲だけなら色や大きさも言わない。
+    fn hint_text(&self, hint: &Hint) -> String {
+        let board = self.game.board();
+        let level = self.hint_level.number();
+        match self.hint_level {
+            HintLevel::Region => {
+                let ((x0, y0), (x1, y1)) = hint.region(board);
+                let h = board.height();
+                format!(
+                    "Hint {}: look between {} and {}",
+                    level,
+                    notation::format_square(x0, y1, h),
+                    notation::format_square(x1, y0, h)
+                )
+            }
+            HintLevel::Group | HintLevel::Line => format!(
+                "Hint {}: {}, {}",
+                level,
+                notation::format_move(board, hint.pos.0, hint.pos.1),
+                hint.rationale
+            ),
+        }
+    }
+
+    // ヒントを見せていれば、その強さをこのゲームの記録に残す
+    fn record_assist(&mut self) {
+        if self.hint.is_some() {
+            self.replay.assist = self.replay.assist.max(Some(self.hint_level));
+        }
+    }
+
+    // 読み筋の各手で消すグループを囲み、何手目かの番号を付ける。先の手ほど薄く描く。
+    pub fn draw_plan(&self, ctx: &mut Context, plan: &[Vec<(usize, usize)>]) -> GameResult {
+        let shadow = Color::from_rgba(0x00, 0x00, 0x00, 0xc0);
+        let gh = self.font.glyph_height() as f32;
+        for (i, cells) in plan.iter().enumerate() {
+            let alpha = 1.0 - 0.7 * i as f32 / plan.len() as f32;
+            let width = if i == 0 { 3.0 } else { 2.0 };
+            let stroke = (width, Color::new(0.0, 1.0, 0.5, alpha));
+            let mesh = highlight::build_mesh(ctx, cells, self.cell_size(), None, Some(stroke))?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+
+            let (x, y) = cells[0];
+            let rect = self.cell_rect(x, y);
+            let label = (i + 1).to_string();
+            let (lx, ly) = (rect.x, rect.y + (rect.h - gh) / 2.0);
+            let center = Align::Center(rect.w);
+            self.font
+                .draw_str_with(ctx, lx + 1.0, ly + 1.0, &label, shadow, center)?;
+            self.font
+                .draw_str_with(ctx, lx, ly, &label, graphics::WHITE, center)?;
+        }
+        Ok(())
+    }
+
+    fn draw_unlock_notice(&self, ctx: &mut Context) -> GameResult {
+        if let Some((tween, reward)) = self.unlock_notice {
+            if tween.is_running(self.anim.now()) {
+                let footer = self.hud_footer();
+                let text = format!("Unlocked: {}!", reward.name);
+                self.font.draw_str(ctx, footer.x(), footer.row(1), text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_status(&self, ctx: &mut Context) -> GameResult {
+        if let Some((tween, text)) = &self.status {
+            if tween.is_running(self.anim.now()) {
+                let footer = self.hud_footer();
+                self.font.draw_str(ctx, footer.x(), footer.row(0), text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_code(&self, ctx: &mut Context) -> GameResult {
+        let rect = self.code_rect();
+        let line = match &self.edited {
+            Some(_) => "Custom board".to_owned(),
+            None => match self.challenge().encode() {
+                Some(code) => format!("C:Code {}", code),
+                // コードにできない大きさの盤面では出さない
+                None => return Ok(()),
+            },
+        };
+        self.font.draw_str(ctx, rect.x, rect.y, line)
+    }
+
+    fn draw_powerups(&self, ctx: &mut Context) -> GameResult {
+        for (i, slot) in self.game.powerups().slots().enumerate() {
+            let origin = self.hud().place(Anchor::TopLeft, 0.0, 0.0);
+            let (x, y) = (origin.x + 48.0 * i as f32, origin.y);
+
+            let color = if slot.armed {
+                Color::from_rgb(0xff, 0xff, 0x00)
+            } else if slot.stock > 0 && slot.cooldown == 0 {
+                graphics::WHITE
+            } else {
+                Color::from_rgb(0x60, 0x60, 0x60)
+            };
+            let mesh = Mesh::new_rectangle(
+                ctx,
+                DrawMode::stroke(2.0),
+                Rect::new(x, y, 32.0, 32.0),
+                color,
+            )?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+
+            let gw = self.font.glyph_width() as f32;
+            let gh = self.font.glyph_height() as f32;
+            self.font.draw_char(
+                ctx,
+                x + (32.0 - gw) / 2.0,
+                y + (32.0 - gh) / 2.0,
+                slot.icon,
+                color,
+            )?;
+
+            let label = if slot.cooldown > 0 {
+                format!("{}:-{}", i + 1, slot.cooldown)
+            } else {
+                format!("{}:x{}", i + 1, slot.stock)
+            };
+            self.font.draw_str(ctx, x, y + 36.0, label)?;
+        }
+
+        Ok(())
+    }
+}
+
+// 場面から呼ぶ処理。update はどの場面でも毎フレーム呼ぶ。
+impl GameState {
+    fn push(&mut self, cmd: Command) {
+        self.cmds.push_back(cmd);
+    }
+
+    fn run(&mut self, ctx: &mut Context, cmd: Command) {
+        match cmd {
+            Command::Erase(x, y) => {
+                self.erase(x, y);
+            }
+            Command::PowerUp(i) => {
+                self.select_powerup(i);
+            }
+            Command::ToggleStreak => {
+                self.config.rules.streak = !self.config.rules.streak;
+                self.leave_modes();
+                self.start(rand::random());
+            }
+            Command::ToggleTimePressure => {
+                self.config.rules.time_pressure = !self.config.rules.time_pressure;
+                self.leave_modes();
+                self.start(rand::random());
+            }
+            Command::ToggleRotate => {
+                self.config.rules.rotate = !self.config.rules.rotate;
+                self.leave_modes();
+                self.start(rand::random());
+            }
+            Command::ToggleEarthquake => {
+                self.config.rules.earthquake = !self.config.rules.earthquake;
+                self.leave_modes();
+                self.start(rand::random());
+            }
+            Command::TwoColor => {
+                // ルールはそのままにする
+                self.config = GameConfig {
+                    rules: self.config.rules,
+                    ..GameConfig::two_color()
+                };
+                self.leave_modes();
+                self.start(rand::random());
+            }
+            Command::RotateBoard => {
+                self.rotate_board();
+            }
+            Command::CopyBoard => {
+                self.copy_board();
+            }
+            Command::PasteBoard => {
+                self.paste_board();
+            }
+            Command::OpenBoard => {
+                self.open_board_dialog();
+            }
+            Command::ExportMoves => {
+                self.export_moves();
+            }
+            Command::Challenge(challenge) => {
+                self.config = challenge.config;
+                self.leave_modes();
+                self.start(challenge.seed);
+            }
+            Command::Rotation(rotation) => {
+                self.start_rotation(rotation);
+            }
+            Command::HintLevel => {
+                self.hint_level = self.hint_level.next();
+                self.record_assist();
+                let text = format!("Hint level {}.", self.hint_level.number());
+                self.announce(&text);
+            }
+            Command::Hint => {
+                self.log(telemetry::Event::Hint {
+                    level: self.hint_level.number(),
+                });
+                let board = self.game.board().clone();
+                self.hint = None;
+                self.cancel_hint();
+                self.hint_job = Some(self.jobs.submit(move |monitor: &mut Monitor| {
+                    JobOutput::Hint(Hint::new(&board, monitor))
+                }));
+            }
+            Command::ApplyWindowMode => {
+                let res = graphics::set_fullscreen(ctx, self.prefs.fullscreen_type());
+                if let Err(e) = res {
+                    self.prefs.fullscreen = false;
+                    self.report("cannot switch to fullscreen", e.into());
+                }
+                self.save_prefs();
+                // F11 で切り替えたときも設定画面の表示を合わせる
+                self.settings_revision += 1;
+            }
+            Command::SelectReward(i) => {
+                let reward = &unlock::REWARDS[i];
+                if self.unlocks.select(reward) {
+                    self.save_unlocks();
+                }
+                // 同じ種類の他の報酬の選択が外れるので作り直す
+                self.settings_revision += 1;
+            }
+            Command::PlayLevel(number, progress) => {
+                self.start_level(number, progress);
+            }
+            Command::NextLevel => {
+                if let Some((level, progress)) = self.curriculum.take() {
+                    let number = if level.is_cleared(self.game.score()) {
+                        level.number + 1
+                    } else {
+                        level.number
+                    };
+                    self.start_level(number, progress);
+                }
+            }
+            Command::NextRound => {
+                let next = self.tournament.as_ref().and_then(|(t, _)| t.current());
+                if let Some(challenge) = next {
+                    self.config = challenge.config;
+                    self.start(challenge.seed);
+                }
+            }
+            Command::ToggleGhost => {
+                self.ghost_enabled = !self.ghost_enabled;
+                self.start(self.game.seed());
+            }
+            Command::CycleOpponent => {
+                self.opponent = Opponent::next(self.opponent);
+                self.start(self.game.seed());
+            }
+            Command::Reset => {
+                self.leave_modes();
+                self.start(rand::random());
+            }
+            Command::Retry => {
+                self.start(self.game.seed());
+            }
+            Command::AnswerResume(choice, suspended) => {
+                self.settle_suspended(choice);
+                match choice {
+                    Choice::Resume => self.resume(suspended),
+                    Choice::Discard | Choice::Later => self.start(self.game.seed()),
+                }
+            }
+            Command::Quit => {
+                self.on_quit(ctx);
+                event::quit(ctx);
+                // 終了したあとの操作は捨てる
+                self.cmds.clear();
+            }
+        }
+    }
+
+    // 溜めておいた操作を実行する。一時停止中も終了などの操作は受け付ける。
+    pub fn run_commands(&mut self, ctx: &mut Context) {
+        while let Some(cmd) = self.cmds.pop_front() {
+            self.run(ctx, cmd);
+        }
+    }
+
+    // 1 回の更新で dt だけ時間を進める
+    pub fn update(&mut self, ctx: &mut Context, dt: Duration) -> GameResult {
+        self.anim.advance(dt);
+        if let Some(rumble) = &mut self.rumble {
+            rumble.update();
+        }
+        self.update_hover_cue();
+        self.run_commands(ctx);
+
+        if matches!(self.estimate_due, Some(t) if self.anim.now() >= t) {
+            self.estimate_due = None;
+            self.submit_estimate();
+        }
+        for (id, output) in self.jobs.poll(Self::JOBS_PER_FRAME) {
+            match output {
+                JobOutput::Hint(hint) if self.hint_job == Some(id) => {
+                    let text = match &hint {
+                        Some(hint) => format!("{}.", self.hint_text(hint)),
+                        None => "No moves left.".to_owned(),
+                    };
+                    self.announce(&text);
+                    self.hint = hint;
+                    self.hint_job = None;
+                    self.record_assist();
+                }
+                JobOutput::Difficulty(difficulty) if self.difficulty_job == Some(id) => {
+                    self.difficulty = Some(difficulty);
+                    self.difficulty_job = None;
+                }
+                JobOutput::Opponent(replay) if self.opponent_job == Some(id) => {
+                    self.ghost = Some(Ghost::new(replay));
+                    self.opponent_job = None;
+                }
+                JobOutput::Rating(rating) => {
+                    self.ratings.add(rating);
+                    let key = Self::ratings_key(&self.profile);
+                    if let Err(e) = self.ratings.save(&*self.backend, &key) {
+                        self.report("cannot save ratings", e);
+                    }
+                }
+                JobOutput::Estimate(total) if self.estimate_job == Some(id) => {
+                    self.estimate = Some(total);
+                    self.estimate_job = None;
+                }
+                JobOutput::Level(level) if matches!(&self.level_job, Some((job, _, _)) if *job == id) =>
+                {
+                    // レベル選択は遊べるレベルしか出さないので、パックの外は頼まない
+                    if let (Some((_, _, progress)), Some(level)) = (self.level_job.take(), level) {
+                        self.begin_level(level, progress);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let now = self.now();
+        if let Some(ghost) = &mut self.ghost {
+            ghost.advance(now);
+        }
+
+        Ok(())
+    }
+
+    pub fn background(&self) -> Color {
+        match self.unlocks.background().kind {
+            RewardKind::Background { color, .. } => Color::from(color),
+            RewardKind::TileTheme { .. } => graphics::BLACK,
+        }
+    }
+
+    // 背景色で塗った画面の上に、盤面より後ろの層を描く
+    pub fn draw_backdrop(&self, ctx: &mut Context) -> GameResult {
+        let backdrop = match self.unlocks.background().kind {
+            RewardKind::Background { backdrop, .. } => backdrop,
+            RewardKind::TileTheme { .. } => Backdrop::Flat,
+        };
+        let screen = self.screen;
+        match backdrop {
+            Backdrop::Flat => {}
+            Backdrop::Gradient { bottom } => {
+                let shift = if self.prefs.reduce_motion {
+                    0.0
+                } else {
+                    backdrop::drift(self.anim.now().as_secs_f32())
+                };
+                let bands =
+                    backdrop::gradient_bands(screen, self.background(), Color::from(bottom), shift);
+                for (rect, color) in bands {
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+                    graphics::draw(ctx, &mesh, DrawParam::default())?;
+                }
+            }
+            Backdrop::Image(path) => {
+                if let Some(img) = self.backdrops.get(path) {
+                    // 縦横比を保ったまま画面を覆う
+                    let scale =
+                        (screen.w / f32::from(img.width())).max(screen.h / f32::from(img.height()));
+                    graphics::draw(
+                        ctx,
+                        img,
+                        DrawParam::default()
+                            .dest(mint::Point2 {
+                                x: screen.x,
+                                y: screen.y,
+                            })
+                            .scale(mint::Vector2 { x: scale, y: scale }),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 今のゲームと同じ大きさのセルで盤面のタイルを描く
+    pub fn draw_board(&self, ctx: &mut Context, board: &Board) -> GameResult {
+        self.draw_tiles(ctx, board, 0.0)
+    }
+
+    // 下端のセルに列、左端のセルに行の表記を重ねる。読めるよう影を付ける。
+    fn draw_coordinates(&self, ctx: &mut Context, board: &Board) -> GameResult {
+        let (w, h) = (board.width(), board.height());
+        let bottom = (0..w).map(|x| (x, h - 1, notation::column_name(x)));
+        let left = (0..h).map(|y| (0, y, notation::row_name(y, h)));
+        let shadow = Color::from_rgba(0x00, 0x00, 0x00, 0xc0);
+        let gh = self.font.glyph_height() as f32;
+        for (i, (x, y, label)) in bottom.chain(left).enumerate() {
+            let rect = self.cell_rect(x, y);
+            // 列は左下、行は左上に置く
+            let (lx, ly) = if i < w {
+                (rect.x + 2.0, rect.y + rect.h - gh - 1.0)
+            } else {
+                (rect.x + 2.0, rect.y + 1.0)
+            };
+            let left = Align::Left;
+            self.font
+                .draw_str_with(ctx, lx + 1.0, ly + 1.0, &label, shadow, left)?;
+            self.font
+                .draw_str_with(ctx, lx, ly, &label, graphics::WHITE, left)?;
+        }
+        Ok(())
+    }
+
+    // 盤面の右に、下から今の得点、その上にここから取れそうな残りの得点を積んだ棒を描く
+    fn draw_eval_bar(&self, ctx: &mut Context) -> GameResult {
+        const W: f32 = 10.0;
+        let board = self.game.board();
+        let cs = self.cell_size();
+        let (x, h) = (cs * board.width() as f32 + 6.0, cs * board.height() as f32);
+        if x + W > self.board_area().w {
+            return Ok(());
+        }
+
+        let frame = Rect::new(x, 0.0, W, h);
+        let bg = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            frame,
+            Color::from_rgb(0x20, 0x20, 0x20),
+        )?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let score = self.game.score();
+        if let Some(total) = self.estimate {
+            let total = total.max(score).max(1) as f32;
+            let bars = [
+                (total, Color::from_rgb(0x00, 0xa0, 0x50)),
+                (score as f32, graphics::WHITE),
+            ];
+            for &(value, color) in &bars {
+                let bh = h * value / total;
+                if bh > 0.0 {
+                    let rect = Rect::new(x, h - bh, W, bh);
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+                    graphics::draw(ctx, &mesh, DrawParam::default())?;
+                }
+            }
+            self.font
+                .draw_str(ctx, x + W + 4.0, 0.0, format!("~{}", total))?;
+        }
+        let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), frame, graphics::WHITE)?;
+        graphics::draw(ctx, &mesh, DrawParam::default())
+    }
+
+    // 計算中ならそこまでの最良から見積もりを出す
+    fn draw_eval_readout(&self, ctx: &mut Context) -> GameResult {
+        let running = self
+            .estimate_job
+            .and_then(|id| self.jobs.progress(id))
+            .map(|(_, best)| self.game.score() + best);
+        let text = match (running, self.estimate) {
+            (Some(total), _) => format!("Est. max: ~{}...", total),
+            (None, Some(total)) => format!("Est. max: ~{}", total),
+            (None, None) => "Est. max: ...".to_owned(),
+        };
+        let column = self.hud_column();
+        let right = Align::Right(column.width());
+        self.font
+            .draw_str_with(ctx, column.x(), column.row(6), text, graphics::WHITE, right)
+    }
+
+    // 揺れ始めは大きく、だんだん収まる
+    fn shake_offset(&self) -> (f32, f32) {
+        match self.shaking.and_then(|t| t.progress(self.anim.now())) {
+            Some(t) => {
+                let amp = Self::SHAKE_AMPLITUDE * (1.0 - t);
+                (amp * (t * 50.0).sin(), amp * (t * 37.0).cos())
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    // 盤面の中心を軸に angle ラジアン回して描く
+    fn draw_tiles(&self, ctx: &mut Context, board: &Board, angle: f32) -> GameResult {
+        let cs = self.cell_size();
+        let (cx, cy) = (
+            cs * board.width() as f32 / 2.0,
+            cs * board.height() as f32 / 2.0,
+        );
+        let (sin, cos) = angle.sin_cos();
+        let (tint, sheet) = match self.unlocks.tile_theme().kind {
+            RewardKind::TileTheme { tint, sheet } => (Color::from(tint), sheet),
+            RewardKind::Background { .. } => (graphics::WHITE, None),
+        };
+        let tiles = sheet
+            .and_then(|dir| self.themed_tiles.get(dir))
+            .unwrap_or(&self.tiles);
+        // 動きを減らす設定なら最初のコマのまま
+        let now = if self.prefs.reduce_motion {
+            0.0
+        } else {
+            self.anim.now().as_secs_f32()
+        };
+        for (x, y) in iproduct!(0..board.width(), 0..board.height()) {
+            let color = board.at(x, y);
+            if color == 0 {
+                continue;
+            }
+
+            // 斜めにずらして、光が盤面を流れていくように見せる
+            let t = now - 0.05 * (x + y) as f32;
+            let (img, src, shade) = tiles.get(color, t);
+            let rect = self.cell_rect(x, y);
+            let scale = rect.w / (f32::from(img.width()) * src.w);
+            let (dx, dy) = (rect.x + rect.w / 2.0 - cx, rect.y + rect.h / 2.0 - cy);
+            graphics::draw(
+                ctx,
+                img,
+                DrawParam::default()
+                    .dest(mint::Point2 {
+                        x: cx + dx * cos - dy * sin,
+                        y: cy + dx * sin + dy * cos,
+                    })
+                    .src(src)
+                    .offset(mint::Point2 { x: 0.5, y: 0.5 })
+                    .rotation(angle)
+                    .scale(mint::Vector2 { x: scale, y: scale })
+                    .color(Color::new(
+                        tint.r * shade.r,
+                        tint.g * shade.g,
+                        tint.b * shade.b,
+                        tint.a,
+                    )),
+            )?;
+        }
+        Ok(())
+    }
+
+    // 遊んでいる画面。ゲーム終了時の結果とダイアログは別に描く。
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let board = self.game.board();
+        // 回った直後は前の向きから回して見せる
+        let angle = match self.rotating.and_then(|t| t.progress(self.anim.now())) {
+            Some(t) => -(1.0 - t) * std::f32::consts::FRAC_PI_2,
+            None => 0.0,
+        };
+        // 揺れている間は画面の座標をずらして盤面を描く
+        let screen = graphics::screen_coordinates(ctx);
+        let (dx, dy) = self.shake_offset();
+        graphics::set_screen_coordinates(
+            ctx,
+            Rect::new(screen.x - dx, screen.y - dy, screen.w, screen.h),
+        )?;
+        self.draw_tiles(ctx, board, angle)?;
+        graphics::set_screen_coordinates(ctx, screen)?;
+        if self.prefs.coordinates {
+            self.draw_coordinates(ctx, board)?;
+        }
+        if self.prefs.eval_bar {
+            self.draw_eval_bar(ctx)?;
+        }
+
+        for &(x, y) in self.game.powerups().targets() {
+            let mesh = Mesh::new_rectangle(
+                ctx,
+                DrawMode::stroke(2.0),
+                self.cell_rect(x, y),
+                Color::from_rgb(0xff, 0xff, 0x00),
+            )?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        let hovered = if self.cursor != CURSOR_INVALID && self.game.powerups().armed().is_none() {
+            board.calc_component(self.cursor.0, self.cursor.1)
+        } else {
+            vec![]
+        };
+        let cs = self.cell_size();
+        let colors = self.highlight_colors();
+        if let Some(glow) = &mut self.glow {
+            // 1 秒あまりの周期でゆっくり明滅させる
+            let pulse = if self.prefs.reduce_motion {
+                0.5
+            } else {
+                let t = self.anim.now().as_secs_f32();
+                0.5 + 0.5 * (t * std::f32::consts::TAU / Self::PULSE_SECONDS).sin()
+            };
+            let dims = (board.width(), board.height());
+            glow.draw(ctx, hovered, dims, cs, colors, pulse)?;
+        } else {
+            let (fill, outline) = colors;
+            let mesh = self.hover.update(ctx, hovered, cs, fill, (2.0, outline))?;
+            if let Some(mesh) = mesh {
+                graphics::draw(ctx, mesh, DrawParam::default())?;
+            }
+        }
+
+        self.draw_last_move(ctx)?;
+        self.draw_hint(ctx)?;
+        self.draw_ghost(ctx)?;
+        self.draw_unlock_notice(ctx)?;
+        self.draw_status(ctx)?;
+
+        // 得点は桁が増えても右端がそろうようにする
+        let column = self.hud_column();
+        let score = format!("Score: {}", self.game.score());
+        let right = Align::Right(column.width());
+        self.font.draw_str_with(
+            ctx,
+            column.x(),
+            column.row(2),
+            score,
+            graphics::WHITE,
+            right,
+        )?;
+
+        let (seed, config) = (self.game.seed(), self.game.config());
+        if let Some(score) = self.reference_score {
+            let (x, y) = self.board_corner(0);
+            let text = format!("Reference score (this crate): {}", score);
+            self.font.draw_str(ctx, x, y, text)?;
+        }
+        if let Some(pb) = self.personal_bests.get(seed, &config) {
+            self.font
+                .draw_str(ctx, column.x(), column.row(0), format!("PB: {}", pb))?;
+        }
+
+        if self.game.rules().streak {
+            self.font.draw_str(
+                ctx,
+                column.x(),
+                column.row(1),
+                format!("Streak: x{}", self.game.streak().multiplier()),
+            )?;
+        }
+
+        if self.game.is_finished() {
+            self.font
+                .draw_str(ctx, column.x(), column.row(3), "Finished")?;
+        } else if self.game.rules().time_pressure {
+            self.draw_decay_bar(ctx)?;
+        }
+
+        self.draw_powerups(ctx)?;
+        self.draw_timer(ctx)?;
+        self.draw_difficulty(ctx)?;
+        if self.prefs.eval_readout {
+            self.draw_eval_readout(ctx)?;
+        }
+
+        let footer = self.hud_footer();
+        if self.tournament.is_some() {
+            self.font
+                .draw_str(ctx, footer.x(), footer.row(2), "Enter:Next round Q:Quit")?;
+            self.font
+                .draw_str(ctx, footer.x(), footer.row(3), "1-9:Power-up")?;
+            self.draw_tournament(ctx)?;
+        } else {
+            self.font.draw_str(
+                ctx,
+                footer.x(),
+                footer.row(2),
+                "R:New Shift+R:Retry D:Daily H:Hard W:Weekly P:Pause Q:Quit F1:Help F2:2-color",
+            )?;
+            self.font.draw_str(
+                ctx,
+                footer.x(),
+                footer.row(3),
+                "L:Levels S:Settings I:Hint M:Streak T:Time O:Rotate Z:Turn K:Quake G:Ghost 1-9:Power-up",
+            )?;
+
+            self.draw_code(ctx)?;
+            self.draw_rotation(ctx)?;
+            self.draw_curriculum(ctx)?;
+        }
+
+        Ok(())
+    }
+
+    // 画面の座標をウィンドウの大きさに合わせる。表示の位置は描くたびに計算し直す。
+    pub fn resize(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.screen = Rect::new(0.0, 0.0, width, height);
+        // 全画面から戻したときの大きさとして覚えておき、終了時に保存する
+        if !self.prefs.fullscreen {
+            self.prefs.window_size = Some((width, height));
+        }
+        if let Err(e) = graphics::set_screen_coordinates(ctx, self.screen) {
+            eprintln!("cannot resize the screen: {}", e);
+        }
+    }
+
+    // 場面から渡された入力を処理する。他の画面を開く操作は場面が受け取る。
+    pub fn action(&mut self, action: input::Action, repeat: bool) {
+        if let input::Action::Point(x, y) = action {
+            self.point(x, y);
+            return;
+        }
+        if repeat {
+            return;
+        }
+        self.board_action(action);
+    }
+
+    fn point(&mut self, x: f32, y: f32) {
+        let cursor = self.calc_cursor(x, y);
+        if cursor != self.cursor && cursor != CURSOR_INVALID {
+            self.record(Action::Cursor(cursor.0, cursor.1));
+        }
+        self.cursor = cursor;
+    }
+
+    // 指しているグループが変わったら、その大きさに応じた音を鳴らす
+    fn update_hover_cue(&mut self) {
+        if !self.prefs.hover_tones || self.cursor == CURSOR_INVALID {
+            self.hover_cue = None;
+            return;
+        }
+        let (x, y) = self.cursor;
+        let group = self.game.board().calc_component(x, y);
+        let cue = group.iter().min().copied();
+        if cue == self.hover_cue {
+            return;
+        }
+        self.hover_cue = cue;
+        if let Some(playback) = Playback::for_hover(group.len()) {
+            self.audio.play(Sound::Tone, playback);
+        }
+    }
+
+    // キーやゲームパッドでカーソルを動かす。盤面の外にあれば左下から始める。
+    fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let w = self.game.board().width() as i32;
+        let h = self.game.board().height() as i32;
+        let (x, y) = if self.cursor == CURSOR_INVALID {
+            (0, h - 1)
+        } else {
+            let (x, y) = self.cursor;
+            let x = (x as i32 + dx).max(0).min(w - 1);
+            let y = (y as i32 + dy).max(0).min(h - 1);
+            (x, y)
+        };
+        let cursor = (x as usize, y as usize);
+        if cursor != self.cursor {
+            self.record(Action::Cursor(cursor.0, cursor.1));
+        }
+        self.cursor = cursor;
+    }
+
+    fn board_action(&mut self, action: input::Action) {
+        // トーナメント中は盤面の切り替えややり直しを許さない
+        let in_tournament = self.tournament.is_some();
+        let finished = self.game.is_finished();
+
+        match action {
+            input::Action::Cancel | input::Action::Quit => {
+                self.confirm_quit();
+            }
+            input::Action::Confirm if in_tournament && finished => {
+                self.push(Command::NextRound);
+            }
+            input::Action::Confirm if self.curriculum.is_some() && finished => {
+                self.push(Command::NextLevel);
+            }
+            input::Action::Confirm if self.cursor != CURSOR_INVALID && !finished => {
+                self.push(Command::Erase(self.cursor.0, self.cursor.1));
+            }
+            input::Action::Click(x, y) => {
+                let cursor = self.calc_cursor(x, y);
+                if cursor != CURSOR_INVALID {
+                    self.push(Command::Erase(cursor.0, cursor.1));
+                }
+            }
+            input::Action::MoveCursor(dx, dy) => {
+                self.move_cursor(dx, dy);
+            }
+            input::Action::Pick(n) if n > 0 => {
+                self.push(Command::PowerUp(usize::from(n - 1)));
+            }
+            input::Action::CopyBoard => {
+                self.push(Command::CopyBoard);
+            }
+            input::Action::ExportMoves => {
+                self.push(Command::ExportMoves);
+            }
+            _ if in_tournament => {}
+            input::Action::Retry => {
+                self.push(Command::Retry);
+            }
+            input::Action::Reset => {
+                self.push(Command::Reset);
+            }
+            input::Action::ToggleGhost => {
+                self.push(Command::ToggleGhost);
+            }
+            input::Action::CycleOpponent => {
+                self.push(Command::CycleOpponent);
+            }
+            input::Action::PasteBoard => {
+                self.push(Command::PasteBoard);
+            }
+            input::Action::OpenBoard => {
+                self.push(Command::OpenBoard);
+            }
+            input::Action::Rotation(rotation) => {
+                self.push(Command::Rotation(rotation));
+            }
+            input::Action::Hint => {
+                self.push(Command::Hint);
+            }
+            input::Action::HintLevel => {
+                self.push(Command::HintLevel);
+            }
+            input::Action::ToggleStreak => {
+                self.push(Command::ToggleStreak);
+            }
+            input::Action::ToggleTimePressure => {
+                self.push(Command::ToggleTimePressure);
+            }
+            input::Action::ToggleRotate => {
+                self.push(Command::ToggleRotate);
+            }
+            input::Action::ToggleEarthquake => {
+                self.push(Command::ToggleEarthquake);
+            }
+            input::Action::TwoColor => {
+                self.push(Command::TwoColor);
+            }
+            input::Action::RotateBoard => {
+                self.push(Command::RotateBoard);
+            }
+            _ => {}
+        }
+    }
+
+    // ウィンドウを閉じようとしたら確認する。確認中にもう一度閉じればそのまま終わる。
+    pub fn quit_event(&mut self, ctx: &Context) -> bool {
+        if self.dialogs.top() == Some(&DialogTag::Quit) {
+            self.on_quit(ctx);
+            return false;
+        }
+        self.confirm_quit();
+        true
+    }
+
+    pub fn dialogs_open(&self) -> bool {
+        self.dialogs.is_open()
+    }
+
+    pub fn dialog_action(&mut self, action: input::Action) {
+        if let Some((tag, answer)) = self.dialogs.on_action(action) {
+            self.on_dialog(tag, answer);
+        }
+    }
+
+    pub fn dialog_text(&mut self, ch: char) {
+        self.dialogs.on_text(ch);
+    }
+
+    pub fn draw_dialogs(&mut self, ctx: &mut Context) -> GameResult {
+        self.dialogs.draw(ctx, &self.font, self.screen)
+    }
+
+    pub fn in_tournament(&self) -> bool {
+        self.tournament.is_some()
+    }
+
+    // 座標 (x, y) にある盤面のセル
+    pub fn cell_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        Some(self.calc_cursor(x, y)).filter(|&cell| cell != CURSOR_INVALID)
+    }
+
+    // 今のゲームの記録
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn ratings(&self) -> &Ratings {
+        &self.ratings
+    }
+
+    pub fn screen(&self) -> Rect {
+        self.screen
+    }
+
+    pub fn anim(&self) -> &AnimClock {
+        &self.anim
+    }
+
+    pub fn reduce_motion(&self) -> bool {
+        self.prefs.reduce_motion
+    }
+
+    // 選択中のグループを塗る色と、輪郭・カーソルの色
+    pub fn highlight_colors(&self) -> (Color, Color) {
+        let style = self.prefs.highlight;
+        let (r, g, b) = style.tint;
+        (
+            Color::from_rgba(r, g, b, style.alpha),
+            Color::from(style.outline),
+        )
+    }
+
+    pub fn crt(&self) -> bool {
+        self.prefs.crt
+    }
+
+    // 止めていた時計を時刻 now から進め直す
+    pub fn reset_clock(&mut self, now: Duration) {
+        self.clock.reset(now);
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        self.prefs.fullscreen = !self.prefs.fullscreen;
+        self.push(Command::ApplyWindowMode);
+    }
+
+    pub fn play_challenge(&mut self, challenge: Challenge) {
+        self.push(Command::Challenge(challenge));
+    }
+
+    // 前回途中で終了したゲームを続けるか、今の盤面を最初から遊ぶ
+    pub fn answer_resume(&mut self, choice: Choice, suspended: Suspended) {
+        self.push(Command::AnswerResume(choice, suspended));
+    }
+
+    // レベル選択に出す到達状況
+    pub fn load_progress(&self) -> Progress {
+        Progress::load(&*self.backend, &self.curriculum_key()).unwrap_or_else(|e| {
+            eprintln!("cannot load curriculum progress: {:#}", e);
+            Progress::default()
+        })
+    }
+
+    // レベルに限りがあれば最後のレベル
+    pub fn last_level(&self) -> Option<u32> {
+        self.campaign.last()
+    }
+
+    pub fn play_level(&mut self, number: u32, progress: Progress) {
+        self.push(Command::PlayLevel(number, progress));
+    }
+
+    // パックの最後のレベルを終えたか。次に進む代わりにレベル選択に戻る。
+    pub fn pack_finished(&self) -> bool {
+        match (&self.curriculum, self.campaign.last()) {
+            (Some((level, _)), Some(last)) => {
+                self.game.is_finished()
+                    && level.is_cleared(self.game.score())
+                    && level.number >= last
+            }
+            _ => false,
+        }
+    }
+
+    // エディタで作った盤面を遊ぶ
+    // 別の大きさの盤面を見せていた場面から戻るとき、config で新しいゲームを始める
+    pub fn play_generated(&mut self, config: GameConfig) {
+        self.leave_modes();
+        self.config = config;
+        self.start(rand::random());
+    }
+
+    pub fn play_board(&mut self, board: Board) {
+        self.leave_modes();
+        self.config = config_for(&board, self.config);
+        let seed = board.fingerprint();
+        self.edited = Some(board);
+        self.start(seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_within() {
+        let mut board = Board::parse("3 2\n121\n212\n".as_bytes()).unwrap();
+        let last_column = (board.width() - 1, 0);
+        assert_eq!(
+            super::cursor_within(last_column, board.width(), board.height()),
+            last_column
+        );
+
+        board.rotate();
+        assert_eq!((board.width(), board.height()), (2, 3));
+        assert_eq!(
+            super::cursor_within(last_column, board.width(), board.height()),
+            CURSOR_INVALID
+        );
+        assert_eq!(
+            super::cursor_within((1, 2), board.width(), board.height()),
+            (1, 2)
+        );
+        assert_eq!(
+            super::cursor_within(CURSOR_INVALID, board.width(), board.height()),
+            CURSOR_INVALID
+        );
+    }
+
+    #[test]
+    fn config_for() {
+        let mut config = GameConfig::default();
+        config.rules.streak = true;
+        let board = Board::parse("4 2\n1323\n3131\n".as_bytes()).unwrap();
+        let played = super::config_for(&board, config);
+        assert_eq!((played.width, played.height, played.colors), (4, 2, 3));
+        assert_eq!(played.rules, config.rules);
+
+        let board = Board::parse("2 1\n00\n".as_bytes()).unwrap();
+        assert_eq!(super::config_for(&board, config).colors, 1);
     }
 }
@@ -0,0 +1,50 @@
+use crate::difficulty::{self, Difficulty};
+use crate::game::{Game, GameConfig};
+
+// この回数試して見つからなければ最も近かった盤面で妥協する
+const MAX_ATTEMPTS: u64 = 200;
+
+// base から決定的に派生させたシードを順に試し、
+// 難しさの見積もりが target になる盤面のシードを返す。
+pub fn find_seed(config: GameConfig, base: u64, target: Difficulty) -> u64 {
+    let range = target.range();
+    let mut best = (f64::INFINITY, base);
+
+    for k in 0..MAX_ATTEMPTS {
+        let seed = base ^ k.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let game = Game::new(seed, config);
+        let score = difficulty::estimate_difficulty(game.board()).score;
+        if range.contains(&score) {
+            return seed;
+        }
+
+        let dist = (range.start - score).max(score - range.end);
+        if dist < best.0 {
+            best = (dist, seed);
+        }
+    }
+
+    best.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_seed() {
+        let config = GameConfig {
+            width: 10,
+            height: 8,
+            colors: 4,
+            ..GameConfig::default()
+        };
+        for &target in &[Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            let seed = super::find_seed(config, 7, target);
+            let game = Game::new(seed, config);
+            let report = difficulty::estimate_difficulty(game.board());
+            assert_eq!(report.difficulty, target);
+            assert_eq!(super::find_seed(config, 7, target), seed);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// FNV-1a (64 ビット)。シードや識別子を文字列やバイト列から決めるのに使う。
+// 暗号的な強さは要らず、版や環境によらず同じ値になることだけが要る。
+pub fn fnv1a<I: IntoIterator<Item = u8>>(bytes: I) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn fnv1a() {
+        assert_eq!(super::fnv1a(vec![]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(super::fnv1a(b"a".iter().copied()), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(super::fnv1a("foobar".bytes()), 0x8594_4171_f739_67e8);
+    }
+}
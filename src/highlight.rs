@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashSet};
+
+use ggez::graphics::{
+    self, Color, DrawMode, DrawParam, EmptyConst, FilterMode, Image, Mesh, MeshBuilder, Rect,
+    Shader,
+};
+use ggez::mint;
+use ggez::{Context, GameResult};
+
+// 格子点の座標。セル (x, y) の左上が (x, y)。
+type Corner = (usize, usize);
+
+// セルの集まりの輪郭。閉じた折れ線(最初の点には戻らない)の列を返す。
+// 外周は時計回り、穴は反時計回りで、一直線に並ぶ点は省く。
+pub fn outline(cells: &[(usize, usize)]) -> Vec<Vec<Corner>> {
+    let set: HashSet<_> = cells.iter().copied().collect();
+    let inside = |x: usize, y: usize, dx: isize, dy: isize| {
+        let (nx, ny) = (x as isize + dx, y as isize + dy);
+        nx >= 0 && ny >= 0 && set.contains(&(nx as usize, ny as usize))
+    };
+
+    // 隣がセルでない辺を、セルを右手に見る向きで集める
+    let mut edges: BTreeMap<Corner, Vec<Corner>> = BTreeMap::new();
+    for &(x, y) in &set {
+        let sides = [
+            ((0, -1), (x, y), (x + 1, y)),
+            ((1, 0), (x + 1, y), (x + 1, y + 1)),
+            ((0, 1), (x + 1, y + 1), (x, y + 1)),
+            ((-1, 0), (x, y + 1), (x, y)),
+        ];
+        for &((dx, dy), from, to) in &sides {
+            if !inside(x, y, dx, dy) {
+                edges.entry(from).or_default().push(to);
+            }
+        }
+    }
+
+    let mut res = vec![];
+    while let Some(&start) = edges.keys().next() {
+        let mut path = vec![start];
+        let mut cur = start;
+        loop {
+            let nexts = edges.get_mut(&cur).expect("internal error");
+            let next = nexts.pop().expect("internal error");
+            if nexts.is_empty() {
+                edges.remove(&cur);
+            }
+            if next == start {
+                break;
+            }
+            path.push(next);
+            cur = next;
+        }
+        res.push(simplify(path));
+    }
+    res
+}
+
+// 一直線に並ぶ点を省く
+fn simplify(path: Vec<Corner>) -> Vec<Corner> {
+    let n = path.len();
+    (0..n)
+        .filter(|&i| {
+            let (a, b, c) = (path[(i + n - 1) % n], path[i], path[(i + 1) % n]);
+            !((a.0 == b.0 && b.0 == c.0) || (a.1 == b.1 && b.1 == c.1))
+        })
+        .map(|i| path[i])
+        .collect()
+}
+
+// セルの集まりを塗りつぶしと輪郭で 1 つのメッシュにする
+pub fn build_mesh(
+    ctx: &mut Context,
+    cells: &[(usize, usize)],
+    cell_size: f32,
+    fill: Option<Color>,
+    stroke: Option<(f32, Color)>,
+) -> GameResult<Mesh> {
+    let mut mb = MeshBuilder::new();
+    if let Some(color) = fill {
+        for &(x, y) in cells {
+            let rect = Rect::new(
+                cell_size * x as f32,
+                cell_size * y as f32,
+                cell_size,
+                cell_size,
+            );
+            mb.rectangle(DrawMode::fill(), rect, color);
+        }
+    }
+    if let Some((width, color)) = stroke {
+        for path in outline(cells) {
+            let points: Vec<_> = path
+                .iter()
+                .map(|&(x, y)| mint::Point2 {
+                    x: cell_size * x as f32,
+                    y: cell_size * y as f32,
+                })
+                .collect();
+            mb.polygon(DrawMode::stroke(width), &points, color)?;
+        }
+    }
+    mb.build(ctx)
+}
+
+// 同じセルの集まりを描き続ける間はメッシュを作り直さない
+#[derive(Debug, Default)]
+pub struct HighlightCache {
+    key: Option<(Vec<(usize, usize)>, u32)>,
+    mesh: Option<Mesh>,
+}
+
+impl HighlightCache {
+    // cells が空なら何も描かない
+    pub fn update(
+        &mut self,
+        ctx: &mut Context,
+        mut cells: Vec<(usize, usize)>,
+        cell_size: f32,
+        fill: Color,
+        stroke: (f32, Color),
+    ) -> GameResult<Option<&Mesh>> {
+        if cells.is_empty() {
+            self.key = None;
+            self.mesh = None;
+            return Ok(None);
+        }
+
+        cells.sort();
+        let key = (cells, cell_size.to_bits());
+        if self.key.as_ref() != Some(&key) {
+            self.mesh = Some(build_mesh(
+                ctx,
+                &key.0,
+                cell_size,
+                Some(fill),
+                Some(stroke),
+            )?);
+            self.key = Some(key);
+        }
+        Ok(self.mesh.as_ref())
+    }
+}
+
+// w x h のマスク画像の RGBA。cells のセルだけ不透明な白にする。
+pub fn mask_rgba(w: usize, h: usize, cells: &[(usize, usize)]) -> Vec<u8> {
+    let mut rgba = vec![0; 4 * w * h];
+    for &(x, y) in cells {
+        let i = 4 * (w * y + x);
+        rgba[i..i + 4].copy_from_slice(&[0xff; 4]);
+    }
+    rgba
+}
+
+// マスクを作ったセルの集まりと盤面の大きさ
+type MaskKey = (Vec<(usize, usize)>, (usize, usize));
+
+// セルの集まりをシェーダで輪郭付きで明るくする。
+// メッシュを組み立てず、セルごとに 1 画素のマスク画像を 1 枚描くだけなので大きな集まりでも軽い。
+#[derive(Debug)]
+pub struct GroupGlow {
+    shader: Shader<EmptyConst>,
+    key: Option<MaskKey>,
+    mask: Option<Image>,
+}
+
+impl GroupGlow {
+    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+        let shader = Shader::new(
+            ctx,
+            "/shader/basic.glslv",
+            "/shader/glow.glslf",
+            EmptyConst,
+            "Glow",
+            None,
+        )?;
+        Ok(Self {
+            shader,
+            key: None,
+            mask: None,
+        })
+    }
+
+    // dims の盤面のうち cells を、左上を原点として cell_size の大きさのセルで描く。
+    // fill で塗って outline で縁取る。pulse (0 から 1) が大きいほど濃く塗る。
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        mut cells: Vec<(usize, usize)>,
+        dims: (usize, usize),
+        cell_size: f32,
+        (fill, outline): (Color, Color),
+        pulse: f32,
+    ) -> GameResult {
+        if cells.is_empty() {
+            self.key = None;
+            self.mask = None;
+            return Ok(());
+        }
+
+        cells.sort();
+        let key = (cells, dims);
+        if self.key.as_ref() != Some(&key) {
+            let (w, h) = dims;
+            let rgba = mask_rgba(w, h, &key.0);
+            let mut mask = Image::from_rgba8(ctx, w as u16, h as u16, &rgba)?;
+            mask.set_filter(FilterMode::Nearest);
+            self.mask = Some(mask);
+            self.key = Some(key);
+        }
+        let mask = self.mask.as_ref().expect("internal error");
+
+        let _lock = graphics::use_shader(ctx, &self.shader);
+        let param = DrawParam::default().scale(mint::Vector2 {
+            x: cell_size,
+            y: cell_size,
+        });
+        // 塗りと輪郭の 2 回に分けて描く。シェーダはアルファが負なら輪郭だけを描く。
+        let alpha = fill.a * (0.6 + 0.4 * pulse);
+        graphics::draw(ctx, mask, param.color(Color { a: alpha, ..fill }))?;
+        graphics::draw(ctx, mask, param.color(Color { a: -1.0, ..outline }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(mut paths: Vec<Vec<Corner>>) -> Vec<Vec<Corner>> {
+        // 始点を最小の点にそろえる
+        for path in &mut paths {
+            let i = (0..path.len()).min_by_key(|&i| path[i]).unwrap();
+            path.rotate_left(i);
+        }
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn outline() {
+        assert_eq!(
+            normalize(super::outline(&[(2, 3)])),
+            [[(2, 3), (3, 3), (3, 4), (2, 4)]]
+        );
+
+        // L 字
+        assert_eq!(
+            normalize(super::outline(&[(0, 0), (0, 1), (1, 1)])),
+            [[(0, 0), (1, 0), (1, 1), (2, 1), (2, 2), (0, 2)]]
+        );
+
+        // 真ん中が抜けた 3x3 は外周と穴の 2 本
+        let ring: Vec<_> = (0..3)
+            .flat_map(|x| (0..3).map(move |y| (x, y)))
+            .filter(|&c| c != (1, 1))
+            .collect();
+        assert_eq!(
+            normalize(super::outline(&ring)),
+            [
+                vec![(0, 0), (3, 0), (3, 3), (0, 3)],
+                vec![(1, 1), (1, 2), (2, 2), (2, 1)],
+            ]
+        );
+
+        assert!(super::outline(&[]).is_empty());
+    }
+
+    #[test]
+    fn mask_rgba() {
+        let rgba = super::mask_rgba(3, 2, &[(1, 0), (2, 1)]);
+        let opaque: Vec<_> = (0..6).filter(|&i| rgba[4 * i + 3] == 0xff).collect();
+        assert_eq!(opaque, [1, 5]);
+        assert_eq!(rgba.len(), 24);
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::hint::HintLevel;
+use crate::speedrun::Splits;
+use crate::storage::{self, SaveBackend};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub score: i32,
+    pub splits: Splits,
+    // 古い記録には名前がない
+    #[serde(default)]
+    pub name: String,
+    // ヒントを使った記録なら、見せた中で一番強いもの
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assist: Option<HintLevel>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    entries: Vec<Entry>,
+}
+
+impl HighScores {
+    pub const CAPACITY: usize = 10;
+
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    // 得点の高い順、同点ならクリアタイムの短い順。圏外なら None。
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let key = |e: &Entry| (-e.score, e.splits.finish);
+        let rank = self.entries.iter().position(|e| key(&entry) < key(e));
+        let rank = rank.unwrap_or(self.entries.len());
+        if rank >= Self::CAPACITY {
+            return None;
+        }
+
+        self.entries.insert(rank, entry);
+        self.entries.truncate(Self::CAPACITY);
+        Some(rank)
+    }
+
+    pub fn rename(&mut self, rank: usize, name: String) {
+        if let Some(entry) = self.entries.get_mut(rank) {
+            entry.name = name;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn entry(score: i32, secs: u64) -> Entry {
+        let mut splits = Splits::new();
+        splits.finish(Duration::from_secs(secs));
+        Entry {
+            score,
+            splits,
+            name: String::new(),
+            assist: None,
+        }
+    }
+
+    #[test]
+    fn insert() {
+        let mut hs = HighScores::default();
+        assert_eq!(hs.insert(entry(100, 50)), Some(0));
+        assert_eq!(hs.insert(entry(200, 50)), Some(0));
+        assert_eq!(hs.insert(entry(100, 40)), Some(1));
+        assert_eq!(hs.insert(entry(100, 60)), Some(3));
+        for _ in 0..10 {
+            hs.insert(entry(300, 10));
+        }
+        assert_eq!(hs.entries().len(), HighScores::CAPACITY);
+        assert_eq!(hs.insert(entry(1, 10)), None);
+
+        hs.rename(0, "alice".to_owned());
+        assert_eq!(hs.entries()[0].name, "alice");
+        hs.rename(HighScores::CAPACITY, "bob".to_owned());
+    }
+}
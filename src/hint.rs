@@ -0,0 +1,221 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{self, Board};
+use crate::eval::Weights;
+use crate::monitor::Monitor;
+use crate::policy::{GreedyPolicy, Policy};
+use crate::solver::{self, Solution};
+
+// タイル画像の色。6 色目からは画像を暗くしたもの。
+const COLOR_NAMES: [&str; board::MAX_COLORS as usize] = [
+    "red",
+    "green",
+    "yellow",
+    "cyan",
+    "magenta",
+    "dark red",
+    "dark green",
+    "dark yellow",
+];
+
+fn color_name(color: u8) -> &'static str {
+    COLOR_NAMES[usize::from(color) - 1]
+}
+
+// ヒントの手を勧める理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rationale {
+    // 一番大きいグループを消すと、この色が 1 個だけ残って消せなくなる
+    AvoidsIsolating { color: u8 },
+    // 数手先で今はない大きさのグループができる
+    SetsUp { color: u8, size: usize },
+    // 読み筋の最後で全消しになる
+    ClearsBoard { moves: usize },
+    TakesGroup { color: u8, size: usize },
+}
+
+impl fmt::Display for Rationale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::AvoidsIsolating { color } => {
+                write!(f, "avoids isolating the last {}", color_name(color))
+            }
+            Self::SetsUp { color, size } => {
+                write!(f, "sets up a {}-tile {} group", size, color_name(color))
+            }
+            Self::ClearsBoard { moves: 1 } => f.write_str("clears the board"),
+            Self::ClearsBoard { moves } => write!(f, "clears the board in {} moves", moves),
+            Self::TakesGroup { color, size } => {
+                write!(f, "takes the {}-tile {} group", size, color_name(color))
+            }
+        }
+    }
+}
+
+// ヒントでどこまで見せるか。手伝ってもらった度合いとして記録にも残す。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HintLevel {
+    // 最善手のあたりの範囲だけ
+    Region,
+    // 最善手で消すグループ
+    #[default]
+    Group,
+    // 何手か先までの読み筋
+    Line,
+}
+
+impl HintLevel {
+    pub fn number(self) -> u8 {
+        self as u8 + 1
+    }
+
+    // 1, 2, 3, 1, ... と切り替える
+    pub fn next(self) -> Self {
+        match self {
+            Self::Region => Self::Group,
+            Self::Group => Self::Line,
+            Self::Line => Self::Region,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub pos: (usize, usize),
+    // 探索で見つけた読み筋
+    pub line: Solution,
+    pub rationale: Rationale,
+}
+
+impl Hint {
+    const BEAM_WIDTH: usize = 16;
+    // 何手先までのグループを「作る」とみなすか
+    const LOOKAHEAD: usize = 5;
+    // 範囲だけのヒントで、グループの外側に広げるセル数
+    const REGION_MARGIN: usize = 1;
+
+    // 消せるグループがなければ None。monitor で止められたら途中までの読み筋から選ぶ。
+    pub fn new(board: &Board, monitor: &mut Monitor) -> Option<Self> {
+        let line = solver::beam_search_with(board, Self::BEAM_WIDTH, Weights::default(), monitor);
+        let pos = *line.moves.first()?;
+        let rationale = Self::explain(board, &line);
+        Some(Self {
+            pos,
+            line,
+            rationale,
+        })
+    }
+
+    // 最善手のグループを囲む範囲を少し広げたもの。左上と右下のセルを返す。
+    pub fn region(&self, board: &Board) -> ((usize, usize), (usize, usize)) {
+        let cells = board.calc_component(self.pos.0, self.pos.1);
+        let xs = cells.iter().map(|&(x, _)| x);
+        let ys = cells.iter().map(|&(_, y)| y);
+        let m = Self::REGION_MARGIN;
+        let (x0, x1) = (xs.clone().min().unwrap_or(0), xs.max().unwrap_or(0));
+        let (y0, y1) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
+        (
+            (x0.saturating_sub(m), y0.saturating_sub(m)),
+            (
+                (x1 + m).min(board.width() - 1),
+                (y1 + m).min(board.height() - 1),
+            ),
+        )
+    }
+
+    fn explain(board: &Board, line: &Solution) -> Rationale {
+        let trace = line.trace(board);
+        let first = trace[0];
+
+        // 素直に一番大きいグループを消した場合と比べる
+        if let Some(greedy) = GreedyPolicy.next_move(board) {
+            if greedy != line.moves[0] {
+                let isolated = |pos: (usize, usize)| {
+                    let mut after = board.clone();
+                    after.erase_component(pos.0, pos.1);
+                    (1..).take(COLOR_NAMES.len()).find(|&color| {
+                        board.color_count(color) > 1 && after.color_count(color) == 1
+                    })
+                };
+                if let (Some(color), None) = (isolated(greedy), isolated(line.moves[0])) {
+                    return Rationale::AvoidsIsolating { color };
+                }
+            }
+        }
+
+        let mut largest = [0; COLOR_NAMES.len() + 1];
+        for ((x, y), size) in board.groups() {
+            let color = usize::from(board.at(x, y));
+            largest[color] = largest[color].max(size);
+        }
+        let set_up = trace
+            .iter()
+            .skip(1)
+            .take(Self::LOOKAHEAD)
+            .filter(|e| e.size > largest[usize::from(e.color)])
+            .max_by_key(|e| e.size);
+        if let Some(e) = set_up {
+            return Rationale::SetsUp {
+                color: e.color,
+                size: e.size,
+            };
+        }
+
+        let erased: usize = trace.iter().map(|e| e.size).sum();
+        if erased == board.tile_count() {
+            return Rationale::ClearsBoard { moves: trace.len() };
+        }
+
+        Rationale::TakesGroup {
+            color: first.color,
+            size: first.size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hint_for(s: &str) -> Option<Hint> {
+        Hint::new(
+            &Board::parse(s.as_bytes()).unwrap(),
+            &mut Monitor::new(None),
+        )
+    }
+
+    #[test]
+    fn hint() {
+        // 1 を消すと 2 がつながる
+        let h = hint_for("3 2\n212\n111\n").unwrap();
+        assert_eq!(h.pos, h.line.moves[0]);
+        assert_eq!(h.line.score, 10);
+        assert_eq!(h.rationale.to_string(), "sets up a 2-tile green group");
+
+        let h = hint_for("2 1\n11\n").unwrap();
+        assert_eq!(h.rationale.to_string(), "clears the board");
+
+        let h = hint_for("3 1\n113\n").unwrap();
+        assert_eq!(h.rationale.to_string(), "takes the 2-tile red group");
+
+        // 一番大きい 1 を先に消すと右上の 1 が取り残される
+        let h = hint_for("4 3\n2321\n3113\n2113\n").unwrap();
+        assert_eq!(h.rationale.to_string(), "avoids isolating the last red");
+
+        assert_eq!(hint_for("2 1\n12\n"), None);
+    }
+
+    #[test]
+    fn region() {
+        let board = Board::parse("5 4\n23232\n32323\n23112\n32323\n".as_bytes()).unwrap();
+        let h = Hint::new(&board, &mut Monitor::new(None)).unwrap();
+        assert_eq!(h.pos.1, 2);
+        assert_eq!(h.region(&board), ((1, 1), (4, 3)));
+
+        assert_eq!(HintLevel::default().number(), 2);
+        assert_eq!(HintLevel::Line.next(), HintLevel::Region);
+        assert!(Some(HintLevel::Region) > None);
+    }
+}
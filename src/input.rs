@@ -0,0 +1,225 @@
+use ggez::event::{Button, KeyCode, KeyMods, MouseButton};
+
+use crate::rotation::Rotation;
+
+// キーやマウス、ゲームパッドの入力を意味に直したもの。場面はこれだけを受け取る。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    // 選択を上下左右に動かす
+    MoveCursor(i32, i32),
+    Confirm,
+    Cancel,
+    Yes,
+    No,
+    // 入力中の文字を 1 文字消す
+    DeleteChar,
+    // 数字キー
+    Pick(u8),
+    // 画面上の位置を指す・クリックする
+    Point(f32, f32),
+    Click(f32, f32),
+
+    Quit,
+    Pause,
+    Hint,
+    // ヒントの強さを切り替える
+    HintLevel,
+    Reset,
+    Retry,
+    ToggleGhost,
+    // コンピュータの相手の強さを切り替える
+    CycleOpponent,
+    ToggleStreak,
+    ToggleTimePressure,
+    ToggleRotate,
+    ToggleEarthquake,
+    // 2 色の盤面をすぐに遊ぶ
+    TwoColor,
+    // 盤面を回す
+    RotateBoard,
+    InputCode,
+    Rotation(Rotation),
+    LevelSelect,
+    Settings,
+    Tutorial,
+    ViewReplay,
+    Editor,
+    ToggleFullscreen,
+    // 盤面をクリップボードに写す・クリップボードの盤面を遊ぶ
+    CopyBoard,
+    PasteBoard,
+    // 盤面のファイルを選んで遊ぶ
+    OpenBoard,
+    // 手の記録を CSV に書き出す
+    ExportMoves,
+    // 終わったゲームを検討する
+    Analyze,
+    // ゲームごとの評価の移り変わりを見る
+    Stats,
+}
+
+// キーとゲームパッドのボタンの割り当て
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    // Shift を押しているときだけのものは bool を true にする
+    keys: Vec<(KeyCode, bool, Action)>,
+    // Ctrl を押しているときのもの
+    ctrl_keys: Vec<(KeyCode, Action)>,
+    buttons: Vec<(Button, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let digits = [
+            KeyCode::Key0,
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        let mut keys = vec![
+            (KeyCode::Up, false, Action::MoveCursor(0, -1)),
+            (KeyCode::Down, false, Action::MoveCursor(0, 1)),
+            (KeyCode::Left, false, Action::MoveCursor(-1, 0)),
+            (KeyCode::Right, false, Action::MoveCursor(1, 0)),
+            (KeyCode::Tab, false, Action::MoveCursor(0, 1)),
+            (KeyCode::Return, false, Action::Confirm),
+            (KeyCode::Space, false, Action::Confirm),
+            (KeyCode::Escape, false, Action::Cancel),
+            (KeyCode::Y, false, Action::Yes),
+            (KeyCode::N, false, Action::No),
+            (KeyCode::Back, false, Action::DeleteChar),
+            (KeyCode::Q, false, Action::Quit),
+            (KeyCode::P, false, Action::Pause),
+            (KeyCode::I, false, Action::Hint),
+            (KeyCode::I, true, Action::HintLevel),
+            (KeyCode::R, false, Action::Reset),
+            (KeyCode::R, true, Action::Retry),
+            (KeyCode::G, false, Action::ToggleGhost),
+            (KeyCode::G, true, Action::CycleOpponent),
+            (KeyCode::M, false, Action::ToggleStreak),
+            (KeyCode::T, false, Action::ToggleTimePressure),
+            (KeyCode::O, false, Action::ToggleRotate),
+            (KeyCode::K, false, Action::ToggleEarthquake),
+            (KeyCode::Z, false, Action::RotateBoard),
+            (KeyCode::C, false, Action::InputCode),
+            (KeyCode::D, false, Action::Rotation(Rotation::Daily)),
+            (KeyCode::H, false, Action::Rotation(Rotation::HardDaily)),
+            (KeyCode::W, false, Action::Rotation(Rotation::Weekly)),
+            (KeyCode::L, false, Action::LevelSelect),
+            (KeyCode::S, false, Action::Settings),
+            (KeyCode::S, true, Action::Stats),
+            (KeyCode::F1, false, Action::Tutorial),
+            (KeyCode::V, false, Action::ViewReplay),
+            (KeyCode::E, false, Action::Editor),
+            (KeyCode::A, false, Action::Analyze),
+            (KeyCode::F2, false, Action::TwoColor),
+            (KeyCode::F11, false, Action::ToggleFullscreen),
+        ];
+        keys.extend(
+            digits
+                .iter()
+                .enumerate()
+                .map(|(i, &key)| (key, false, Action::Pick(i as u8))),
+        );
+
+        let buttons = vec![
+            (Button::DPadUp, Action::MoveCursor(0, -1)),
+            (Button::DPadDown, Action::MoveCursor(0, 1)),
+            (Button::DPadLeft, Action::MoveCursor(-1, 0)),
+            (Button::DPadRight, Action::MoveCursor(1, 0)),
+            (Button::South, Action::Confirm),
+            (Button::East, Action::Cancel),
+            (Button::North, Action::Hint),
+            (Button::Start, Action::Pause),
+            (Button::Select, Action::Settings),
+        ];
+
+        let ctrl_keys = vec![
+            (KeyCode::C, Action::CopyBoard),
+            (KeyCode::V, Action::PasteBoard),
+            (KeyCode::O, Action::OpenBoard),
+            (KeyCode::S, Action::ExportMoves),
+        ];
+
+        Self {
+            keys,
+            ctrl_keys,
+            buttons,
+        }
+    }
+}
+
+impl KeyMap {
+    // Shift 付きの割り当てがなければ Shift なしのものを使う。Ctrl 付きも同じ。
+    pub fn key(&self, keycode: KeyCode, keymods: KeyMods) -> Option<Action> {
+        if keymods.contains(KeyMods::CTRL) {
+            let action = self
+                .ctrl_keys
+                .iter()
+                .find(|&&(k, _)| k == keycode)
+                .map(|&(_, action)| action);
+            if action.is_some() {
+                return action;
+            }
+        }
+        let shift = keymods.contains(KeyMods::SHIFT);
+        let find = |shift: bool| {
+            self.keys
+                .iter()
+                .find(|&&(k, s, _)| k == keycode && s == shift)
+                .map(|&(_, _, action)| action)
+        };
+        find(shift).or_else(|| find(false))
+    }
+
+    pub fn button(&self, button: Button) -> Option<Action> {
+        self.buttons
+            .iter()
+            .find(|&&(b, _)| b == button)
+            .map(|&(_, action)| action)
+    }
+}
+
+// クリックは左ボタンだけ使う
+pub fn mouse_button(button: MouseButton, x: f32, y: f32) -> Option<Action> {
+    match button {
+        MouseButton::Left => Some(Action::Click(x, y)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let keys = KeyMap::default();
+        assert_eq!(keys.key(KeyCode::R, KeyMods::NONE), Some(Action::Reset));
+        assert_eq!(keys.key(KeyCode::R, KeyMods::SHIFT), Some(Action::Retry));
+        assert_eq!(
+            keys.key(KeyCode::Up, KeyMods::SHIFT),
+            Some(Action::MoveCursor(0, -1))
+        );
+        assert_eq!(
+            keys.key(KeyCode::Key7, KeyMods::NONE),
+            Some(Action::Pick(7))
+        );
+        assert_eq!(keys.key(KeyCode::F12, KeyMods::NONE), None);
+        assert_eq!(keys.key(KeyCode::C, KeyMods::CTRL), Some(Action::CopyBoard));
+        assert_eq!(keys.key(KeyCode::C, KeyMods::NONE), Some(Action::InputCode));
+        assert_eq!(
+            keys.key(KeyCode::V, KeyMods::CTRL),
+            Some(Action::PasteBoard)
+        );
+        assert_eq!(keys.key(KeyCode::R, KeyMods::CTRL), Some(Action::Reset));
+        assert_eq!(keys.button(Button::South), Some(Action::Confirm));
+        assert_eq!(mouse_button(MouseButton::Right, 1.0, 2.0), None);
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::monitor::{CancelToken, Monitor, Progress};
+
+// 投入した仕事の識別子
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+type Task<T> = Box<dyn FnOnce(&mut Monitor) -> T + Send>;
+
+struct Job<T> {
+    id: JobId,
+    cancel: CancelToken,
+    task: Task<T>,
+}
+
+enum Event<T> {
+    // 調べた局面の数と最良得点
+    Progress(JobId, u64, i32),
+    Done(JobId, T),
+}
+
+// 描画スレッドの外で計算する仕事の待ち行列。
+// 投入した順にワーカースレッドが取り出して実行し、結果は poll で受け取る。
+// 捨てると全ての仕事を取り消し、スレッドの終了を待つ。
+pub struct JobQueue<T> {
+    tx: Option<Sender<Job<T>>>,
+    rx: Receiver<Event<T>>,
+    threads: Vec<JoinHandle<()>>,
+    next_id: u64,
+    // 結果を待っている仕事と、その最後の途中経過
+    pending: HashMap<JobId, (CancelToken, Option<(u64, i32)>)>,
+}
+
+impl<T> fmt::Debug for JobQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JobQueue")
+            .field("threads", &self.threads.len())
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+impl<T: Send + 'static> JobQueue<T> {
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0);
+
+        let (tx, job_rx) = mpsc::channel::<Job<T>>();
+        let (event_tx, rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let threads = (0..threads)
+            .map(|_| {
+                let job_rx = Arc::clone(&job_rx);
+                let event_tx = event_tx.clone();
+                thread::spawn(move || Self::work(&job_rx, &event_tx))
+            })
+            .collect();
+
+        Self {
+            tx: Some(tx),
+            rx,
+            threads,
+            next_id: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    fn work(job_rx: &Mutex<Receiver<Job<T>>>, event_tx: &Sender<Event<T>>) {
+        loop {
+            // 送り手が捨てられたら終わる
+            let job = match job_rx.lock().expect("job queue poisoned").recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            };
+            if job.cancel.is_cancelled() {
+                continue;
+            }
+
+            let id = job.id;
+            let progress_tx = event_tx.clone();
+            let mut monitor =
+                Monitor::new(None)
+                    .cancel_on(job.cancel)
+                    .on_progress(move |p: &Progress| {
+                        // 受け手がいなくなっていても構わない
+                        let _ = progress_tx.send(Event::Progress(id, p.nodes, p.best.score));
+                    });
+            let res = (job.task)(&mut monitor);
+            let _ = event_tx.send(Event::Done(id, res));
+        }
+    }
+
+    pub fn submit<F>(&mut self, f: F) -> JobId
+    where
+        F: FnOnce(&mut Monitor) -> T + Send + 'static,
+    {
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+
+        let cancel = CancelToken::new();
+        self.pending.insert(id, (cancel.clone(), None));
+        let job = Job {
+            id,
+            cancel,
+            task: Box::new(f),
+        };
+        self.tx
+            .as_ref()
+            .expect("internal error")
+            .send(job)
+            .expect("worker threads are gone");
+        id
+    }
+
+    // 取り消した仕事の結果は poll で返さない
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some((cancel, _)) = self.pending.remove(&id) {
+            cancel.cancel();
+        }
+    }
+
+    pub fn cancel_all(&mut self) {
+        for (_, (cancel, _)) in self.pending.drain() {
+            cancel.cancel();
+        }
+    }
+
+    // 最後に届いた途中経過(調べた局面の数と最良得点)
+    pub fn progress(&self, id: JobId) -> Option<(u64, i32)> {
+        self.pending.get(&id).and_then(|&(_, progress)| progress)
+    }
+
+    // 終わった仕事の結果を、1 フレームで処理する分として最大 budget 個まで受け取る
+    pub fn poll(&mut self, budget: usize) -> Vec<(JobId, T)> {
+        let mut res = vec![];
+        while res.len() < budget {
+            match self.rx.try_recv() {
+                Ok(Event::Progress(id, nodes, score)) => {
+                    if let Some((_, progress)) = self.pending.get_mut(&id) {
+                        *progress = Some((nodes, score));
+                    }
+                }
+                Ok(Event::Done(id, out)) => {
+                    if self.pending.remove(&id).is_some() {
+                        res.push((id, out));
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        res
+    }
+}
+
+impl<T> Drop for JobQueue<T> {
+    fn drop(&mut self) {
+        for (_, (cancel, _)) in self.pending.drain() {
+            cancel.cancel();
+        }
+        self.tx = None;
+        for handle in self.threads.drain(..) {
+            if handle.join().is_err() {
+                eprintln!("worker thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn wait<T: Send + 'static>(jobs: &mut JobQueue<T>, n: usize) -> Vec<(JobId, T)> {
+        let mut res = vec![];
+        while res.len() < n {
+            res.extend(jobs.poll(1));
+            thread::sleep(Duration::from_millis(1));
+        }
+        res
+    }
+
+    #[test]
+    fn jobs() {
+        let mut jobs = JobQueue::new(2);
+        let a = jobs.submit(|_: &mut Monitor| 1);
+        let b = jobs.submit(|_: &mut Monitor| 2);
+
+        let mut res = wait(&mut jobs, 2);
+        res.sort_by_key(|&(_, x)| x);
+        assert_eq!(res, [(a, 1), (b, 2)]);
+        assert!(jobs.poll(10).is_empty());
+    }
+
+    #[test]
+    fn cancel() {
+        let mut jobs = JobQueue::new(1);
+        let slow = jobs.submit(|monitor: &mut Monitor| {
+            while !monitor.is_over() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            0
+        });
+        let fast = jobs.submit(|_: &mut Monitor| 1);
+        jobs.cancel(slow);
+
+        // 取り消した仕事の結果は返らない
+        assert_eq!(wait(&mut jobs, 1), [(fast, 1)]);
+
+        // 実行中の仕事があっても捨てれば止まる
+        jobs.submit(|monitor: &mut Monitor| {
+            while !monitor.is_over() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            2
+        });
+        drop(jobs);
+    }
+}
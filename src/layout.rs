@@ -0,0 +1,153 @@
+use ggez::graphics::Rect;
+
+// 領域のどこに寄せるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    // 横と縦の寄せ方。0 なら左(上)、0.5 なら中央、1 なら右(下)。
+    fn factors(self) -> (f32, f32) {
+        match self {
+            Self::TopLeft => (0.0, 0.0),
+            Self::TopRight => (1.0, 0.0),
+            Self::Center => (0.5, 0.5),
+            Self::BottomLeft => (0.0, 1.0),
+            Self::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+// 画面の一部に要素を寄せて置く。画面や盤面の大きさが変わっても同じ寄せ方で置き直せる。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    area: Rect,
+}
+
+impl Layout {
+    pub fn new(area: Rect) -> Self {
+        Self { area }
+    }
+
+    // 四方を padding だけ狭めた領域
+    pub fn padding(self, padding: f32) -> Self {
+        let Rect { x, y, w, h } = self.area;
+        let w = (w - 2.0 * padding).max(0.0);
+        let h = (h - 2.0 * padding).max(0.0);
+        Self::new(Rect::new(x + padding, y + padding, w, h))
+    }
+
+    // 大きさ w x h の要素を anchor に寄せて置いた位置
+    pub fn place(&self, anchor: Anchor, w: f32, h: f32) -> Rect {
+        let (fx, fy) = anchor.factors();
+        let x = self.area.x + (self.area.w - w) * fx;
+        let y = self.area.y + (self.area.h - h) * fy;
+        Rect::new(x, y, w, h)
+    }
+
+    // 高さ line_height の行を n 行積んだものを anchor に寄せて置く
+    pub fn stack(&self, anchor: Anchor, w: f32, line_height: f32, n: usize) -> Stack {
+        let rect = self.place(anchor, w, line_height * n as f32);
+        Stack { rect, line_height }
+    }
+}
+
+// ウィンドウ上の位置(マウスの座標)を画面の座標に直す。
+// 全画面で解像度が変わり、ウィンドウと画面の大きさが違っても合うようにする。
+pub fn window_to_screen(window: (f32, f32), screen: Rect, x: f32, y: f32) -> (f32, f32) {
+    let (w, h) = window;
+    if w <= 0.0 || h <= 0.0 {
+        return (x, y);
+    }
+    (screen.x + x * screen.w / w, screen.y + y * screen.h / h)
+}
+
+// 同じ高さの行を上から順に積んだもの
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stack {
+    rect: Rect,
+    line_height: f32,
+}
+
+impl Stack {
+    pub fn x(&self) -> f32 {
+        self.rect.x
+    }
+
+    pub fn width(&self) -> f32 {
+        self.rect.w
+    }
+
+    // i 行目の上端
+    pub fn row(&self, i: usize) -> f32 {
+        self.rect.y + self.line_height * i as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place() {
+        let layout = Layout::new(Rect::new(0.0, 320.0, 640.0, 160.0)).padding(10.0);
+        assert_eq!(
+            layout.place(Anchor::TopLeft, 100.0, 20.0),
+            Rect::new(10.0, 330.0, 100.0, 20.0)
+        );
+        assert_eq!(
+            layout.place(Anchor::TopRight, 100.0, 20.0),
+            Rect::new(530.0, 330.0, 100.0, 20.0)
+        );
+        assert_eq!(
+            layout.place(Anchor::Center, 100.0, 20.0),
+            Rect::new(270.0, 390.0, 100.0, 20.0)
+        );
+        assert_eq!(
+            layout.place(Anchor::BottomLeft, 100.0, 20.0),
+            Rect::new(10.0, 450.0, 100.0, 20.0)
+        );
+
+        // 狭すぎる領域は大きさ 0 になる
+        let tiny = Layout::new(Rect::new(0.0, 0.0, 10.0, 10.0)).padding(10.0);
+        assert_eq!(
+            tiny.place(Anchor::TopLeft, 0.0, 0.0),
+            Rect::new(10.0, 10.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn stack() {
+        let layout = Layout::new(Rect::new(0.0, 0.0, 640.0, 480.0));
+        let bottom = layout.stack(Anchor::BottomLeft, 200.0, 17.0, 2);
+        assert_eq!((bottom.x(), bottom.width()), (0.0, 200.0));
+        assert_eq!(bottom.row(0), 446.0);
+        assert_eq!(bottom.row(1), 463.0);
+
+        let top = layout.stack(Anchor::TopRight, 100.0, 20.0, 3);
+        assert_eq!(top.x(), 540.0);
+        assert_eq!(top.row(2), 40.0);
+    }
+
+    #[test]
+    fn window_to_screen() {
+        let screen = Rect::new(0.0, 0.0, 640.0, 480.0);
+        assert_eq!(
+            super::window_to_screen((640.0, 480.0), screen, 10.0, 20.0),
+            (10.0, 20.0)
+        );
+        assert_eq!(
+            super::window_to_screen((1280.0, 960.0), screen, 100.0, 200.0),
+            (50.0, 100.0)
+        );
+        assert_eq!(
+            super::window_to_screen((0.0, 0.0), screen, 1.0, 2.0),
+            (1.0, 2.0)
+        );
+    }
+}
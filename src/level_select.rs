@@ -0,0 +1,91 @@
+// レベル選択画面の状態。遊べるのは到達済みのレベルまで。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelSelect {
+    selected: u32,
+    furthest: u32,
+}
+
+impl LevelSelect {
+    pub const COLUMNS: u32 = 6;
+    pub const ROWS: u32 = 4;
+    const PAGE: u32 = Self::COLUMNS * Self::ROWS;
+
+    // last: レベルに限りがあれば最後のレベル
+    pub fn new(furthest: u32, last: Option<u32>) -> Self {
+        let furthest = match last {
+            Some(last) => furthest.min(last),
+            None => furthest,
+        };
+        assert!(furthest > 0);
+        Self {
+            selected: furthest,
+            furthest,
+        }
+    }
+
+    pub fn selected(&self) -> u32 {
+        self.selected
+    }
+
+    pub fn furthest(&self) -> u32 {
+        self.furthest
+    }
+
+    // 選択を dx, dy マスだけ動かす。範囲外には出ない。
+    pub fn move_by(&mut self, dx: i32, dy: i32) {
+        let delta = i64::from(dx) + i64::from(dy) * i64::from(Self::COLUMNS);
+        let selected = (i64::from(self.selected) + delta)
+            .max(1)
+            .min(i64::from(self.furthest));
+        self.selected = selected as u32;
+    }
+
+    // 表示中のページに並ぶレベル(行優先)
+    pub fn page(&self) -> impl Iterator<Item = u32> {
+        let first = (self.selected - 1) / Self::PAGE * Self::PAGE + 1;
+        let last = (first + Self::PAGE - 1).min(self.furthest);
+        first..=last
+    }
+
+    // 表示中のページの (col, row) にあるレベル
+    pub fn at(&self, col: u32, row: u32) -> Option<u32> {
+        if col >= Self::COLUMNS || row >= Self::ROWS {
+            return None;
+        }
+        self.page().nth((row * Self::COLUMNS + col) as usize)
+    }
+
+    // レベルの表示位置 (col, row)
+    pub fn position(number: u32) -> (u32, u32) {
+        let i = (number - 1) % Self::PAGE;
+        (i % Self::COLUMNS, i / Self::COLUMNS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_select() {
+        let mut ls = LevelSelect::new(30, None);
+        assert_eq!(ls.selected(), 30);
+        assert_eq!(ls.page().collect::<Vec<_>>(), (25..=30).collect::<Vec<_>>());
+        assert_eq!(ls.at(1, 0), Some(26));
+        assert_eq!(ls.at(0, 1), None);
+
+        ls.move_by(1, 0);
+        assert_eq!(ls.selected(), 30);
+        ls.move_by(0, -1);
+        assert_eq!(ls.selected(), 24);
+        assert_eq!(ls.page().count(), 24);
+        assert_eq!(LevelSelect::position(24), (5, 3));
+        ls.move_by(0, -100);
+        assert_eq!(ls.selected(), 1);
+
+        // 全部クリアしても最後のレベルまで
+        let ls = LevelSelect::new(11, Some(10));
+        assert_eq!(ls.selected(), 10);
+        assert_eq!(ls.furthest(), 10);
+    }
+}
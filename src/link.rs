@@ -0,0 +1,101 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+
+use crate::board::Board;
+use crate::challenge::Challenge;
+
+// チャットなどに貼る起動用のリンク。
+//
+// ```
+// samegame://play?code=<Challenge のコード>
+// samegame://play?board=<Board::to_bytes を URL-safe base64 (パディングなし) にしたもの>
+// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    Challenge(Challenge),
+    Board(Board),
+}
+
+impl Link {
+    const PREFIX: &'static str = "samegame://play?";
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let query = s
+            .trim()
+            .strip_prefix(Self::PREFIX)
+            .with_context(|| format!("link must start with {}", Self::PREFIX))?;
+        // 知らない項目は読み飛ばす
+        for param in query.split('&') {
+            let mut kv = param.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some("code"), Some(code)) => return Ok(Self::Challenge(Challenge::decode(code)?)),
+                (Some("board"), Some(board)) => {
+                    let buf = base64::decode_config(board, base64::URL_SAFE_NO_PAD)
+                        .context("invalid board in link")?;
+                    return Ok(Self::Board(Board::from_bytes(&buf)?));
+                }
+                _ => {}
+            }
+        }
+        anyhow::bail!("link has neither code nor board")
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // コードから読んだ Challenge なので必ずコードに戻せる
+            Self::Challenge(challenge) => write!(
+                f,
+                "{}code={}",
+                Self::PREFIX,
+                challenge.encode().ok_or(fmt::Error)?
+            ),
+            Self::Board(board) => write!(
+                f,
+                "{}board={}",
+                Self::PREFIX,
+                base64::encode_config(board.to_bytes(), base64::URL_SAFE_NO_PAD)
+            ),
+        }
+    }
+}
+
+impl FromStr for Link {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::game::GameConfig;
+
+    #[test]
+    fn roundtrip() {
+        let link = Link::Challenge(Challenge {
+            config: GameConfig::default(),
+            seed: 42,
+        });
+        assert_eq!(link.to_string().parse::<Link>().unwrap(), link);
+
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let link = Link::Board(board);
+        let uri = link.to_string();
+        assert!(uri.starts_with("samegame://play?board="));
+        assert_eq!(uri.parse::<Link>().unwrap(), link);
+
+        let uri = format!("samegame://play?from=chat&{}", &uri[Link::PREFIX.len()..]);
+        assert_eq!(uri.parse::<Link>().unwrap(), link);
+
+        assert!("https://example.com/?board=AAA".parse::<Link>().is_err());
+        assert!("samegame://play?from=chat".parse::<Link>().is_err());
+        assert!("samegame://play?board=!!".parse::<Link>().is_err());
+    }
+}
@@ -1,23 +1,310 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
 use ggez::conf;
 use ggez::event;
+use ggez::graphics;
 use ggez::ContextBuilder;
+use structopt::StructOpt;
 
+mod analysis;
+mod anim;
+mod arena;
+mod audio;
+mod backdrop;
+mod batch;
+mod beam_stack;
 mod board;
+mod challenge;
+mod cluster;
+mod compare;
+mod crt;
+mod curriculum;
+mod dfbnb;
+mod dialog;
+mod difficulty;
+mod editor;
+mod eval;
 mod font;
+mod game;
 mod game_state;
+mod generator;
+mod hash;
+mod highlight;
+mod highscore;
+mod hint;
+mod input;
+mod jobs;
+mod layout;
+mod level_select;
+mod link;
+mod mcts;
+mod monitor;
+mod movelist;
+mod movelog;
+mod narrator;
+mod notation;
+mod nrpa;
+mod opponent;
+mod pack;
+mod personal_best;
+mod policy;
+mod powerup;
+mod profile;
+mod profiling;
+mod puzzle;
+mod rating;
+mod recent;
+mod reference;
+mod replay;
+mod rotation;
+mod rules;
+mod rumble;
+mod rush;
+mod scene;
+mod settings;
+mod sim;
+mod snapshot;
+mod solver;
+mod speedrun;
+mod stats;
+mod storage;
+mod suspend;
+mod tactics;
+mod telemetry;
+mod tiles;
+mod tournament;
+mod tt;
+mod tutorial;
+mod unlock;
+mod variation;
+mod widget;
 
+use crate::challenge::Challenge;
+use crate::curriculum::Campaign;
+use crate::game::GameConfig;
 use crate::game_state::GameState;
+use crate::link::Link;
+use crate::narrator::CommandNarrator;
+use crate::opponent::Opponent;
+use crate::pack::Pack;
+use crate::profile::Profile;
+use crate::puzzle::Puzzle;
+use crate::replay::Ghost;
+use crate::scene::{LevelMenu, Playing, ReplayViewer, Scene, SceneStack, Title};
+use crate::settings::Settings;
+use crate::sim::SystemClock;
+use crate::storage::FsBackend;
+use crate::tactics::Tactics;
+use crate::tournament::Tournament;
+
+#[derive(Debug, StructOpt)]
+struct Opt {
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+
+    /// Play the board in a samegame://play?... link
+    #[structopt(conflicts_with_all = &["challenge", "seed", "tournament", "pack"])]
+    link: Option<Link>,
+
+    /// Player profile to use; each profile keeps its own scores and saves
+    #[structopt(long, default_value = Profile::DEFAULT)]
+    profile: Profile,
+
+    /// List existing profiles and exit
+    #[structopt(long)]
+    list_profiles: bool,
+
+    /// Play the challenge given as a shared code
+    #[structopt(long, conflicts_with = "seed")]
+    challenge: Option<Challenge>,
+
+    /// Seed of the board (with the default configuration)
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// Play the boards listed in the file (one seed or code per line) back-to-back
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["challenge", "seed"])]
+    tournament: Option<PathBuf>,
+
+    /// Where to write the tournament results [default: <tournament>.summary.csv]
+    #[structopt(long, parse(from_os_str), requires = "tournament")]
+    summary: Option<PathBuf>,
+
+    /// Play the puzzle pack in the file (one seed or code per line, optionally followed by
+    /// the scores needed for 1, 2 and 3 stars)
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["challenge", "seed", "tournament"])]
+    pack: Option<PathBuf>,
+
+    /// Step through the move list in the file (one move per line, as "c4" or "x y") on the
+    /// chosen board
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["tournament", "pack"])]
+    moves: Option<PathBuf>,
+
+    /// Read scores, hints and the end of the game aloud by piping them to this command
+    /// (e.g. "espeak --stdin")
+    #[structopt(long)]
+    narrate: Option<CommandNarrator>,
+
+    /// Race a computer opponent shown as a ghost (easy, medium, hard or expert)
+    #[structopt(long)]
+    opponent: Option<Opponent>,
+
+    /// Solve the "find the best move" puzzles in the file (made with the mine-puzzles command)
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["tournament", "pack", "moves"])]
+    tactics: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Cmd {
+    /// Play seeded boards headlessly with a policy and print statistics
+    Sim(batch::SimOpt),
+
+    /// Compare solvers on the same boards under equal time budgets
+    Compare(compare::CompareOpt),
+
+    /// Check every move of a replay or move list and print the score or the first illegal move
+    VerifyReplay(movelist::VerifyOpt),
+
+    /// Find positions where one move is far better than the rest and save them as puzzles
+    MinePuzzles(puzzle::MineOpt),
+
+    /// Split a board into tasks, hand them to workers over TCP and keep the best line
+    Coordinate(cluster::CoordinatorOpt),
+
+    /// Solve tasks from a coordinator until it has none left
+    Work(cluster::WorkerOpt),
+
+    /// Write out the play telemetry recorded for a profile (turned on in Settings)
+    ExportTelemetry(telemetry::ExportOpt),
+}
 
 fn main() -> Result<()> {
+    let result = run();
+    // profiling フィーチャーで作ったときは、測った時間を終わりにまとめて出す
+    if profiling::ENABLED {
+        eprint!("{}", profiling::summary());
+    }
+    result
+}
+
+fn run() -> Result<()> {
+    let opt = Opt::from_args();
+    match &opt.cmd {
+        Some(Cmd::Sim(sim)) => return batch::run(sim),
+        Some(Cmd::Compare(compare)) => return compare::run(compare),
+        Some(Cmd::VerifyReplay(verify)) => return movelist::run(verify),
+        Some(Cmd::MinePuzzles(mine)) => return puzzle::run(mine),
+        Some(Cmd::Coordinate(coordinator)) => return cluster::run_coordinator(coordinator),
+        Some(Cmd::Work(worker)) => return cluster::run_worker(worker),
+        Some(Cmd::ExportTelemetry(export)) => return telemetry::run_export(export),
+        None => {}
+    }
+
+    let backend = FsBackend::new(storage::data_dir()?);
+    if opt.list_profiles {
+        for name in Profile::list(&backend)? {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    let (link_challenge, link_board) = match &opt.link {
+        Some(Link::Challenge(challenge)) => (Some(*challenge), None),
+        Some(Link::Board(board)) => (None, Some(board.clone())),
+        None => (None, None),
+    };
+    let challenge = opt.challenge.or(link_challenge).or_else(|| {
+        opt.seed.map(|seed| Challenge {
+            config: GameConfig::default(),
+            seed,
+        })
+    });
+    let tournament = match &opt.tournament {
+        Some(path) => {
+            let summary = opt
+                .summary
+                .clone()
+                .unwrap_or_else(|| path.with_extension("summary.csv"));
+            Some((Tournament::load(path)?, summary))
+        }
+        None => None,
+    };
+    let puzzles = match &opt.tactics {
+        Some(path) => Some(Puzzle::load(path)?),
+        None => None,
+    };
+    let campaign = match &opt.pack {
+        Some(path) => Campaign::Pack(Pack::load(path)?),
+        None => Campaign::Generated,
+    };
+
+    // 前回のウィンドウの大きさと位置で開く
+    let prefs = Settings::load(&backend, &Settings::key(&opt.profile)).unwrap_or_else(|e| {
+        eprintln!("cannot load settings: {:#}", e);
+        Settings::default()
+    });
     let cb = ContextBuilder::new("samegame", "author")
-        .window_setup(conf::WindowSetup::default().title("samegame"))
-        .window_mode(conf::WindowMode::default().dimensions(640.0, 480.0))
+        .window_setup(conf::WindowSetup::default().title(&format!("samegame [{}]", opt.profile)))
+        .window_mode(prefs.window_mode())
         .add_resource_path(concat!(env!("CARGO_MANIFEST_DIR"), "/asset"));
 
+    // 遊ぶ盤面を指定されていなければタイトルから始める
+    let title = challenge.is_none()
+        && link_board.is_none()
+        && tournament.is_none()
+        && puzzles.is_none()
+        && matches!(campaign, Campaign::Generated);
+    let pack = matches!(campaign, Campaign::Pack(_));
     let (mut ctx, mut events_loop) = cb.build()?;
-    let mut state = GameState::new(&mut ctx)?;
-    event::run(&mut ctx, &mut events_loop, &mut state)?;
+    // ggez 0.5 の WindowMode には位置がないので、開いてから前回の位置に動かす
+    if let (Some(position), false) = (prefs.window_position, prefs.fullscreen) {
+        graphics::window(&ctx).set_position(position.into());
+    }
+    let mut state = GameState::new(
+        &mut ctx,
+        Box::new(SystemClock::new()),
+        Box::new(backend),
+        opt.profile,
+        challenge,
+        tournament,
+        campaign,
+    )?;
+    if let Some(narrator) = opt.narrate {
+        state.set_narrator(Box::new(narrator));
+    }
+    if let Some(board) = link_board {
+        state.play_board(board);
+    }
+    if let Some(level) = opt.opponent {
+        state.set_opponent(Some(level));
+    }
+    let replay = match &opt.moves {
+        Some(path) => {
+            let moves = movelist::load(path, state.game().board().height())?;
+            let replay = movelist::to_replay(state.game(), &moves);
+            movelist::verify(&replay)?;
+            Some(replay)
+        }
+        None => None,
+    };
+    let tactics = puzzles.map(|puzzles| Tactics::new(&mut state, puzzles));
+    let first: Box<dyn Scene> = if title && replay.is_none() {
+        Box::new(Title::new(&state))
+    } else if pack {
+        // パックを遊ぶときはレベル選択から始める
+        Box::new(Playing::then(Box::new(LevelMenu::new(&state))))
+    } else {
+        Box::new(Playing::default())
+    };
+    let mut scenes = SceneStack::new(state, first);
+    if let Some(replay) = replay {
+        scenes.push(Box::new(ReplayViewer::stepping(Ghost::new(replay))));
+    }
+    if let Some(tactics) = tactics {
+        scenes.push(Box::new(tactics));
+    }
+    event::run(&mut ctx, &mut events_loop, &mut scenes)?;
 
     Ok(())
 }
@@ -0,0 +1,251 @@
+use std::fmt;
+
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::arena::{Arena, ArenaStats, Span};
+use crate::board::Board;
+use crate::monitor::Monitor;
+use crate::policy::RandomPolicy;
+use crate::profiling;
+use crate::rules;
+use crate::sim::GameRng;
+use crate::solver::{self, Solution};
+
+// 子ノードを選ぶ式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    // 平均 + c * sqrt(ln N / n)
+    Uct { c: f64 },
+    // UCT に分散の項 sqrt((Σx² - n * 平均² + d) / n) を足したもの (Schadd et al.)。
+    // 得点のばらつきが大きい手を優先して調べる。
+    SpMcts { c: f64, d: f64 },
+}
+
+impl Selection {
+    pub const DEFAULT_UCT: Self = Self::Uct { c: 0.5 };
+    pub const DEFAULT_SP_MCTS: Self = Self::SpMcts { c: 0.5, d: 10000.0 };
+
+    fn value(self, node: &Node, ln_parent: f64) -> f64 {
+        let n = f64::from(node.visits);
+        let mean = node.sum / n;
+        let explore = (ln_parent / n).sqrt();
+        match self {
+            Self::Uct { c } => mean + c * explore,
+            Self::SpMcts { c, d } => {
+                let variance = (node.sum_sq - n * mean * mean + d) / n;
+                mean + c * explore + variance.max(0.0).sqrt()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Uct { c } => write!(f, "uct:{}", c),
+            Self::SpMcts { c, d } => write!(f, "sp:{}:{}", c, d),
+        }
+    }
+}
+
+// 手と、その手で行く子ノードの添字。まだ展開していない手の child は使わない。
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    mv: (usize, usize),
+    child: usize,
+}
+
+#[derive(Debug)]
+struct Node {
+    // このノードからの手。先頭の expanded 個は展開済みで、残りはまだ試していない。
+    edges: Span,
+    expanded: usize,
+    visits: u32,
+    // このノードを通ったプレイアウトの最終得点の和と二乗和
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl Node {
+    fn new(groups: Vec<((usize, usize), usize)>, edges: &mut Arena<Edge>) -> Self {
+        let moves = groups.into_iter().map(|(mv, _)| Edge { mv, child: 0 });
+        Self {
+            edges: edges.alloc_all(moves),
+            expanded: 0,
+            visits: 0,
+            sum: 0.0,
+            sum_sq: 0.0,
+        }
+    }
+}
+
+fn play(board: &mut Board, line: &mut Solution, (x, y): (usize, usize)) {
+    let n = board.erase_component(x, y);
+    line.moves.push((x, y));
+    line.score += rules::group_score(n);
+}
+
+// モンテカルロ木探索。プレイアウトはランダム。
+#[derive(Debug)]
+pub struct Mcts<'a> {
+    board: &'a Board,
+    selection: Selection,
+    rng: Pcg32,
+    nodes: Arena<Node>,
+    edges: Arena<Edge>,
+    // これまでのプレイアウトで最も良かった手順
+    best: Solution,
+    // 1 回のプレイアウトで使う盤面・手順・通ったノード。毎回確保し直さないよう使い回す。
+    scratch: Board,
+    line: Solution,
+    path: Vec<usize>,
+}
+
+impl<'a> Mcts<'a> {
+    const INITIAL_NODES: usize = 1 << 12;
+
+    pub fn new(board: &'a Board, selection: Selection, seed: u64) -> Self {
+        let mut nodes = Arena::with_capacity(Self::INITIAL_NODES);
+        let mut edges = Arena::with_capacity(Self::INITIAL_NODES * 8);
+        nodes.alloc(Node::new(board.distinct_groups(), &mut edges));
+        Self {
+            board,
+            selection,
+            rng: Pcg32::seed_from_u64(seed),
+            nodes,
+            edges,
+            best: Solution::default(),
+            scratch: board.clone(),
+            line: Solution::default(),
+            path: vec![],
+        }
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        self.nodes.stats().merge(self.edges.stats())
+    }
+
+    // monitor の期限が来るか iterations 回プレイアウトするまで探索する
+    pub fn search(&mut self, monitor: &mut Monitor, iterations: Option<usize>) -> Solution {
+        let mut i = 0;
+        while !monitor.is_over() {
+            match iterations {
+                Some(iterations) if i >= iterations => break,
+                _ => {}
+            }
+            self.iterate();
+            monitor.set_arena(self.stats());
+            monitor.visit(1, &self.best);
+            i += 1;
+        }
+        self.best.clone()
+    }
+
+    fn iterate(&mut self) {
+        self.scratch.clone_from(self.board);
+        self.line.moves.clear();
+        self.line.score = 0;
+        self.path.clear();
+        self.path.push(0);
+
+        // 選択
+        let scope = profiling::scope("mcts::select");
+        loop {
+            let node = &self.nodes[*self.path.last().expect("internal error")];
+            if node.expanded < node.edges.len() || node.edges.is_empty() {
+                break;
+            }
+            let edge = self.select(node);
+            play(&mut self.scratch, &mut self.line, edge.mv);
+            self.path.push(edge.child);
+        }
+
+        drop(scope);
+
+        // 展開
+        let scope = profiling::scope("mcts::expand");
+        let leaf = *self.path.last().expect("internal error");
+        let (edges, expanded) = (self.nodes[leaf].edges, self.nodes[leaf].expanded);
+        let untried = edges.len() - expanded;
+        if untried > 0 {
+            let i = expanded + self.rng.below(untried as u32) as usize;
+            self.edges.slice_mut(edges).swap(expanded, i);
+            let mv = self.edges.slice(edges)[expanded].mv;
+            play(&mut self.scratch, &mut self.line, mv);
+
+            let node = Node::new(self.scratch.groups(), &mut self.edges);
+            let child = self.nodes.alloc(node);
+            self.edges.slice_mut(edges)[expanded].child = child;
+            self.nodes[leaf].expanded += 1;
+            self.path.push(child);
+        }
+
+        drop(scope);
+
+        // プレイアウト
+        let seed = u64::from(self.rng.next_u32());
+        let mut policy = RandomPolicy::new(Pcg32::seed_from_u64(seed));
+        solver::rollout_in_place(&mut self.scratch, &mut policy, &mut self.line);
+
+        // 逆伝播
+        let _scope = profiling::scope("mcts::backprop");
+        let x = f64::from(self.line.score);
+        for &i in &self.path {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            node.sum += x;
+            node.sum_sq += x * x;
+        }
+
+        if self.line.score > self.best.score {
+            self.best.clone_from(&self.line);
+        }
+    }
+
+    fn select(&self, node: &Node) -> Edge {
+        let ln_parent = f64::from(node.visits).ln();
+        self.edges.slice(node.edges)[..node.expanded]
+            .iter()
+            .map(|&edge| {
+                let value = self.selection.value(&self.nodes[edge.child], ln_parent);
+                (value, edge)
+            })
+            .max_by(|a, b| a.0.partial_cmp(&b.0).expect("NaN in selection"))
+            .map(|(_, edge)| edge)
+            .expect("internal error")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mcts() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        for &selection in &[Selection::DEFAULT_UCT, Selection::DEFAULT_SP_MCTS] {
+            let solution =
+                Mcts::new(&board, selection, 0).search(&mut Monitor::new(None), Some(50));
+            assert_eq!(solution.score, 9 + 1);
+        }
+
+        let mut rng = Pcg32::seed_from_u64(5);
+        let board = Board::random_with(6, 5, 3, &mut rng);
+        let a = Mcts::new(&board, Selection::DEFAULT_SP_MCTS, 1)
+            .search(&mut Monitor::new(None), Some(200));
+        let b = Mcts::new(&board, Selection::DEFAULT_SP_MCTS, 1)
+            .search(&mut Monitor::new(None), Some(200));
+        assert_eq!(a, b);
+        assert!(a.score > 0);
+
+        // 1 回のプレイアウトで展開するノードは高々 1 つ
+        let mut mcts = Mcts::new(&board, Selection::DEFAULT_UCT, 1);
+        mcts.search(&mut Monitor::new(None), Some(100));
+        let nodes = mcts.nodes.stats().items;
+        assert!(nodes <= 101);
+        let stats = mcts.stats();
+        assert_eq!(stats.items, nodes + mcts.edges.stats().items);
+        assert_eq!(stats.grows, 0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::arena::ArenaStats;
+use crate::solver::Solution;
+use crate::tt::TableStats;
+
+// 探索の途中経過
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress<'a> {
+    // これまでに調べた局面(ソルバーによってはプレイアウト)の数
+    pub nodes: u64,
+    pub best: &'a Solution,
+    pub elapsed: Duration,
+    // 期限までの時間のうち経った割合。期限がなければ None。
+    pub fraction: Option<f64>,
+    // 置換表を使うソルバーならその使われ方
+    pub table: Option<TableStats>,
+    // 探索木を Arena に積むソルバーならその大きさ
+    pub arena: Option<ArenaStats>,
+}
+
+// 探索を外から止めるためのフラグ。複製したものは同じフラグを指す。
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+type Callback<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+// 探索の期限を持ち、途中経過をコールバックに知らせる
+pub struct Monitor<'a> {
+    start: Instant,
+    deadline: Option<Instant>,
+    cancel: Option<CancelToken>,
+    callback: Option<Callback<'a>>,
+    nodes: u64,
+    table: Option<TableStats>,
+    arena: Option<ArenaStats>,
+    // 最後に知らせた時刻と最良得点
+    reported: Option<(Instant, i32)>,
+}
+
+impl fmt::Debug for Monitor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Monitor")
+            .field("deadline", &self.deadline)
+            .field("nodes", &self.nodes)
+            .finish()
+    }
+}
+
+impl<'a> Monitor<'a> {
+    // 最良が変わらなくてもこの間隔で知らせる
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn new(deadline: Option<Instant>) -> Self {
+        Self {
+            start: Instant::now(),
+            deadline,
+            cancel: None,
+            callback: None,
+            nodes: 0,
+            table: None,
+            arena: None,
+            reported: None,
+        }
+    }
+
+    // 期限を後から決め直す
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    // token が取り消されたら期限前でも探索をやめる
+    pub fn cancel_on(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn on_progress<F: FnMut(&Progress) + 'a>(mut self, f: F) -> Self {
+        self.callback = Some(Box::new(f));
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        match &self.cancel {
+            Some(token) => token.is_cancelled(),
+            None => false,
+        }
+    }
+
+    // 次に知らせる途中経過に置換表の使われ方を添える
+    pub fn set_table(&mut self, stats: TableStats) {
+        self.table = Some(stats);
+    }
+
+    // 次に知らせる途中経過に探索木の大きさを添える
+    pub fn set_arena(&mut self, stats: ArenaStats) {
+        self.arena = Some(stats);
+    }
+
+    // 探索をやめるべきか
+    pub fn is_over(&self) -> bool {
+        if self.is_cancelled() {
+            return true;
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    // nodes 個の局面を調べ、今の最良が best だった。
+    // 最良が良くなったときと一定時間ごとにコールバックを呼ぶ。
+    pub fn visit(&mut self, nodes: u64, best: &Solution) {
+        self.nodes += nodes;
+        if self.callback.is_none() {
+            return;
+        }
+
+        let now = Instant::now();
+        let due = match self.reported {
+            Some((at, score)) => best.score > score || now >= at + Self::INTERVAL,
+            None => true,
+        };
+        if !due {
+            return;
+        }
+        self.reported = Some((now, best.score));
+
+        let elapsed = now - self.start;
+        let start = self.start;
+        let fraction = self.deadline.map(|deadline| {
+            let total = deadline.saturating_duration_since(start).as_secs_f64();
+            if total > 0.0 {
+                (elapsed.as_secs_f64() / total).min(1.0)
+            } else {
+                1.0
+            }
+        });
+        let callback = self.callback.as_mut().expect("internal error");
+        callback(&Progress {
+            nodes: self.nodes,
+            best,
+            elapsed,
+            fraction,
+            table: self.table,
+            arena: self.arena,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitor() {
+        let mut log = vec![];
+        {
+            let mut monitor = Monitor::new(None).on_progress(|p: &Progress| {
+                log.push((p.nodes, p.best.score, p.fraction));
+            });
+            let sol = |score| Solution {
+                moves: vec![],
+                score,
+            };
+            monitor.visit(1, &sol(0));
+            monitor.visit(5, &sol(0));
+            monitor.visit(2, &sol(4));
+            assert!(!monitor.is_over());
+        }
+        // 最良が変わらない間はすぐには知らせない
+        assert_eq!(log, [(1, 0, None), (8, 4, None)]);
+
+        let mut monitor = Monitor::new(Some(Instant::now()));
+        assert!(monitor.is_over());
+        monitor.set_deadline(None);
+        assert!(!monitor.is_over());
+    }
+
+    #[test]
+    fn cancel() {
+        let token = CancelToken::new();
+        let monitor = Monitor::new(None).cancel_on(token.clone());
+        assert!(!monitor.is_over());
+        token.clone().cancel();
+        assert!(monitor.is_over());
+        assert!(monitor.is_cancelled());
+    }
+}
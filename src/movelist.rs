@@ -0,0 +1,282 @@
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use crate::board::Board;
+use crate::challenge::Challenge;
+use crate::game::{Game, GameConfig};
+use crate::notation;
+use crate::replay::{Action, Replay};
+use crate::storage;
+
+// 手順の 1 手
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub pos: (usize, usize),
+    // 手と同じ行の '#' から後ろに書いた注釈
+    pub comment: Option<String>,
+}
+
+// 手書きの手順。1 行に 1 手、"c4" のような表記か、盤面の座標 (左上が 0 0) を "x y" か "x,y" で書く。
+// 表記に添えた色は読む人のためのもので確かめない。h は盤面の高さ。
+// 空行と '#' だけの行は読み飛ばし、手の後ろの '#' から後ろはその手の注釈にする。
+pub fn parse<R: BufRead>(rdr: R, h: usize) -> Result<Vec<Move>> {
+    let mut moves = vec![];
+    for (i, line) in rdr.lines().enumerate() {
+        let line = line?;
+        let mut parts = line.splitn(2, '#');
+        let line = parts.next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let comment = parts
+            .next()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned);
+
+        let pos = if line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let (_, pos) =
+                notation::parse_move(line, h).with_context(|| format!("line {}", i + 1))?;
+            pos
+        } else {
+            let coords: Vec<_> = line
+                .split(|c: char| c == ',' || c.is_ascii_whitespace())
+                .filter(|s| !s.is_empty())
+                .map(str::parse::<usize>)
+                .collect();
+            match coords.as_slice() {
+                [Ok(x), Ok(y)] => (*x, *y),
+                _ => anyhow::bail!("line {}: expected \"x y\"", i + 1),
+            }
+        };
+        moves.push(Move { pos, comment });
+    }
+    Ok(moves)
+}
+
+pub fn load<P: AsRef<Path>>(path: P, h: usize) -> Result<Vec<Move>> {
+    let path = path.as_ref();
+    let file = fs::File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+    parse(std::io::BufReader::new(file), h)
+}
+
+// 画面で追えるよう、1 手ずつ間を空けた記録にする。注釈はクリックに付ける。
+pub fn to_replay(game: &Game, moves: &[Move]) -> Replay {
+    const STEP: Duration = Duration::from_millis(600);
+    let mut replay = Replay::new(game);
+    for (i, mv) in moves.iter().enumerate() {
+        let (x, y) = mv.pos;
+        let t = STEP * (i as u32 + 1);
+        replay.push(t - STEP / 2, Action::Cursor(x, y));
+        replay.push(t, Action::Click(x, y));
+        if let Some(comment) = &mv.comment {
+            replay.annotate(comment);
+        }
+    }
+    replay
+}
+
+// カーソルの移動以外の全ての手が盤面を変えることを確かめ、指し終えたゲームを返す。
+// 最初の不正な手をエラーにする。
+pub fn verify(replay: &Replay) -> Result<Game> {
+    let mut game = replay.new_game();
+    let moves = replay
+        .events
+        .iter()
+        .filter(|ev| !matches!(ev.action, Action::Cursor(..)));
+    for (i, ev) in moves.enumerate() {
+        let n = i + 1;
+        if let Action::Click(x, y) = ev.action {
+            let (w, h) = (game.board().width(), game.board().height());
+            anyhow::ensure!(
+                x < w && y < h,
+                "move {} ({}, {}) is outside the {}x{} board",
+                n,
+                x,
+                y,
+                w,
+                h
+            );
+        }
+        if !ev.action.apply(&mut game, ev.t) {
+            match ev.action {
+                Action::Click(x, y) => anyhow::bail!("move {} ({}, {}) is illegal", n, x, y),
+                action => anyhow::bail!("move {} ({:?}) is illegal", n, action),
+            }
+        }
+    }
+    Ok(game)
+}
+
+#[derive(Debug, StructOpt)]
+pub struct VerifyOpt {
+    /// Replay saved by the game, or a move list (one move per line, as "c4" with a1 at the
+    /// bottom left or as "x y" with 0 0 at the top left, optionally followed by "# comment")
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+
+    /// Board file to play a move list on
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["challenge", "seed"])]
+    board: Option<PathBuf>,
+
+    /// Challenge code of the board
+    #[structopt(long, conflicts_with = "seed")]
+    challenge: Option<Challenge>,
+
+    /// Seed of the board (with the default configuration)
+    #[structopt(long)]
+    seed: Option<u64>,
+}
+
+impl VerifyOpt {
+    fn game(&self) -> Result<Game> {
+        if let Some(path) = &self.board {
+            let file =
+                fs::File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+            let board = Board::parse(file)?;
+            let config = GameConfig {
+                width: board.width(),
+                height: board.height(),
+                ..GameConfig::default()
+            };
+            return Ok(Game::with_board(board.fingerprint(), config, board));
+        }
+        let challenge = match (self.challenge, self.seed) {
+            (Some(challenge), _) => challenge,
+            (None, Some(seed)) => Challenge {
+                config: GameConfig::default(),
+                seed,
+            },
+            (None, None) => anyhow::bail!("give the board with --board, --challenge or --seed"),
+        };
+        Ok(Game::new(challenge.seed, challenge.config))
+    }
+}
+
+// 保存された記録ならそのまま、手順だけなら盤面の指定と合わせて記録にする。
+// 保存された記録なら true も返す。
+fn read_replay(opt: &VerifyOpt) -> Result<(Replay, bool)> {
+    let path = &opt.file;
+    let data = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+    if let Ok(replay) = storage::parse_json::<Replay>(&data) {
+        let given = opt.board.is_some() || opt.challenge.is_some() || opt.seed.is_some();
+        anyhow::ensure!(!given, "a replay file already names its board");
+        return Ok((replay, true));
+    }
+    let game = opt.game()?;
+    let moves = parse(data.as_slice(), game.board().height())
+        .with_context(|| format!("cannot parse {}", path.display()))?;
+    Ok((to_replay(&game, &moves), false))
+}
+
+pub fn run(opt: &VerifyOpt) -> Result<()> {
+    let (replay, recorded) = read_replay(opt)?;
+    let game = verify(&replay)?;
+    anyhow::ensure!(
+        !recorded || replay.score == game.score(),
+        "recorded score {} does not match the replayed score {}",
+        replay.score,
+        game.score()
+    );
+    let moves = replay
+        .events
+        .iter()
+        .filter(|ev| !matches!(ev.action, Action::Cursor(..)))
+        .count();
+    println!("moves: {}", moves);
+    println!("score: {}", game.score());
+    let yes_no = |b| if b { "yes" } else { "no" };
+    println!("finished: {}", yes_no(game.is_finished()));
+    println!("cleared: {}", yes_no(game.board().tile_count() == 0));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(moves: &[(usize, usize)]) -> Vec<Move> {
+        moves
+            .iter()
+            .map(|&pos| Move { pos, comment: None })
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let src = "# solution\n0 1\n\n0,1  # last\n";
+        let mut expected = moves(&[(0, 1), (0, 1)]);
+        expected[1].comment = Some("last".to_owned());
+        assert_eq!(super::parse(src.as_bytes(), 2).unwrap(), expected);
+        let src = "a1 #\nB@c2\n";
+        assert_eq!(
+            super::parse(src.as_bytes(), 2).unwrap(),
+            moves(&[(0, 1), (2, 0)])
+        );
+        assert!(super::parse("0\n".as_bytes(), 2).is_err());
+        assert!(super::parse("0 1 2\n".as_bytes(), 2).is_err());
+        assert!(super::parse("a b\n".as_bytes(), 2).is_err());
+        assert!(super::parse("a3\n".as_bytes(), 2).is_err());
+    }
+
+    #[test]
+    fn verify() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 3,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_board(1, config, board);
+
+        let replay = to_replay(&game, &moves(&[(0, 1), (0, 1)]));
+        let played = super::verify(&replay).unwrap();
+        assert!(played.is_finished());
+        assert_eq!(played.board().tile_count(), 0);
+        assert_eq!(replay.play().score(), played.score());
+
+        assert!(super::verify(&to_replay(&game, &moves(&[(0, 1), (2, 1)]))).is_err());
+        assert!(super::verify(&to_replay(&game, &moves(&[(5, 0)]))).is_err());
+
+        let mut replay = Replay::new(&game);
+        replay.push(Duration::from_secs(1), Action::Rotate);
+        let e = super::verify(&replay).unwrap_err();
+        assert_eq!(e.to_string(), "move 1 (Rotate) is illegal");
+    }
+
+    #[test]
+    fn replay_file() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 3,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_board(1, config, board);
+        let mut list = moves(&[(0, 1)]);
+        list[0].comment = Some("big group".to_owned());
+        let mut replay = to_replay(&game, &list);
+        assert_eq!(replay.events[1].comment.as_deref(), Some("big group"));
+        replay.score = replay.play().score();
+
+        let dir = std::env::temp_dir().join(format!("samegame-movelist-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ghost.json");
+        fs::write(&path, serde_json::to_vec(&replay).unwrap()).unwrap();
+        let opt = VerifyOpt {
+            file: path.clone(),
+            board: None,
+            challenge: None,
+            seed: Some(1),
+        };
+        assert!(read_replay(&opt).is_err());
+        let opt = VerifyOpt { seed: None, ..opt };
+        assert_eq!(read_replay(&opt).unwrap(), (replay, true));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::notation;
+use crate::replay::{Action, Replay};
+
+// 表計算ソフトで見るための 1 手分の記録
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub action: Action,
+    // クリックした手の notation の表記。色も添える。
+    pub notation: Option<String>,
+    // 消したタイルの色。クリックでなければ 0。
+    pub color: u8,
+    // 消したタイルの数
+    pub size: usize,
+    pub points: i32,
+    pub score: i32,
+    pub t: Duration,
+}
+
+// 記録を再生し、盤面が変わった手ごとにまとめる
+pub fn rows(replay: &Replay) -> Vec<Row> {
+    let mut game = replay.new_game();
+    let mut rows = vec![];
+    for ev in &replay.events {
+        let (color, notation) = match ev.action {
+            Action::Click(x, y) => (
+                game.board().at(x, y),
+                Some(notation::format_move(game.board(), x, y)),
+            ),
+            _ => (0, None),
+        };
+        let (tiles, score) = (game.board().tile_count(), game.score());
+        if !ev.action.apply(&mut game, ev.t) {
+            continue;
+        }
+        rows.push(Row {
+            action: ev.action,
+            notation,
+            color,
+            size: tiles.saturating_sub(game.board().tile_count()),
+            points: game.score() - score,
+            score: game.score(),
+            t: ev.t,
+        });
+    }
+    rows
+}
+
+pub fn write_csv<W: Write>(replay: &Replay, mut wtr: W) -> Result<()> {
+    writeln!(wtr, "move,action,notation,x,y,color,size,points,score,time")?;
+    for (i, row) in rows(replay).iter().enumerate() {
+        let (action, pos) = match row.action {
+            Action::Click(x, y) => ("click", format!("{},{}", x, y)),
+            Action::PowerUp(_) => ("power-up", ",".to_owned()),
+            Action::Rotate => ("rotate", ",".to_owned()),
+            Action::Cursor(..) => unreachable!("cursor moves do not change the board"),
+        };
+        writeln!(
+            wtr,
+            "{},{},{},{},{},{},{},{},{:.3}",
+            i + 1,
+            action,
+            row.notation.as_deref().unwrap_or(""),
+            pos,
+            row.color,
+            row.size,
+            row.points,
+            row.score,
+            row.t.as_secs_f64()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn save_csv<P: AsRef<Path>>(replay: &Replay, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let file =
+        fs::File::create(path).with_context(|| format!("cannot create {}", path.display()))?;
+    write_csv(replay, io::BufWriter::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::board::Board;
+    use crate::game::{Game, GameConfig};
+
+    #[test]
+    fn csv() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 3,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_board(1, config, board);
+        let mut replay = Replay::new(&game);
+        replay.push(Duration::from_millis(500), Action::Cursor(0, 1));
+        replay.push(Duration::from_millis(1500), Action::Click(0, 1));
+        replay.push(Duration::from_millis(2000), Action::Click(0, 1));
+
+        let rows = rows(&replay);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].color, 1);
+        assert_eq!(rows[0].size, 4);
+        assert_eq!(rows[1].color, 2);
+        assert_eq!(rows[1].size, 2);
+        assert_eq!(rows[1].score, rows[0].points + rows[1].points);
+
+        let mut buf = vec![];
+        write_csv(&replay, &mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1,click,A@a1,0,1,1,4,"));
+        assert!(lines[2].starts_with("2,click,B@a1,0,1,2,2,"));
+        assert!(lines[1].ends_with(",1.500"));
+    }
+}
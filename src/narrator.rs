@@ -0,0 +1,81 @@
+use std::fmt;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+
+use anyhow::{bail, Error};
+
+// 得点やゲームオーバーなどを読み上げる
+pub trait Narrator: fmt::Debug {
+    fn announce(&mut self, text: &str);
+}
+
+// 外部の読み上げコマンド ("espeak --stdin" など) の標準入力に文章を渡す。
+// 前の文章を読み終えていなければ止めてから読む。
+#[derive(Debug)]
+pub struct CommandNarrator {
+    program: String,
+    args: Vec<String>,
+    speaking: Option<Child>,
+}
+
+impl FromStr for CommandNarrator {
+    type Err = Error;
+
+    // 空白で区切ったコマンドと引数
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace().map(str::to_owned);
+        let program = match words.next() {
+            Some(program) => program,
+            None => bail!("empty narration command"),
+        };
+        Ok(Self {
+            program,
+            args: words.collect(),
+            speaking: None,
+        })
+    }
+}
+
+impl CommandNarrator {
+    fn speak(&mut self, text: &str) -> std::io::Result<()> {
+        if let Some(mut child) = self.speaking.take() {
+            if child.try_wait()?.is_none() {
+                child.kill()?;
+                child.wait()?;
+            }
+        }
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        // 閉じると入力の終わりになる
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(stdin, "{}", text)?;
+        }
+        self.speaking = Some(child);
+        Ok(())
+    }
+}
+
+impl Narrator for CommandNarrator {
+    fn announce(&mut self, text: &str) {
+        if let Err(e) = self.speak(text) {
+            eprintln!("cannot run {}: {}", self.program, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let narrator: CommandNarrator = "espeak  --stdin -s 200".parse().unwrap();
+        assert_eq!(narrator.program, "espeak");
+        assert_eq!(narrator.args, ["--stdin", "-s", "200"]);
+        assert!(" ".parse::<CommandNarrator>().is_err());
+    }
+}
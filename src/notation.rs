@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+
+use crate::board::{self, Board};
+
+// 手の表記。列を左から a, b, ..., z, aa, ab, ... 、行を下から 1, 2, ... で表し、
+// 盤面の左下が "a1" になる。消す色を添えるときは A (色 1) から始まる大文字を "B@c4" のように前に付ける。
+
+// 列 x の表記
+pub fn column_name(x: usize) -> String {
+    let mut col = vec![];
+    let mut n = x + 1;
+    while n > 0 {
+        n -= 1;
+        col.push(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    col.reverse();
+    String::from_utf8(col).expect("internal error")
+}
+
+// 盤面の高さが h のときの行 y の表記
+pub fn row_name(y: usize, h: usize) -> String {
+    assert!(y < h);
+    (h - y).to_string()
+}
+
+// 盤面の高さが h のときのセル (x, y) の表記
+pub fn format_square(x: usize, y: usize, h: usize) -> String {
+    format!("{}{}", column_name(x), row_name(y, h))
+}
+
+// 盤面の (x, y) を消す手の表記。色も添える。
+pub fn format_move(board: &Board, x: usize, y: usize) -> String {
+    let square = format_square(x, y, board.height());
+    match board.at(x, y) {
+        0 => square,
+        color => format!("{}@{}", char::from(b'A' + color - 1), square),
+    }
+}
+
+// 表記を読み、色の指定と (x, y) を返す。盤面の外かどうかは確かめない。
+pub fn parse_move(s: &str, h: usize) -> Result<(Option<u8>, (usize, usize))> {
+    let (color, square) = match s.find('@') {
+        Some(i) => {
+            let color = match &s.as_bytes()[..i] {
+                &[c @ b'A'..=b'Z'] if c - b'A' < board::MAX_COLORS => c - b'A' + 1,
+                _ => anyhow::bail!("invalid color in {:?}", s),
+            };
+            (Some(color), &s[i + 1..])
+        }
+        None => (None, s),
+    };
+
+    let digits = square
+        .find(|c: char| c.is_ascii_digit())
+        .with_context(|| format!("missing row in {:?}", s))?;
+    let (col, row) = square.split_at(digits);
+    anyhow::ensure!(
+        !col.is_empty() && col.bytes().all(|c| c.is_ascii_lowercase()),
+        "invalid column in {:?}",
+        s
+    );
+    let x = col
+        .bytes()
+        .try_fold(0_usize, |n, c| {
+            n.checked_mul(26)?.checked_add(usize::from(c - b'a') + 1)
+        })
+        .with_context(|| format!("column too large in {:?}", s))?
+        - 1;
+    let row: usize = row
+        .parse()
+        .with_context(|| format!("invalid row in {:?}", s))?;
+    anyhow::ensure!((1..=h).contains(&row), "row out of range in {:?}", s);
+    Ok((color, (x, h - row)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square() {
+        assert_eq!(format_square(0, 9, 10), "a1");
+        assert_eq!(format_square(2, 6, 10), "c4");
+        assert_eq!(format_square(25, 0, 10), "z10");
+        assert_eq!(format_square(26, 0, 10), "aa10");
+        assert_eq!(format_square(27, 0, 10), "ab10");
+        for x in 0..800 {
+            let s = format_square(x, 3, 5);
+            assert_eq!(parse_move(&s, 5).unwrap(), (None, (x, 3)));
+        }
+    }
+
+    #[test]
+    fn moves() {
+        let board = Board::parse("3 2\n212\n101\n".as_bytes()).unwrap();
+        assert_eq!(format_move(&board, 0, 0), "B@a2");
+        assert_eq!(format_move(&board, 1, 1), "b1");
+        assert_eq!(parse_move("B@a2", 2).unwrap(), (Some(2), (0, 0)));
+        assert_eq!(parse_move("c1", 2).unwrap(), (None, (2, 1)));
+
+        assert!(parse_move("a0", 2).is_err());
+        assert!(parse_move("a3", 2).is_err());
+        assert!(parse_move("A1", 2).is_err());
+        assert!(parse_move("a", 2).is_err());
+        assert!(parse_move("1", 2).is_err());
+        assert!(parse_move("I@a1", 2).is_err());
+        assert!(parse_move("@a1", 2).is_err());
+        assert!(parse_move("a1x", 2).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::board::Board;
+use crate::monitor::Monitor;
+use crate::policy::Policy;
+use crate::sim::GameRng;
+use crate::solver::{self, Solution};
+
+// 手の識別子。代表セルと色から決める。
+fn code(board: &Board, (x, y): (usize, usize)) -> u64 {
+    ((x * board.height() + y) as u64) << 8 | u64::from(board.at(x, y))
+}
+
+// 手ごとの重み。重みの指数に比例する確率で手を選ぶ。
+type Weights = HashMap<u64, f64>;
+
+// 重みに従ってプレイアウトする
+struct SoftmaxPolicy<'a> {
+    weights: &'a Weights,
+    rng: &'a mut Pcg32,
+}
+
+impl fmt::Debug for SoftmaxPolicy<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SoftmaxPolicy")
+            .field("weights", &self.weights.len())
+            .finish()
+    }
+}
+
+impl Policy for SoftmaxPolicy<'_> {
+    fn next_move(&mut self, board: &Board) -> Option<(usize, usize)> {
+        let groups = board.groups();
+        let ps: Vec<_> = groups
+            .iter()
+            .map(|&(cell, _)| {
+                self.weights
+                    .get(&code(board, cell))
+                    .copied()
+                    .unwrap_or(0.0)
+                    .exp()
+            })
+            .collect();
+
+        let r =
+            f64::from(self.rng.next_u32()) / f64::from(u32::max_value()) * ps.iter().sum::<f64>();
+        let mut acc = 0.0;
+        for (&(cell, _), p) in groups.iter().zip(&ps) {
+            acc += p;
+            if r <= acc {
+                return Some(cell);
+            }
+        }
+        groups.last().map(|&(cell, _)| cell)
+    }
+}
+
+// Nested Rollout Policy Adaptation。
+// 各レベルで下のレベルを iterations 回呼び、最良の手順に向けて重みを学習する。
+#[derive(Debug)]
+pub struct Nrpa<'a> {
+    board: &'a Board,
+    iterations: usize,
+    rng: Pcg32,
+}
+
+impl<'a> Nrpa<'a> {
+    const ALPHA: f64 = 1.0;
+
+    pub fn new(board: &'a Board, iterations: usize, seed: u64) -> Self {
+        assert!(iterations > 0);
+        Self {
+            board,
+            iterations,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    // monitor の期限で打ち切る
+    pub fn search(&mut self, level: usize, monitor: &mut Monitor) -> Solution {
+        self.nested(monitor, level, Weights::new())
+    }
+
+    fn nested(&mut self, monitor: &mut Monitor, level: usize, mut weights: Weights) -> Solution {
+        if level == 0 {
+            let mut policy = SoftmaxPolicy {
+                weights: &weights,
+                rng: &mut self.rng,
+            };
+            let solution = solver::rollout(self.board, &mut policy);
+            monitor.visit(1, &solution);
+            return solution;
+        }
+
+        let mut best: Option<Solution> = None;
+        for _ in 0..self.iterations {
+            let solution = self.nested(monitor, level - 1, weights.clone());
+            let better = match &best {
+                Some(best) => solution.score >= best.score,
+                None => true,
+            };
+            if better {
+                best = Some(solution);
+            }
+
+            let best = best.as_ref().expect("internal error");
+            weights = self.adapt(&weights, best);
+            if monitor.is_over() {
+                break;
+            }
+        }
+        best.expect("internal error")
+    }
+
+    // best の各手を選ぶ確率が上がるように重みを更新する
+    fn adapt(&self, weights: &Weights, best: &Solution) -> Weights {
+        let mut res = weights.clone();
+        let mut board = self.board.clone();
+        for &(x, y) in &best.moves {
+            let codes: Vec<_> = board
+                .groups()
+                .iter()
+                .map(|&(cell, _)| code(&board, cell))
+                .collect();
+            let ps: Vec<_> = codes
+                .iter()
+                .map(|c| weights.get(c).copied().unwrap_or(0.0).exp())
+                .collect();
+            let z: f64 = ps.iter().sum();
+
+            *res.entry(code(&board, (x, y))).or_insert(0.0) += Self::ALPHA;
+            for (c, p) in codes.iter().zip(&ps) {
+                *res.entry(*c).or_insert(0.0) -= Self::ALPHA * p / z;
+            }
+
+            board.erase_component(x, y);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nrpa() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let solution = Nrpa::new(&board, 10, 0).search(2, &mut Monitor::new(None));
+        assert_eq!(solution.score, 9 + 1);
+
+        let mut rng = Pcg32::seed_from_u64(5);
+        let board = Board::random_with(6, 5, 3, &mut rng);
+        let a = Nrpa::new(&board, 5, 1).search(2, &mut Monitor::new(None));
+        let b = Nrpa::new(&board, 5, 1).search(2, &mut Monitor::new(None));
+        assert_eq!(a, b);
+    }
+}
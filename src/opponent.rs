@@ -0,0 +1,165 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::board::Board;
+use crate::game::Game;
+use crate::policy::{GreedyPolicy, Policy};
+use crate::replay::{Action, Replay};
+use crate::sim::GameRng;
+use crate::solver;
+
+// 競争の練習相手になるコンピュータ。
+// ソルバーを元に、弱い段階ほど浅く読み、ときどきでたらめに打ち、ゆっくり指す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opponent {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Opponent {
+    pub const ALL: [Self; 4] = [Self::Easy, Self::Medium, Self::Hard, Self::Expert];
+
+    // カーソルを動かしてからクリックするまでの時間
+    const AIM: Duration = Duration::from_millis(300);
+
+    // 読みのビーム幅 (0 なら一番大きいグループを取るだけ) と、でたらめに打つ確率 (%)
+    fn strength(self) -> (usize, u32) {
+        match self {
+            Self::Easy => (0, 30),
+            Self::Medium => (2, 10),
+            Self::Hard => (16, 3),
+            Self::Expert => (128, 0),
+        }
+    }
+
+    // 1 手に考える時間の平均。前後に半分までばらつかせる。
+    fn think_time(self) -> Duration {
+        match self {
+            Self::Easy => Duration::from_millis(2500),
+            Self::Medium => Duration::from_millis(1800),
+            Self::Hard => Duration::from_millis(1200),
+            Self::Expert => Duration::from_millis(800),
+        }
+    }
+
+    // 段階を 1 つ上げる。一番上の次はなし。
+    pub fn next(this: Option<Self>) -> Option<Self> {
+        match this {
+            None => Some(Self::Easy),
+            Some(Self::Easy) => Some(Self::Medium),
+            Some(Self::Medium) => Some(Self::Hard),
+            Some(Self::Hard) => Some(Self::Expert),
+            Some(Self::Expert) => None,
+        }
+    }
+
+    fn choose(self, board: &Board, rng: &mut dyn GameRng) -> Option<(usize, usize)> {
+        let groups = board.groups();
+        if groups.is_empty() {
+            return None;
+        }
+        let (width, noise) = self.strength();
+        if rng.below(100) < noise {
+            return Some(groups[rng.below(groups.len() as u32) as usize].0);
+        }
+        match width {
+            0 => GreedyPolicy.next_move(board),
+            width => solver::beam_search(board, width, None)
+                .moves
+                .first()
+                .copied(),
+        }
+    }
+
+    // game と同じ盤面を最初から最後まで指した記録。ゴーストとして一緒に再生する。
+    // 同じ盤面と段階なら毎回同じ手順になる。
+    pub fn play(self, game: &Game) -> Replay {
+        let mut replay = Replay::new(game);
+        let mut game = replay.new_game();
+        let mut rng = Pcg32::seed_from_u64(game.seed().wrapping_add(self as u64));
+        let think = self.think_time().as_millis() as u32;
+        let mut t = Duration::default();
+        while let Some((x, y)) = self.choose(game.board(), &mut rng) {
+            t += Duration::from_millis(u64::from(think / 2 + rng.below(think)));
+            replay.push(t.saturating_sub(Self::AIM), Action::Cursor(x, y));
+            replay.push(t, Action::Click(x, y));
+            if !game.click(x, y, t) || game.is_finished() {
+                break;
+            }
+        }
+        replay.score = game.score();
+        replay
+    }
+}
+
+impl fmt::Display for Opponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Easy => "easy",
+            Self::Medium => "medium",
+            Self::Hard => "hard",
+            Self::Expert => "expert",
+        })
+    }
+}
+
+impl FromStr for Opponent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|level| level.to_string() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown opponent level: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::game::GameConfig;
+
+    #[test]
+    fn parse() {
+        for level in &Opponent::ALL {
+            assert_eq!(level.to_string().parse::<Opponent>().unwrap(), *level);
+        }
+        assert!("impossible".parse::<Opponent>().is_err());
+        assert_eq!(Opponent::next(None), Some(Opponent::Easy));
+        assert_eq!(Opponent::next(Some(Opponent::Expert)), None);
+    }
+
+    #[test]
+    fn play() {
+        let config = GameConfig {
+            width: 8,
+            height: 6,
+            colors: 3,
+            ..GameConfig::default()
+        };
+        let game = Game::new(5, config);
+        let scores: Vec<_> = Opponent::ALL
+            .iter()
+            .map(|level| {
+                let replay = level.play(&game);
+                assert_eq!(replay, level.play(&game));
+                assert!(replay.events.windows(2).all(|w| w[0].t <= w[1].t));
+                let played = replay.play();
+                assert!(played.is_finished());
+                assert_eq!(played.score(), replay.score);
+                replay.score
+            })
+            .collect();
+        // 強い段階ほど稼ぐ
+        assert!(scores[0] < scores[3]);
+        assert!(scores[2] <= scores[3]);
+    }
+}
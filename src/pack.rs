@@ -0,0 +1,146 @@
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::challenge::Challenge;
+use crate::curriculum::Level;
+use crate::difficulty;
+use crate::game::{Game, GameConfig};
+
+// 作者が用意したレベルを順番に並べたパズルパック
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pack {
+    pub name: String,
+    levels: Vec<Level>,
+}
+
+impl Pack {
+    // 名前はファイル名(拡張子を除く)。進行状況の保存先に使う。
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .with_context(|| format!("invalid pack name: {}", path.display()))?;
+        let file =
+            fs::File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+        Self::parse(name, io::BufReader::new(file))
+    }
+
+    // 1 行に 1 レベル。シード(10 進、デフォルト設定)かチャレンジコードに続けて、
+    // 星 1, 2, 3 個に必要な得点を書く。得点を省くと自動で決める。
+    // 空行と '#' 以降は無視する。
+    pub fn parse<R: BufRead>(name: &str, rdr: R) -> Result<Self> {
+        let mut levels = vec![];
+        for (i, line) in rdr.lines().enumerate() {
+            let line = line?;
+            let line = line.split('#').next().expect("internal error").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let level = Self::parse_level(levels.len() as u32 + 1, line)
+                .with_context(|| format!("line {}", i + 1))?;
+            levels.push(level);
+        }
+        anyhow::ensure!(!levels.is_empty(), "no levels");
+
+        Ok(Self {
+            name: name.to_owned(),
+            levels,
+        })
+    }
+
+    fn parse_level(number: u32, line: &str) -> Result<Level> {
+        let fields: Vec<_> = line.split_ascii_whitespace().collect();
+        let challenge = match fields[0].parse::<u64>() {
+            Ok(seed) => Challenge {
+                config: GameConfig::default(),
+                seed,
+            },
+            Err(_) => fields[0]
+                .parse::<Challenge>()
+                .context("invalid seed or code")?,
+        };
+
+        let thresholds = match fields[1..] {
+            [] => Level::auto_thresholds(challenge),
+            [a, b, c] => {
+                let mut thresholds = [0; 3];
+                for (t, s) in thresholds.iter_mut().zip(&[a, b, c]) {
+                    *t = s.parse().context("invalid star threshold")?;
+                }
+                anyhow::ensure!(
+                    thresholds[0] > 0
+                        && thresholds[0] <= thresholds[1]
+                        && thresholds[1] <= thresholds[2],
+                    "star thresholds must be positive and non-decreasing"
+                );
+                thresholds
+            }
+            _ => anyhow::bail!("expected 3 star thresholds"),
+        };
+
+        let game = Game::new(challenge.seed, challenge.config);
+        let target = difficulty::estimate_difficulty(game.board()).difficulty;
+
+        Ok(Level {
+            number,
+            challenge,
+            target,
+            thresholds,
+        })
+    }
+
+    pub fn len(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    // 1 から
+    pub fn level(&self, number: u32) -> Option<&Level> {
+        self.levels.get((number as usize).checked_sub(1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack() {
+        let code = Challenge {
+            config: GameConfig {
+                width: 5,
+                height: 5,
+                colors: 3,
+                ..GameConfig::default()
+            },
+            seed: 7,
+        }
+        .encode()
+        .unwrap();
+        let src = format!("# starter pack\n12345 100 200 300\n\n{}  # auto\n", code);
+        let pack = Pack::parse("starter", src.as_bytes()).unwrap();
+        assert_eq!(pack.name, "starter");
+        assert_eq!(pack.len(), 2);
+
+        let first = pack.level(1).unwrap();
+        assert_eq!(first.challenge.seed, 12345);
+        assert_eq!(first.thresholds, [100, 200, 300]);
+        assert_eq!(first.stars(250), 2);
+
+        let second = pack.level(2).unwrap();
+        assert_eq!(second.number, 2);
+        assert_eq!(second.challenge.config.width, 5);
+        assert!(second.goal() > 0);
+        assert_eq!(pack.level(0), None);
+        assert_eq!(pack.level(3), None);
+
+        assert!(Pack::parse("x", "# nothing\n".as_bytes()).is_err());
+        assert!(Pack::parse("x", "1 100 200\n".as_bytes()).is_err());
+        assert!(Pack::parse("x", "1 300 200 100\n".as_bytes()).is_err());
+        assert!(Pack::parse("x", "1 0 0 0\n".as_bytes()).is_err());
+    }
+}
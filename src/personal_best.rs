@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::game::GameConfig;
+use crate::storage::{self, SaveBackend};
+
+// シード・設定ごとの自己ベスト
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PersonalBests {
+    best: BTreeMap<String, i32>,
+}
+
+impl PersonalBests {
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    fn key(seed: u64, config: &GameConfig) -> String {
+        format!("{:016x}-{}", seed, config.key())
+    }
+
+    pub fn get(&self, seed: u64, config: &GameConfig) -> Option<i32> {
+        self.best.get(&Self::key(seed, config)).copied()
+    }
+
+    // 自己ベストを更新したら true
+    pub fn update(&mut self, seed: u64, config: &GameConfig, score: i32) -> bool {
+        let key = Self::key(seed, config);
+        match self.best.get(&key) {
+            Some(&best) if best >= score => false,
+            _ => {
+                self.best.insert(key, score);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update() {
+        let config = GameConfig::default();
+        let mut pb = PersonalBests::default();
+        assert_eq!(pb.get(1, &config), None);
+
+        assert!(pb.update(1, &config, 100));
+        assert!(!pb.update(1, &config, 50));
+        assert!(!pb.update(1, &config, 100));
+        assert!(pb.update(1, &config, 120));
+        assert_eq!(pb.get(1, &config), Some(120));
+
+        let small = GameConfig { width: 5, ..config };
+        assert_eq!(pb.get(1, &small), None);
+        assert_eq!(pb.get(2, &config), None);
+    }
+}
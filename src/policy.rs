@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::board::Board;
+use crate::sim::GameRng;
+use crate::solver;
+
+// 盤面を見て次の手を決めるもの
+pub trait Policy: fmt::Debug {
+    // 次に消すグループのセル。消せるグループがなければ None。
+    fn next_move(&mut self, board: &Board) -> Option<(usize, usize)>;
+}
+
+// 消せるグループから一様に選ぶ
+#[derive(Debug)]
+pub struct RandomPolicy {
+    rng: Pcg32,
+}
+
+impl RandomPolicy {
+    pub fn new(rng: Pcg32) -> Self {
+        Self { rng }
+    }
+}
+
+impl Policy for RandomPolicy {
+    fn next_move(&mut self, board: &Board) -> Option<(usize, usize)> {
+        let groups = board.groups();
+        if groups.is_empty() {
+            return None;
+        }
+        let i = self.rng.below(groups.len() as u32) as usize;
+        Some(groups[i].0)
+    }
+}
+
+// 一番大きいグループを消す
+#[derive(Debug)]
+pub struct GreedyPolicy;
+
+impl Policy for GreedyPolicy {
+    fn next_move(&mut self, board: &Board) -> Option<(usize, usize)> {
+        board
+            .groups()
+            .into_iter()
+            .rev()
+            .max_by_key(|&(_, n)| n)
+            .map(|(cell, _)| cell)
+    }
+}
+
+// 最初にビームサーチで手順を求め、その通りに打つ
+#[derive(Debug)]
+pub struct BeamPolicy {
+    width: usize,
+    plan: VecDeque<(usize, usize)>,
+}
+
+impl Policy for BeamPolicy {
+    fn next_move(&mut self, board: &Board) -> Option<(usize, usize)> {
+        if self.plan.is_empty() {
+            let solution = solver::beam_search(board, self.width, None);
+            self.plan = solution.moves.into();
+        }
+        self.plan.pop_front()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyKind {
+    Random,
+    Greedy,
+    Beam { width: usize },
+}
+
+impl PolicyKind {
+    const DEFAULT_BEAM_WIDTH: usize = 100;
+
+    // 盤面ごとに作る。乱数を使うものは seed で再現できる。
+    pub fn build(self, seed: u64) -> Box<dyn Policy> {
+        match self {
+            Self::Random => Box::new(RandomPolicy::new(Pcg32::seed_from_u64(seed))),
+            Self::Greedy => Box::new(GreedyPolicy),
+            Self::Beam { width } => Box::new(BeamPolicy {
+                width,
+                plan: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for PolicyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Random => f.write_str("random"),
+            Self::Greedy => f.write_str("greedy"),
+            Self::Beam { width } => write!(f, "beam:{}", width),
+        }
+    }
+}
+
+// "random", "greedy", "beam", "beam:<width>"
+impl FromStr for PolicyKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut it = s.splitn(2, ':');
+        let name = it.next().expect("internal error");
+        let arg = it.next();
+
+        let kind = match (name, arg) {
+            ("random", None) => Self::Random,
+            ("greedy", None) => Self::Greedy,
+            ("beam", None) => Self::Beam {
+                width: Self::DEFAULT_BEAM_WIDTH,
+            },
+            ("beam", Some(width)) => {
+                let width: usize = width.parse().context("invalid beam width")?;
+                anyhow::ensure!(width > 0, "beam width must be positive");
+                Self::Beam { width }
+            }
+            _ => anyhow::bail!("unknown policy: {}", s),
+        };
+        Ok(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for s in &["random", "greedy", "beam:20"] {
+            assert_eq!(s.parse::<PolicyKind>().unwrap().to_string(), *s);
+        }
+        assert_eq!(
+            "beam".parse::<PolicyKind>().unwrap(),
+            PolicyKind::Beam { width: 100 }
+        );
+        assert!("beam:0".parse::<PolicyKind>().is_err());
+        assert!("greedy:1".parse::<PolicyKind>().is_err());
+        assert!("mcts".parse::<PolicyKind>().is_err());
+    }
+}
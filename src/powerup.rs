@@ -0,0 +1,404 @@
+use std::fmt;
+
+use crate::board::Board;
+use crate::sim::GameRng;
+
+// パワーアップの共通インターフェース。
+//
+// 発動までにプレイヤーが盤面上のセルを `target_count()` 個選ぶ。
+// 0 個のものはキーを押した時点で即発動する。
+pub trait PowerUp: fmt::Debug {
+    fn name(&self) -> &'static str;
+
+    // ゲームを複製するためのコピー
+    fn box_clone(&self) -> Box<dyn PowerUp>;
+
+    // HUD に表示する 1 文字のアイコン
+    fn icon(&self) -> char;
+
+    // 使用後、再使用可能になるまでの手数
+    fn cooldown(&self) -> u32 {
+        0
+    }
+
+    fn target_count(&self) -> usize;
+
+    // 途中まで選ばれたターゲットが妥当かどうか
+    fn accepts(&self, _board: &Board, _targets: &[(usize, usize)]) -> bool {
+        true
+    }
+
+    // 効果を適用し、得点を返す。適用できなければ None (盤面は変更しないこと)。
+    fn apply(
+        &self,
+        board: &mut Board,
+        targets: &[(usize, usize)],
+        rng: &mut dyn GameRng,
+    ) -> Option<i32>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Armed,
+    Cancelled,
+    Pending,
+    Applied(i32),
+    Rejected,
+}
+
+impl Clone for Box<dyn PowerUp> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    power: Box<dyn PowerUp>,
+    stock: u32,
+    cooldown: u32,
+}
+
+impl Slot {
+    fn is_available(&self) -> bool {
+        self.stock > 0 && self.cooldown == 0
+    }
+}
+
+// HUD 描画用
+#[derive(Debug, Clone, Copy)]
+pub struct SlotView {
+    pub icon: char,
+    pub stock: u32,
+    pub cooldown: u32,
+    pub armed: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    slots: Vec<Slot>,
+    armed: Option<usize>,
+    targets: Vec<(usize, usize)>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, power: Box<dyn PowerUp>, stock: u32) {
+        if let Some(slot) = self
+            .slots
+            .iter_mut()
+            .find(|s| s.power.name() == power.name())
+        {
+            slot.stock += stock;
+            return;
+        }
+        self.slots.push(Slot {
+            power,
+            stock,
+            cooldown: 0,
+        });
+    }
+
+    pub fn slots(&self) -> impl Iterator<Item = SlotView> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(move |(i, slot)| SlotView {
+                icon: slot.power.icon(),
+                stock: slot.stock,
+                cooldown: slot.cooldown,
+                armed: self.armed == Some(i),
+            })
+    }
+
+    pub fn armed(&self) -> Option<usize> {
+        self.armed
+    }
+
+    pub fn targets(&self) -> &[(usize, usize)] {
+        &self.targets
+    }
+
+    pub fn disarm(&mut self) {
+        self.armed = None;
+        self.targets.clear();
+    }
+
+    pub fn select(&mut self, i: usize, board: &mut Board, rng: &mut dyn GameRng) -> Outcome {
+        if self.armed == Some(i) {
+            self.disarm();
+            return Outcome::Cancelled;
+        }
+        match self.slots.get(i) {
+            Some(slot) if slot.is_available() => {}
+            _ => return Outcome::Rejected,
+        }
+
+        self.disarm();
+        self.armed = Some(i);
+        if self.slots[i].power.target_count() == 0 {
+            self.fire(board, rng)
+        } else {
+            Outcome::Armed
+        }
+    }
+
+    pub fn pick(
+        &mut self,
+        board: &mut Board,
+        x: usize,
+        y: usize,
+        rng: &mut dyn GameRng,
+    ) -> Outcome {
+        let i = match self.armed {
+            Some(i) => i,
+            None => return Outcome::Rejected,
+        };
+
+        self.targets.push((x, y));
+        let power = &self.slots[i].power;
+        if !power.accepts(board, &self.targets) {
+            self.targets.clear();
+            return Outcome::Rejected;
+        }
+        if self.targets.len() < power.target_count() {
+            return Outcome::Pending;
+        }
+
+        self.fire(board, rng)
+    }
+
+    // 1 手経過
+    pub fn tick(&mut self) {
+        for slot in &mut self.slots {
+            slot.cooldown = slot.cooldown.saturating_sub(1);
+        }
+    }
+
+    fn fire(&mut self, board: &mut Board, rng: &mut dyn GameRng) -> Outcome {
+        let i = self.armed.expect("internal error");
+        let res = self.slots[i].power.apply(board, &self.targets, rng);
+        self.targets.clear();
+
+        match res {
+            Some(score) => {
+                self.armed = None;
+                self.tick();
+                let slot = &mut self.slots[i];
+                slot.stock -= 1;
+                slot.cooldown = slot.power.cooldown();
+                Outcome::Applied(score)
+            }
+            None => {
+                if self.slots[i].power.target_count() == 0 {
+                    self.armed = None;
+                }
+                Outcome::Rejected
+            }
+        }
+    }
+}
+
+// 残っているタイルの色をシャッフルする
+#[derive(Debug)]
+pub struct Shuffle;
+
+impl PowerUp for Shuffle {
+    fn name(&self) -> &'static str {
+        "shuffle"
+    }
+
+    fn box_clone(&self) -> Box<dyn PowerUp> {
+        Box::new(Self)
+    }
+
+    fn icon(&self) -> char {
+        'S'
+    }
+
+    fn cooldown(&self) -> u32 {
+        5
+    }
+
+    fn target_count(&self) -> usize {
+        0
+    }
+
+    fn apply(
+        &self,
+        board: &mut Board,
+        _targets: &[(usize, usize)],
+        rng: &mut dyn GameRng,
+    ) -> Option<i32> {
+        board.shuffle(rng);
+        Some(0)
+    }
+}
+
+// 隣接する 2 タイルの色を入れ替える
+#[derive(Debug)]
+pub struct Swap;
+
+impl PowerUp for Swap {
+    fn name(&self) -> &'static str {
+        "swap"
+    }
+
+    fn box_clone(&self) -> Box<dyn PowerUp> {
+        Box::new(Self)
+    }
+
+    fn icon(&self) -> char {
+        'W'
+    }
+
+    fn cooldown(&self) -> u32 {
+        3
+    }
+
+    fn target_count(&self) -> usize {
+        2
+    }
+
+    fn accepts(&self, board: &Board, targets: &[(usize, usize)]) -> bool {
+        match *targets {
+            [(x, y)] => board.at(x, y) != 0,
+            [(x1, y1), (x2, y2)] => {
+                let (c1, c2) = (board.at(x1, y1), board.at(x2, y2));
+                c2 != 0 && c1 != c2 && x1.max(x2) - x1.min(x2) + y1.max(y2) - y1.min(y2) == 1
+            }
+            _ => false,
+        }
+    }
+
+    fn apply(
+        &self,
+        board: &mut Board,
+        targets: &[(usize, usize)],
+        _rng: &mut dyn GameRng,
+    ) -> Option<i32> {
+        if board.swap(targets[0], targets[1]) {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+// 選んだタイルと同じ色のタイルを全て消す(得点は通常の半分)
+#[derive(Debug)]
+pub struct ColorBomb;
+
+impl PowerUp for ColorBomb {
+    fn name(&self) -> &'static str {
+        "color bomb"
+    }
+
+    fn box_clone(&self) -> Box<dyn PowerUp> {
+        Box::new(Self)
+    }
+
+    fn icon(&self) -> char {
+        'B'
+    }
+
+    fn cooldown(&self) -> u32 {
+        10
+    }
+
+    fn target_count(&self) -> usize {
+        1
+    }
+
+    fn accepts(&self, board: &Board, targets: &[(usize, usize)]) -> bool {
+        targets.iter().all(|&(x, y)| board.at(x, y) != 0)
+    }
+
+    fn apply(
+        &self,
+        board: &mut Board,
+        targets: &[(usize, usize)],
+        _rng: &mut dyn GameRng,
+    ) -> Option<i32> {
+        let (x, y) = targets[0];
+        let n = board.erase_color(board.at(x, y));
+        if n == 0 {
+            return None;
+        }
+        Some((n - 1).pow(2) as i32 / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Pick2;
+
+    impl PowerUp for Pick2 {
+        fn name(&self) -> &'static str {
+            "pick2"
+        }
+
+        fn box_clone(&self) -> Box<dyn PowerUp> {
+            Box::new(Self)
+        }
+
+        fn icon(&self) -> char {
+            'P'
+        }
+
+        fn cooldown(&self) -> u32 {
+            2
+        }
+
+        fn target_count(&self) -> usize {
+            2
+        }
+
+        fn accepts(&self, _board: &Board, targets: &[(usize, usize)]) -> bool {
+            targets.iter().all(|&(x, _)| x == 0)
+        }
+
+        fn apply(
+            &self,
+            _board: &mut Board,
+            _targets: &[(usize, usize)],
+            _rng: &mut dyn GameRng,
+        ) -> Option<i32> {
+            Some(7)
+        }
+    }
+
+    #[test]
+    fn inventory() {
+        let mut board = Board::random(3, 3);
+        let rng = &mut Pcg32::seed_from_u64(0);
+        let mut inv = Inventory::new();
+        inv.add(Box::new(Pick2), 1);
+        inv.add(Box::new(Pick2), 1);
+        assert_eq!(inv.slots().count(), 1);
+
+        assert_eq!(inv.select(1, &mut board, rng), Outcome::Rejected);
+        assert_eq!(inv.select(0, &mut board, rng), Outcome::Armed);
+        assert_eq!(inv.pick(&mut board, 1, 0, rng), Outcome::Rejected);
+        assert_eq!(inv.pick(&mut board, 0, 0, rng), Outcome::Pending);
+        assert_eq!(inv.pick(&mut board, 0, 1, rng), Outcome::Applied(7));
+        assert_eq!(inv.armed(), None);
+
+        let slot = inv.slots().next().unwrap();
+        assert_eq!((slot.stock, slot.cooldown), (1, 2));
+        assert_eq!(inv.select(0, &mut board, rng), Outcome::Rejected);
+        inv.tick();
+        inv.tick();
+        assert_eq!(inv.select(0, &mut board, rng), Outcome::Armed);
+        assert_eq!(inv.select(0, &mut board, rng), Outcome::Cancelled);
+    }
+}
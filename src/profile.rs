@@ -0,0 +1,82 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+
+use crate::storage::SaveBackend;
+
+// プレイヤーごとの保存先。ハイスコアやゴーストなどは全てプロファイルのキーの下に置く。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    name: String,
+}
+
+impl Profile {
+    pub const DEFAULT: &'static str = "default";
+    const MAX_NAME_LEN: usize = 32;
+
+    const PREFIX: &'static str = "profiles/";
+
+    // プロファイル内の相対キーを保存用のキーにする
+    pub fn key(&self, rel: &str) -> String {
+        format!("{}{}/{}", Self::PREFIX, self.name, rel)
+    }
+
+    // データを保存したことのあるプロファイル名(ソート済み)
+    pub fn list(backend: &dyn SaveBackend) -> Result<Vec<String>> {
+        let mut res: Vec<_> = backend
+            .list(Self::PREFIX)?
+            .iter()
+            .filter_map(|key| key[Self::PREFIX.len()..].split('/').next())
+            .filter(|name| Self::is_valid_name(name))
+            .map(str::to_owned)
+            .collect();
+        res.sort();
+        res.dedup();
+        Ok(res)
+    }
+
+    fn is_valid_name(name: &str) -> bool {
+        (1..=Self::MAX_NAME_LEN).contains(&name.len())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl FromStr for Profile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        anyhow::ensure!(
+            Self::is_valid_name(s),
+            "profile name must be 1-{} characters of [A-Za-z0-9_-]",
+            Self::MAX_NAME_LEN
+        );
+        Ok(Self { name: s.to_owned() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("alice".parse::<Profile>().unwrap().to_string(), "alice");
+        assert_eq!("p_2-b".parse::<Profile>().unwrap().to_string(), "p_2-b");
+        assert!("".parse::<Profile>().is_err());
+        assert!("../x".parse::<Profile>().is_err());
+        assert!("a b".parse::<Profile>().is_err());
+        assert!("x".repeat(33).parse::<Profile>().is_err());
+
+        let profile: Profile = "alice".parse().unwrap();
+        assert_eq!(profile.key("a/b.json"), "profiles/alice/a/b.json");
+    }
+}
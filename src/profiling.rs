@@ -0,0 +1,119 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+#[cfg(feature = "profiling")]
+use std::{sync::Mutex, time::Instant};
+
+// `profiling` フィーチャーを付けて作ったときだけ、名前を付けた区間の時間を集計する。
+// 付けなければ scope は何もせず、測る手間もかからない。
+pub const ENABLED: bool = cfg!(feature = "profiling");
+
+// 1 つの区間の集計
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub name: &'static str,
+    pub calls: u64,
+    pub total: Duration,
+}
+
+// 区間はせいぜい数十なので名前で線形に探す
+#[cfg(feature = "profiling")]
+static ENTRIES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+// 作ってから落とすまでを name の区間として測る。入れ子にした区間はそれぞれに足す。
+#[must_use]
+pub struct Scope {
+    #[cfg(feature = "profiling")]
+    name: &'static str,
+    #[cfg(feature = "profiling")]
+    start: Instant,
+}
+
+#[cfg(feature = "profiling")]
+pub fn scope(name: &'static str) -> Scope {
+    Scope {
+        name,
+        start: Instant::now(),
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn scope(_name: &'static str) -> Scope {
+    Scope {}
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let mut entries = ENTRIES.lock().expect("internal error");
+        match entries.iter_mut().find(|e| e.name == self.name) {
+            Some(entry) => {
+                entry.calls += 1;
+                entry.total += elapsed;
+            }
+            None => entries.push(Entry {
+                name: self.name,
+                calls: 1,
+                total: elapsed,
+            }),
+        }
+    }
+}
+
+// 時間のかかった順
+pub fn entries() -> Vec<Entry> {
+    #[cfg(feature = "profiling")]
+    {
+        let mut entries = ENTRIES.lock().expect("internal error").clone();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.total));
+        entries
+    }
+    #[cfg(not(feature = "profiling"))]
+    vec![]
+}
+
+// 終了時に出す表。区間ごとの呼び出し回数、合計、1 回あたりの時間。
+pub fn summary() -> String {
+    let mut res = format!(
+        "{:<28} {:>10} {:>10} {:>10}\n",
+        "scope", "calls", "total(ms)", "mean(us)"
+    );
+    for e in entries() {
+        let mean = e.total.as_secs_f64() * 1e6 / e.calls as f64;
+        let _ = writeln!(
+            res,
+            "{:<28} {:>10} {:>10} {:>10.1}",
+            e.name,
+            e.calls,
+            e.total.as_millis(),
+            mean
+        );
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope() {
+        {
+            let _outer = super::scope("test::outer");
+            let _inner = super::scope("test::inner");
+        }
+        drop(super::scope("test::inner"));
+
+        let entries = entries();
+        let calls = |name| entries.iter().find(|e| e.name == name).map(|e| e.calls);
+        if ENABLED {
+            assert_eq!(calls("test::outer"), Some(1));
+            assert_eq!(calls("test::inner"), Some(2));
+            assert!(summary().contains("test::inner"));
+        } else {
+            assert!(entries.is_empty());
+            assert_eq!(summary().lines().count(), 1);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::batch::BoardSetOpt;
+use crate::board::Board;
+use crate::game::Game;
+use crate::notation;
+use crate::replay::{Action, Replay};
+use crate::rules;
+use crate::solver;
+use crate::storage;
+
+// 1 つの手だけが他のどの手よりずっと良い局面。その手を当てる問題にする。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub board: Board,
+    // 各グループの代表セルと、その手から最後まで指したときの得点。良い順。
+    pub moves: Vec<((usize, usize), i32)>,
+}
+
+impl Puzzle {
+    // 答えの手の得点と次に良い手との差
+    pub fn best(&self) -> ((usize, usize), i32) {
+        self.moves[0]
+    }
+
+    pub fn margin(&self) -> i32 {
+        self.moves[0].1 - self.moves[1].1
+    }
+
+    // (x, y) を消す手が何番目に良いか。0 なら正解。消せないセルなら None。
+    pub fn rank(&self, x: usize, y: usize) -> Option<usize> {
+        let group = self.board.calc_component(x, y);
+        self.moves.iter().position(|(pos, _)| group.contains(pos))
+    }
+
+    // 各手を指してから先をソルバーに読ませた得点
+    pub fn evaluate(board: &Board, width: usize) -> Vec<((usize, usize), i32)> {
+        let mut moves: Vec<_> = board
+            .groups()
+            .into_iter()
+            .map(|((x, y), n)| {
+                let mut after = board.clone();
+                after.erase_component(x, y);
+                let rest = solver::beam_search(&after, width, None).score;
+                ((x, y), rules::group_score(n) + rest)
+            })
+            .collect();
+        moves.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        moves
+    }
+
+    // 一番良い手が次の手より最後の得点の margin % 以上 (かつ MIN_GAP 点以上) 良ければ問題にする
+    pub fn find(board: &Board, width: usize, margin: u32) -> Option<Self> {
+        const MIN_GAP: i32 = 20;
+        let moves = Self::evaluate(board, width);
+        if moves.len() < 2 {
+            return None;
+        }
+        let puzzle = Self {
+            board: board.clone(),
+            moves,
+        };
+        let gap = puzzle.margin();
+        let best = puzzle.best().1;
+        if gap >= MIN_GAP && i64::from(gap) * 100 >= i64::from(margin) * i64::from(best) {
+            Some(puzzle)
+        } else {
+            None
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        let data = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+        let puzzles: Vec<Self> = storage::parse_json(&data)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        anyhow::ensure!(!puzzles.is_empty(), "{} has no puzzles", path.display());
+        anyhow::ensure!(
+            puzzles.iter().all(|p| p.moves.len() >= 2),
+            "{} has a puzzle with fewer than two moves",
+            path.display()
+        );
+        Ok(puzzles)
+    }
+}
+
+// 記録で手を指す前の各局面
+fn replay_positions(replay: &Replay) -> Vec<Board> {
+    let mut game = replay.new_game();
+    let mut positions = vec![];
+    for ev in &replay.events {
+        let before = game.board().clone();
+        if ev.action.apply(&mut game, ev.t) && matches!(ev.action, Action::Click(..)) {
+            positions.push(before);
+        }
+    }
+    positions
+}
+
+// ソルバーが最初から指したときの各局面
+fn solver_positions(game: &Game, width: usize) -> Vec<Board> {
+    let mut board = game.board().clone();
+    let line = solver::beam_search(&board, width, None);
+    let mut positions = vec![];
+    for &(x, y) in &line.moves {
+        positions.push(board.clone());
+        board.erase_component(x, y);
+    }
+    positions
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MineOpt {
+    /// Where to write the puzzles (JSON)
+    #[structopt(parse(from_os_str))]
+    out: PathBuf,
+
+    /// Replays of played games to mine before the generated boards
+    #[structopt(long = "replay", parse(from_os_str))]
+    replays: Vec<PathBuf>,
+
+    #[structopt(flatten)]
+    boards: BoardSetOpt,
+
+    /// Beam width the solver reads each candidate move with
+    #[structopt(long, default_value = "4")]
+    width: usize,
+
+    /// How much better the best move must be than the next one, in percent of its final score
+    #[structopt(long, default_value = "20")]
+    margin: u32,
+
+    /// Stop after finding this many puzzles
+    #[structopt(long, default_value = "20")]
+    max: usize,
+}
+
+pub fn run(opt: &MineOpt) -> Result<()> {
+    anyhow::ensure!(opt.width > 0, "width must be positive");
+    let config = opt.boards.config()?;
+    let mut games = vec![];
+    for path in &opt.replays {
+        let data = fs::read(path).with_context(|| format!("cannot read {}", path.display()))?;
+        let replay: Replay = storage::parse_json(&data)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        games.push((path.display().to_string(), replay_positions(&replay)));
+    }
+    let generated = opt.boards.seeds().map(|seed| {
+        let game = Game::new(seed, config);
+        (format!("seed {}", seed), solver_positions(&game, opt.width))
+    });
+
+    let mut seen = HashSet::new();
+    let mut puzzles = vec![];
+    'mine: for (name, positions) in games.into_iter().chain(generated) {
+        for (i, board) in positions.iter().enumerate() {
+            if !seen.insert(board.fingerprint()) {
+                continue;
+            }
+            if let Some(puzzle) = Puzzle::find(board, opt.width, opt.margin) {
+                let ((x, y), score) = puzzle.best();
+                eprintln!(
+                    "{} move {}: {} wins by {} ({})",
+                    name,
+                    i + 1,
+                    notation::format_move(board, x, y),
+                    puzzle.margin(),
+                    score
+                );
+                puzzles.push(puzzle);
+                if puzzles.len() >= opt.max {
+                    break 'mine;
+                }
+            }
+        }
+    }
+
+    let body = serde_json::to_vec_pretty(&puzzles)?;
+    fs::write(&opt.out, body).with_context(|| format!("cannot write {}", opt.out.display()))?;
+    println!("puzzles: {}", puzzles.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::game::GameConfig;
+
+    #[test]
+    fn find() {
+        // 真ん中の 1 を消すと両側の 2 がつながる
+        let board = Board::parse("3 5\n212\n212\n212\n212\n212\n".as_bytes()).unwrap();
+        let puzzle = Puzzle::find(&board, 4, 20).unwrap();
+        assert_eq!(puzzle.moves.len(), 3);
+        assert_eq!(puzzle.best().1, 16 + 81);
+        assert_eq!(puzzle.margin(), 97 - 48);
+        assert_eq!(puzzle.rank(1, 2), Some(0));
+        assert!(matches!(puzzle.rank(0, 4), Some(r) if r > 0));
+
+        // 消せないセル
+        let board = Board::parse("2 2\n12\n13\n".as_bytes()).unwrap();
+        let moves = Puzzle::evaluate(&board, 4);
+        assert_eq!(Puzzle { board, moves }.rank(1, 1), None);
+
+        // 良い手の差が小さければ問題にしない
+        let board = Board::parse("2 2\n12\n12\n".as_bytes()).unwrap();
+        assert_eq!(Puzzle::find(&board, 4, 20), None);
+    }
+
+    #[test]
+    fn positions() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 3,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_board(1, config, board.clone());
+        let mut replay = Replay::new(&game);
+        replay.push(Duration::from_secs(1), Action::Cursor(0, 1));
+        replay.push(Duration::from_secs(2), Action::Click(0, 0));
+        replay.push(Duration::from_secs(3), Action::Click(0, 1));
+        replay.push(Duration::from_secs(4), Action::Click(0, 1));
+        let positions = replay_positions(&replay);
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0], board);
+        assert_eq!(solver_positions(&game, 4), positions);
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::solver;
+use crate::storage::{self, SaveBackend};
+
+// ソルバーの基準点の計算に使うビーム幅。
+// 時間で区切ると機械の速さで基準が変わるので、幅を決めて同じ盤面なら同じ基準にする。
+const BASELINE_WIDTH: usize = 32;
+
+// 盤面を最初から指したときのソルバーの得点
+pub fn baseline(board: &Board) -> i32 {
+    solver::beam_search(board, BASELINE_WIDTH, None).score
+}
+
+// 1 ゲームの評価。同じ盤面でのソルバーの得点と比べるので、盤面の運に左右されにくい。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rating {
+    pub seed: u64,
+    pub score: i32,
+    pub baseline: i32,
+}
+
+impl Rating {
+    // ソルバーの得点に対する割合。ソルバーより稼げば 1 を超える。
+    pub fn efficiency(&self) -> f64 {
+        if self.baseline <= 0 {
+            return 1.0;
+        }
+        f64::from(self.score) / f64::from(self.baseline)
+    }
+}
+
+// これまでのゲームの評価。古いものが先頭。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ratings {
+    games: Vec<Rating>,
+}
+
+impl Ratings {
+    // 覚えておくゲームの数
+    const MAX: usize = 500;
+
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    pub fn games(&self) -> &[Rating] {
+        &self.games
+    }
+
+    pub fn add(&mut self, rating: Rating) {
+        self.games.push(rating);
+        let excess = self.games.len().saturating_sub(Self::MAX);
+        self.games.drain(..excess);
+    }
+
+    // 最近の n ゲームの平均。まだなければ None。
+    pub fn mean(&self, n: usize) -> Option<f64> {
+        let recent = &self.games[self.games.len().saturating_sub(n)..];
+        if recent.is_empty() {
+            return None;
+        }
+        Some(recent.iter().map(Rating::efficiency).sum::<f64>() / recent.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratings() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        // 1 を消してから 2 を消して全消し
+        assert_eq!(baseline(&board), 10);
+
+        let mut ratings = Ratings::default();
+        assert_eq!(ratings.mean(10), None);
+        for score in 1..=Ratings::MAX as i32 + 2 {
+            ratings.add(Rating {
+                seed: 0,
+                score,
+                baseline: 4,
+            });
+        }
+        assert_eq!(ratings.games().len(), Ratings::MAX);
+        assert_eq!(ratings.games()[0].score, 3);
+        assert!((ratings.mean(2).unwrap() - 125.375).abs() < 1e-9);
+
+        let rating = Rating {
+            seed: 0,
+            score: 10,
+            baseline: 0,
+        };
+        assert!((rating.efficiency() - 1.0).abs() < 1e-9);
+    }
+}
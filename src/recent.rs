@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, SaveBackend};
+
+// 最近開いた盤面のファイル。新しいものが先頭。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentBoards {
+    paths: Vec<PathBuf>,
+}
+
+impl RecentBoards {
+    // 覚えておく数
+    const MAX: usize = 5;
+
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    // 開いたファイルを先頭に置く。前にも開いていればそこから移す。
+    pub fn add(&mut self, path: &Path) {
+        self.paths.retain(|p| p != path);
+        self.paths.insert(0, path.to_owned());
+        self.paths.truncate(Self::MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add() {
+        let mut recent = RecentBoards::default();
+        for name in &["a", "b", "c"] {
+            recent.add(Path::new(name));
+        }
+        recent.add(Path::new("a"));
+        let names: Vec<_> = recent.paths().iter().map(|p| p.to_str().unwrap()).collect();
+        assert_eq!(names, ["a", "c", "b"]);
+
+        for i in 0..10 {
+            recent.add(&PathBuf::from(i.to_string()));
+        }
+        assert_eq!(recent.paths().len(), RecentBoards::MAX);
+        assert_eq!(recent.paths()[0], Path::new("9"));
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+
+use crate::board::Board;
+
+// 決まった盤面でこのクレートのソルバーが取った得点。文献の記録ではなく、ソルバーを変えたときの目安にする。
+const SCORES: &str = include_str!("../asset/reference-scores.txt");
+
+// 1 行に 1 盤面。盤面の識別子 (16 進) と得点を書く。
+// 空行と '#' 以降は無視する。
+fn parse_table(src: &str) -> Result<Vec<(u64, i32)>> {
+    let mut res = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let line = line.split('#').next().expect("internal error").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let entry = (|| -> Result<_> {
+            let mut it = line.split_ascii_whitespace();
+            let fingerprint = u64::from_str_radix(it.next().context("no fingerprint")?, 16)?;
+            let score = it.next().context("no score")?.parse()?;
+            anyhow::ensure!(it.next().is_none(), "trailing fields");
+            Ok((fingerprint, score))
+        })()
+        .with_context(|| format!("line {}", i + 1))?;
+        res.push(entry);
+    }
+    Ok(res)
+}
+
+// board が表に載っていればその得点
+pub fn score(board: &Board) -> Option<i32> {
+    let table = parse_table(SCORES).expect("broken reference score table");
+    let fingerprint = board.fingerprint();
+    table
+        .into_iter()
+        .find(|&(fp, _)| fp == fingerprint)
+        .map(|(_, score)| score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::board;
+    use crate::game::{Game, GameConfig};
+
+    #[test]
+    fn table() {
+        // 表に載せた盤面は 15x15、5 色でシード 1 から 20 で配られる盤面
+        let config = GameConfig {
+            width: 15,
+            height: 15,
+            colors: board::DEFAULT_COLORS,
+            ..GameConfig::default()
+        };
+        let bundled = parse_table(SCORES).unwrap();
+        assert_eq!(bundled.len(), 20);
+        assert_eq!(score(Game::new(1, config).board()), Some(2024));
+        assert!((1..=20).all(|seed| score(Game::new(seed, config).board()).is_some()));
+
+        let table = parse_table("# comment\n\n00000000000000ff 3413  # position 1\n").unwrap();
+        assert_eq!(table, [(0xff, 3413)]);
+        assert!(parse_table("xyz 1\n").is_err());
+        assert!(parse_table("ff\n").is_err());
+        assert!(parse_table("ff 1 2\n").is_err());
+    }
+
+    #[test]
+    fn fingerprint() {
+        let board = Board::parse("2 2\n12\n21\n".as_bytes()).unwrap();
+        assert_eq!(board.fingerprint(), board.clone().fingerprint());
+        let other = Board::parse("2 2\n21\n12\n".as_bytes()).unwrap();
+        assert_ne!(board.fingerprint(), other.fingerprint());
+        assert_eq!(score(&board), None);
+    }
+}
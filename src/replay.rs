@@ -0,0 +1,433 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::anim::{Ease, Tween};
+use crate::board::Board;
+use crate::game::{Game, GameConfig};
+use crate::hint::HintLevel;
+use crate::notation;
+use crate::storage::{self, SaveBackend};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Cursor(usize, usize),
+    Click(usize, usize),
+    PowerUp(usize),
+    Rotate,
+}
+
+impl Action {
+    // ゲームに入力を与える。盤面が変化したら true。
+    pub fn apply(self, game: &mut Game, t: Duration) -> bool {
+        match self {
+            Self::Cursor(..) => false,
+            Self::Click(x, y) => game.click(x, y, t),
+            Self::PowerUp(i) => game.select_powerup(i, t),
+            Self::Rotate => game.rotate(t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub t: Duration,
+    pub action: Action,
+    // 手に添えた注釈。解説付きの記録で再生中に出す。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+// 1 ゲーム分の入力記録。シードと入力列から `Game` を再現できる。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub config: GameConfig,
+    // 自作の盤面など決まった盤面から始めたならその盤面
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board: Option<Board>,
+    pub score: i32,
+    pub events: Vec<Event>,
+    // ヒントを使っていれば、見せた中で一番強いもの
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assist: Option<HintLevel>,
+}
+
+impl Replay {
+    pub fn new(game: &Game) -> Self {
+        Self {
+            seed: game.seed(),
+            config: game.config(),
+            board: game.given_board().cloned(),
+            score: game.score(),
+            events: vec![],
+            assist: None,
+        }
+    }
+
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Option<Self>> {
+        storage::load_json(backend, key)
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    pub fn push(&mut self, t: Duration, action: Action) {
+        self.events.push(Event {
+            t,
+            action,
+            comment: None,
+        });
+    }
+
+    // 最後の入力に注釈を付ける
+    pub fn annotate(&mut self, text: &str) {
+        if let Some(ev) = self.events.last_mut() {
+            ev.comment = Some(text.to_owned());
+        }
+    }
+
+    pub fn new_game(&self) -> Game {
+        match &self.board {
+            Some(board) => Game::with_board(self.seed, self.config, board.clone()),
+            None => Game::new(self.seed, self.config),
+        }
+    }
+
+    // 全ての入力を与え終えた状態のゲーム
+    pub fn play(&self) -> Game {
+        let mut game = self.new_game();
+        for ev in &self.events {
+            ev.action.apply(&mut game, ev.t);
+        }
+        game
+    }
+}
+
+// 記録の途中の局面へ素早く移るため、INTERVAL 手ごとのゲームを取っておく
+#[derive(Debug)]
+pub struct Timeline {
+    replay: Replay,
+    // 盤面を変えた入力の位置
+    moves: Vec<usize>,
+    // i * INTERVAL 手指したゲームと、次に与える入力の位置
+    snapshots: Vec<(Game, usize)>,
+}
+
+impl Timeline {
+    const INTERVAL: usize = 10;
+
+    pub fn new(replay: Replay) -> Self {
+        let mut game = replay.new_game();
+        let mut moves = vec![];
+        let mut snapshots = vec![(game.clone(), 0)];
+        for (i, ev) in replay.events.iter().enumerate() {
+            if !ev.action.apply(&mut game, ev.t) {
+                continue;
+            }
+            moves.push(i);
+            if moves.len() % Self::INTERVAL == 0 {
+                snapshots.push((game.clone(), i + 1));
+            }
+        }
+        Self {
+            replay,
+            moves,
+            snapshots,
+        }
+    }
+
+    // 盤面を変えた手の数
+    pub fn move_count(&self) -> usize {
+        self.moves.len()
+    }
+
+    // n 手目を指した入力の位置
+    pub fn move_event(&self, n: usize) -> &Event {
+        &self.replay.events[self.moves[n - 1]]
+    }
+
+    // 入力の位置 next より前に指した手の数
+    pub fn moves_before(&self, next: usize) -> usize {
+        self.moves.iter().take_while(|&&i| i < next).count()
+    }
+
+    // n 手指したゲームと、次に与える入力の位置。近いスナップショットから打ち直す。
+    pub fn game_at(&self, n: usize) -> (Game, usize) {
+        let n = n.min(self.moves.len());
+        let (game, start) = &self.snapshots[n / Self::INTERVAL];
+        let mut game = game.clone();
+        let end = match n {
+            0 => 0,
+            n => self.moves[n - 1] + 1,
+        };
+        for ev in &self.replay.events[*start..end] {
+            ev.action.apply(&mut game, ev.t);
+        }
+        (game, end)
+    }
+}
+
+// 過去のプレイをタイマーに同期して再生する
+#[derive(Debug)]
+pub struct Ghost {
+    timeline: Timeline,
+    game: Game,
+    next: usize,
+    cursor: Option<(usize, usize)>,
+    // 直前に消したグループとその時刻
+    erased: Option<(Duration, Vec<(usize, usize)>)>,
+    // 直前にクリックした手の表記
+    last_move: Option<String>,
+    // 直前の手の注釈
+    comment: Option<String>,
+}
+
+impl Ghost {
+    // 消したグループを光らせる秒数
+    const FLASH_SECONDS: f32 = 0.5;
+
+    pub fn new(replay: Replay) -> Self {
+        let game = replay.new_game();
+        Self {
+            timeline: Timeline::new(replay),
+            game,
+            next: 0,
+            cursor: None,
+            erased: None,
+            last_move: None,
+            comment: None,
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        self.cursor
+    }
+
+    pub fn last_move(&self) -> Option<&str> {
+        self.last_move.as_deref()
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    // 時刻 now の時点で光らせるべきセル
+    pub fn flash(&self, now: Duration) -> &[(usize, usize)] {
+        match &self.erased {
+            Some((t, cells))
+                if Tween::new(*t, Self::FLASH_SECONDS, Ease::Linear).is_running(now) =>
+            {
+                cells
+            }
+            _ => &[],
+        }
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.timeline.move_count()
+    }
+
+    // これまでに指した手の数
+    pub fn moves_played(&self) -> usize {
+        self.timeline.moves_before(self.next)
+    }
+
+    // n 手指した局面に移り、その手の時刻を返す。先へも後へも移れる。
+    pub fn seek(&mut self, n: usize) -> Duration {
+        let n = n.min(self.move_count());
+        let (game, next) = self.timeline.game_at(n);
+        self.game = game;
+        self.next = next;
+        self.erased = None;
+        self.cursor = self.timeline.replay.events[..next]
+            .iter()
+            .rev()
+            .find_map(|ev| match ev.action {
+                Action::Cursor(x, y) | Action::Click(x, y) => Some((x, y)),
+                _ => None,
+            });
+        if n == 0 {
+            self.last_move = None;
+            self.comment = None;
+            return Duration::default();
+        }
+
+        let ev = self.timeline.move_event(n);
+        self.last_move = match ev.action {
+            Action::Click(x, y) => {
+                let (before, _) = self.timeline.game_at(n - 1);
+                Some(notation::format_move(before.board(), x, y))
+            }
+            _ => None,
+        };
+        self.comment = ev.comment.clone();
+        ev.t
+    }
+
+    // 次に盤面を操作する時刻。カーソルの移動は飛ばす。
+    pub fn next_move_time(&self) -> Option<Duration> {
+        self.timeline.replay.events[self.next..]
+            .iter()
+            .find(|ev| !matches!(ev.action, Action::Cursor(..)))
+            .map(|ev| ev.t)
+    }
+
+    pub fn advance(&mut self, now: Duration) {
+        while let Some(ev) = self.timeline.replay.events.get(self.next) {
+            if ev.t > now {
+                break;
+            }
+            self.next += 1;
+            if !matches!(ev.action, Action::Cursor(..)) {
+                self.comment = ev.comment.clone();
+            }
+
+            match ev.action {
+                Action::Cursor(x, y) => {
+                    self.cursor = Some((x, y));
+                }
+                Action::Click(x, y) => {
+                    self.cursor = Some((x, y));
+                    let cells = self.game.board().calc_component(x, y);
+                    let armed = self.game.powerups().armed().is_some();
+                    self.last_move = Some(notation::format_move(self.game.board(), x, y));
+                    if ev.action.apply(&mut self.game, ev.t) && !armed {
+                        self.erased = Some((ev.t, cells));
+                    }
+                }
+                Action::PowerUp(_) | Action::Rotate => {
+                    ev.action.apply(&mut self.game, ev.t);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+
+    use super::*;
+
+    #[test]
+    fn ghost() {
+        let config = GameConfig {
+            width: 6,
+            height: 4,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new(1, config);
+        let mut replay = Replay::new(&game);
+
+        let (x, y) = iproduct!(0..6, 0..4)
+            .find(|&(x, y)| !game.board().calc_component(x, y).is_empty())
+            .unwrap();
+        let cells = game.board().calc_component(x, y);
+        let t = Duration::from_secs(2);
+        replay.push(Duration::from_secs(1), Action::Cursor(x, y));
+        replay.push(t, Action::Click(x, y));
+        replay.annotate("first group");
+        assert!(game.click(x, y, t));
+        replay.score = game.score();
+
+        let mut ghost = Ghost::new(replay);
+        assert_eq!(ghost.next_move_time(), Some(t));
+        ghost.advance(Duration::from_millis(1500));
+        assert_eq!(ghost.cursor(), Some((x, y)));
+        assert_eq!(ghost.game().score(), 0);
+        assert_eq!(ghost.comment(), None);
+
+        ghost.advance(Duration::from_millis(2100));
+        assert_eq!(ghost.next_move_time(), None);
+        assert_eq!(ghost.comment(), Some("first group"));
+        assert_eq!(ghost.game().board(), game.board());
+        assert_eq!(ghost.game().score(), game.score());
+        assert_eq!(ghost.flash(Duration::from_millis(2100)), &cells[..]);
+        assert!(ghost.flash(Duration::from_secs(3)).is_empty());
+
+        let played = ghost.timeline.replay.play();
+        assert_eq!(played.board(), game.board());
+        assert_eq!(played.score(), game.score());
+    }
+
+    #[test]
+    fn seek() {
+        // 左の列から 1 列ずつ消せる盤面
+        let board = Board::parse("12 2\n121212121212\n121212121212\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 12,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_board(1, config, board);
+        let mut replay = Replay::new(&game);
+        for i in 0..12 {
+            let t = Duration::from_secs(i + 1);
+            replay.push(t, Action::Cursor(0, 1));
+            replay.push(t, Action::Click(0, 1));
+            if i == 10 {
+                replay.annotate("eleventh");
+            }
+        }
+
+        let timeline = Timeline::new(replay.clone());
+        assert_eq!(timeline.move_count(), 12);
+        assert_eq!(timeline.snapshots.len(), 2);
+        for n in 0..=12 {
+            let (game, next) = timeline.game_at(n);
+            let expected = Replay {
+                events: replay.events[..2 * n].to_vec(),
+                ..replay.clone()
+            }
+            .play();
+            assert_eq!(next, 2 * n);
+            assert_eq!(game.board(), expected.board());
+            assert_eq!(game.score(), expected.score());
+        }
+
+        let mut ghost = Ghost::new(replay);
+        ghost.advance(Duration::from_secs(12));
+        assert_eq!(ghost.moves_played(), 12);
+        assert_eq!(ghost.seek(11), Duration::from_secs(11));
+        assert_eq!(ghost.moves_played(), 11);
+        assert_eq!(ghost.comment(), Some("eleventh"));
+        assert_eq!(ghost.last_move(), Some("A@a1"));
+        assert_eq!(ghost.game().board().tile_count(), 2);
+        assert_eq!(ghost.seek(0), Duration::default());
+        assert_eq!(ghost.last_move(), None);
+        assert_eq!(ghost.cursor(), None);
+
+        // 戻ったところから再生を続けられる
+        ghost.advance(Duration::from_secs(3));
+        assert_eq!(ghost.moves_played(), 3);
+        assert_eq!(ghost.game().board().tile_count(), 18);
+    }
+
+    #[test]
+    fn given_board() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 3,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let mut game = Game::with_board(9, config, board.clone());
+        let mut replay = Replay::new(&game);
+        let t = Duration::from_secs(1);
+        assert!(game.click(0, 1, t));
+        replay.push(t, Action::Click(0, 1));
+
+        let json = serde_json::to_string(&replay).unwrap();
+        assert!(!json.contains("comment"));
+        let replay: Replay = serde_json::from_str(&json).unwrap();
+        assert_eq!(replay.new_game().board(), &board);
+        assert_eq!(replay.play().board(), game.board());
+    }
+}
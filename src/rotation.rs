@@ -0,0 +1,189 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDateTime, Utc};
+
+use crate::challenge::Challenge;
+use crate::difficulty::Difficulty;
+use crate::game::GameConfig;
+use crate::generator;
+use crate::hash;
+
+// 日替わり・週替わりの盤面。日付から決まるので全員が同じ盤面を遊ぶ。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Daily,
+    // 難しいと見積もられた盤面だけを選ぶ日替わり
+    HardDaily,
+    Weekly,
+}
+
+impl Rotation {
+    pub fn config(self) -> GameConfig {
+        match self {
+            Self::Daily => GameConfig {
+                width: 15,
+                height: 10,
+                colors: 4,
+                ..GameConfig::default()
+            },
+            Self::HardDaily => GameConfig::default(),
+            Self::Weekly => GameConfig {
+                width: 25,
+                height: 12,
+                colors: 5,
+                ..GameConfig::default()
+            },
+        }
+    }
+
+    pub fn target(self) -> Option<Difficulty> {
+        match self {
+            Self::HardDaily => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn current(self) -> Period {
+        self.period_at(Utc::now().naive_utc())
+    }
+
+    // 時刻 now (UTC) を含む期間
+    pub fn period_at(self, now: NaiveDateTime) -> Period {
+        let today = now.date();
+        let midnight = |date: chrono::NaiveDate| date.and_hms_opt(0, 0, 0).expect("internal error");
+
+        match self {
+            Self::Daily | Self::HardDaily => Period {
+                rotation: self,
+                id: today.format("%Y-%m-%d").to_string(),
+                start: midnight(today),
+                end: midnight(today + Duration::days(1)),
+            },
+            Self::Weekly => {
+                let week = today.iso_week();
+                let monday =
+                    today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+                Period {
+                    rotation: self,
+                    id: format!("{}-W{:02}", week.year(), week.week()),
+                    start: midnight(monday),
+                    end: midnight(monday + Duration::weeks(1)),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Daily => "Daily",
+            Self::HardDaily => "HardDaily",
+            Self::Weekly => "Weekly",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Period {
+    pub rotation: Rotation,
+    pub id: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl Period {
+    // 期間 ID から決まるシード (FNV-1a)
+    pub fn seed(&self) -> u64 {
+        let key = format!("{}:{}", self.rotation, self.id);
+        hash::fnv1a(key.bytes())
+    }
+
+    pub fn challenge(&self) -> Challenge {
+        let config = self.rotation.config();
+        let seed = match self.rotation.target() {
+            Some(target) => generator::find_seed(config, self.seed(), target),
+            None => self.seed(),
+        };
+        Challenge { config, seed }
+    }
+
+    // 次の盤面に切り替わるまでの時間
+    pub fn remaining(&self) -> Duration {
+        (self.end - Utc::now().naive_utc()).max(Duration::zero())
+    }
+
+    pub fn is_over(&self) -> bool {
+        Utc::now().naive_utc() >= self.end
+    }
+}
+
+// 1d02:03:04
+pub fn format_countdown(d: Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let hms = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    if days > 0 {
+        format!("{}d{}", days, hms)
+    } else {
+        hms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .and_then(|date| date.and_hms_opt(h, 0, 0))
+            .unwrap()
+    }
+
+    #[test]
+    fn weekly() {
+        // 2021-01-03 (日) は 2020-W53
+        let period = Rotation::Weekly.period_at(at(2021, 1, 3, 12));
+        assert_eq!(period.id, "2020-W53");
+        assert_eq!(period.start, at(2020, 12, 28, 0));
+        assert_eq!(period.end, at(2021, 1, 4, 0));
+
+        let next = Rotation::Weekly.period_at(at(2021, 1, 4, 0));
+        assert_eq!(next.id, "2021-W01");
+        assert_ne!(next.seed(), period.seed());
+        assert_eq!(
+            Rotation::Weekly.period_at(at(2021, 1, 10, 23)).seed(),
+            next.seed()
+        );
+    }
+
+    #[test]
+    fn daily() {
+        let period = Rotation::Daily.period_at(at(2020, 2, 29, 5));
+        assert_eq!(period.id, "2020-02-29");
+        assert_eq!(period.end, at(2020, 3, 1, 0));
+        assert_ne!(
+            period.seed(),
+            Rotation::Weekly.period_at(at(2020, 2, 29, 5)).seed()
+        );
+
+        let hard = Rotation::HardDaily.period_at(at(2020, 2, 29, 5));
+        assert_eq!(hard.id, period.id);
+        let challenge = hard.challenge();
+        let game = crate::game::Game::new(challenge.seed, challenge.config);
+        let report = crate::difficulty::estimate_difficulty(game.board());
+        assert_eq!(report.difficulty, Difficulty::Hard);
+    }
+
+    #[test]
+    fn countdown() {
+        assert_eq!(format_countdown(Duration::seconds(3723)), "01:02:03");
+        assert_eq!(
+            format_countdown(Duration::seconds(2 * 86400 + 59)),
+            "2d00:00:59"
+        );
+        assert_eq!(format_countdown(Duration::seconds(-5)), "00:00:00");
+    }
+}
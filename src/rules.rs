@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+// n 個のタイルからなるグループを消したときの得点
+pub fn group_score(n: usize) -> i32 {
+    if n < 2 {
+        return 0;
+    }
+    (n - 1).pow(2) as i32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Rules {
+    pub streak: bool,
+    pub time_pressure: bool,
+    // 盤面を回せる。ROTATE_EVERY 手ごとにも勝手に回る。
+    #[serde(default)]
+    pub rotate: bool,
+    // Earthquake::EVERY 手ごとに盤面の一部がかき混ぜられる
+    #[serde(default)]
+    pub earthquake: bool,
+}
+
+// 回転ルールで盤面が勝手に回るまでの手数
+pub const ROTATE_EVERY: usize = 5;
+
+impl Rules {
+    const STREAK: u8 = 1 << 0;
+    const TIME_PRESSURE: u8 = 1 << 1;
+    const ROTATE: u8 = 1 << 2;
+    const EARTHQUAKE: u8 = 1 << 3;
+
+    pub fn to_bits(self) -> u8 {
+        let mut res = 0;
+        if self.streak {
+            res |= Self::STREAK;
+        }
+        if self.time_pressure {
+            res |= Self::TIME_PRESSURE;
+        }
+        if self.rotate {
+            res |= Self::ROTATE;
+        }
+        if self.earthquake {
+            res |= Self::EARTHQUAKE;
+        }
+        res
+    }
+
+    // 未知のビットが立っていれば None
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        let known = Self::STREAK | Self::TIME_PRESSURE | Self::ROTATE | Self::EARTHQUAKE;
+        if bits & !known != 0 {
+            return None;
+        }
+        Some(Self {
+            streak: bits & Self::STREAK != 0,
+            time_pressure: bits & Self::TIME_PRESSURE != 0,
+            rotate: bits & Self::ROTATE != 0,
+            earthquake: bits & Self::EARTHQUAKE != 0,
+        })
+    }
+}
+
+// 連続して `THRESHOLD` 個以上消すと倍率が上がっていく。小さい手でリセット。
+#[derive(Debug, Clone, Default)]
+pub struct Streak {
+    len: u32,
+}
+
+impl Streak {
+    pub const THRESHOLD: usize = 5;
+    pub const MAX_MULTIPLIER: i32 = 5;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 次の手が条件を満たしたときに掛かる倍率
+    pub fn multiplier(&self) -> i32 {
+        (self.len as i32 + 1).min(Self::MAX_MULTIPLIER)
+    }
+
+    // n 個消す手を記録し、その手の得点倍率を返す
+    pub fn record(&mut self, n: usize) -> i32 {
+        if n < Self::THRESHOLD {
+            self.len = 0;
+            return 1;
+        }
+
+        let res = self.multiplier();
+        self.len += 1;
+        res
+    }
+}
+
+// 地震の予定。EVERY 手ごとに SIZE 四方の範囲を揺らす。
+#[derive(Debug, Clone, Default)]
+pub struct Earthquake {
+    moves: usize,
+    // 直前の手のあとに揺れた範囲 (x, y, w, h)
+    region: Option<(usize, usize, usize, usize)>,
+}
+
+impl Earthquake {
+    pub const EVERY: usize = 6;
+    pub const SIZE: usize = 4;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 手を打ったことを記録する。揺れる番なら true。
+    pub fn tick(&mut self) -> bool {
+        self.moves += 1;
+        self.region = None;
+        self.moves % Self::EVERY == 0
+    }
+
+    // 地震の起きない手 (Z で回すなど) を打ったので、前の揺れを忘れる
+    pub fn calm(&mut self) {
+        self.region = None;
+    }
+
+    pub fn strike(&mut self, region: (usize, usize, usize, usize)) {
+        self.region = Some(region);
+    }
+
+    pub fn region(&self) -> Option<(usize, usize, usize, usize)> {
+        self.region
+    }
+}
+
+// 考慮時間による得点の減衰。
+// `GRACE` 以内なら満点、そこから `LIMIT` にかけて `MIN_FACTOR` まで線形に減る。
+#[derive(Debug)]
+pub struct TimePressure;
+
+impl TimePressure {
+    pub const GRACE: Duration = Duration::from_secs(3);
+    pub const LIMIT: Duration = Duration::from_secs(15);
+    pub const MIN_FACTOR: f32 = 0.1;
+
+    pub fn factor(elapsed: Duration) -> f32 {
+        if elapsed <= Self::GRACE {
+            return 1.0;
+        }
+        if elapsed >= Self::LIMIT {
+            return Self::MIN_FACTOR;
+        }
+
+        let t = (elapsed - Self::GRACE).as_secs_f32() / (Self::LIMIT - Self::GRACE).as_secs_f32();
+        1.0 - (1.0 - Self::MIN_FACTOR) * t
+    }
+
+    pub fn apply(score: i32, elapsed: Duration) -> i32 {
+        (score as f32 * Self::factor(elapsed)).round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streak() {
+        let mut streak = Streak::new();
+        assert_eq!(streak.record(5), 1);
+        assert_eq!(streak.record(8), 2);
+        assert_eq!(streak.record(6), 3);
+        assert_eq!(streak.multiplier(), 4);
+        assert_eq!(streak.record(2), 1);
+        assert_eq!(streak.multiplier(), 1);
+        assert_eq!(streak.record(5), 1);
+
+        for _ in 0..10 {
+            streak.record(10);
+        }
+        assert_eq!(streak.multiplier(), Streak::MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn bits() {
+        let rules = Rules {
+            streak: true,
+            time_pressure: false,
+            rotate: true,
+            earthquake: false,
+        };
+        assert_eq!(rules.to_bits(), 0b101);
+        assert_eq!(Rules::from_bits(0b101), Some(rules));
+        assert_eq!(Rules::from_bits(0b1_0000), None);
+    }
+
+    #[test]
+    fn earthquake() {
+        let mut quake = Earthquake::new();
+        for _ in 1..Earthquake::EVERY {
+            assert!(!quake.tick());
+        }
+        assert!(quake.tick());
+        quake.strike((1, 2, 3, 4));
+        assert_eq!(quake.region(), Some((1, 2, 3, 4)));
+        assert!(!quake.tick());
+        assert_eq!(quake.region(), None);
+    }
+
+    #[test]
+    fn time_pressure() {
+        assert_eq!(TimePressure::apply(100, Duration::from_secs(1)), 100);
+        assert_eq!(TimePressure::apply(100, Duration::from_secs(3)), 100);
+        assert_eq!(TimePressure::apply(100, Duration::from_secs(9)), 55);
+        assert_eq!(TimePressure::apply(100, Duration::from_secs(60)), 10);
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Replay, Ticks};
+use gilrs::Gilrs;
+
+// 大きく稼いだ手でゲームパッドを震わせる。
+// ggez はフォースフィードバックを扱えないので、ゲームパッドを別に開く。
+pub struct Rumble {
+    gilrs: Gilrs,
+    // 再生中の振動。捨てると止まるので次を鳴らすまで持っておく。
+    effect: Option<Effect>,
+}
+
+impl fmt::Debug for Rumble {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rumble")
+            .field("playing", &self.effect.is_some())
+            .finish()
+    }
+}
+
+impl Rumble {
+    // これ未満の得点では震わせない
+    const MIN_SCORE: i32 = 25;
+    // この得点で最も強く長くなる
+    const FULL_SCORE: i32 = 400;
+    const MIN_MS: u32 = 80;
+    const MAX_MS: u32 = 400;
+
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Self {
+                gilrs,
+                effect: None,
+            }),
+            Err(e) => {
+                eprintln!("cannot open gamepads for rumble: {}", e);
+                None
+            }
+        }
+    }
+
+    // 得点に応じた振動の強さと長さ(ミリ秒)。小さい得点なら None。
+    fn strength(score: i32) -> Option<(u16, u32)> {
+        if score < Self::MIN_SCORE {
+            return None;
+        }
+        let t = (score - Self::MIN_SCORE) as f32 / (Self::FULL_SCORE - Self::MIN_SCORE) as f32;
+        let t = t.min(1.0);
+        let magnitude = (f32::from(u16::MAX) * (0.3 + 0.7 * t)) as u16;
+        let ms = Self::MIN_MS + ((Self::MAX_MS - Self::MIN_MS) as f32 * t) as u32;
+        Some((magnitude, ms))
+    }
+
+    // 接続の変化を拾う。毎フレーム呼ぶ。
+    pub fn update(&mut self) {
+        while self.gilrs.next_event().is_some() {}
+    }
+
+    // 振動に対応したゲームパッドが繋がっていれば、score に応じて震わせる
+    pub fn on_score(&mut self, score: i32) {
+        let (magnitude, ms) = match Self::strength(score) {
+            Some(strength) => strength,
+            None => return,
+        };
+        let ids: Vec<_> = self
+            .gilrs
+            .gamepads()
+            .filter(|(_, pad)| pad.is_ff_supported())
+            .map(|(id, _)| id)
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong { magnitude },
+                scheduling: Replay {
+                    play_for: Ticks::from_ms(ms),
+                    ..Replay::default()
+                },
+                envelope: Default::default(),
+            })
+            .gamepads(&ids)
+            .finish(&mut self.gilrs);
+        let res = effect.and_then(|effect| {
+            effect.play()?;
+            Ok(effect)
+        });
+        match res {
+            Ok(effect) => self.effect = Some(effect),
+            Err(e) => eprintln!("cannot rumble: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strength() {
+        assert_eq!(Rumble::strength(0), None);
+        assert_eq!(Rumble::strength(Rumble::MIN_SCORE - 1), None);
+        let (weak, short) = Rumble::strength(Rumble::MIN_SCORE).unwrap();
+        assert_eq!(short, Rumble::MIN_MS);
+        let (strong, long) = Rumble::strength(Rumble::FULL_SCORE * 2).unwrap();
+        assert_eq!((strong, long), (u16::MAX, Rumble::MAX_MS));
+        assert!(weak < strong);
+    }
+}
@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh};
+use ggez::{Context, GameResult};
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::board::Board;
+use crate::game::GameConfig;
+use crate::game_state::GameState;
+use crate::highscore::HighScores;
+use crate::input::Action;
+use crate::scene::{Scene, Transition};
+use crate::speedrun;
+
+// 小さな盤面を次々に出し、決まった大きさ以上のグループを見つけさせる。
+// 見つけるたびに連続正解が伸び、3 回間違えるか時間切れで終わる。
+#[derive(Debug, Clone)]
+pub struct Run {
+    rng: Pcg32,
+    pub board: Board,
+    // 消すグループに要る大きさ。盤面で一番大きいグループの大きさ。
+    pub need: usize,
+    pub solved: i32,
+    pub misses: u32,
+}
+
+impl Run {
+    const SIZE: usize = 6;
+    const COLORS: u8 = 4;
+    // 探しがいがあるよう、一番大きいグループがこれより小さい盤面は出さない
+    const MIN_NEED: usize = 4;
+    pub const MAX_MISSES: u32 = 3;
+    pub const TIME_LIMIT: Duration = Duration::from_secs(120);
+
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg32::seed_from_u64(seed);
+        let (board, need) = Self::deal(&mut rng);
+        Self {
+            rng,
+            board,
+            need,
+            solved: 0,
+            misses: 0,
+        }
+    }
+
+    fn deal(rng: &mut Pcg32) -> (Board, usize) {
+        loop {
+            let board = Board::random_with(Self::SIZE, Self::SIZE, Self::COLORS, rng);
+            let need = board.groups().iter().map(|&(_, n)| n).max().unwrap_or(0);
+            if need >= Self::MIN_NEED {
+                return (board, need);
+            }
+        }
+    }
+
+    // (x, y) のグループで答える。足りる大きさなら true。当たり外れによらず次の盤面に進む。
+    pub fn answer(&mut self, x: usize, y: usize) -> bool {
+        let ok = self.board.calc_component(x, y).len() >= self.need;
+        if ok {
+            self.solved += 1;
+        } else {
+            self.misses += 1;
+        }
+        let (board, need) = Self::deal(&mut self.rng);
+        self.board = board;
+        self.need = need;
+        ok
+    }
+
+    pub fn is_over(&self, elapsed: Duration) -> bool {
+        self.misses >= Self::MAX_MISSES || elapsed >= Self::TIME_LIMIT
+    }
+}
+
+// 遊んでいる間と、終わって連続正解の表を見せている間
+#[derive(Debug)]
+enum Phase {
+    Playing { run: Run, start: Duration },
+    // 表の中の順位。圏外なら None。
+    Over { solved: i32, rank: Option<usize> },
+}
+
+// パズルラッシュの場面。盤面の大きさを変えるので、抜けるときは元の設定で新しいゲームを始める。
+#[derive(Debug)]
+pub struct Rush {
+    phase: Phase,
+    // 前の手の当たり外れ
+    last: Option<bool>,
+    config: GameConfig,
+}
+
+impl Rush {
+    pub fn new(state: &mut GameState) -> Self {
+        let mut this = Self {
+            phase: Phase::Over {
+                solved: 0,
+                rank: None,
+            },
+            last: None,
+            config: state.game().config(),
+        };
+        this.restart(state);
+        this
+    }
+
+    fn restart(&mut self, state: &mut GameState) {
+        let run = Run::new(rand::random());
+        state.play_board(run.board.clone());
+        self.phase = Phase::Playing {
+            run,
+            start: state.anim().now(),
+        };
+        self.last = None;
+    }
+
+    fn leave(&self, state: &mut GameState) -> Transition {
+        state.play_generated(self.config);
+        Transition::Pop
+    }
+
+    fn draw_scores(
+        state: &GameState,
+        ctx: &mut Context,
+        y: f32,
+        rank: Option<usize>,
+    ) -> GameResult {
+        let font = state.font();
+        let line = GameState::LINE_HEIGHT;
+        for i in 0..HighScores::CAPACITY {
+            let yy = y + line * i as f32;
+            if rank == Some(i) {
+                let rect = graphics::Rect::new(6.0, yy, 300.0, line);
+                let color = Color::from_rgba(0xff, 0xff, 0x00, 0x60);
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+            let text = match state.rush_scores().entries().get(i) {
+                Some(entry) => {
+                    let time = entry
+                        .splits
+                        .finish
+                        .map_or_else(|| "--:--.-".to_owned(), speedrun::format_duration);
+                    format!("{:>2}. {:>4} {} {}", i + 1, entry.score, time, entry.name)
+                }
+                None => format!("{:>2}.    -", i + 1),
+            };
+            font.draw_str(ctx, 10.0, yy, text)?;
+        }
+        Ok(())
+    }
+}
+
+impl Scene for Rush {
+    fn update(&mut self, state: &mut GameState) -> Transition {
+        if let Phase::Playing { run, start } = &self.phase {
+            let elapsed = state.anim().now() - *start;
+            if run.is_over(elapsed) {
+                let solved = run.solved;
+                let rank = state.record_rush(solved, elapsed.min(Run::TIME_LIMIT));
+                self.phase = Phase::Over { solved, rank };
+            }
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let area = state.board_area();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), state.screen(), graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let line = GameState::LINE_HEIGHT;
+
+        let (run, start) = match &self.phase {
+            Phase::Playing { run, start } => (run, *start),
+            Phase::Over { solved, rank } => {
+                let font = state.font();
+                let text = format!("Puzzle rush over. Streak: {}", solved);
+                font.draw_str(ctx, 10.0, 10.0, text)?;
+                font.draw_str(ctx, 10.0, 10.0 + line, "Best streaks")?;
+                Self::draw_scores(state, ctx, 10.0 + line * 3.0, *rank)?;
+                let y = 10.0 + line * (HighScores::CAPACITY + 4) as f32;
+                return font.draw_str(ctx, 10.0, y, "Enter:Again Esc:Back");
+            }
+        };
+        state.draw_board(ctx, &run.board)?;
+
+        let left = Run::TIME_LIMIT
+            .checked_sub(state.anim().now() - start)
+            .unwrap_or_default();
+        let last = match self.last {
+            Some(true) => "  Good!",
+            Some(false) => "  Miss!",
+            None => "",
+        };
+        let font = state.font();
+        let y = area.y + area.h + 10.0;
+        font.draw_str(
+            ctx,
+            10.0,
+            y,
+            format!(
+                "Puzzle rush  Streak: {}  Misses: {}/{}  Time: {}{}",
+                run.solved,
+                run.misses,
+                Run::MAX_MISSES,
+                speedrun::format_duration(left),
+                last
+            ),
+        )?;
+        font.draw_str(
+            ctx,
+            10.0,
+            y + line,
+            format!("Click a group of {} or more tiles.", run.need),
+        )?;
+        font.draw_str(ctx, 10.0, y + line * 2.0, "Esc:Quit")
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if repeat {
+            return Transition::None;
+        }
+        if action == Action::Cancel {
+            return self.leave(state);
+        }
+        match (&mut self.phase, action) {
+            (Phase::Over { .. }, Action::Confirm) => self.restart(state),
+            (Phase::Playing { run, .. }, Action::Click(x, y)) => {
+                if let Some((x, y)) = state.cell_at(x, y) {
+                    if run.board.at(x, y) != 0 {
+                        self.last = Some(run.answer(x, y));
+                        state.play_board(run.board.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run() {
+        let mut run = Run::new(1);
+        assert_eq!(run.board.width(), Run::SIZE);
+        assert!(run.need >= Run::MIN_NEED);
+        assert_eq!(Run::new(1).board, run.board);
+
+        // 一番大きいグループなら当たり
+        let groups = run.board.groups();
+        let &(big, _) = groups.iter().find(|&&(_, n)| n == run.need).unwrap();
+        assert!(run.answer(big.0, big.1));
+        assert_eq!(run.solved, 1);
+
+        // 小さいグループなら外れ。3 回外れると終わる。
+        for i in 0..Run::MAX_MISSES {
+            assert!(!run.is_over(Duration::default()));
+            let board = run.board.clone();
+            let (x, y) = (0..Run::SIZE * Run::SIZE)
+                .map(|i| (i % Run::SIZE, i / Run::SIZE))
+                .find(|&(x, y)| board.calc_component(x, y).len() < run.need)
+                .unwrap();
+            assert!(!run.answer(x, y));
+            assert_eq!(run.misses, i + 1);
+        }
+        assert!(run.is_over(Duration::default()));
+        assert!(Run::new(2).is_over(Run::TIME_LIMIT));
+    }
+}
@@ -0,0 +1,1171 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ggez::event::{self, Button, GamepadId, KeyCode, KeyMods, MouseButton};
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh, Rect};
+use ggez::{timer, Context, GameResult};
+
+use crate::analysis::Analysis;
+use crate::anim::{AnimClock, Ease, Tween};
+use crate::challenge::Challenge;
+use crate::crt::Crt;
+use crate::curriculum::Progress;
+use crate::editor::Editor;
+use crate::font::Align;
+use crate::game_state::GameState;
+use crate::highlight;
+use crate::input::{self, Action, KeyMap};
+use crate::layout::{self, Anchor, Layout};
+use crate::level_select::LevelSelect;
+use crate::profiling;
+use crate::replay::Ghost;
+use crate::rush::Rush;
+use crate::sim::{Clock, ManualClock};
+use crate::speedrun;
+use crate::stats::Stats;
+use crate::suspend::{Choice, Suspended};
+use crate::tutorial::{Trigger, Tutorial};
+use crate::widget::{Form, Response, Widget};
+
+// 場面の切り替え
+pub enum Transition {
+    None,
+    Push(Box<dyn Scene>),
+    Pop,
+    Replace(Box<dyn Scene>),
+}
+
+// タイトル、ゲーム中、一時停止などの画面。
+// 場面をまたいで使う状態は GameState が持ち、場面はその画面だけの状態を持つ。
+pub trait Scene: fmt::Debug {
+    fn update(&mut self, _state: &mut GameState) -> Transition {
+        Transition::None
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult;
+
+    // repeat はキーを押し続けたときの繰り返し
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition;
+
+    fn text_input(&mut self, _state: &mut GameState, _ch: char) {}
+
+    // 下の場面の上に重ねて描くか
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    // 一番上にある間、ゲームの時計や演出を止めるか
+    fn pauses_game(&self) -> bool {
+        false
+    }
+}
+
+// 場面を切り替えたときの演出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EffectKind {
+    // 黒から明るくする
+    Fade,
+    // 重ねる場面を下から滑り込ませる
+    Slide,
+}
+
+impl EffectKind {
+    fn tween(self, clock: &AnimClock) -> Tween {
+        let seconds = match self {
+            Self::Fade => 0.25,
+            Self::Slide => 0.3,
+        };
+        clock.tween(seconds, Ease::OutCubic)
+    }
+}
+
+// 場面を積んだもの。入力は一番上の場面だけが受け取る。
+// ダイアログは場面より前に入力を受け取り、全ての場面の上に描く。
+#[derive(Debug)]
+pub struct SceneStack {
+    state: GameState,
+    scenes: Vec<Box<dyn Scene>>,
+    effect: Option<(EffectKind, Tween)>,
+    keys: KeyMap,
+    // 後処理。設定で有効になったら作る。作れなければ設定を切るまで試さない。
+    crt: Option<Crt>,
+    crt_failed: bool,
+}
+
+impl SceneStack {
+    // 描画の頻度によらず、1 秒にこの回数だけ更新する
+    const UPDATES_PER_SECOND: u32 = 60;
+    const STEP: Duration = Duration::from_nanos(1_000_000_000 / Self::UPDATES_PER_SECOND as u64);
+    // 1 フレームで追いつく更新の上限。止まっていた間の分はそれ以上追わずに捨てる。
+    const MAX_STEPS_PER_FRAME: u32 = 5;
+
+    pub fn new(state: GameState, first: Box<dyn Scene>) -> Self {
+        Self {
+            state,
+            scenes: vec![first],
+            effect: None,
+            keys: KeyMap::default(),
+            crt: None,
+            crt_failed: false,
+        }
+    }
+
+    // 起動直後から最初の場面の上に重ねる
+    pub fn push(&mut self, scene: Box<dyn Scene>) {
+        self.scenes.push(scene);
+    }
+
+    fn sync_crt(&mut self, ctx: &mut Context) {
+        if !self.state.crt() {
+            self.crt = None;
+            self.crt_failed = false;
+            return;
+        }
+        if self.crt.is_some() || self.crt_failed {
+            return;
+        }
+        match Crt::new(ctx) {
+            Ok(crt) => self.crt = Some(crt),
+            Err(e) => {
+                eprintln!("cannot enable CRT filter: {}", e);
+                self.crt_failed = true;
+            }
+        }
+    }
+
+    fn to_screen(ctx: &Context, x: f32, y: f32) -> (f32, f32) {
+        let screen = graphics::screen_coordinates(ctx);
+        layout::window_to_screen(graphics::size(ctx), screen, x, y)
+    }
+
+    fn dispatch(&mut self, action: Action, repeat: bool) {
+        // 全画面の切り替えはどの場面でも効く
+        if action == Action::ToggleFullscreen {
+            if !repeat {
+                self.state.toggle_fullscreen();
+            }
+            return;
+        }
+        if self.state.dialogs_open() {
+            if !repeat {
+                self.state.dialog_action(action);
+            }
+            return;
+        }
+        let scene = self.scenes.last_mut().expect("no scene");
+        let transition = scene.action(&mut self.state, action, repeat);
+        self.apply(transition);
+    }
+
+    fn apply(&mut self, transition: Transition) {
+        let kind = match transition {
+            Transition::None => return,
+            Transition::Push(scene) => {
+                let kind = if scene.is_overlay() {
+                    EffectKind::Slide
+                } else {
+                    EffectKind::Fade
+                };
+                self.scenes.push(scene);
+                Some(kind)
+            }
+            // 最後の場面は残す
+            Transition::Pop if self.scenes.len() > 1 => {
+                let scene = self.scenes.pop().expect("no scene");
+                // 重ねていた場面を閉じるときは下の場面がそのまま見えるので演出しない
+                Some(EffectKind::Fade).filter(|_| !scene.is_overlay())
+            }
+            Transition::Pop => None,
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+                Some(EffectKind::Fade)
+            }
+        };
+        let anim = self.state.anim();
+        self.effect = kind
+            .filter(|_| !self.state.reduce_motion())
+            .map(|kind| (kind, kind.tween(anim)));
+    }
+}
+
+impl event::EventHandler for SceneStack {
+    // 前回から経った時間の分だけ決まった間隔の更新を繰り返す。描画が少し遅れても追いつくが、
+    // 最小化などで長く止まっていたら MAX_STEPS_PER_FRAME 回で諦める。
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        let _scope = profiling::scope("frame::update");
+        let mut steps = 0;
+        while timer::check_update_time(ctx, Self::UPDATES_PER_SECOND) {
+            // 溜まった時間を使い切るまで回して捨てる
+            if steps == Self::MAX_STEPS_PER_FRAME {
+                continue;
+            }
+            steps += 1;
+            if self.scenes.last().expect("no scene").pauses_game() {
+                self.state.run_commands(ctx);
+            } else {
+                self.state.update(ctx, Self::STEP)?;
+            }
+            let scene = self.scenes.last_mut().expect("no scene");
+            let transition = scene.update(&mut self.state);
+            self.apply(transition);
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let _scope = profiling::scope("frame::draw");
+        self.sync_crt(ctx);
+        if let Some(crt) = &self.crt {
+            crt.begin(ctx);
+        }
+        graphics::clear(ctx, self.state.background());
+        {
+            let _scope = profiling::scope("frame::backdrop");
+            self.state.draw_backdrop(ctx)?;
+        }
+
+        // 重ねて描く場面の下にある最初の不透明な場面から描く
+        let start = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        let now = self.state.anim().now();
+        let effect = self
+            .effect
+            .and_then(|(kind, tween)| Some((kind, tween.progress(now)?)));
+        if effect.is_none() {
+            self.effect = None;
+        }
+
+        let screen = self.state.screen();
+        let top = self.scenes.len() - 1;
+        let scope = profiling::scope("frame::scenes");
+        for (i, scene) in self.scenes.iter_mut().enumerate().skip(start) {
+            match effect {
+                // 画面の座標をずらして、一番上の場面だけを下にずらして描く
+                Some((EffectKind::Slide, t)) if i == top => {
+                    let offset = (1.0 - t) * screen.h;
+                    let shifted = Rect::new(screen.x, screen.y - offset, screen.w, screen.h);
+                    graphics::set_screen_coordinates(ctx, shifted)?;
+                    scene.draw(&mut self.state, ctx)?;
+                    graphics::set_screen_coordinates(ctx, screen)?;
+                }
+                _ => scene.draw(&mut self.state, ctx)?,
+            }
+        }
+        if let Some((EffectKind::Fade, t)) = effect {
+            let black = Color::new(0.0, 0.0, 0.0, 1.0 - t);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), screen, black)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+        self.state.draw_dialogs(ctx)?;
+        drop(scope);
+
+        let _scope = profiling::scope("frame::present");
+        if let Some(crt) = &self.crt {
+            crt.finish(ctx)?;
+        }
+        graphics::present(ctx)
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.state.resize(ctx, width, height);
+        if let Some(crt) = &mut self.crt {
+            if let Err(e) = crt.resize(ctx) {
+                eprintln!("cannot resize CRT filter: {}", e);
+                self.crt = None;
+                self.crt_failed = true;
+            }
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        let (x, y) = Self::to_screen(ctx, x, y);
+        if let Some(action) = input::mouse_button(button, x, y) {
+            self.dispatch(action, false);
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        let (x, y) = Self::to_screen(ctx, x, y);
+        self.dispatch(Action::Point(x, y), false);
+    }
+
+    fn key_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        keycode: KeyCode,
+        keymods: KeyMods,
+        repeat: bool,
+    ) {
+        if let Some(action) = self.keys.key(keycode, keymods) {
+            self.dispatch(action, repeat);
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, button: Button, _id: GamepadId) {
+        if let Some(action) = self.keys.button(button) {
+            self.dispatch(action, false);
+        }
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, ch: char) {
+        if self.state.dialogs_open() {
+            self.state.dialog_text(ch);
+            return;
+        }
+        let scene = self.scenes.last_mut().expect("no scene");
+        scene.text_input(&mut self.state, ch);
+    }
+
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        self.state.quit_event(ctx)
+    }
+}
+
+// 起動時のタイトル画面
+#[derive(Debug)]
+pub struct Title {
+    menu: Form,
+    // メニューに並べた最近の盤面
+    recent: Vec<PathBuf>,
+}
+
+impl Title {
+    const MENU_WIDTH: f32 = 160.0;
+
+    pub fn new(state: &GameState) -> Self {
+        let recent = state.recent_boards().to_vec();
+        let mut items = vec![Widget::button("Play"), Widget::button("Open board...")];
+        items.extend(recent.iter().map(|path| {
+            let name = path.file_name().unwrap_or_else(|| path.as_os_str());
+            Widget::button(format!("  {}", name.to_string_lossy()))
+        }));
+        items.extend(vec![
+            Widget::button("Levels"),
+            Widget::button("Puzzle rush"),
+            Widget::button("Settings"),
+            Widget::button("Quit"),
+        ]);
+        Self {
+            menu: Form::new(items, 0.0, 0.0, Self::MENU_WIDTH),
+            recent,
+        }
+    }
+
+    fn choose(&self, state: &mut GameState, item: usize) -> Transition {
+        let n = self.recent.len();
+        let next: Option<Box<dyn Scene>> = match item {
+            0 => welcome(state),
+            1 => {
+                if !state.open_board_dialog() {
+                    return Transition::None;
+                }
+                None
+            }
+            i if i < 2 + n => {
+                if !state.open_board(&self.recent[i - 2]) {
+                    return Transition::None;
+                }
+                None
+            }
+            i if i == 2 + n => Some(Box::new(LevelMenu::new(state))),
+            i if i == 3 + n => return Transition::Push(Box::new(Rush::new(state))),
+            i if i == 4 + n => Some(Box::new(SettingsMenu::new(state))),
+            _ => {
+                state.confirm_quit();
+                return Transition::None;
+            }
+        };
+        // タイトルを見ていた間は数えない
+        state.reset_clock(Duration::default());
+        let playing = Playing {
+            next,
+            ..Playing::default()
+        };
+        Transition::Replace(Box::new(playing))
+    }
+}
+
+// タイトルから遊び始めたときに出す画面。前回の続きがあれば再開を尋ね、初めてなら遊び方を説明する。
+fn welcome(state: &GameState) -> Option<Box<dyn Scene>> {
+    if let Some(suspended) = state.load_suspended() {
+        return Some(Box::new(ResumePrompt::new(suspended)));
+    }
+    if !state.tutorial_done() {
+        return Some(Box::new(TutorialOverlay::new(state)));
+    }
+    None
+}
+
+// 盤面を遊んでいる場面とゲームが終わった場面で、重ねる画面を開く操作
+fn open_mode(state: &GameState, action: Action) -> Option<Box<dyn Scene>> {
+    if state.in_tournament() {
+        return None;
+    }
+    match action {
+        Action::InputCode => Some(Box::new(CodeEntry::default())),
+        Action::LevelSelect => Some(Box::new(LevelMenu::new(state))),
+        Action::Confirm if state.pack_finished() => Some(Box::new(LevelMenu::new(state))),
+        Action::Settings => Some(Box::new(SettingsMenu::new(state))),
+        Action::Tutorial => Some(Box::new(TutorialOverlay::new(state))),
+        _ => None,
+    }
+}
+
+impl Scene for Title {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let area = Layout::new(state.screen());
+        let h = 40.0 + self.menu.height();
+        let rect = area.place(Anchor::Center, Self::MENU_WIDTH, h);
+
+        let yellow = Color::from_rgb(0xff, 0xff, 0x00);
+        let center = Align::Center(Self::MENU_WIDTH);
+        let font = state.font();
+        font.draw_str_with(ctx, rect.x, rect.y, "S A M E G A M E", yellow, center)?;
+        self.menu.move_to(rect.x, rect.y + 40.0);
+        self.menu.draw(ctx, font)
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if repeat {
+            return Transition::None;
+        }
+        match action {
+            Action::Cancel | Action::Quit => {
+                state.confirm_quit();
+                Transition::None
+            }
+            _ => match self.menu.on_action(action) {
+                Some((item, Response::Clicked)) => self.choose(state, item),
+                _ => Transition::None,
+            },
+        }
+    }
+}
+
+// 盤面を遊んでいる場面
+#[derive(Debug, Default)]
+pub struct Playing {
+    // 終わったゲームの結果を出したか
+    shown_result: bool,
+    // 始めてすぐ重ねる画面
+    next: Option<Box<dyn Scene>>,
+}
+
+impl Playing {
+    // scene を重ねた状態で始める
+    pub fn then(scene: Box<dyn Scene>) -> Self {
+        Self {
+            next: Some(scene),
+            ..Self::default()
+        }
+    }
+}
+
+impl Scene for Playing {
+    fn update(&mut self, state: &mut GameState) -> Transition {
+        if let Some(next) = self.next.take() {
+            return Transition::Push(next);
+        }
+        if !state.game().is_finished() {
+            self.shown_result = false;
+            return Transition::None;
+        }
+        if self.shown_result {
+            return Transition::None;
+        }
+        self.shown_result = true;
+        Transition::Push(Box::new(GameOver))
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        state.draw(ctx)
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if repeat {
+            state.action(action, repeat);
+            return Transition::None;
+        }
+        if let Some(scene) = open_mode(state, action) {
+            return Transition::Push(scene);
+        }
+        let free = !state.in_tournament();
+        match action {
+            Action::Pause if !state.game().is_finished() => {
+                Transition::Push(Box::new(Paused::new(state)))
+            }
+            Action::ViewReplay if free => match state.load_best_replay() {
+                Some(replay) => Transition::Push(Box::new(ReplayViewer::new(Ghost::new(replay)))),
+                None => Transition::None,
+            },
+            Action::Editor if free => Transition::Push(Box::new(Editor::new(state.game().board()))),
+            Action::Stats if free => Transition::Push(Box::new(Stats)),
+            _ => {
+                state.action(action, repeat);
+                Transition::None
+            }
+        }
+    }
+}
+
+// ゲームが終わったときに盤面に重ねるハイスコア表。
+// 次のゲームが始まるか Esc で閉じる。A で検討の場面、Shift+S で評価の移り変わりを開く。
+#[derive(Debug)]
+pub struct GameOver;
+
+impl Scene for GameOver {
+    fn update(&mut self, state: &mut GameState) -> Transition {
+        if state.game().is_finished() {
+            Transition::None
+        } else {
+            Transition::Pop
+        }
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        state.draw_highscores(ctx)
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        match action {
+            Action::Cancel => return Transition::Pop,
+            Action::Analyze if !repeat => {
+                return Transition::Push(Box::new(Analysis::new(state.replay())));
+            }
+            Action::Stats if !repeat => return Transition::Push(Box::new(Stats)),
+            _ => {}
+        }
+        if let Some(scene) = open_mode(state, action).filter(|_| !repeat) {
+            return Transition::Push(scene);
+        }
+        state.action(action, repeat);
+        Transition::None
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// 一時停止中。盤面を隠し、時計を止める。
+#[derive(Debug)]
+pub struct Paused {
+    // 止めた時点のゲームの経過時間
+    at: Duration,
+}
+
+impl Paused {
+    pub fn new(state: &GameState) -> Self {
+        Self { at: state.now() }
+    }
+}
+
+impl Scene for Paused {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let screen = state.screen();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), screen, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+
+        let w = 240.0;
+        let rect = Layout::new(screen).place(Anchor::Center, w, 40.0);
+        let font = state.font();
+        let center = Align::Center(w);
+        font.draw_str_with(ctx, rect.x, rect.y, "Paused", graphics::WHITE, center)?;
+        font.draw_str_with(
+            ctx,
+            rect.x,
+            rect.y + 20.0,
+            "P/Enter/Esc:Resume",
+            graphics::WHITE,
+            center,
+        )
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, _repeat: bool) -> Transition {
+        match action {
+            Action::Pause | Action::Confirm | Action::Cancel => {
+                state.reset_clock(self.at);
+                Transition::Pop
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn pauses_game(&self) -> bool {
+        true
+    }
+}
+
+// 数字キーで打ち込む、移りたい手の番号
+#[derive(Debug, Default)]
+pub struct MoveNumber {
+    digits: String,
+}
+
+impl MoveNumber {
+    const MAX_DIGITS: usize = 4;
+
+    pub fn is_empty(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn push(&mut self, digit: u8) {
+        if digit < 10 && self.digits.len() < Self::MAX_DIGITS {
+            self.digits.push(char::from(b'0' + digit));
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.digits.pop();
+    }
+
+    // 打ち込んだ番号を取り出して空にする
+    pub fn take(&mut self) -> Option<usize> {
+        let n = self.digits.parse().ok();
+        self.digits.clear();
+        n
+    }
+
+    // 打ち込み中なら "Go to: 12_" のような表示
+    pub fn prompt(&self) -> Option<String> {
+        Some(format!("Go to: {}_", self.digits)).filter(|_| !self.is_empty())
+    }
+}
+
+// この盤面の最高記録や読み込んだ手順を再生する
+#[derive(Debug)]
+pub struct ReplayViewer {
+    ghost: Ghost,
+    // 更新ごとに進めるので、再生の速さは描画の頻度によらない
+    clock: ManualClock,
+    // 止めて 1 手ずつ進めている
+    stepping: bool,
+    jump: MoveNumber,
+}
+
+impl ReplayViewer {
+    pub fn new(ghost: Ghost) -> Self {
+        Self {
+            ghost,
+            clock: ManualClock::default(),
+            stepping: false,
+            jump: MoveNumber::default(),
+        }
+    }
+
+    // 最初から止めておき、1 手ずつ進める
+    pub fn stepping(ghost: Ghost) -> Self {
+        Self {
+            stepping: true,
+            ..Self::new(ghost)
+        }
+    }
+
+    fn step(&mut self) {
+        self.stepping = true;
+        if let Some(t) = self.ghost.next_move_time() {
+            self.clock.advance(t.saturating_sub(self.clock.now()));
+            self.ghost.advance(self.clock.now());
+        }
+    }
+
+    // n 手指した局面で止める
+    fn seek(&mut self, n: usize) {
+        self.stepping = true;
+        let t = self.ghost.seek(n);
+        self.clock.reset(t);
+    }
+}
+
+impl Scene for ReplayViewer {
+    fn update(&mut self, _state: &mut GameState) -> Transition {
+        if !self.stepping {
+            self.clock.advance(SceneStack::STEP);
+            self.ghost.advance(self.clock.now());
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let game = self.ghost.game();
+        state.draw_board(ctx, game.board())?;
+
+        let color = Color::from_rgba(0x40, 0xe0, 0xff, 0x80);
+        for &(x, y) in self.ghost.flash(self.clock.now()) {
+            let rect = state.cell_rect(x, y);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+        if let Some((x, y)) = self.ghost.cursor() {
+            let rect = state.cell_rect(x, y);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), rect, color)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        let area = state.board_area();
+        let status = format!(
+            "Replay  Move {}/{} {}  Score: {}",
+            self.ghost.moves_played(),
+            self.ghost.move_count(),
+            self.ghost.last_move().unwrap_or("-"),
+            game.score()
+        );
+        let help = self
+            .jump
+            .prompt()
+            .unwrap_or_else(|| "Left/Right:Step 0-9,Enter:Go to move Esc:Back".to_owned());
+        let y = area.y + area.h + 10.0;
+        state.font().draw_str(ctx, 10.0, y, status)?;
+        state.font().draw_str(ctx, 10.0, y + 17.0, help)?;
+        if let Some(comment) = self.ghost.comment() {
+            let width = graphics::screen_coordinates(ctx).w - 20.0;
+            state
+                .font()
+                .draw_wrapped(ctx, 10.0, y + 34.0, comment, width, graphics::WHITE)?;
+        }
+        Ok(())
+    }
+
+    fn action(&mut self, _state: &mut GameState, action: Action, _repeat: bool) -> Transition {
+        match action {
+            Action::Cancel if !self.jump.is_empty() => {
+                self.jump.take();
+                Transition::None
+            }
+            Action::Cancel | Action::ViewReplay => Transition::Pop,
+            Action::Pick(digit) => {
+                self.jump.push(digit);
+                Transition::None
+            }
+            Action::DeleteChar => {
+                self.jump.pop();
+                Transition::None
+            }
+            Action::Confirm if !self.jump.is_empty() => {
+                if let Some(n) = self.jump.take() {
+                    self.seek(n);
+                }
+                Transition::None
+            }
+            Action::Confirm | Action::MoveCursor(1, 0) => {
+                self.step();
+                Transition::None
+            }
+            Action::MoveCursor(-1, 0) => {
+                let n = self.ghost.moves_played();
+                self.seek(n.saturating_sub(1));
+                Transition::None
+            }
+            _ => Transition::None,
+        }
+    }
+}
+
+// チャレンジコードを打ち込む。盤面に重ねて右下のコードの行に出す。
+#[derive(Debug, Default)]
+pub struct CodeEntry {
+    code: String,
+    error: Option<String>,
+    // 開いたキー入力と同じフレームで届く文字入力を拾わないよう、最初の更新までは受け付けない
+    ready: bool,
+}
+
+impl Scene for CodeEntry {
+    fn update(&mut self, _state: &mut GameState) -> Transition {
+        self.ready = true;
+        Transition::None
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let rect = state.code_rect();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+
+        let font = state.font();
+        match &self.error {
+            Some(e) => {
+                let red = Color::from_rgb(0xff, 0x60, 0x60);
+                let line = format!("Error: {}", e);
+                font.draw_str_with(ctx, rect.x, rect.y, line, red, Align::Left)
+            }
+            None => font.draw_str(ctx, rect.x, rect.y, format!("Code> {}_", self.code)),
+        }
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, _repeat: bool) -> Transition {
+        match action {
+            Action::Cancel => return Transition::Pop,
+            Action::Confirm => match self.code.parse::<Challenge>() {
+                Ok(challenge) => {
+                    state.play_challenge(challenge);
+                    return Transition::Pop;
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            },
+            Action::DeleteChar => {
+                self.code.pop();
+                self.error = None;
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn text_input(&mut self, _state: &mut GameState, ch: char) {
+        if self.ready && (ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') {
+            self.code.push(ch);
+            self.error = None;
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// レベル選択。盤面の領域に到達済みのレベルを並べる。
+#[derive(Debug)]
+pub struct LevelMenu {
+    select: LevelSelect,
+    progress: Progress,
+}
+
+impl LevelMenu {
+    const ORIGIN: (f32, f32) = (20.0, 24.0);
+    const CELL: (f32, f32) = (100.0, 70.0);
+
+    pub fn new(state: &GameState) -> Self {
+        let progress = state.load_progress();
+        Self {
+            select: LevelSelect::new(progress.furthest, state.last_level()),
+            progress,
+        }
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Option<u32> {
+        let (ox, oy) = Self::ORIGIN;
+        let (cw, ch) = Self::CELL;
+        if x < ox || y < oy {
+            return None;
+        }
+        self.select
+            .at(((x - ox) / cw) as u32, ((y - oy) / ch) as u32)
+    }
+
+    fn play(&self, state: &mut GameState, number: u32) -> Transition {
+        state.play_level(number, self.progress.clone());
+        Transition::Pop
+    }
+}
+
+impl Scene for LevelMenu {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), state.board_area(), graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let font = state.font();
+        font.draw_str(
+            ctx,
+            20.0,
+            4.0,
+            "Select level  Arrows:Move Enter:Play Esc:Close",
+        )?;
+
+        let (ox, oy) = Self::ORIGIN;
+        let (cw, ch) = Self::CELL;
+        for number in self.select.page() {
+            let (col, row) = LevelSelect::position(number);
+            let (x, y) = (ox + cw * col as f32, oy + ch * row as f32);
+
+            let color = if number == self.select.selected() {
+                Color::from_rgb(0xff, 0xff, 0x00)
+            } else {
+                graphics::WHITE
+            };
+            let frame = Mesh::new_rectangle(
+                ctx,
+                DrawMode::stroke(1.0),
+                Rect::new(x + 2.0, y + 2.0, cw - 4.0, ch - 4.0),
+                color,
+            )?;
+            graphics::draw(ctx, &frame, DrawParam::default())?;
+
+            let center = Align::Center(cw);
+            let label = format!("Lv {}", number);
+            font.draw_str_with(ctx, x, y + 6.0, label, color, center)?;
+            if let Some(record) = self.progress.get(number) {
+                let stars: String = (0..3)
+                    .map(|i| if i < record.stars { '*' } else { '.' })
+                    .collect();
+                let gold = Color::from_rgb(0xff, 0xd0, 0x40);
+                font.draw_str_with(ctx, x, y + 25.0, stars, gold, center)?;
+                let score = record.score.to_string();
+                font.draw_str_with(ctx, x, y + 44.0, score, graphics::WHITE, center)?;
+            } else if number == self.select.furthest() {
+                let new = Color::from_rgb(0x80, 0xff, 0x80);
+                font.draw_str_with(ctx, x, y + 25.0, "New", new, center)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if repeat {
+            return Transition::None;
+        }
+        match action {
+            Action::MoveCursor(dx, dy) => {
+                self.select.move_by(dx, dy);
+                Transition::None
+            }
+            Action::Confirm => self.play(state, self.select.selected()),
+            Action::Click(x, y) => match self.cell(x, y) {
+                Some(number) => self.play(state, number),
+                None => Transition::None,
+            },
+            Action::Cancel | Action::LevelSelect => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// 設定画面。項目の並びと変えたときの処理は GameState が持つ。
+#[derive(Debug)]
+pub struct SettingsMenu {
+    form: Form,
+    // 項目を作ったときの GameState::settings_revision
+    revision: u32,
+}
+
+impl SettingsMenu {
+    pub fn new(state: &GameState) -> Self {
+        Self {
+            form: state.settings_form(0),
+            revision: state.settings_revision(),
+        }
+    }
+}
+
+impl Scene for SettingsMenu {
+    // F11 などで設定が変わったら表示を合わせる
+    fn update(&mut self, state: &mut GameState) -> Transition {
+        if self.revision != state.settings_revision() {
+            self.form = state.settings_form(self.form.focus());
+            self.revision = state.settings_revision();
+        }
+        Transition::None
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), state.board_area(), graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        state.font().draw_str(
+            ctx,
+            20.0,
+            4.0,
+            "Settings  Up/Down:Move Enter:Use Left/Right:Adjust Esc:Close",
+        )?;
+        self.form.draw(ctx, state.font())
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if let Action::Point(..) = action {
+            self.form.on_action(action);
+            return Transition::None;
+        }
+        if repeat {
+            return Transition::None;
+        }
+        match action {
+            Action::Cancel | Action::Settings => Transition::Pop,
+            _ => match self.form.on_action(action) {
+                Some(res) if state.on_settings(res) => Transition::Pop,
+                _ => Transition::None,
+            },
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// 前回途中で終了したゲームを再開するか尋ねる
+#[derive(Debug)]
+pub struct ResumePrompt {
+    suspended: Suspended,
+    form: Form,
+}
+
+impl ResumePrompt {
+    const WIDTH: f32 = 240.0;
+
+    pub fn new(suspended: Suspended) -> Self {
+        let items = vec![Widget::button("Resume (Y)"), Widget::button("New game (N)")];
+        Self {
+            suspended,
+            form: Form::new(items, 0.0, 0.0, Self::WIDTH),
+        }
+    }
+
+    fn answer(&self, state: &mut GameState, choice: Choice) -> Transition {
+        state.answer_resume(choice, self.suspended.clone());
+        Transition::Pop
+    }
+}
+
+impl Scene for ResumePrompt {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let area = state.board_area();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), area, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+
+        // 盤面の領域の中央に置く
+        let h = 40.0 + self.form.height();
+        let rect = Layout::new(area).place(Anchor::Center, Self::WIDTH, h);
+        let font = state.font();
+        font.draw_str(ctx, rect.x, rect.y, "Resume previous game?")?;
+        let config = self.suspended.replay.config;
+        font.draw_str(
+            ctx,
+            rect.x,
+            rect.y + 20.0,
+            format!(
+                "{}x{} {}",
+                config.width,
+                config.height,
+                speedrun::format_duration(self.suspended.elapsed)
+            ),
+        )?;
+        self.form.move_to(rect.x, rect.y + 40.0);
+        self.form.draw(ctx, font)
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if let Action::Point(..) = action {
+            self.form.on_action(action);
+            return Transition::None;
+        }
+        if repeat {
+            return Transition::None;
+        }
+        if let Some(choice) = Choice::from_action(action) {
+            return self.answer(state, choice);
+        }
+        match self.form.on_action(action) {
+            Some((0, _)) => self.answer(state, Choice::Resume),
+            Some((_, _)) => self.answer(state, Choice::Discard),
+            None => Transition::None,
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+// 遊び方の説明。盤面の下に説明を重ね、説明の間も盤面は操作できる。
+#[derive(Debug)]
+pub struct TutorialOverlay {
+    tutorial: Tutorial,
+    // 前回の更新での盤面のタイルの数。減ったらグループを消したとみなす。
+    tiles: usize,
+}
+
+impl TutorialOverlay {
+    pub fn new(state: &GameState) -> Self {
+        Self {
+            tutorial: Tutorial::new(),
+            tiles: state.game().board().tile_count(),
+        }
+    }
+
+    // 最後まで見るか飛ばしたら閉じる
+    fn close_if_over(&self, state: &mut GameState) -> Transition {
+        if !self.tutorial.is_over() {
+            return Transition::None;
+        }
+        state.finish_tutorial();
+        Transition::Pop
+    }
+}
+
+impl Scene for TutorialOverlay {
+    fn update(&mut self, state: &mut GameState) -> Transition {
+        let tiles = state.game().board().tile_count();
+        if tiles < self.tiles {
+            self.tutorial.on_move();
+        }
+        self.tiles = tiles;
+        self.close_if_over(state)
+    }
+
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let step = match self.tutorial.step() {
+            Some(step) => step,
+            None => return Ok(()),
+        };
+
+        if step.highlight {
+            let board = state.game().board();
+            let largest = board.groups().into_iter().rev().max_by_key(|&(_, n)| n);
+            if let Some(((x, y), _)) = largest {
+                let cells = board.calc_component(x, y);
+                let stroke = (3.0, Color::from_rgb(0x00, 0xff, 0xff));
+                let mesh =
+                    highlight::build_mesh(ctx, &cells, state.cell_size(), None, Some(stroke))?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+        }
+
+        let help = match step.trigger {
+            Trigger::Move => "Esc:Skip tutorial",
+            Trigger::Continue => "Enter:Next Esc:Skip tutorial",
+        };
+        let text = format!("{}\n{}", step.text, help);
+        let font = state.font();
+        let area = state.board_area();
+        let text_width = area.w - 20.0;
+        let lines = font.wrap(ctx, &text, text_width);
+        let h = (font.glyph_height() * lines.len()) as f32 + 12.0;
+        let panel = Layout::new(area).place(Anchor::BottomLeft, area.w, h);
+        let bg = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            panel,
+            Color::from_rgba(0x00, 0x00, 0x00, 0xc0),
+        )?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+
+        font.draw_wrapped(ctx, 10.0, panel.y + 6.0, text, text_width, graphics::WHITE)?;
+        Ok(())
+    }
+
+    // 読むだけの説明はクリックでも進める。それ以外の入力は盤面に渡す。
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        let reading = self.tutorial.step().map(|step| step.trigger) == Some(Trigger::Continue);
+        match action {
+            Action::Cancel if !repeat => self.tutorial.skip(),
+            Action::Confirm if !repeat => self.tutorial.on_continue(),
+            Action::Click(..) if !repeat && reading => self.tutorial.on_continue(),
+            _ => {
+                state.action(action, repeat);
+                return Transition::None;
+            }
+        }
+        self.close_if_over(state)
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use ggez::conf::{FullscreenType, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::profile::Profile;
+use crate::storage::{self, SaveBackend};
+use crate::tiles::TileSkin;
+
+// プロファイルごとの表示の設定
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    // 場面を切り替えるときの動きを出さない
+    #[serde(default)]
+    pub reduce_motion: bool,
+    // 最後に全画面でなかったときのウィンドウの大きさ
+    #[serde(default)]
+    pub window_size: Option<(f32, f32)>,
+    // 同じく左上の角のデスクトップ上の位置
+    #[serde(default)]
+    pub window_position: Option<(f64, f64)>,
+    #[serde(default)]
+    pub fullscreen: bool,
+    // 全画面にするとき、解像度を変えずにデスクトップの大きさの枠なしウィンドウにする
+    #[serde(default)]
+    pub borderless: bool,
+    // 画面にブラウン管風の後処理をかける
+    #[serde(default)]
+    pub crt: bool,
+    // 指したグループの大きさを音の高さで知らせる
+    #[serde(default)]
+    pub hover_tones: bool,
+    // 盤面の端に列と行の表記を重ねて描く
+    #[serde(default)]
+    pub coordinates: bool,
+    // 盤面の横に、今の得点とここから取れそうな得点を比べる棒を出す
+    #[serde(default)]
+    pub eval_bar: bool,
+    // 同じ見積もりを得点の近くに数字で出す
+    #[serde(default)]
+    pub eval_readout: bool,
+    // ゲームが終わるたびに手の記録を CSV に書き出す
+    #[serde(default)]
+    pub export_moves: bool,
+    // 考えた時間や選んだグループの大きさ、ヒントの使い方を手元の記録に残す。既定では残さない。
+    #[serde(default)]
+    pub telemetry: bool,
+    // 選択中のグループとカーソルの色
+    #[serde(default)]
+    pub highlight: HighlightStyle,
+    // 色ごとのタイル画像と色。起動時に読む。
+    #[serde(default)]
+    pub tile_skins: BTreeMap<u8, TileSkin>,
+}
+
+impl Settings {
+    pub const MIN_WINDOW_SIZE: (f32, f32) = (640.0, 480.0);
+
+    pub fn key(profile: &Profile) -> String {
+        profile.key("settings.json")
+    }
+
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    // 前回の大きさと全画面かどうかを戻したウィンドウ。位置は WindowMode にないので開いてから戻す。
+    pub fn window_mode(&self) -> WindowMode {
+        let (min_w, min_h) = Self::MIN_WINDOW_SIZE;
+        let (w, h) = self.window_size.unwrap_or(Self::MIN_WINDOW_SIZE);
+        WindowMode::default()
+            .dimensions(w.max(min_w), h.max(min_h))
+            .min_dimensions(min_w, min_h)
+            .resizable(true)
+            .fullscreen_type(self.fullscreen_type())
+    }
+
+    pub fn fullscreen_type(&self) -> FullscreenType {
+        match (self.fullscreen, self.borderless) {
+            (false, _) => FullscreenType::Windowed,
+            (true, false) => FullscreenType::True,
+            (true, true) => FullscreenType::Desktop,
+        }
+    }
+}
+
+// 選択中のグループを塗る色と濃さ、輪郭とカーソルの色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightStyle {
+    pub tint: (u8, u8, u8),
+    pub alpha: u8,
+    pub outline: (u8, u8, u8),
+}
+
+impl Default for HighlightStyle {
+    fn default() -> Self {
+        Self::PRESETS[0].1
+    }
+}
+
+impl HighlightStyle {
+    // 設定画面で順に選べる色。明るいタイルには暗い色を使う。
+    pub const PRESETS: &'static [(&'static str, HighlightStyle)] = &[
+        (
+            "Gray",
+            HighlightStyle {
+                tint: (0xc0, 0xc0, 0xc0),
+                alpha: 0x80,
+                outline: (0xff, 0xff, 0xff),
+            },
+        ),
+        (
+            "Yellow",
+            HighlightStyle {
+                tint: (0xff, 0xff, 0x60),
+                alpha: 0x80,
+                outline: (0xff, 0xff, 0x00),
+            },
+        ),
+        (
+            "Cyan",
+            HighlightStyle {
+                tint: (0x60, 0xff, 0xff),
+                alpha: 0x80,
+                outline: (0x00, 0xff, 0xff),
+            },
+        ),
+        (
+            "Dark",
+            HighlightStyle {
+                tint: (0x00, 0x00, 0x00),
+                alpha: 0x80,
+                outline: (0x00, 0x00, 0x00),
+            },
+        ),
+    ];
+
+    // 濃さはそのままにして色だけ同じもの。設定ファイルで好きな色にしていれば None。
+    pub fn preset(&self) -> Option<usize> {
+        Self::PRESETS
+            .iter()
+            .position(|(_, p)| p.tint == self.tint && p.outline == self.outline)
+    }
+
+    // 設定画面で次に選ぶ色。濃さは変えない。
+    pub fn next_preset(&self) -> Self {
+        let i = self.preset().map_or(0, |i| (i + 1) % Self::PRESETS.len());
+        Self {
+            alpha: self.alpha,
+            ..Self::PRESETS[i].1
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.preset().map_or("Custom", |i| Self::PRESETS[i].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        // 項目が増えても古いファイルを読める
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, Settings::default());
+        let settings: Settings = serde_json::from_str(r#"{"reduce_motion":true}"#).unwrap();
+        assert!(settings.reduce_motion);
+        assert_eq!(settings.window_size, None);
+
+        let settings = Settings {
+            window_size: Some((800.0, 600.0)),
+            window_position: Some((-20.0, 40.0)),
+            fullscreen: true,
+            ..Settings::default()
+        };
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(serde_json::from_str::<Settings>(&json).unwrap(), settings);
+    }
+
+    #[test]
+    fn highlight() {
+        let style = HighlightStyle::default();
+        assert_eq!(style.name(), "Gray");
+        let next = HighlightStyle {
+            alpha: 0x40,
+            ..style
+        }
+        .next_preset();
+        assert_eq!(next.name(), "Yellow");
+        assert_eq!(next.alpha, 0x40);
+
+        // 好きな色にしていたら先頭に戻る
+        let custom = HighlightStyle {
+            tint: (1, 2, 3),
+            ..style
+        };
+        assert_eq!(custom.name(), "Custom");
+        assert_eq!(custom.next_preset().name(), "Gray");
+
+        let last = HighlightStyle::PRESETS.last().unwrap().1;
+        assert_eq!(last.next_preset().name(), "Gray");
+    }
+
+    #[test]
+    fn fullscreen_type() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.fullscreen_type(), FullscreenType::Windowed);
+        settings.borderless = true;
+        assert_eq!(settings.fullscreen_type(), FullscreenType::Windowed);
+        settings.fullscreen = true;
+        assert_eq!(settings.fullscreen_type(), FullscreenType::Desktop);
+        settings.borderless = false;
+        assert_eq!(settings.fullscreen_type(), FullscreenType::True);
+    }
+}
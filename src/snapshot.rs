@@ -0,0 +1,108 @@
+use itertools::iproduct;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::game::Move;
+
+// ある時点のゲームの状態
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub board: Board,
+    pub score: i32,
+    pub history: Vec<Move>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellChange {
+    pub x: usize,
+    pub y: usize,
+    pub from: u8,
+    pub to: u8,
+}
+
+// 2 つのスナップショットの差分
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diff {
+    pub cells: Vec<CellChange>,
+    pub score: i32,
+    // b で新たに増えた手
+    pub moves: Vec<Move>,
+}
+
+// a から b への変化。盤面が回って大きさが変わっていたらセルの差分は取らない。
+pub fn diff(a: &GameSnapshot, b: &GameSnapshot) -> Diff {
+    let (w, h) = (a.board.width(), a.board.height());
+    let (w, h) = if (w, h) == (b.board.width(), b.board.height()) {
+        (w, h)
+    } else {
+        (0, 0)
+    };
+
+    let cells = iproduct!(0..w, 0..h)
+        .filter_map(|(x, y)| {
+            let (from, to) = (a.board.at(x, y), b.board.at(x, y));
+            if from == to {
+                return None;
+            }
+            Some(CellChange { x, y, from, to })
+        })
+        .collect();
+
+    let moves = b
+        .history
+        .get(a.history.len()..)
+        .unwrap_or_default()
+        .to_vec();
+
+    Diff {
+        cells,
+        score: b.score - a.score,
+        moves,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::game::{Game, GameConfig};
+    use crate::replay::Action;
+
+    #[test]
+    fn diff() {
+        let config = GameConfig {
+            width: 6,
+            height: 4,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new(5, config);
+        let a = game.snapshot();
+        assert_eq!(super::diff(&a, &a), Diff::default());
+
+        let (x, y) = iproduct!(0..6, 0..4)
+            .find(|&(x, y)| !game.board().calc_component(x, y).is_empty())
+            .unwrap();
+        let n = game.board().calc_component(x, y).len();
+        assert!(game.click(x, y, Duration::from_secs(1)));
+        let b = game.snapshot();
+
+        let d = super::diff(&a, &b);
+        assert_eq!(d.score, b.score);
+        assert_eq!(
+            d.moves,
+            [Move {
+                action: Action::Click(x, y),
+                score: b.score
+            }]
+        );
+        // 消えたタイルの数だけ空きセルが増える
+        let emptied = d.cells.iter().filter(|c| c.to == 0).count();
+        let filled = d.cells.iter().filter(|c| c.from == 0).count();
+        assert_eq!(emptied - filled, n);
+        for c in &d.cells {
+            assert_eq!(a.board.at(c.x, c.y), c.from);
+            assert_eq!(b.board.at(c.x, c.y), c.to);
+        }
+    }
+}
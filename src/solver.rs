@@ -0,0 +1,578 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::beam_stack::BeamStack;
+use crate::board::{self, Board};
+use crate::dfbnb::Dfbnb;
+use crate::eval::{Evaluator, Weights};
+use crate::mcts::{Mcts, Selection};
+use crate::monitor::Monitor;
+use crate::nrpa::Nrpa;
+use crate::policy::{GreedyPolicy, Policy, RandomPolicy};
+use crate::profiling;
+use crate::rules;
+
+// 消すグループの代表セルの列と、その得点
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Solution {
+    pub moves: Vec<(usize, usize)>,
+    pub score: i32,
+}
+
+// 手順の各手で消したグループ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Erased {
+    pub color: u8,
+    pub size: usize,
+    pub score: i32,
+}
+
+impl Solution {
+    // board から手順をたどり、各手で何を消したかを返す
+    pub fn trace(&self, board: &Board) -> Vec<Erased> {
+        let mut board = board.clone();
+        self.moves
+            .iter()
+            .map(|&(x, y)| {
+                let color = board.at(x, y);
+                let size = board.erase_component(x, y);
+                Erased {
+                    color,
+                    size,
+                    score: rules::group_score(size),
+                }
+            })
+            .collect()
+    }
+
+    // board から最初の n 手で消すグループを、手を指す前の board の座標で返す。
+    // 各グループの先頭はその手で選ぶセル。
+    pub fn plan(&self, board: &Board, n: usize) -> Vec<Vec<(usize, usize)>> {
+        let h = board.height();
+        // 今の各列にあるタイルの元の位置。下から順に並べる。
+        let mut cols: Vec<Vec<(usize, usize)>> = (0..board.width())
+            .map(|x| {
+                (0..h)
+                    .rev()
+                    .filter(|&y| board.at(x, y) != 0)
+                    .map(|y| (x, y))
+                    .collect()
+            })
+            .collect();
+        let mut board = board.clone();
+        let mut plan = vec![];
+        for &(x, y) in self.moves.iter().take(n) {
+            let cells = board.calc_component(x, y);
+            if cells.is_empty() {
+                break;
+            }
+            board.erase_component(x, y);
+            plan.push(cells.iter().map(|&(x, y)| cols[x][h - 1 - y]).collect());
+
+            // 盤面と同じように落として空の列を詰める
+            for (x, col) in cols.iter_mut().enumerate() {
+                let mut i = 0;
+                col.retain(|_| {
+                    i += 1;
+                    !cells.contains(&(x, h - i))
+                });
+            }
+            cols.retain(|col| !col.is_empty());
+        }
+        plan
+    }
+}
+
+// これ以上稼げない得点の上界。各色が全部 1 グループで消えたときの得点の和。
+pub fn upper_bound(board: &Board) -> i32 {
+    (1..=board::MAX_COLORS)
+        .map(|color| rules::group_score(board.color_count(color)))
+        .sum()
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    board: Board,
+    solution: Solution,
+    value: f64,
+}
+
+// ビームサーチ。各深さで得点の高い順に width 個の盤面を残す。
+// 同じ盤面や左右逆にしただけの盤面は 1 つにまとめる。
+// deadline を過ぎたらその時点までで最も良い手順を返す。
+pub fn beam_search(board: &Board, width: usize, deadline: Option<Instant>) -> Solution {
+    beam_search_with(
+        board,
+        width,
+        Weights::default(),
+        &mut Monitor::new(deadline),
+    )
+}
+
+// 得点の代わりに weights による評価で盤面を比べるビームサーチ。monitor の期限で打ち切る。
+pub fn beam_search_with(
+    board: &Board,
+    width: usize,
+    weights: Weights,
+    monitor: &mut Monitor,
+) -> Solution {
+    assert!(width > 0);
+    let _scope = profiling::scope("solver::beam_search");
+
+    let eval = Evaluator::new(weights, board);
+    let mut best = Solution::default();
+    let mut beam = vec![Node {
+        board: board.clone(),
+        solution: Solution::default(),
+        value: 0.0,
+    }];
+
+    while !beam.is_empty() && !monitor.is_over() {
+        let mut seen = HashSet::new();
+        let mut next = vec![];
+        for node in &beam {
+            let groups = if node.solution.moves.is_empty() {
+                node.board.distinct_groups()
+            } else {
+                node.board.groups()
+            };
+            for ((x, y), n) in groups {
+                let mut board = node.board.clone();
+                board.erase_component(x, y);
+                if !seen.insert(board.canonical()) {
+                    continue;
+                }
+
+                let mut solution = node.solution.clone();
+                solution.moves.push((x, y));
+                solution.score += rules::group_score(n);
+                let value = eval.evaluate(&board, solution.score);
+                next.push(Node {
+                    board,
+                    solution,
+                    value,
+                });
+            }
+        }
+
+        next.sort_by(|a, b| b.value.partial_cmp(&a.value).expect("NaN in evaluation"));
+        // 評価が得点と違えば先頭が最高得点とは限らない
+        if let Some(node) = next.iter().min_by_key(|node| Reverse(node.solution.score)) {
+            if node.solution.score > best.score {
+                best = node.solution.clone();
+            }
+        }
+        monitor.visit(next.len() as u64, &best);
+        next.truncate(width);
+        beam = next;
+    }
+
+    best
+}
+
+// 幅を 1, 2, 4, ... と倍にしながら monitor の期限までビームサーチを繰り返す
+pub fn iterative_beam_search(board: &Board, weights: Weights, monitor: &mut Monitor) -> Solution {
+    let mut best = Solution::default();
+    let mut width = 1;
+    while !monitor.is_over() {
+        let solution = beam_search_with(board, width, weights, monitor);
+        if solution.score > best.score {
+            best = solution;
+        }
+        width *= 2;
+    }
+    best
+}
+
+// policy に最後まで打たせる
+pub fn rollout(board: &Board, policy: &mut dyn Policy) -> Solution {
+    let mut board = board.clone();
+    let mut solution = Solution::default();
+    rollout_in_place(&mut board, policy, &mut solution);
+    solution
+}
+
+// rollout と同じだが、board をそのまま進め、手と得点を line に足す
+pub fn rollout_in_place(board: &mut Board, policy: &mut dyn Policy, line: &mut Solution) {
+    let _scope = profiling::scope("solver::rollout");
+    while let Some((x, y)) = policy.next_move(board) {
+        let n = board.erase_component(x, y);
+        assert!(n >= 2, "policy chose an invalid move");
+        line.moves.push((x, y));
+        line.score += rules::group_score(n);
+    }
+}
+
+// SolverKind::solve_cached で探索の途中経過を残す場所と残し方
+#[derive(Debug, Clone)]
+pub struct SearchCache {
+    pub dir: PathBuf,
+    // 探索の途中でも書き残す間隔
+    pub checkpoint: Option<Duration>,
+    // 前に打ち切ったところから続けるか
+    pub resume: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolverKind {
+    // 時間いっぱいランダムプレイアウトを繰り返す
+    Random,
+    // 貪欲法 1 回
+    Greedy,
+    // 幅固定のビームサーチ。幅がなければ反復深化。
+    Beam { width: Option<usize> },
+    // ビームスタックサーチ。時間内に調べきれば最適解。
+    BeamStack { width: usize },
+    // 深さ優先の分枝限定法。時間内に調べきれば最適解。
+    // relabel なら色の名前だけが違う盤面を同じとみなして刈る。memory は置換表の大きさ (MiB)。
+    Dfbnb { relabel: bool, memory: usize },
+    // 入れ子のレベルと、各レベルで下のレベルを呼ぶ回数
+    Nrpa { level: usize, iterations: usize },
+    // 時間いっぱいモンテカルロ木探索
+    Mcts { selection: Selection },
+}
+
+impl SolverKind {
+    const BEAM_STACK_WIDTH: usize = 100;
+    const DFBNB_MEMORY: usize = Dfbnb::DEFAULT_MEMORY >> 20;
+    const NRPA_LEVEL: usize = 3;
+    const NRPA_ITERATIONS: usize = 100;
+
+    // 乱数を使うものは seed で再現できる(時間制限で打ち切られない限り)。
+    // weights はビームサーチ系で盤面を比べるのに使う。
+    // 時間いっぱい探索するものがあるので monitor には期限を付けておくこと。
+    pub fn solve(
+        self,
+        board: &Board,
+        weights: Weights,
+        seed: u64,
+        monitor: &mut Monitor,
+    ) -> Solution {
+        match self {
+            Self::Random => {
+                let mut policy = RandomPolicy::new(Pcg32::seed_from_u64(seed));
+                let mut best = rollout(board, &mut policy);
+                monitor.visit(1, &best);
+                while !monitor.is_over() {
+                    let solution = rollout(board, &mut policy);
+                    if solution.score > best.score {
+                        best = solution;
+                    }
+                    monitor.visit(1, &best);
+                }
+                best
+            }
+            Self::Greedy => {
+                let solution = rollout(board, &mut GreedyPolicy);
+                monitor.visit(1, &solution);
+                solution
+            }
+            Self::Beam { width: Some(width) } => beam_search_with(board, width, weights, monitor),
+            Self::Beam { width: None } => iterative_beam_search(board, weights, monitor),
+            Self::BeamStack { width } => {
+                BeamStack::new(board, width)
+                    .weights(weights)
+                    .search(monitor)
+                    .0
+            }
+            Self::Dfbnb { relabel, memory } => {
+                Dfbnb::new(board)
+                    .relabel(relabel)
+                    .memory(memory << 20)
+                    .search(monitor)
+                    .0
+            }
+            Self::Nrpa { level, iterations } => {
+                Nrpa::new(board, iterations, seed).search(level, monitor)
+            }
+            Self::Mcts { selection } => Mcts::new(board, selection, seed).search(monitor, None),
+        }
+    }
+
+    // solve と同じだが、dfbnb は cache.dir に盤面ごとの探索の途中経過を残し、次はそれを使って調べる
+    pub fn solve_cached(
+        self,
+        board: &Board,
+        weights: Weights,
+        seed: u64,
+        monitor: &mut Monitor,
+        cache: &SearchCache,
+    ) -> anyhow::Result<Solution> {
+        match self {
+            Self::Dfbnb { relabel, memory } => {
+                let suffix = if relabel { "-relabel" } else { "" };
+                let path = cache
+                    .dir
+                    .join(format!("{:016x}{}.dfbnb", board.fingerprint(), suffix));
+                let (solution, _) = Dfbnb::new(board)
+                    .relabel(relabel)
+                    .memory(memory << 20)
+                    .resume(cache.resume)
+                    .checkpoint(cache.checkpoint)
+                    .search_cached(monitor, &path)?;
+                Ok(solution)
+            }
+            _ => Ok(self.solve(board, weights, seed, monitor)),
+        }
+    }
+}
+
+impl fmt::Display for SolverKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Random => f.write_str("random"),
+            Self::Greedy => f.write_str("greedy"),
+            Self::Beam { width: None } => f.write_str("beam"),
+            Self::Beam { width: Some(width) } => write!(f, "beam:{}", width),
+            Self::BeamStack { width } => write!(f, "beamstack:{}", width),
+            Self::Dfbnb { relabel, memory } => {
+                f.write_str("dfbnb")?;
+                if *relabel {
+                    f.write_str(":relabel")?;
+                }
+                if *memory != Self::DFBNB_MEMORY {
+                    write!(f, ":{}", memory)?;
+                }
+                Ok(())
+            }
+            Self::Nrpa { level, iterations } => write!(f, "nrpa:{}:{}", level, iterations),
+            Self::Mcts { selection } => write!(f, "mcts:{}", selection),
+        }
+    }
+}
+
+// "random", "greedy", "beam", "beam:<width>", "beamstack", "beamstack:<width>",
+// "dfbnb", "dfbnb:relabel", "dfbnb:<MiB>", "dfbnb:relabel:<MiB>",
+// "nrpa", "nrpa:<level>", "nrpa:<level>:<iterations>",
+// "mcts" (SP-MCTS), "mcts:uct", "mcts:uct:<c>", "mcts:sp", "mcts:sp:<c>:<d>"
+impl FromStr for SolverKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut it = s.splitn(2, ':');
+        let name = it.next().expect("internal error");
+        let arg = it.next();
+
+        let kind = match (name, arg) {
+            ("random", None) => Self::Random,
+            ("greedy", None) => Self::Greedy,
+            ("beam", None) => Self::Beam { width: None },
+            ("beam", Some(width)) => {
+                let width: usize = width.parse().context("invalid beam width")?;
+                anyhow::ensure!(width > 0, "beam width must be positive");
+                Self::Beam { width: Some(width) }
+            }
+            ("beamstack", arg) => {
+                let width = match arg {
+                    Some(width) => width.parse().context("invalid beam width")?,
+                    None => Self::BEAM_STACK_WIDTH,
+                };
+                anyhow::ensure!(width > 0, "beam width must be positive");
+                Self::BeamStack { width }
+            }
+            ("dfbnb", arg) => {
+                let mut args = arg.into_iter().flat_map(|arg| arg.split(':')).peekable();
+                let relabel = args.peek() == Some(&"relabel");
+                if relabel {
+                    args.next();
+                }
+                let memory = match args.next() {
+                    Some(memory) => memory.parse().context("invalid dfbnb memory")?,
+                    None => Self::DFBNB_MEMORY,
+                };
+                anyhow::ensure!(memory > 0, "dfbnb memory must be positive");
+                anyhow::ensure!(args.next().is_none(), "too many dfbnb arguments");
+                Self::Dfbnb { relabel, memory }
+            }
+            ("nrpa", arg) => {
+                let mut args = arg.into_iter().flat_map(|arg| arg.split(':'));
+                let level = match args.next() {
+                    Some(level) => level.parse().context("invalid nrpa level")?,
+                    None => Self::NRPA_LEVEL,
+                };
+                let iterations = match args.next() {
+                    Some(n) => n.parse().context("invalid nrpa iterations")?,
+                    None => Self::NRPA_ITERATIONS,
+                };
+                anyhow::ensure!(iterations > 0, "nrpa iterations must be positive");
+                anyhow::ensure!(args.next().is_none(), "too many nrpa arguments");
+                Self::Nrpa { level, iterations }
+            }
+            ("mcts", arg) => {
+                let args: Vec<_> = arg.into_iter().flat_map(|arg| arg.split(':')).collect();
+                let num = |s: &str| -> anyhow::Result<f64> {
+                    let x: f64 = s.parse().context("invalid mcts constant")?;
+                    anyhow::ensure!(x.is_finite() && x >= 0.0, "invalid mcts constant");
+                    Ok(x)
+                };
+                let selection = match args[..] {
+                    [] | ["sp"] => Selection::DEFAULT_SP_MCTS,
+                    ["uct"] => Selection::DEFAULT_UCT,
+                    ["uct", c] => Selection::Uct { c: num(c)? },
+                    ["sp", c, d] => Selection::SpMcts {
+                        c: num(c)?,
+                        d: num(d)?,
+                    },
+                    _ => anyhow::bail!("invalid mcts options: {}", s),
+                };
+                Self::Mcts { selection }
+            }
+            _ => anyhow::bail!("unknown solver: {}", s),
+        };
+        Ok(kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn beam_search() {
+        // 1 を消すと 2 がつながって全消しできる
+        let board = Board::parse(
+            b"\
+3 2
+212
+111
+"
+            .as_ref(),
+        )
+        .unwrap();
+
+        let solution = super::beam_search(&board, 10, None);
+        assert_eq!(solution.score, 9 + 1);
+
+        let trace = solution.trace(&board);
+        assert_eq!(
+            trace.iter().map(|e| (e.color, e.size)).collect::<Vec<_>>(),
+            [(1, 4), (2, 2)]
+        );
+
+        let mut board = board;
+        let mut total = 0;
+        for (x, y) in solution.moves {
+            total += rules::group_score(board.erase_component(x, y));
+        }
+        assert_eq!(total, solution.score);
+        assert_eq!(board.tile_count(), 0);
+    }
+
+    #[test]
+    fn plan() {
+        let board = Board::parse("3 2\n122\n133\n".as_bytes()).unwrap();
+        let solution = Solution {
+            moves: vec![(0, 0), (0, 0), (0, 1)],
+            score: 0,
+        };
+        let sorted = |mut cells: Vec<(usize, usize)>| {
+            cells.sort();
+            cells
+        };
+
+        // 1 を消すと列が左に詰まる
+        let plan = solution.plan(&board, 5);
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan[0][0], (0, 0));
+        assert_eq!(sorted(plan[0].clone()), [(0, 0), (0, 1)]);
+        assert_eq!(plan[1][0], (1, 0));
+        assert_eq!(sorted(plan[1].clone()), [(1, 0), (2, 0)]);
+        assert_eq!(sorted(plan[2].clone()), [(1, 1), (2, 1)]);
+        assert_eq!(solution.plan(&board, 1).len(), 1);
+
+        // 落ちたタイルも元の位置で返す
+        let board = Board::parse("2 3\n21\n12\n11\n".as_bytes()).unwrap();
+        let solution = Solution {
+            moves: vec![(0, 2), (0, 2)],
+            score: 0,
+        };
+        let plan = solution.plan(&board, 2);
+        assert_eq!(sorted(plan[0].clone()), [(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(sorted(plan[1].clone()), [(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn upper_bound() {
+        let board = Board::parse("3 2\n212\n111\n".as_bytes()).unwrap();
+        assert_eq!(super::upper_bound(&board), 9 + 1);
+
+        let mut rng = Pcg32::seed_from_u64(3);
+        let board = Board::random_with(8, 6, 3, &mut rng);
+        let solution = super::beam_search(&board, 20, None);
+        assert!(solution.score <= super::upper_bound(&board));
+    }
+
+    #[test]
+    fn solvers() {
+        let mut rng = Pcg32::seed_from_u64(3);
+        let board = Board::random_with(8, 6, 3, &mut rng);
+        let deadline = Instant::now() + Duration::from_millis(50);
+
+        let mut monitor = Monitor::new(Some(deadline));
+        let greedy = SolverKind::Greedy.solve(&board, Weights::default(), 0, &mut monitor);
+        for kind in &[
+            "random",
+            "beam:4",
+            "beam",
+            "beamstack:8",
+            "dfbnb",
+            "dfbnb:relabel",
+            "dfbnb:relabel:1",
+            "nrpa:2:10",
+            "mcts:uct:2.5",
+            "mcts",
+        ] {
+            let kind: SolverKind = kind.parse().unwrap();
+            assert_eq!(kind.to_string().parse::<SolverKind>().unwrap(), kind);
+
+            let mut monitor = Monitor::new(Some(deadline));
+            let solution = kind.solve(&board, Weights::default(), 0, &mut monitor);
+            let replayed = rollout(&board, &mut Replay(solution.moves.iter().copied()));
+            assert_eq!(replayed, solution);
+
+            let weights = Weights {
+                group_size: 0.5,
+                color_count: 3.0,
+                tabu_color: 1.0,
+            };
+            let mut monitor = Monitor::new(Some(deadline));
+            let solution = kind.solve(&board, weights, 0, &mut monitor);
+            let replayed = rollout(&board, &mut Replay(solution.moves.iter().copied()));
+            assert_eq!(replayed, solution);
+        }
+        assert!(greedy.score > 0);
+        assert!("beam:0".parse::<SolverKind>().is_err());
+        assert!("beamstack:0".parse::<SolverKind>().is_err());
+        assert_eq!(
+            "nrpa".parse::<SolverKind>().unwrap().to_string(),
+            "nrpa:3:100"
+        );
+        assert!("nrpa:2:0".parse::<SolverKind>().is_err());
+        assert!("nrpa:1:2:3".parse::<SolverKind>().is_err());
+        assert_eq!(
+            "mcts".parse::<SolverKind>().unwrap().to_string(),
+            "mcts:sp:0.5:10000"
+        );
+        assert!("mcts:sp:1".parse::<SolverKind>().is_err());
+        assert!("mcts:uct:-1".parse::<SolverKind>().is_err());
+    }
+
+    #[derive(Debug)]
+    struct Replay<I>(I);
+
+    impl<I: Iterator<Item = (usize, usize)> + fmt::Debug> Policy for Replay<I> {
+        fn next_move(&mut self, _board: &Board) -> Option<(usize, usize)> {
+            self.0.next()
+        }
+    }
+}
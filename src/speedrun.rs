@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+// スプリットを記録する消去率(%)
+pub const SPLIT_PERCENTS: [usize; 3] = [25, 50, 75];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Splits {
+    pub cleared: [Option<Duration>; 3],
+    pub finish: Option<Duration>,
+}
+
+impl Splits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 全 total 枚中 erased 枚消えた時点の経過時間を記録する
+    pub fn record(&mut self, erased: usize, total: usize, elapsed: Duration) {
+        for (split, &percent) in self.cleared.iter_mut().zip(SPLIT_PERCENTS.iter()) {
+            if split.is_none() && 100 * erased >= percent * total {
+                *split = Some(elapsed);
+            }
+        }
+    }
+
+    pub fn finish(&mut self, elapsed: Duration) {
+        if self.finish.is_none() {
+            self.finish = Some(elapsed);
+        }
+    }
+}
+
+// mm:ss.t
+pub fn format_duration(d: Duration) -> String {
+    let ds = d.as_millis() / 100;
+    format!("{:02}:{:02}.{}", ds / 600, ds / 10 % 60, ds % 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits() {
+        let mut splits = Splits::new();
+        splits.record(10, 100, Duration::from_secs(1));
+        assert_eq!(splits.cleared, [None, None, None]);
+        splits.record(60, 100, Duration::from_secs(2));
+        splits.record(80, 100, Duration::from_secs(3));
+        splits.finish(Duration::from_secs(4));
+        splits.finish(Duration::from_secs(5));
+        assert_eq!(
+            splits.cleared,
+            [
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(2)),
+                Some(Duration::from_secs(3))
+            ]
+        );
+        assert_eq!(splits.finish, Some(Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_duration(Duration::from_millis(83_456)), "01:23.4");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "60:00.0");
+    }
+}
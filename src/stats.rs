@@ -0,0 +1,128 @@
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh, Rect};
+use ggez::mint;
+use ggez::{Context, GameResult};
+
+use crate::game_state::GameState;
+use crate::input::Action;
+use crate::rating::Ratings;
+use crate::scene::{Scene, Transition};
+
+// ゲームごとの評価 (ソルバーの得点に対する割合) の移り変わりを見る場面
+#[derive(Debug, Default)]
+pub struct Stats;
+
+impl Stats {
+    // グラフに出すゲームの数
+    const CHART_GAMES: usize = 50;
+    // 平均を取る最近のゲームの数
+    const RECENT: usize = 10;
+
+    fn percent(efficiency: f64) -> String {
+        format!("{:.0}%", efficiency * 100.0)
+    }
+
+    // 最近のゲームの評価を折れ線で描く。100% の線も引く。
+    fn draw_chart(ctx: &mut Context, ratings: &Ratings, area: Rect) -> GameResult {
+        let games = ratings.games();
+        let games = &games[games.len().saturating_sub(Self::CHART_GAMES)..];
+        let top = games.iter().map(|r| r.efficiency()).fold(1.2_f64, f64::max);
+        let point = |i: usize, efficiency: f64| {
+            let step = area.w / (Self::CHART_GAMES - 1) as f32;
+            let y = area.y + area.h * (1.0 - (efficiency.max(0.0) / top) as f32);
+            mint::Point2 {
+                x: area.x + step * i as f32,
+                y,
+            }
+        };
+
+        let frame = Mesh::new_rectangle(
+            ctx,
+            DrawMode::stroke(1.0),
+            area,
+            Color::from_rgb(0x60, 0x60, 0x60),
+        )?;
+        graphics::draw(ctx, &frame, DrawParam::default())?;
+        let par = [point(0, 1.0), point(Self::CHART_GAMES - 1, 1.0)];
+        let par = Mesh::new_line(ctx, &par, 1.0, Color::from_rgb(0x80, 0x80, 0x00))?;
+        graphics::draw(ctx, &par, DrawParam::default())?;
+
+        let points: Vec<_> = games
+            .iter()
+            .enumerate()
+            .map(|(i, r)| point(i, r.efficiency()))
+            .collect();
+        if points.len() >= 2 {
+            let line = Mesh::new_line(ctx, &points, 2.0, graphics::WHITE)?;
+            graphics::draw(ctx, &line, DrawParam::default())?;
+        }
+        Ok(())
+    }
+}
+
+impl Scene for Stats {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let screen = state.screen();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), screen, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+
+        let ratings = state.ratings();
+        let font = state.font();
+        let line = GameState::LINE_HEIGHT;
+        let mut y = screen.y + 10.0;
+        font.draw_str(
+            ctx,
+            10.0,
+            y,
+            "Efficiency rating (your score / solver score)",
+        )?;
+        y += line * 2.0;
+
+        let games = ratings.games();
+        let lines = match games.last() {
+            Some(last) => vec![
+                format!("Games rated: {}", games.len()),
+                format!(
+                    "Last game:   {} ({} / {})",
+                    Self::percent(last.efficiency()),
+                    last.score,
+                    last.baseline
+                ),
+                format!(
+                    "Last {}:     {}",
+                    Self::RECENT,
+                    ratings
+                        .mean(Self::RECENT)
+                        .map_or_else(String::new, Self::percent)
+                ),
+                format!(
+                    "All games:   {}",
+                    ratings
+                        .mean(games.len())
+                        .map_or_else(String::new, Self::percent)
+                ),
+            ],
+            None => vec!["No games rated yet. Finish a game to get a rating.".to_owned()],
+        };
+        for text in &lines {
+            font.draw_str(ctx, 10.0, y, text)?;
+            y += line;
+        }
+        y += line;
+
+        let chart = Rect::new(
+            10.0,
+            y,
+            screen.w - 20.0,
+            screen.y + screen.h - y - line * 3.0,
+        );
+        Self::draw_chart(ctx, ratings, chart)?;
+        font.draw_str(ctx, 10.0, chart.y + chart.h + line, "Esc:Back")
+    }
+
+    fn action(&mut self, _state: &mut GameState, action: Action, _repeat: bool) -> Transition {
+        match action {
+            Action::Cancel | Action::Stats => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+}
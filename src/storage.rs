@@ -0,0 +1,268 @@
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::hash;
+
+// 保存形式のバージョン。ヘッダの後ろに JSON 本体が続く。
+const FORMAT_VERSION: u32 = 1;
+const HEADER_MAGIC: &str = "#samegame";
+
+// 書き換える前の内容を置いておくキー。本体が壊れていたらこちらから復旧する。
+const BACKUP_SUFFIX: &str = ".bak";
+const TEMP_SUFFIX: &str = ".tmp";
+
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = dirs::data_dir().context("data directory not found")?;
+    Ok(dir.join("samegame"))
+}
+
+// 保存データの置き場所。キーは "profiles/default/highscores.json" のような '/' 区切りの文字列。
+// クラウドやデータベースに保存したければこれを実装して差し替える。
+pub trait SaveBackend: Debug {
+    // キーが存在しなければ None
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    fn store(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    // キーが存在しなくてもエラーにはしない
+    fn remove(&self, key: &str) -> Result<()>;
+
+    // prefix で始まるキーを全て返す(順不同)
+    fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+// ファイルシステム上のディレクトリに保存する。
+// 一時ファイルに書いてから置き換えるので、書き込み中に落ちても元の内容は残る。
+#[derive(Debug)]
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+impl FsBackend {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let rel = Path::new(key);
+        let valid = !key.is_empty() && rel.components().all(|c| matches!(c, Component::Normal(_)));
+        anyhow::ensure!(valid, "invalid key: {}", key);
+        Ok(self.root.join(rel))
+    }
+
+    fn walk(&self, dir: &Path, res: &mut Vec<String>) -> Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("cannot read {}", dir.display())),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.walk(&path, res)?;
+                continue;
+            }
+
+            let name = path.to_string_lossy();
+            if name.ends_with(BACKUP_SUFFIX) || name.ends_with(TEMP_SUFFIX) {
+                continue;
+            }
+
+            let rel = path.strip_prefix(&self.root).expect("internal error");
+            let key: Option<Vec<_>> = rel.components().map(|c| c.as_os_str().to_str()).collect();
+            if let Some(key) = key {
+                res.push(key.join("/"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SaveBackend for FsBackend {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(key)?;
+        match fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("cannot read {}", path.display())),
+        }
+    }
+
+    fn store(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let tmp = self.path(&format!("{}{}", key, TEMP_SUFFIX))?;
+        {
+            let mut file = fs::File::create(&tmp)
+                .with_context(|| format!("cannot create {}", tmp.display()))?;
+            file.write_all(data)
+                .and_then(|()| file.sync_all())
+                .with_context(|| format!("cannot write {}", tmp.display()))?;
+        }
+
+        if path.exists() {
+            let bak = self.path(&format!("{}{}", key, BACKUP_SUFFIX))?;
+            fs::rename(&path, &bak).with_context(|| format!("cannot rename {}", path.display()))?;
+        }
+        fs::rename(&tmp, &path).with_context(|| format!("cannot rename {}", tmp.display()))
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        // バックアップが残っていると復旧されてしまうので一緒に消す
+        for key in &[key.to_owned(), format!("{}{}", key, BACKUP_SUFFIX)] {
+            let path = self.path(key)?;
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("cannot remove {}", path.display()))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut res = vec![];
+        self.walk(&self.root, &mut res)?;
+        res.retain(|key| key.starts_with(prefix));
+        Ok(res)
+    }
+}
+
+fn checksum(data: &[u8]) -> u64 {
+    hash::fnv1a(data.iter().copied())
+}
+
+// "#samegame <version> <checksum>\n" に続けて本体を置く
+fn encode(body: &[u8]) -> Vec<u8> {
+    let mut res = format!(
+        "{} {} {:016x}\n",
+        HEADER_MAGIC,
+        FORMAT_VERSION,
+        checksum(body)
+    )
+    .into_bytes();
+    res.extend_from_slice(body);
+    res
+}
+
+// ヘッダを検証して本体を返す。ヘッダのない古い形式はそのまま本体とみなす。
+fn decode(data: &[u8]) -> Result<&[u8]> {
+    if !data.starts_with(HEADER_MAGIC.as_bytes()) {
+        return Ok(data);
+    }
+
+    let eol = data
+        .iter()
+        .position(|&b| b == b'\n')
+        .context("truncated header")?;
+    let header = std::str::from_utf8(&data[..eol]).context("broken header")?;
+    let body = &data[eol + 1..];
+
+    let mut it = header.split_ascii_whitespace().skip(1);
+    let version: u32 = it.next().context("broken header")?.parse()?;
+    anyhow::ensure!(
+        version <= FORMAT_VERSION,
+        "unsupported format version: {}",
+        version
+    );
+    let sum = u64::from_str_radix(it.next().context("broken header")?, 16)?;
+    anyhow::ensure!(sum == checksum(body), "checksum mismatch");
+
+    Ok(body)
+}
+
+fn load_json_raw<T: DeserializeOwned>(backend: &dyn SaveBackend, key: &str) -> Result<Option<T>> {
+    let data = match backend.load(key)? {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+
+    let value = parse_json(&data).with_context(|| format!("cannot parse {}", key))?;
+    Ok(Some(value))
+}
+
+// 保存した内容を読む。書き出したゴーストなどを外で検証するのにも使う。
+pub fn parse_json<T: DeserializeOwned>(data: &[u8]) -> Result<T> {
+    Ok(serde_json::from_slice(decode(data)?)?)
+}
+
+// キーが存在しなければ None。
+// 本体が壊れているか見つからなければ、書き換え前のバックアップから読む。
+pub fn load_json<T: DeserializeOwned>(backend: &dyn SaveBackend, key: &str) -> Result<Option<T>> {
+    let err = match load_json_raw(backend, key) {
+        Ok(Some(value)) => return Ok(Some(value)),
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+
+    let bak = format!("{}{}", key, BACKUP_SUFFIX);
+    match (load_json_raw(backend, &bak), err) {
+        (Ok(Some(value)), err) => {
+            if let Some(e) = err {
+                eprintln!("{:#}; recovered from backup", e);
+            }
+            Ok(Some(value))
+        }
+        (_, Some(e)) => Err(e),
+        (_, None) => Ok(None),
+    }
+}
+
+pub fn save_json<T: Serialize>(backend: &dyn SaveBackend, key: &str, value: &T) -> Result<()> {
+    let body = serde_json::to_vec_pretty(value)?;
+    backend.store(key, &encode(&body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fs_backend() {
+        let root = std::env::temp_dir().join(format!("samegame-test-{}", std::process::id()));
+        let backend = FsBackend::new(&root);
+
+        assert_eq!(backend.load("a/b.json").unwrap(), None);
+        backend.store("a/b.json", b"[1]").unwrap();
+        backend.store("c.json", b"[2]").unwrap();
+        assert_eq!(backend.load("a/b.json").unwrap().unwrap(), b"[1]");
+        assert_eq!(
+            load_json::<Vec<i32>>(&backend, "c.json").unwrap(),
+            Some(vec![2])
+        );
+        assert_eq!(backend.list("a/").unwrap(), ["a/b.json"]);
+        backend.remove("c.json").unwrap();
+        backend.remove("c.json").unwrap();
+        assert_eq!(backend.load("c.json").unwrap(), None);
+
+        assert!(backend.store("../x", b"").is_err());
+
+        // 壊れたらバックアップから読む
+        save_json(&backend, "d.json", &1).unwrap();
+        save_json(&backend, "d.json", &2).unwrap();
+        assert_eq!(load_json::<i32>(&backend, "d.json").unwrap(), Some(2));
+        let mut data = backend.load("d.json").unwrap().unwrap();
+        *data.last_mut().unwrap() = b'3';
+        fs::write(root.join("d.json"), &data).unwrap();
+        assert_eq!(load_json::<i32>(&backend, "d.json").unwrap(), Some(1));
+        assert_eq!(backend.list("d").unwrap(), ["d.json"]);
+        backend.remove("d.json").unwrap();
+        assert_eq!(load_json::<i32>(&backend, "d.json").unwrap(), None);
+        assert!(backend.load("/etc/passwd").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::game::Game;
+use crate::input;
+use crate::replay::{Action, Replay};
+use crate::storage::{self, SaveBackend};
+
+// 前回のゲームを再開するかの答え
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Resume,
+    // 捨てて新しいゲームを遊ぶ
+    Discard,
+    // 今回は新しいゲームを遊び、前回のゲームは残しておく
+    Later,
+}
+
+impl Choice {
+    // Y と N 以外に Esc でも閉じられるが、そのときは保存を消さない
+    pub fn from_action(action: input::Action) -> Option<Self> {
+        match action {
+            input::Action::Yes => Some(Self::Resume),
+            input::Action::No => Some(Self::Discard),
+            input::Action::Cancel => Some(Self::Later),
+            _ => None,
+        }
+    }
+}
+
+// 途中で終了したゲーム。入力記録から盤面を復元し、経過時間から再開する。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suspended {
+    pub replay: Replay,
+    pub elapsed: Duration,
+}
+
+impl Suspended {
+    // 一手も進めていなければ保存する意味がないので None
+    pub fn new(replay: &Replay, elapsed: Duration) -> Option<Self> {
+        let moved = replay
+            .events
+            .iter()
+            .any(|ev| !matches!(ev.action, Action::Cursor(..)));
+        if !moved {
+            return None;
+        }
+
+        Some(Self {
+            replay: replay.clone(),
+            elapsed,
+        })
+    }
+
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Option<Self>> {
+        storage::load_json(backend, key)
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    pub fn game(&self) -> Game {
+        self.replay.play()
+    }
+
+    // 答えに応じて保存を消す。再開した続きは次に終了するときに保存し直す。
+    pub fn settle(backend: &dyn SaveBackend, key: &str, choice: Choice) -> Result<()> {
+        match choice {
+            Choice::Resume | Choice::Discard => backend.remove(key),
+            Choice::Later => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::iproduct;
+
+    use super::*;
+    use crate::game::GameConfig;
+    use crate::storage::FsBackend;
+
+    #[test]
+    fn suspended() {
+        let config = GameConfig {
+            width: 6,
+            height: 4,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new(3, config);
+        let mut replay = Replay::new(&game);
+        replay.push(Duration::from_secs(1), Action::Cursor(0, 0));
+        assert_eq!(Suspended::new(&replay, Duration::from_secs(2)), None);
+
+        let (x, y) = iproduct!(0..6, 0..4)
+            .find(|&(x, y)| !game.board().calc_component(x, y).is_empty())
+            .unwrap();
+        let t = Duration::from_secs(3);
+        replay.push(t, Action::Click(x, y));
+        assert!(game.click(x, y, t));
+
+        let suspended = Suspended::new(&replay, Duration::from_secs(4)).unwrap();
+        let resumed = suspended.game();
+        assert_eq!(resumed.board(), game.board());
+        assert_eq!(resumed.score(), game.score());
+
+        // Esc で閉じても保存は残り、N で初めて消える
+        let root = std::env::temp_dir().join(format!("samegame-suspend-{}", std::process::id()));
+        let backend = FsBackend::new(&root);
+        let key = "p/suspended.json";
+        suspended.save(&backend, key).unwrap();
+        let later = Choice::from_action(input::Action::Cancel).unwrap();
+        assert_eq!(later, Choice::Later);
+        Suspended::settle(&backend, key, later).unwrap();
+        assert_eq!(Suspended::load(&backend, key).unwrap(), Some(suspended));
+        let discard = Choice::from_action(input::Action::No).unwrap();
+        Suspended::settle(&backend, key, discard).unwrap();
+        assert_eq!(Suspended::load(&backend, key).unwrap(), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh};
+use ggez::{Context, GameResult};
+
+use crate::game_state::GameState;
+use crate::input::Action;
+use crate::notation;
+use crate::puzzle::Puzzle;
+use crate::scene::{Scene, Transition};
+
+// 「一番良い手を探せ」の問題を順に解く場面。答えるとすぐに正誤を出す。
+#[derive(Debug)]
+pub struct Tactics {
+    puzzles: Vec<Puzzle>,
+    // 解いている問題。全部解き終えたら puzzles.len()。
+    index: usize,
+    // 答えたセルとその手の順位。答えるまでは None。
+    answer: Option<((usize, usize), usize)>,
+    solved: usize,
+    cursor: Option<(usize, usize)>,
+}
+
+impl Tactics {
+    pub fn new(state: &mut GameState, puzzles: Vec<Puzzle>) -> Self {
+        let this = Self {
+            puzzles,
+            index: 0,
+            answer: None,
+            solved: 0,
+            cursor: None,
+        };
+        this.show(state);
+        this
+    }
+
+    // 盤面の大きさに合わせてセルの位置を決めるよう、問題の盤面をゲームにも置く
+    fn show(&self, state: &mut GameState) {
+        if let Some(puzzle) = self.puzzles.get(self.index) {
+            state.play_board(puzzle.board.clone());
+        }
+    }
+
+    fn answer(&mut self, x: usize, y: usize) {
+        let puzzle = &self.puzzles[self.index];
+        if let Some(rank) = puzzle.rank(x, y) {
+            if rank == 0 {
+                self.solved += 1;
+            }
+            self.answer = Some(((x, y), rank));
+        }
+    }
+
+    fn next(&mut self, state: &mut GameState) {
+        self.index += 1;
+        self.answer = None;
+        self.show(state);
+    }
+
+    fn draw_group(
+        state: &GameState,
+        ctx: &mut Context,
+        puzzle: &Puzzle,
+        (x, y): (usize, usize),
+        color: Color,
+    ) -> GameResult {
+        for (x, y) in puzzle.board.calc_component(x, y) {
+            let rect = state.cell_rect(x, y);
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.0), rect, color)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+        Ok(())
+    }
+
+    fn feedback(puzzle: &Puzzle, (x, y): (usize, usize), rank: usize) -> String {
+        let board = &puzzle.board;
+        let ((bx, by), best) = puzzle.best();
+        if rank == 0 {
+            return format!(
+                "Correct! {} beats the next best move by {} points.",
+                notation::format_move(board, bx, by),
+                puzzle.margin()
+            );
+        }
+        format!(
+            "Wrong. {} leads to {}, but {} leads to {}.",
+            notation::format_move(board, x, y),
+            puzzle.moves[rank].1,
+            notation::format_move(board, bx, by),
+            best
+        )
+    }
+}
+
+impl Scene for Tactics {
+    fn draw(&mut self, state: &mut GameState, ctx: &mut Context) -> GameResult {
+        let area = state.board_area();
+        let bg = Mesh::new_rectangle(ctx, DrawMode::fill(), area, graphics::BLACK)?;
+        graphics::draw(ctx, &bg, DrawParam::default())?;
+        let y = area.y + area.h + 10.0;
+
+        let puzzle = match self.puzzles.get(self.index) {
+            Some(puzzle) => puzzle,
+            None => {
+                let font = state.font();
+                let text = format!("Solved {} of {} puzzles.", self.solved, self.puzzles.len());
+                font.draw_str(ctx, 10.0, y, text)?;
+                return font.draw_str(ctx, 10.0, y + 17.0, "Enter/Esc:Back");
+            }
+        };
+        state.draw_board(ctx, &puzzle.board)?;
+        match self.answer {
+            Some((cell, rank)) => {
+                let green = Color::from_rgb(0x00, 0xff, 0x00);
+                Self::draw_group(state, ctx, puzzle, puzzle.best().0, green)?;
+                if rank > 0 {
+                    let red = Color::from_rgb(0xff, 0x40, 0x40);
+                    Self::draw_group(state, ctx, puzzle, cell, red)?;
+                }
+            }
+            None => {
+                if let Some((x, y)) = self.cursor {
+                    let rect = state.cell_rect(x, y);
+                    let mesh =
+                        Mesh::new_rectangle(ctx, DrawMode::stroke(1.0), rect, graphics::WHITE)?;
+                    graphics::draw(ctx, &mesh, DrawParam::default())?;
+                }
+            }
+        }
+
+        let font = state.font();
+        font.draw_str(
+            ctx,
+            10.0,
+            y,
+            format!(
+                "Tactics  Puzzle {}/{}  Solved: {}",
+                self.index + 1,
+                self.puzzles.len(),
+                self.solved
+            ),
+        )?;
+        let (text, help) = match self.answer {
+            Some((cell, rank)) => (Self::feedback(puzzle, cell, rank), "Enter:Next Esc:Back"),
+            None => (
+                "Find the one move that is far better than all the others.".to_owned(),
+                "Click/Enter:Answer Esc:Back",
+            ),
+        };
+        font.draw_str(ctx, 10.0, y + 17.0, text)?;
+        font.draw_str(ctx, 10.0, y + 34.0, help)
+    }
+
+    fn action(&mut self, state: &mut GameState, action: Action, repeat: bool) -> Transition {
+        if self.index >= self.puzzles.len() {
+            return match action {
+                Action::Cancel | Action::Confirm => Transition::Pop,
+                _ => Transition::None,
+            };
+        }
+        match action {
+            Action::Cancel => return Transition::Pop,
+            Action::Confirm if repeat => {}
+            Action::Confirm => match (self.answer, self.cursor) {
+                (Some(_), _) => self.next(state),
+                (None, Some((x, y))) => self.answer(x, y),
+                (None, None) => {}
+            },
+            Action::Click(x, y) if self.answer.is_none() => {
+                if let Some((x, y)) = state.cell_at(x, y) {
+                    self.answer(x, y);
+                }
+            }
+            Action::Point(x, y) => self.cursor = state.cell_at(x, y),
+            Action::MoveCursor(dx, dy) => {
+                let board = &self.puzzles[self.index].board;
+                let (x, y) = self.cursor.unwrap_or((0, board.height() - 1));
+                let x = (x as i32 + dx).clamp(0, board.width() as i32 - 1) as usize;
+                let y = (y as i32 + dy).clamp(0, board.height() as i32 - 1) as usize;
+                self.cursor = Some((x, y));
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+}
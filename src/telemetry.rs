@@ -0,0 +1,237 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+use crate::profile::Profile;
+use crate::storage::{self, FsBackend, SaveBackend};
+
+// 遊び方の記録に残す出来事
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Start {
+        width: usize,
+        height: usize,
+        colors: u8,
+    },
+    // グループを消した。largest と groups はその手を指す前の盤面の一番大きいグループの大きさとグループの数、
+    // think_ms は前の手 (かゲームの始め) から考えた時間。
+    Move {
+        x: usize,
+        y: usize,
+        group: usize,
+        largest: usize,
+        groups: usize,
+        think_ms: u64,
+    },
+    Hint {
+        level: u8,
+    },
+    Finish {
+        score: i32,
+        cleared: bool,
+        time_ms: u64,
+    },
+}
+
+// 記録の 1 行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    // 記録した時刻 (RFC 3339)
+    pub at: String,
+    pub seed: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+// 人の遊び方を調べるための手元の記録 (JSON Lines)。設定で有効にしたときだけ残し、外には送らない。
+// 手ごとに書くと重いので、ゲームの区切りでまとめて書き足す。
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    pending: Vec<Record>,
+    // 前の手を指したときのゲームの時計
+    last_move: Duration,
+}
+
+impl Telemetry {
+    pub fn key(profile: &Profile) -> String {
+        profile.key("telemetry.jsonl")
+    }
+
+    pub fn log(&mut self, seed: u64, event: Event) {
+        self.pending.push(Record {
+            at: chrono::Local::now().to_rfc3339(),
+            seed,
+            event,
+        });
+    }
+
+    // 前の手から now までの時間。次の手はここから数える。
+    pub fn think(&mut self, now: Duration) -> Duration {
+        let think = now.saturating_sub(self.last_move);
+        self.last_move = now;
+        think
+    }
+
+    pub fn restart(&mut self) {
+        self.last_move = Duration::default();
+    }
+
+    pub fn flush(&mut self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut data = backend.load(key)?.unwrap_or_default();
+        for record in &self.pending {
+            serde_json::to_writer(&mut data, record)?;
+            data.push(b'\n');
+        }
+        backend.store(key, &data)?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Vec<Record>> {
+    let data = match backend.load(key)? {
+        Some(data) => data,
+        None => return Ok(vec![]),
+    };
+    let text = String::from_utf8(data).with_context(|| format!("cannot parse {}", key))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| format!("{}: line {}", key, i + 1))
+        })
+        .collect()
+}
+
+// 手だけを表計算ソフトで見るための CSV
+pub fn write_moves_csv<W: Write>(records: &[Record], mut wtr: W) -> Result<()> {
+    writeln!(wtr, "at,seed,x,y,group,largest,groups,think_ms")?;
+    for record in records {
+        if let Event::Move {
+            x,
+            y,
+            group,
+            largest,
+            groups,
+            think_ms,
+        } = record.event
+        {
+            writeln!(
+                wtr,
+                "{},{},{},{},{},{},{},{}",
+                record.at, record.seed, x, y, group, largest, groups, think_ms
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ExportOpt {
+    /// Profile whose telemetry to export
+    #[structopt(long, default_value = Profile::DEFAULT)]
+    profile: Profile,
+
+    /// Write only the moves, as CSV, instead of every event as JSON lines
+    #[structopt(long)]
+    csv: bool,
+
+    /// Where to write; standard output if omitted
+    #[structopt(parse(from_os_str))]
+    out: Option<PathBuf>,
+}
+
+pub fn run_export(opt: &ExportOpt) -> Result<()> {
+    let backend = FsBackend::new(storage::data_dir()?);
+    let records = load(&backend, &Telemetry::key(&opt.profile))?;
+    if records.is_empty() {
+        eprintln!(
+            "no telemetry recorded for profile {} (turn it on in Settings)",
+            opt.profile
+        );
+    }
+
+    let mut body = vec![];
+    if opt.csv {
+        write_moves_csv(&records, &mut body)?;
+    } else {
+        for record in &records {
+            serde_json::to_writer(&mut body, record)?;
+            body.push(b'\n');
+        }
+    }
+    match &opt.out {
+        Some(path) => {
+            fs::write(path, body).with_context(|| format!("cannot write {}", path.display()))
+        }
+        None => Ok(io::stdout().write_all(&body)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn telemetry() {
+        let root = std::env::temp_dir().join(format!("samegame-telemetry-{}", std::process::id()));
+        let backend = FsBackend::new(&root);
+        let key = "p/telemetry.jsonl";
+
+        let mut telemetry = Telemetry::default();
+        telemetry.flush(&backend, key).unwrap();
+        assert_eq!(load(&backend, key).unwrap(), vec![]);
+
+        let think = telemetry.think(Duration::from_millis(1500));
+        assert_eq!(think, Duration::from_millis(1500));
+        assert_eq!(
+            telemetry.think(Duration::from_millis(2000)),
+            Duration::from_millis(500)
+        );
+        telemetry.log(
+            7,
+            Event::Start {
+                width: 3,
+                height: 2,
+                colors: 2,
+            },
+        );
+        telemetry.log(
+            7,
+            Event::Move {
+                x: 0,
+                y: 1,
+                group: 4,
+                largest: 4,
+                groups: 2,
+                think_ms: 500,
+            },
+        );
+        telemetry.flush(&backend, key).unwrap();
+        telemetry.log(7, Event::Hint { level: 2 });
+        telemetry.flush(&backend, key).unwrap();
+
+        let records = load(&backend, key).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].event, Event::Hint { level: 2 });
+        let line = serde_json::to_string(&records[2]).unwrap();
+        assert!(line.contains("\"event\":\"hint\""));
+
+        let mut csv = vec![];
+        write_moves_csv(&records, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.lines().nth(1).unwrap().ends_with(",7,0,1,4,4,2,500"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use ggez::filesystem;
+use ggez::graphics::{self, Color, Image, Rect};
+use ggez::{Context, GameResult};
+use serde::{Deserialize, Serialize};
+
+use crate::board;
+
+// 設定ファイルで色ごとに指定する見た目。指定のないものは既定のまま。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileSkin {
+    // リソースのパス ("/my-tile.png" など)
+    #[serde(default)]
+    pub image: Option<String>,
+    // 画像に掛ける色
+    #[serde(default)]
+    pub tint: Option<(u8, u8, u8)>,
+}
+
+// 色ごとのタイル画像。専用の画像がない色は、ある画像を暗くしたもので代える。
+// 画像が横長なら正方形のコマを横に並べたものとみなし、順に切り替えて動かす。
+#[derive(Debug)]
+pub struct TileSet {
+    // 画像とコマ数
+    imgs: Vec<(Image, u16)>,
+    // 色 1 から順に、使う画像の添字と掛ける色
+    variants: Vec<(usize, Color)>,
+}
+
+impl TileSet {
+    // 代わりの画像を 1 周使うごとに掛ける明るさ
+    const SHADE: f32 = 0.55;
+    // 1 コマを出す時間
+    const FRAME_SECONDS: f32 = 0.15;
+
+    // dir 以下の tile-<色>.png を読む。
+    // skins は色から見た目への対応。読めない画像は既定の見た目に戻す。
+    pub fn load(ctx: &mut Context, dir: &str, skins: &BTreeMap<u8, TileSkin>) -> GameResult<Self> {
+        let path = |color: u8| format!("{}/tile-{}.png", dir, color);
+        // 最初の 1 枚は必ず要る
+        let mut imgs = vec![Self::load_image(ctx, &path(1))?];
+        for color in 2..=board::MAX_COLORS {
+            if !filesystem::exists(ctx, path(color)) {
+                break;
+            }
+            imgs.push(Self::load_image(ctx, &path(color))?);
+        }
+
+        let mut variants: Vec<_> = (1..=board::MAX_COLORS)
+            .map(|color| {
+                let (i, shade) = Self::variant(color, imgs.len());
+                (i, Color::new(shade, shade, shade, 1.0))
+            })
+            .collect();
+        for (&color, skin) in skins {
+            let variant = match variants.get_mut(usize::from(color).wrapping_sub(1)) {
+                Some(variant) => variant,
+                None => {
+                    eprintln!("tile skin: no such color: {}", color);
+                    continue;
+                }
+            };
+            if let Some(image) = &skin.image {
+                match Self::load_image(ctx, image) {
+                    Ok(img) => {
+                        imgs.push(img);
+                        *variant = (imgs.len() - 1, graphics::WHITE);
+                    }
+                    Err(e) => eprintln!("cannot load tile image {}: {}", image, e),
+                }
+            }
+            if let Some(tint) = skin.tint {
+                variant.1 = Color::from(tint);
+            }
+        }
+        Ok(Self { imgs, variants })
+    }
+
+    fn load_image(ctx: &mut Context, path: &str) -> GameResult<(Image, u16)> {
+        let img = Image::new(ctx, path)?;
+        let frames = (img.width() / img.height().max(1)).max(1);
+        Ok((img, frames))
+    }
+
+    // 画像が n 枚のときに色 color を描く画像の添字と明るさ
+    fn variant(color: u8, n: usize) -> (usize, f32) {
+        let i = usize::from(color - 1);
+        (i % n, Self::SHADE.powi((i / n) as i32))
+    }
+
+    // frames コマの画像で、時刻 t (秒) に出すコマ
+    fn frame(frames: u16, t: f32) -> u16 {
+        ((t.max(0.0) / Self::FRAME_SECONDS) as u64 % u64::from(frames)) as u16
+    }
+
+    // 色 color (1 以上) を時刻 t (秒) に描くときの画像、切り出す範囲 (0 から 1)、掛ける色
+    pub fn get(&self, color: u8, t: f32) -> (&Image, Rect, Color) {
+        let (i, tint) = self.variants[usize::from(color - 1)];
+        let (img, frames) = &self.imgs[i];
+        let w = 1.0 / f32::from(*frames);
+        let src = Rect::new(w * f32::from(Self::frame(*frames, t)), 0.0, w, 1.0);
+        (img, src, tint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variant() {
+        assert_eq!(TileSet::variant(1, 5), (0, 1.0));
+        assert_eq!(TileSet::variant(5, 5), (4, 1.0));
+        assert_eq!(TileSet::variant(6, 5), (0, TileSet::SHADE));
+        assert_eq!(TileSet::variant(8, 5), (2, TileSet::SHADE));
+        assert_eq!(TileSet::variant(8, 3), (1, TileSet::SHADE * TileSet::SHADE));
+    }
+
+    #[test]
+    fn frame() {
+        assert_eq!(TileSet::frame(1, 10.0), 0);
+        assert_eq!(TileSet::frame(4, 0.0), 0);
+        assert_eq!(TileSet::frame(4, 0.2), 1);
+        assert_eq!(TileSet::frame(4, 0.65), 0);
+        assert_eq!(TileSet::frame(4, -1.0), 0);
+    }
+
+    #[test]
+    fn skin() {
+        let skins: BTreeMap<u8, TileSkin> =
+            serde_json::from_str(r#"{"1":{"image":"/a.png"},"3":{"tint":[255,128,0]}}"#).unwrap();
+        assert_eq!(skins[&1].image.as_deref(), Some("/a.png"));
+        assert_eq!(skins[&1].tint, None);
+        assert_eq!(skins[&3].tint, Some((255, 128, 0)));
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::challenge::Challenge;
+use crate::game::GameConfig;
+use crate::speedrun;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundResult {
+    pub score: i32,
+    pub time: Duration,
+    pub cleared: bool,
+}
+
+// 決められた盤面を順番に遊び、合計得点を競う
+#[derive(Debug)]
+pub struct Tournament {
+    rounds: Vec<Challenge>,
+    results: Vec<RoundResult>,
+}
+
+impl Tournament {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            fs::File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+        Self::parse(io::BufReader::new(file))
+    }
+
+    // 1 行に 1 盤面。シード(10 進、デフォルト設定)かチャレンジコードを書く。
+    // 空行と '#' 以降は無視する。
+    pub fn parse<R: BufRead>(rdr: R) -> Result<Self> {
+        let mut rounds = vec![];
+        for (i, line) in rdr.lines().enumerate() {
+            let line = line?;
+            let line = line.split('#').next().expect("internal error").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let challenge = match line.parse::<u64>() {
+                Ok(seed) => Challenge {
+                    config: GameConfig::default(),
+                    seed,
+                },
+                Err(_) => line
+                    .parse::<Challenge>()
+                    .with_context(|| format!("line {}: invalid seed or code", i + 1))?,
+            };
+            rounds.push(challenge);
+        }
+        anyhow::ensure!(!rounds.is_empty(), "no rounds");
+
+        Ok(Self {
+            rounds,
+            results: vec![],
+        })
+    }
+
+    pub fn round_count(&self) -> usize {
+        self.rounds.len()
+    }
+
+    // 0-based
+    pub fn round(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn current(&self) -> Option<Challenge> {
+        self.rounds.get(self.round()).copied()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.current().is_none()
+    }
+
+    pub fn record(&mut self, result: RoundResult) {
+        assert!(!self.is_over());
+        self.results.push(result);
+    }
+
+    pub fn total(&self) -> i32 {
+        self.results.iter().map(|r| r.score).sum()
+    }
+
+    pub fn write_summary<W: Write>(&self, mut wtr: W) -> Result<()> {
+        writeln!(wtr, "round,code,score,time,cleared")?;
+        for (i, (challenge, result)) in self.rounds.iter().zip(&self.results).enumerate() {
+            writeln!(
+                wtr,
+                "{},{},{},{},{}",
+                i + 1,
+                challenge.encode().unwrap_or_default(),
+                result.score,
+                speedrun::format_duration(result.time),
+                if result.cleared { "yes" } else { "no" }
+            )?;
+        }
+        writeln!(wtr, "total,,{},,", self.total())?;
+        Ok(())
+    }
+
+    pub fn save_summary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file =
+            fs::File::create(path).with_context(|| format!("cannot create {}", path.display()))?;
+        self.write_summary(io::BufWriter::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tournament() {
+        let code = Challenge {
+            config: GameConfig {
+                width: 5,
+                height: 5,
+                ..GameConfig::default()
+            },
+            seed: 7,
+        }
+        .encode()
+        .unwrap();
+        let src = format!("# finals\n12345\n\n{}  # small one\n", code);
+        let mut t = Tournament::parse(src.as_bytes()).unwrap();
+        assert_eq!(t.round_count(), 2);
+        assert_eq!(t.current().unwrap().seed, 12345);
+
+        t.record(RoundResult {
+            score: 100,
+            time: Duration::from_secs(60),
+            cleared: false,
+        });
+        assert_eq!(t.current().unwrap().config.width, 5);
+        t.record(RoundResult {
+            score: 50,
+            time: Duration::from_secs(5),
+            cleared: true,
+        });
+        assert!(t.is_over());
+        assert_eq!(t.total(), 150);
+
+        let mut buf = vec![];
+        t.write_summary(&mut buf).unwrap();
+        let summary = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            summary,
+            format!(
+                "round,code,score,time,cleared\n\
+                 1,{},100,01:00.0,no\n\
+                 2,{},50,00:05.0,yes\n\
+                 total,,150,,\n",
+                Challenge {
+                    config: GameConfig::default(),
+                    seed: 12345
+                }
+                .encode()
+                .unwrap(),
+                code
+            )
+        );
+
+        assert!(Tournament::parse("# nothing\n".as_bytes()).is_err());
+        assert!(Tournament::parse("xyz\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+
+// 置換表の 1 項目。盤面そのものではなく識別子を覚えるので、まれに別の盤面と取り違える。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Entry {
+    key: u64,
+    score: i32,
+    // 残りの探索の大きさの目安。大きいものほど消さずに残す。
+    depth: u16,
+    generation: u8,
+    used: bool,
+}
+
+// 置換表の使われ方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableStats {
+    pub capacity: usize,
+    pub filled: usize,
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    // 別の盤面を追い出して覚えた回数
+    pub replaced: u64,
+}
+
+impl fmt::Display for TableStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |n: f64, total: f64| if total > 0.0 { 100.0 * n / total } else { 0.0 };
+        write!(
+            f,
+            "tt {:.0}% full, {:.0}% hits",
+            percent(self.filled as f64, self.capacity as f64),
+            percent(self.hits as f64, self.probes as f64)
+        )
+    }
+}
+
+// 決まった大きさのメモリに収める置換表。
+// 各バケツの 1 つ目は深さ優先 (浅い項目では追い出さない)、2 つ目は常に上書きする。
+// 前の世代の項目は深さによらず追い出してよい。
+#[derive(Debug, Clone)]
+pub struct TranspositionTable {
+    buckets: Vec<[Entry; 2]>,
+    generation: u8,
+    stats: TableStats,
+}
+
+impl TranspositionTable {
+    // memory バイトに収まるだけのバケツを用意する
+    pub fn new(memory: usize) -> Self {
+        let n = (memory / mem::size_of::<[Entry; 2]>()).max(1);
+        Self {
+            buckets: vec![[Entry::default(); 2]; n],
+            generation: 0,
+            stats: TableStats {
+                capacity: 2 * n,
+                ..TableStats::default()
+            },
+        }
+    }
+
+    // 新しい探索を始める。それまでの項目は古い世代になる。
+    pub fn next_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    fn bucket(&mut self, key: u64) -> &mut [Entry; 2] {
+        let i = (key % self.buckets.len() as u64) as usize;
+        &mut self.buckets[i]
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<i32> {
+        self.stats.probes += 1;
+        let generation = self.generation;
+        let entry = self
+            .bucket(key)
+            .iter_mut()
+            .find(|e| e.used && e.key == key)?;
+        // 今の探索でも使った項目は新しい世代にする
+        entry.generation = generation;
+        let score = entry.score;
+        self.stats.hits += 1;
+        Some(score)
+    }
+
+    pub fn insert(&mut self, key: u64, score: i32, depth: u16) {
+        self.stats.stores += 1;
+        let generation = self.generation;
+        let new = Entry {
+            key,
+            score,
+            depth,
+            generation,
+            used: true,
+        };
+        let bucket = self.bucket(key);
+        let slot = match bucket.iter().position(|e| e.used && e.key == key) {
+            Some(i) => i,
+            None => {
+                let deep = &bucket[0];
+                if !deep.used || deep.generation != generation || depth >= deep.depth {
+                    0
+                } else {
+                    1
+                }
+            }
+        };
+        let old = mem::replace(&mut bucket[slot], new);
+        if !old.used {
+            self.stats.filled += 1;
+        } else if old.key != key {
+            self.stats.replaced += 1;
+        }
+    }
+
+    // 覚えている項目の数のあとに、各項目の識別子・得点・深さを並べて書く
+    pub fn save<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let entries: Vec<_> = self.buckets.iter().flatten().filter(|e| e.used).collect();
+        w.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for e in entries {
+            w.write_all(&e.key.to_le_bytes())?;
+            w.write_all(&e.score.to_le_bytes())?;
+            w.write_all(&e.depth.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    // save で書いた項目を読み足す。入りきらなければ置き換えの規則どおりに捨てる。
+    pub fn load<R: Read>(&mut self, r: &mut R) -> io::Result<()> {
+        for _ in 0..read_u64(r)? {
+            let key = read_u64(r)?;
+            let score = read_u32(r)? as i32;
+            let depth = read_u16(r)?;
+            self.insert(key, score, depth);
+        }
+        Ok(())
+    }
+}
+
+pub fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replacement() {
+        // バケツ 1 つだけの表
+        let mut tt = TranspositionTable::new(1);
+        assert_eq!(tt.stats().capacity, 2);
+        assert_eq!(tt.get(1), None);
+
+        tt.insert(1, 10, 5);
+        tt.insert(2, 20, 3);
+        assert_eq!(tt.get(1), Some(10));
+        assert_eq!(tt.get(2), Some(20));
+        tt.insert(1, 11, 5);
+        assert_eq!(tt.get(1), Some(11));
+
+        // 浅い項目は深い項目を追い出さず、2 つ目を上書きする
+        tt.insert(3, 30, 1);
+        assert_eq!(tt.get(1), Some(11));
+        assert_eq!(tt.get(2), None);
+        assert_eq!(tt.get(3), Some(30));
+
+        // 深い項目は 1 つ目に入る
+        tt.insert(4, 40, 9);
+        assert_eq!(tt.get(1), None);
+        assert_eq!(tt.get(4), Some(40));
+
+        // 前の世代なら浅くても追い出す
+        tt.next_generation();
+        tt.insert(5, 50, 0);
+        assert_eq!(tt.get(4), None);
+        assert_eq!(tt.get(5), Some(50));
+
+        let stats = tt.stats();
+        assert_eq!(stats.filled, 2);
+        assert_eq!(stats.stores, 6);
+        assert_eq!(stats.replaced, 3);
+        assert!(stats.hits < stats.probes);
+        assert_eq!(stats.to_string(), "tt 100% full, 64% hits");
+    }
+
+    #[test]
+    fn save_load() {
+        let mut tt = TranspositionTable::new(1 << 10);
+        for key in 0..20 {
+            tt.insert(key, key as i32 * 10, key as u16);
+        }
+        let mut buf = vec![];
+        tt.save(&mut buf).unwrap();
+
+        let mut loaded = TranspositionTable::new(1 << 10);
+        loaded.load(&mut buf.as_slice()).unwrap();
+        assert_eq!(loaded.stats().filled, tt.stats().filled);
+        for key in 0..20 {
+            assert_eq!(loaded.get(key), tt.get(key));
+        }
+        assert!(loaded.load(&mut &buf[..buf.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+
+use crate::storage::{self, SaveBackend};
+
+// 次の段階に進む条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    // グループを消す
+    Move,
+    // Enter かクリック
+    Continue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    // 説明文。描くときに折り返すので、改行は段落の区切りにだけ使う。
+    pub text: &'static str,
+    pub trigger: Trigger,
+    // 一番大きいグループを強調するか
+    pub highlight: bool,
+}
+
+const STEPS: &[Step] = &[
+    Step {
+        text: "Welcome to SameGame!\n\
+               Click a group of 2 or more tiles of the same color to erase it. \
+               Try the highlighted group.",
+        trigger: Trigger::Move,
+        highlight: true,
+    },
+    Step {
+        text: "Erasing n tiles at once scores (n-1)^2 points: \
+               2 tiles = 1, 5 tiles = 16, 10 tiles = 81. \
+               Bigger groups are worth much more.",
+        trigger: Trigger::Continue,
+        highlight: false,
+    },
+    Step {
+        text: "Tiles above an erased group fall down, and empty columns slide to the left. \
+               Use this to join tiles of the same color.",
+        trigger: Trigger::Continue,
+        highlight: false,
+    },
+    Step {
+        text: "The game ends when no group is left. \
+               Make big groups and aim for the highest score!",
+        trigger: Trigger::Continue,
+        highlight: false,
+    },
+];
+
+// 初回起動時に遊び方を順に説明する
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tutorial {
+    step: usize,
+}
+
+impl Tutorial {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 終わっていれば None
+    pub fn step(&self) -> Option<&'static Step> {
+        STEPS.get(self.step)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.step().is_none()
+    }
+
+    fn advance(&mut self, trigger: Trigger) {
+        if let Some(step) = self.step() {
+            if step.trigger == trigger {
+                self.step += 1;
+            }
+        }
+    }
+
+    pub fn on_move(&mut self) {
+        self.advance(Trigger::Move);
+    }
+
+    pub fn on_continue(&mut self) {
+        self.advance(Trigger::Continue);
+    }
+
+    pub fn skip(&mut self) {
+        self.step = STEPS.len();
+    }
+
+    // 一度見終えたかどうか
+    pub fn load_done(backend: &dyn SaveBackend, key: &str) -> Result<bool> {
+        Ok(storage::load_json(backend, key)?.unwrap_or(false))
+    }
+
+    pub fn save_done(backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, &true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tutorial() {
+        let mut tutorial = Tutorial::new();
+        assert!(tutorial.step().unwrap().highlight);
+
+        // 消すまで先に進まない
+        tutorial.on_continue();
+        assert_eq!(tutorial.step().unwrap().trigger, Trigger::Move);
+        tutorial.on_move();
+        assert_eq!(tutorial.step().unwrap().trigger, Trigger::Continue);
+        tutorial.on_move();
+        assert_eq!(tutorial.step().unwrap().trigger, Trigger::Continue);
+
+        while !tutorial.is_over() {
+            tutorial.on_continue();
+        }
+        tutorial.on_continue();
+        assert!(tutorial.is_over());
+
+        let mut tutorial = Tutorial::new();
+        tutorial.skip();
+        assert!(tutorial.is_over());
+    }
+}
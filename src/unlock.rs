@@ -0,0 +1,281 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::backdrop::Backdrop;
+use crate::storage::{self, SaveBackend};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewardKind {
+    // タイル画像に掛ける色と、既定と違う画像を使うならその置き場所
+    TileTheme {
+        tint: (u8, u8, u8),
+        sheet: Option<&'static str>,
+    },
+    // 画面を塗る色と、その上に描く背景
+    Background {
+        color: (u8, u8, u8),
+        backdrop: Backdrop,
+    },
+}
+
+// 解放条件。None なら最初から使える。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Requirement {
+    None,
+    // レベルで集めた星の合計
+    Stars(u32),
+    // 1 ゲームの得点
+    Score(i32),
+}
+
+impl Requirement {
+    fn is_met(self, stats: &Stats) -> bool {
+        match self {
+            Self::None => true,
+            Self::Stars(n) => stats.stars >= n,
+            Self::Score(score) => stats.score >= score,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Self::None => String::new(),
+            Self::Stars(n) => format!("{} stars", n),
+            Self::Score(score) => format!("score {}", score),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reward {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub kind: RewardKind,
+    pub requirement: Requirement,
+}
+
+impl Reward {
+    pub fn is_tile_theme(&self) -> bool {
+        matches!(self.kind, RewardKind::TileTheme { .. })
+    }
+}
+
+// 解放できる見た目の一覧。各種類の先頭が既定値。
+pub const REWARDS: &[Reward] = &[
+    Reward {
+        id: "tiles-classic",
+        name: "Classic tiles",
+        kind: RewardKind::TileTheme {
+            tint: (0xff, 0xff, 0xff),
+            sheet: None,
+        },
+        requirement: Requirement::None,
+    },
+    Reward {
+        id: "tiles-warm",
+        name: "Warm tiles",
+        kind: RewardKind::TileTheme {
+            tint: (0xff, 0xc0, 0x90),
+            sheet: None,
+        },
+        requirement: Requirement::Stars(10),
+    },
+    Reward {
+        id: "tiles-cool",
+        name: "Cool tiles",
+        kind: RewardKind::TileTheme {
+            tint: (0x90, 0xc0, 0xff),
+            sheet: None,
+        },
+        requirement: Requirement::Stars(30),
+    },
+    Reward {
+        id: "tiles-night",
+        name: "Night tiles",
+        kind: RewardKind::TileTheme {
+            tint: (0x80, 0x80, 0xa0),
+            sheet: None,
+        },
+        requirement: Requirement::Score(3000),
+    },
+    Reward {
+        id: "tiles-shimmer",
+        name: "Shimmer tiles",
+        kind: RewardKind::TileTheme {
+            tint: (0xff, 0xff, 0xff),
+            sheet: Some("/shimmer"),
+        },
+        requirement: Requirement::Stars(50),
+    },
+    Reward {
+        id: "bg-black",
+        name: "Black background",
+        kind: RewardKind::Background {
+            color: (0x00, 0x00, 0x00),
+            backdrop: Backdrop::Flat,
+        },
+        requirement: Requirement::None,
+    },
+    Reward {
+        id: "bg-navy",
+        name: "Navy background",
+        kind: RewardKind::Background {
+            color: (0x10, 0x18, 0x40),
+            backdrop: Backdrop::Gradient {
+                bottom: (0x04, 0x06, 0x14),
+            },
+        },
+        requirement: Requirement::Stars(5),
+    },
+    Reward {
+        id: "bg-forest",
+        name: "Forest background",
+        kind: RewardKind::Background {
+            color: (0x10, 0x30, 0x18),
+            backdrop: Backdrop::Gradient {
+                bottom: (0x04, 0x10, 0x08),
+            },
+        },
+        requirement: Requirement::Stars(20),
+    },
+    Reward {
+        id: "bg-wine",
+        name: "Wine background",
+        kind: RewardKind::Background {
+            color: (0x40, 0x10, 0x20),
+            backdrop: Backdrop::Flat,
+        },
+        requirement: Requirement::Score(2000),
+    },
+    Reward {
+        id: "bg-starfield",
+        name: "Starfield background",
+        kind: RewardKind::Background {
+            color: (0x06, 0x08, 0x18),
+            backdrop: Backdrop::Image("/bg/starfield.png"),
+        },
+        requirement: Requirement::Stars(40),
+    },
+];
+
+fn find(id: &str) -> Option<&'static Reward> {
+    REWARDS.iter().find(|r| r.id == id)
+}
+
+// 解放条件の判定に使う記録
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub stars: u32,
+    pub score: i32,
+}
+
+// 解放済みの報酬と使用中の見た目。プロファイルごとに保存する。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Unlocks {
+    #[serde(default)]
+    unlocked: BTreeSet<String>,
+    #[serde(default)]
+    tile_theme: Option<String>,
+    #[serde(default)]
+    background: Option<String>,
+}
+
+impl Unlocks {
+    pub fn load(backend: &dyn SaveBackend, key: &str) -> Result<Self> {
+        Ok(storage::load_json(backend, key)?.unwrap_or_default())
+    }
+
+    pub fn save(&self, backend: &dyn SaveBackend, key: &str) -> Result<()> {
+        storage::save_json(backend, key, self)
+    }
+
+    pub fn is_unlocked(&self, reward: &Reward) -> bool {
+        reward.requirement == Requirement::None || self.unlocked.contains(reward.id)
+    }
+
+    // 条件を満たした報酬を解放し、新しく解放されたものを返す
+    pub fn check(&mut self, stats: &Stats) -> Vec<&'static Reward> {
+        let mut res = vec![];
+        for reward in REWARDS {
+            if !self.is_unlocked(reward) && reward.requirement.is_met(stats) {
+                self.unlocked.insert(reward.id.to_owned());
+                res.push(reward);
+            }
+        }
+        res
+    }
+
+    // 使う見た目を選ぶ。未解放なら false。
+    pub fn select(&mut self, reward: &Reward) -> bool {
+        if !self.is_unlocked(reward) {
+            return false;
+        }
+        let slot = if reward.is_tile_theme() {
+            &mut self.tile_theme
+        } else {
+            &mut self.background
+        };
+        *slot = Some(reward.id.to_owned());
+        true
+    }
+
+    pub fn is_selected(&self, reward: &Reward) -> bool {
+        let selected = if reward.is_tile_theme() {
+            self.tile_theme()
+        } else {
+            self.background()
+        };
+        selected.id == reward.id
+    }
+
+    // 保存された ID が不明か未解放なら既定値
+    fn current(&self, id: &Option<String>, tile_theme: bool) -> &'static Reward {
+        id.as_deref()
+            .and_then(find)
+            .filter(|r| r.is_tile_theme() == tile_theme && self.is_unlocked(r))
+            .or_else(|| REWARDS.iter().find(|r| r.is_tile_theme() == tile_theme))
+            .expect("internal error")
+    }
+
+    pub fn tile_theme(&self) -> &'static Reward {
+        self.current(&self.tile_theme, true)
+    }
+
+    pub fn background(&self) -> &'static Reward {
+        self.current(&self.background, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlocks() {
+        let mut unlocks = Unlocks::default();
+        assert_eq!(unlocks.tile_theme().id, "tiles-classic");
+        assert_eq!(unlocks.background().id, "bg-black");
+
+        let warm = find("tiles-warm").unwrap();
+        assert!(!unlocks.select(warm));
+
+        let stats = Stats {
+            stars: 12,
+            score: 100,
+        };
+        let new: Vec<_> = unlocks.check(&stats).iter().map(|r| r.id).collect();
+        assert_eq!(new, ["tiles-warm", "bg-navy"]);
+        assert!(unlocks.check(&stats).is_empty());
+
+        assert!(unlocks.select(warm));
+        assert!(unlocks.is_selected(warm));
+        assert_eq!(unlocks.tile_theme().id, "tiles-warm");
+        assert_eq!(unlocks.background().id, "bg-black");
+
+        // 解放されていない見た目が保存されていても使わない
+        unlocks.background = Some("bg-wine".to_owned());
+        assert_eq!(unlocks.background().id, "bg-black");
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::Duration;
+
+use crate::board::Board;
+use crate::game::Game;
+use crate::replay::{Action, Replay};
+use crate::solver::Solution;
+
+pub type NodeId = usize;
+
+// ソルバーの読み
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eval {
+    // 最後まで指したときの得点
+    pub score: i32,
+    // 最善と読んだ手順。終わった局面なら空。
+    pub line: Solution,
+}
+
+impl Eval {
+    pub fn best(&self) -> Option<(usize, usize)> {
+        self.line.moves.first().copied()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub parent: Option<NodeId>,
+    // 親からこの局面に進めた手
+    pub action: Option<Action>,
+    pub t: Duration,
+    // 先頭が本筋
+    pub children: Vec<NodeId>,
+    pub board: Board,
+    // 盤面の fingerprint。同じ局面を見分けるのに使う。
+    pub hash: u64,
+    pub score: i32,
+    pub finished: bool,
+    // 記録の手に添えた注釈
+    pub comment: Option<String>,
+    pub eval: Option<Eval>,
+}
+
+// 記録した手順 (本筋) と、途中から試した別の手順 (変化) の木。
+// 局面はシードと根からの手で決まるので、ゲームは必要なときに打ち直して作る。
+#[derive(Debug, Clone)]
+pub struct VariationTree {
+    // 最初の局面を作るための記録。手は持たない。
+    base: Replay,
+    nodes: Vec<Node>,
+}
+
+impl VariationTree {
+    pub const ROOT: NodeId = 0;
+    // 試した手に付ける時刻の間隔
+    const STEP: Duration = Duration::from_secs(1);
+
+    // 記録の盤面を変えた手を本筋にする
+    pub fn new(replay: &Replay) -> Self {
+        let base = Replay {
+            events: vec![],
+            ..replay.clone()
+        };
+        let mut game = base.new_game();
+        let root = Self::node(None, None, Duration::default(), &game);
+        let mut tree = Self {
+            base,
+            nodes: vec![root],
+        };
+
+        let mut current = Self::ROOT;
+        for ev in &replay.events {
+            if ev.action.apply(&mut game, ev.t) {
+                current = tree.push(current, ev.action, ev.t, &game);
+                tree.nodes[current].comment = ev.comment.clone();
+            }
+        }
+        tree
+    }
+
+    fn node(parent: Option<NodeId>, action: Option<Action>, t: Duration, game: &Game) -> Node {
+        Node {
+            parent,
+            action,
+            t,
+            children: vec![],
+            board: game.board().clone(),
+            hash: game.board().fingerprint(),
+            score: game.score(),
+            finished: game.is_finished(),
+            comment: None,
+            eval: None,
+        }
+    }
+
+    fn push(&mut self, parent: NodeId, action: Action, t: Duration, game: &Game) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes
+            .push(Self::node(Some(parent), Some(action), t, game));
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    pub fn get(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    // id から先の本筋の次の節点
+    pub fn main_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].children.first().copied()
+    }
+
+    // id から本筋をたどって終わりまでの節点。id を含む。
+    pub fn main_line(&self, id: NodeId) -> Vec<NodeId> {
+        let mut line = vec![id];
+        while let Some(child) = self.main_child(*line.last().expect("internal error")) {
+            line.push(child);
+        }
+        line
+    }
+
+    // 根から id までの節点
+    pub fn path(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![id];
+        while let Some(parent) = self.nodes[*path.last().expect("internal error")].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    // id を通る手順 (id から先は本筋) の n 手目の節点。手順より長ければ最後の節点。
+    pub fn at_depth(&self, id: NodeId, n: usize) -> NodeId {
+        let path = self.path(id);
+        match path.get(n) {
+            Some(&node) => node,
+            None => {
+                let line = self.main_line(id);
+                line[(n + 1 - path.len()).min(line.len() - 1)]
+            }
+        }
+    }
+
+    // id の局面まで指したゲーム
+    pub fn game_at(&self, id: NodeId) -> Game {
+        let mut game = self.base.new_game();
+        for &node in &self.path(id)[1..] {
+            let node = &self.nodes[node];
+            let action = node.action.expect("internal error");
+            action.apply(&mut game, node.t);
+        }
+        game
+    }
+
+    // 根から id までの手の記録。途中の変化もふつうの記録として再生できる。
+    pub fn replay_to(&self, id: NodeId) -> Replay {
+        let mut replay = self.base.clone();
+        for &node in &self.path(id)[1..] {
+            let node = &self.nodes[node];
+            replay.push(node.t, node.action.expect("internal error"));
+            if let Some(comment) = &node.comment {
+                replay.annotate(comment);
+            }
+        }
+        replay.score = self.nodes[id].score;
+        replay
+    }
+
+    // id の局面で action を指した局面。盤面が変わらなければ None。
+    // 同じグループを消すなど、前に試した手と同じ局面になるならその節点を返す。
+    pub fn play(&mut self, id: NodeId, action: Action) -> Option<NodeId> {
+        let mut game = self.game_at(id);
+        let t = self.nodes[id].t + Self::STEP;
+        if !action.apply(&mut game, t) {
+            return None;
+        }
+        let existing = self.nodes[id].children.iter().copied().find(|&child| {
+            let node = &self.nodes[child];
+            node.hash == game.board().fingerprint() && node.score == game.score()
+        });
+        Some(existing.unwrap_or_else(|| self.push(id, action, t, &game)))
+    }
+
+    pub fn set_eval(&mut self, id: NodeId, eval: Eval) {
+        self.nodes[id].eval = Some(eval);
+    }
+
+    // 兄弟の中での位置と兄弟の数 (自分を含む)
+    pub fn sibling_index(&self, id: NodeId) -> (usize, usize) {
+        match self.nodes[id].parent {
+            Some(parent) => {
+                let siblings = &self.nodes[parent].children;
+                let i = siblings
+                    .iter()
+                    .position(|&c| c == id)
+                    .expect("internal error");
+                (i, siblings.len())
+            }
+            None => (0, 1),
+        }
+    }
+
+    // delta だけ隣の兄弟。端では回り込む。
+    pub fn sibling(&self, id: NodeId, delta: i32) -> NodeId {
+        let parent = match self.nodes[id].parent {
+            Some(parent) => parent,
+            None => return id,
+        };
+        let (i, n) = self.sibling_index(id);
+        let j = (i as i32 + delta).rem_euclid(n as i32) as usize;
+        self.nodes[parent].children[j]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::game::GameConfig;
+
+    #[test]
+    fn tree() {
+        let board = Board::parse("3 2\n212\n112\n".as_bytes()).unwrap();
+        let config = GameConfig {
+            width: 3,
+            height: 2,
+            ..GameConfig::default()
+        };
+        let game = Game::with_board(1, config, board);
+        let mut replay = Replay::new(&game);
+        replay.push(Duration::from_secs(1), Action::Cursor(0, 1));
+        replay.push(Duration::from_secs(2), Action::Click(0, 1));
+        replay.annotate("ones first");
+        replay.push(Duration::from_secs(3), Action::Click(0, 1));
+
+        let mut tree = VariationTree::new(&replay);
+        let main = tree.main_line(VariationTree::ROOT);
+        assert_eq!(main.len(), 3);
+        assert_eq!(tree.get(main[1]).comment.as_deref(), Some("ones first"));
+        assert_eq!(tree.get(main[2]).comment, None);
+        assert_eq!(tree.main_line(main[1]), &main[1..]);
+        assert_eq!(tree.parent(main[1]), Some(VariationTree::ROOT));
+        assert_eq!(tree.main_child(main[2]), None);
+        let end = *main.last().unwrap();
+        assert!(tree.get(end).finished);
+        assert_eq!(tree.path(end), main);
+        assert_eq!(tree.game_at(end).score(), tree.get(end).score);
+
+        // 同じグループを消す手は同じ節点、消せない手は None
+        assert_eq!(
+            tree.play(VariationTree::ROOT, Action::Click(1, 1)),
+            Some(main[1])
+        );
+        assert_eq!(tree.play(VariationTree::ROOT, Action::Click(0, 0)), None);
+
+        // 分岐
+        let branch = tree.play(VariationTree::ROOT, Action::Click(2, 0)).unwrap();
+        assert_ne!(branch, main[1]);
+        assert_eq!(tree.sibling_index(branch), (1, 2));
+        assert_eq!(tree.sibling(branch, 1), main[1]);
+        assert_eq!(tree.sibling(main[1], -1), branch);
+        assert_eq!(tree.sibling(VariationTree::ROOT, 1), VariationTree::ROOT);
+        assert_eq!(tree.at_depth(end, 0), VariationTree::ROOT);
+        assert_eq!(tree.at_depth(VariationTree::ROOT, 2), end);
+        assert_eq!(tree.at_depth(main[1], 9), end);
+        assert_eq!(tree.at_depth(branch, 1), branch);
+        assert_eq!(tree.game_at(branch).board(), &tree.get(branch).board);
+        assert_eq!(tree.get(branch).hash, tree.get(branch).board.fingerprint());
+
+        // 変化の記録は打ち直すと同じ局面になる
+        let replay = tree.replay_to(branch);
+        assert_eq!(replay.play().board(), &tree.get(branch).board);
+        assert_eq!(replay.score, tree.get(branch).score);
+        assert_eq!(tree.replay_to(end).events.len(), 2);
+    }
+}
@@ -0,0 +1,358 @@
+use std::ops::RangeInclusive;
+
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Mesh, Rect};
+use ggez::{Context, GameResult};
+
+use crate::font::{Align, Font};
+use crate::input::Action;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Button,
+    Toggle(bool),
+    Slider { value: i32, min: i32, max: i32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Widget {
+    pub label: String,
+    pub kind: Kind,
+    // false なら選べるが操作できない
+    pub enabled: bool,
+}
+
+impl Widget {
+    pub fn button<S: Into<String>>(label: S) -> Self {
+        Self {
+            label: label.into(),
+            kind: Kind::Button,
+            enabled: true,
+        }
+    }
+
+    pub fn toggle<S: Into<String>>(label: S, on: bool) -> Self {
+        Self {
+            label: label.into(),
+            kind: Kind::Toggle(on),
+            enabled: true,
+        }
+    }
+
+    pub fn slider<S: Into<String>>(label: S, value: i32, range: RangeInclusive<i32>) -> Self {
+        let (min, max) = range.into_inner();
+        Self {
+            label: label.into(),
+            kind: Kind::Slider {
+                value: value.max(min).min(max),
+                min,
+                max,
+            },
+            enabled: true,
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+// ウィジェットを操作した結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Clicked,
+    Toggled(bool),
+    Changed(i32),
+}
+
+// 縦に並べたウィジェット。キーボードでの選択とマウスでの操作を受け付ける。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Form {
+    widgets: Vec<Widget>,
+    origin: (f32, f32),
+    width: f32,
+    focus: usize,
+    hover: Option<usize>,
+}
+
+impl Form {
+    pub const ROW_HEIGHT: f32 = 18.0;
+    // スライダーのつまみを動かす部分が行の幅に占める割合と、右端の値の幅
+    const TRACK_START: f32 = 0.6;
+    const VALUE_WIDTH: f32 = 40.0;
+
+    pub fn new(widgets: Vec<Widget>, x: f32, y: f32, width: f32) -> Self {
+        assert!(!widgets.is_empty());
+        Self {
+            widgets,
+            origin: (x, y),
+            width,
+            focus: 0,
+            hover: None,
+        }
+    }
+
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.origin = (x, y);
+    }
+
+    pub fn height(&self) -> f32 {
+        Self::ROW_HEIGHT * self.widgets.len() as f32
+    }
+
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, i: usize) {
+        self.focus = i.min(self.widgets.len() - 1);
+    }
+
+    fn rect(&self, i: usize) -> Rect {
+        let (x, y) = self.origin;
+        Rect::new(
+            x,
+            y + Self::ROW_HEIGHT * i as f32,
+            self.width,
+            Self::ROW_HEIGHT,
+        )
+    }
+
+    // スライダーのつまみが動く範囲
+    fn track(&self, i: usize) -> Rect {
+        let rect = self.rect(i);
+        let start = rect.w * Self::TRACK_START;
+        let w = (rect.w - start - Self::VALUE_WIDTH).max(1.0);
+        Rect::new(rect.x + start, rect.y, w, rect.h)
+    }
+
+    fn at(&self, x: f32, y: f32) -> Option<usize> {
+        (0..self.widgets.len()).find(|&i| {
+            let rect = self.rect(i);
+            (rect.left()..rect.right()).contains(&x) && (rect.top()..rect.bottom()).contains(&y)
+        })
+    }
+
+    // 上下で選び、決定で押し、左右でスライダーを動かす。マウスでも操作できる。
+    pub fn on_action(&mut self, action: Action) -> Option<(usize, Response)> {
+        let i = self.focus;
+        let res = match action {
+            Action::MoveCursor(0, dy) if dy < 0 => {
+                self.set_focus(i.saturating_sub(1));
+                None
+            }
+            Action::MoveCursor(0, _) => {
+                self.set_focus(i + 1);
+                None
+            }
+            Action::MoveCursor(dx, _) => self.adjust(i, |value| value + dx.signum()),
+            Action::Confirm => self.activate(i),
+            Action::Point(x, y) => {
+                self.on_mouse_move(x, y);
+                None
+            }
+            Action::Click(x, y) => return self.on_click(x, y),
+            _ => None,
+        };
+        res.map(|res| (i, res))
+    }
+
+    fn on_mouse_move(&mut self, x: f32, y: f32) {
+        self.hover = self.at(x, y);
+    }
+
+    // クリックした行を選んで押す。スライダーはつまみをクリックした位置に動かす。
+    fn on_click(&mut self, x: f32, y: f32) -> Option<(usize, Response)> {
+        let i = self.at(x, y)?;
+        self.focus = i;
+        let res = match self.widgets[i].kind {
+            Kind::Slider { min, max, .. } => {
+                let track = self.track(i);
+                if x < track.x {
+                    return None;
+                }
+                let t = ((x - track.x) / track.w).min(1.0);
+                self.adjust(i, |_| min + (t * (max - min) as f32).round() as i32)
+            }
+            _ => self.activate(i),
+        };
+        res.map(|res| (i, res))
+    }
+
+    fn activate(&mut self, i: usize) -> Option<Response> {
+        let widget = &mut self.widgets[i];
+        if !widget.enabled {
+            return None;
+        }
+        match &mut widget.kind {
+            Kind::Button => Some(Response::Clicked),
+            Kind::Toggle(on) => {
+                *on = !*on;
+                Some(Response::Toggled(*on))
+            }
+            Kind::Slider { .. } => None,
+        }
+    }
+
+    fn adjust<F: FnOnce(i32) -> i32>(&mut self, i: usize, f: F) -> Option<Response> {
+        let widget = &mut self.widgets[i];
+        if !widget.enabled {
+            return None;
+        }
+        match &mut widget.kind {
+            Kind::Slider { value, min, max } => {
+                let next = f(*value).max(*min).min(*max);
+                if next == *value {
+                    return None;
+                }
+                *value = next;
+                Some(Response::Changed(next))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, font: &Font) -> GameResult {
+        for (i, widget) in self.widgets.iter().enumerate() {
+            let rect = self.rect(i);
+            if self.hover == Some(i) && widget.enabled {
+                let shade = Color::from_rgba(0xff, 0xff, 0xff, 0x30);
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, shade)?;
+                graphics::draw(ctx, &mesh, DrawParam::default())?;
+            }
+
+            let color = if !widget.enabled {
+                Color::from_rgb(0x60, 0x60, 0x60)
+            } else if i == self.focus {
+                Color::from_rgb(0xff, 0xff, 0x00)
+            } else {
+                graphics::WHITE
+            };
+            let mark = if i == self.focus { '>' } else { ' ' };
+            let line = match widget.kind {
+                Kind::Button => format!("{} [ {} ]", mark, widget.label),
+                Kind::Toggle(on) => {
+                    let check = if on { 'x' } else { ' ' };
+                    format!("{} [{}] {}", mark, check, widget.label)
+                }
+                Kind::Slider { .. } => format!("{} {}", mark, widget.label),
+            };
+            font.draw_str_with(ctx, rect.x, rect.y, line, color, Align::Left)?;
+
+            if let Kind::Slider { value, min, max } = widget.kind {
+                self.draw_slider(ctx, font, i, (value, min, max), color)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_slider(
+        &self,
+        ctx: &mut Context,
+        font: &Font,
+        i: usize,
+        (value, min, max): (i32, i32, i32),
+        color: Color,
+    ) -> GameResult {
+        let track = self.track(i);
+        let bar_w = track.w;
+        let mid = track.y + track.h / 2.0;
+        let bar = Mesh::new_rectangle(
+            ctx,
+            DrawMode::fill(),
+            Rect::new(track.x, mid - 1.0, bar_w, 2.0),
+            color,
+        )?;
+        graphics::draw(ctx, &bar, DrawParam::default())?;
+
+        let t = if max > min {
+            (value - min) as f32 / (max - min) as f32
+        } else {
+            0.0
+        };
+        let knob = Rect::new(track.x + bar_w * t - 3.0, track.y + 2.0, 6.0, track.h - 4.0);
+        let knob = Mesh::new_rectangle(ctx, DrawMode::fill(), knob, color)?;
+        graphics::draw(ctx, &knob, DrawParam::default())?;
+
+        // 値はつまみの右に出す
+        let x = track.x + bar_w;
+        let right = Align::Right(Self::VALUE_WIDTH);
+        font.draw_str_with(ctx, x, track.y, value.to_string(), color, right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form() -> Form {
+        let widgets = vec![
+            Widget::toggle("Sound", false),
+            Widget::toggle("Locked", false).enabled(false),
+            Widget::slider("Colors", 5, 2..=5),
+            Widget::button("Close"),
+        ];
+        Form::new(widgets, 20.0, 30.0, 200.0)
+    }
+
+    #[test]
+    fn keyboard() {
+        let mut form = form();
+        assert_eq!(
+            form.on_action(Action::Confirm),
+            Some((0, Response::Toggled(true)))
+        );
+        assert_eq!(
+            form.on_action(Action::Confirm),
+            Some((0, Response::Toggled(false)))
+        );
+        assert_eq!(form.on_action(Action::MoveCursor(0, -1)), None);
+        assert_eq!(form.focus(), 0);
+
+        // 操作できないものは選べるが押せない
+        form.on_action(Action::MoveCursor(0, 1));
+        assert_eq!(form.on_action(Action::Confirm), None);
+
+        form.on_action(Action::MoveCursor(0, 1));
+        assert_eq!(form.on_action(Action::MoveCursor(1, 0)), None);
+        assert_eq!(
+            form.on_action(Action::MoveCursor(-1, 0)),
+            Some((2, Response::Changed(4)))
+        );
+
+        form.on_action(Action::MoveCursor(0, 1));
+        form.on_action(Action::MoveCursor(0, 1));
+        assert_eq!(form.focus(), 3);
+        assert_eq!(
+            form.on_action(Action::Confirm),
+            Some((3, Response::Clicked))
+        );
+    }
+
+    #[test]
+    fn mouse() {
+        let mut form = form();
+        assert_eq!(form.on_click(10.0, 35.0), None);
+        assert_eq!(
+            form.on_click(30.0, 35.0),
+            Some((0, Response::Toggled(true)))
+        );
+        assert_eq!(
+            form.on_click(30.0, 30.0 + 18.0 * 3.0),
+            Some((3, Response::Clicked))
+        );
+        assert_eq!(form.focus(), 3);
+
+        // スライダーはクリックした位置の値になる
+        let y = 30.0 + 18.0 * 2.5;
+        assert_eq!(form.on_click(30.0, y), None);
+        assert_eq!(form.focus(), 2);
+        assert_eq!(form.on_click(140.0, y), Some((2, Response::Changed(2))));
+        assert_eq!(form.on_click(219.0, y), Some((2, Response::Changed(5))));
+
+        form.on_mouse_move(30.0, 31.0);
+        assert_eq!(form.hover, Some(0));
+        form.on_mouse_move(300.0, 31.0);
+        assert_eq!(form.hover, None);
+    }
+}